@@ -0,0 +1,25 @@
+use std::env;
+
+/// The OpenZFS releases with API changes this crate has to work around, as in libzfs-sys.
+const VERSION_CFGS: &[(u32, u32)] = &[(2, 1), (2, 2), (2, 3)];
+
+fn main() {
+    // libzfs-sys passes on the version of libzfs it was built against, so the same `zfs_x_y`
+    // cfgs can be set here.
+    let version = env::var("DEP_ZFS_VERSION").expect("libzfs-sys didn't give the libzfs version");
+    let (major, minor) = parse_version(&version)
+        .unwrap_or_else(|| panic!("unrecognized libzfs version {:?}", version));
+    let cfgs = VERSION_CFGS.iter().map(|(maj, min)| format!("zfs_{}_{}", maj, min));
+    println!("cargo:rustc-check-cfg=cfg({})", cfgs.collect::<Vec<_>>().join(", "));
+    for &(maj, min) in VERSION_CFGS {
+        if (major, minor) >= (maj, min) {
+            println!("cargo:rustc-cfg=zfs_{}_{}", maj, min);
+        }
+    }
+}
+
+/// Get the major and minor numbers from a version like `2.2.2` or `2.3.0-rc1`.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(['.', '-']);
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
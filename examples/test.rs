@@ -7,7 +7,7 @@ fn main() {
     let client = libzfs::LibZfs::new().expect("lib fail");
 
     println!("Opening ZPool {:?}", poolname);
-    let pool = client.pool_by_name(&poolname.into())
+    let pool = client.pool_by_name(poolname.as_str())
         .expect("pool fail");
     println!("{:?}", pool);
     println!("name: {:?}", pool.get_name());
@@ -16,7 +16,7 @@ fn main() {
     println!();
     println!("Opening dataset {:?}", dsname);
     let ds = client.dataset_by_name(
-            &dsname.into(),
+            dsname.as_str(),
             libzfs::DatasetType::Filesystem.into())
         .expect("dataset fail");
     println!("{:?}", ds);
@@ -29,14 +29,17 @@ fn main() {
     }
 
     println!("sub filesystems (recursively):");
-    for ds in ds.get_all_dependents().expect("get_dependents") {
-        if ds.get_type() == libzfs::DatasetType::Filesystem {
-            println!("\t{:?}", ds.get_name());
+    let dependents = ds.dependents().expect("dependents");
+    for dep in libzfs::Dependent::destroy_order(&dependents).into_iter().rev() {
+        if dep.dataset_type == libzfs::DatasetType::Filesystem
+            && dep.relation == libzfs::DependentRelation::Child
+        {
+            println!("\t{:?}", dep.dataset.get_name());
         }
     }
 
     println!("snapshots:");
-    ds.foreach_snapshot_ordered(Box::new(|snap| {
+    ds.foreach_snapshot_ordered(|snap| {
         println!("\t{:?}", snap.get_name());
-    })).expect("foreach_snapshot_ordered");
+    }).expect("foreach_snapshot_ordered");
 }
@@ -1,18 +1,138 @@
 use std::env;
+use std::fs;
 use std::path::PathBuf;
 
+/// The OpenZFS releases whose API changes the bindings' users need to know about. For each one
+/// the libzfs found is at least as new as, `zfs_<major>_<minor>` is set, both here and (through
+/// the `links` metadata) in the libzfs crate.
+const VERSION_CFGS: &[(u32, u32)] = &[(2, 1), (2, 2), (2, 3)];
+
+/// Where FreeBSD keeps the sources of the OpenZFS in its base system.
+const FREEBSD_OPENZFS_SRC: &str = "/usr/src/sys/contrib/openzfs";
+
+/// Where the OpenZFS on OS X installer puts its pkg-config files, which isn't somewhere
+/// pkg-config looks by default.
+const MACOS_PKG_CONFIG_DIR: &str = "/usr/local/zfs/lib/pkgconfig";
+
+/// What's needed to build against the libzfs found.
+struct Libzfs {
+    include_paths: Vec<PathBuf>,
+    clang_args: Vec<String>,
+    version: String,
+}
+
+/// Find libzfs with pkg-config (also looking where OpenZFS on OS X installs it, on macOS), or on
+/// FreeBSD, fall back to the one in the base system.
+fn probe() -> Libzfs {
+    if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("macos") {
+        let mut path = env::var("PKG_CONFIG_PATH").unwrap_or_default();
+        if !path.is_empty() {
+            path.push(':');
+        }
+        path.push_str(MACOS_PKG_CONFIG_DIR);
+        env::set_var("PKG_CONFIG_PATH", path);
+    }
+    match pkg_config::Config::new().probe("libzfs") {
+        Ok(pkg) => Libzfs {
+            include_paths: pkg.include_paths,
+            clang_args: vec![],
+            version: pkg.version,
+        },
+        Err(_) if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("freebsd") => {
+            probe_freebsd_base()
+        }
+        Err(e) => panic!("pkg-config for libzfs failed: {}", e),
+    }
+}
+
+/// FreeBSD's base system has libzfs, but no pkg-config file, and doesn't install its headers;
+/// they're used from the source tree, at `OPENZFS_SRC` if set, or else the system's copy (which
+/// needs the system sources installed).
+fn probe_freebsd_base() -> Libzfs {
+    println!("cargo:rerun-if-env-changed=OPENZFS_SRC");
+    let src = PathBuf::from(env::var("OPENZFS_SRC").unwrap_or(FREEBSD_OPENZFS_SRC.to_owned()));
+    let meta = fs::read_to_string(src.join("META")).unwrap_or_else(|e| panic!(
+        "libzfs isn't known to pkg-config, and reading {} failed: {}; set OPENZFS_SRC to the \
+        OpenZFS source tree", src.join("META").display(), e));
+    let version = meta.lines()
+        .find_map(|line| line.strip_prefix("Version:"))
+        .expect("no version in OpenZFS's META file")
+        .trim()
+        .to_owned();
+    for lib in ["zfs", "zfs_core", "nvpair"] {
+        println!("cargo:rustc-link-lib={}", lib);
+    }
+    // The same flags as the base system's own build of libzfs.
+    let ccompile = src.join("include/os/freebsd/spl/sys/ccompile.h");
+    Libzfs {
+        include_paths: vec![
+            src.join("include"),
+            src.join("lib/libspl/include"),
+            src.join("lib/libspl/include/os/freebsd"),
+        ],
+        clang_args: vec![
+            "-DIN_BASE".to_owned(),
+            "-DHAVE_ISSETUGID".to_owned(),
+            "-include".to_owned(),
+            ccompile.to_str().expect("non-Unicode include path").to_owned(),
+        ],
+        version,
+    }
+}
+
 fn main() {
-    let pkg = pkg_config::Config::new().probe("libzfs").expect("pkg-config for libzfs failed");
+    let pkg = probe();
+
+    // The version can be overridden, for libzfs builds whose pkg-config file is wrong.
+    println!("cargo:rerun-if-env-changed=LIBZFS_VERSION");
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=wrapper.h");
+    let version = env::var("LIBZFS_VERSION").unwrap_or(pkg.version);
+    let (major, minor) = parse_version(&version)
+        .unwrap_or_else(|| panic!("unrecognized libzfs version {:?}", version));
+    let cfgs = VERSION_CFGS.iter().map(|(maj, min)| format!("zfs_{}_{}", maj, min));
+    println!("cargo:rustc-check-cfg=cfg({})", cfgs.collect::<Vec<_>>().join(", "));
+    for &(maj, min) in VERSION_CFGS {
+        if (major, minor) >= (maj, min) {
+            println!("cargo:rustc-cfg=zfs_{}_{}", maj, min);
+        }
+    }
+    println!("cargo:rustc-env=LIBZFS_VERSION={}", version);
+    println!("cargo:version={}", version);
 
     let bindings = bindgen::Builder::default()
         .header("wrapper.h")
         .clang_args(pkg.include_paths.iter().map(|path|
             format!("-I{}", path.to_str().expect("non-Unicode include path"))))
+        .clang_args(pkg.clang_args)
         .constified_enum_module("pool_state")
         .constified_enum_module("zfs_type_t")
+        .constified_enum_module("zpool_prop_t")
+        .constified_enum_module("zfs_prop_t")
+        .constified_enum_module("zfs_keyformat")
+        .constified_enum_module("zpool_compat_status_t")
+        .constified_enum_module("pool_scan_func_t")
+        .constified_enum_module("pool_scrub_cmd_t")
+        .constified_enum_module("dsl_scan_state_t")
+        .constified_enum_module("pool_trim_func_t")
+        .constified_enum_module("vdev_trim_state_t")
+        .constified_enum_module("vdev_initializing_state_t")
+        .constified_enum_module("data_type_t")
+        .constified_enum_module("namecheck_err_t")
+        .constified_enum_module("zprop_source_t")
+        .constified_enum_module("zfs_errno")
+        .constified_enum_module("zfs_wait_activity_t")
+        .constified_enum_module("zpool_wait_activity_t")
+        .constified_enum_module("sa_protocol")
+        .constified_enum_module("vdev_state")
+        .constified_enum_module("zprop_type_t")
+        .constified_enum_module("diff_flags")
+        .constified_enum_module("zfs_userquota_prop_t")
+        .constified_enum_module("zpool_status_t")
         //.constified_enum_module(".*_t")
         .rustified_enum("zfs_error")
         .bitfield_enum("lzc_send_flags")
+        .bitfield_enum("zfeature_flags")
         .opaque_type("libzfs_handle")
         .opaque_type("zfs_handle")
         .opaque_type("zpool_handle")
@@ -24,3 +144,9 @@ fn main() {
     bindings.write_to_file(path)
         .expect("failed to write libzfs bindings");
 }
+
+/// Get the major and minor numbers from a version like `2.2.2` or `2.3.0-rc1`.
+fn parse_version(version: &str) -> Option<(u32, u32)> {
+    let mut parts = version.split(['.', '-']);
+    Some((parts.next()?.parse().ok()?, parts.next()?.parse().ok()?))
+}
@@ -12,3 +12,6 @@
 #![allow(improper_ctypes)]
 
 include!(concat!(env!("OUT_DIR"), "/bindings.rs"));
+
+/// The version of libzfs these bindings were generated for, like `2.2.2`.
+pub const LIBZFS_VERSION: &str = env!("LIBZFS_VERSION");
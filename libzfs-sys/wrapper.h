@@ -1,2 +1,14 @@
 #define _LARGEFILE64_SOURCE
 #include <libzfs.h>
+#include <libzutil.h>
+#include <zfeature_common.h>
+#include <zfs_namecheck.h>
+#include <zfs_prop.h>
+
+/*
+ * libspl's mount flags are skipped, since they clash with the system's, but their values differ
+ * between platforms, so the ones the bindings need are given again under names of their own.
+ */
+enum {
+	LIBZFS_MS_OVERLAY = MS_OVERLAY,
+};
@@ -0,0 +1,84 @@
+use crate::{DatasetType, DatasetTypeMask, LibZfs, Result, SafeString};
+
+use std::iter;
+
+/// The main dataset operations, by name, so code using them can be tested against a
+/// [`FakeZfs`](crate::FakeZfs) instead of real pools.
+///
+/// [`LibZfs`] implements this by calling its own methods; write application logic against
+/// `&dyn ZfsApi` (or a generic `Z: ZfsApi`) and pass it a `LibZfs` for real.
+pub trait ZfsApi {
+    /// The names of all imported pools.
+    fn pool_names(&self) -> Result<Vec<SafeString>>;
+
+    /// Whether a filesystem, volume, snapshot, or bookmark exists.
+    fn exists(&self, name: &str) -> Result<bool>;
+
+    /// The names of the filesystems and volumes directly under a filesystem.
+    fn children(&self, name: &str) -> Result<Vec<SafeString>>;
+
+    /// The full names of a filesystem or volume's snapshots, oldest first.
+    fn snapshots(&self, name: &str) -> Result<Vec<SafeString>>;
+
+    /// Create a filesystem with the given properties. Its parent has to exist already.
+    fn create_filesystem(&self, name: &str, props: &[(&str, &str)]) -> Result<()>;
+
+    /// Create a snapshot, given its full name (like `pool/fs@snap`).
+    fn create_snapshot(&self, name: &str) -> Result<()>;
+
+    /// Destroy a filesystem, volume, or snapshot, which must have nothing depending on it.
+    fn destroy(&self, name: &str) -> Result<()>;
+
+    /// Get a property's value in its parsable form, like [`Dataset::get_property`]; unset user
+    /// properties are not-found errors.
+    ///
+    /// [`Dataset::get_property`]: crate::Dataset::get_property
+    fn get_property(&self, name: &str, prop: &str) -> Result<String>;
+
+    /// Set a native or user property.
+    fn set_property(&self, name: &str, prop: &str, value: &str) -> Result<()>;
+}
+
+impl ZfsApi for LibZfs {
+    fn pool_names(&self) -> Result<Vec<SafeString>> {
+        Ok(self.get_zpools()?.iter().map(|pool| pool.get_name()).collect())
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        match self.dataset_by_name(name, DatasetTypeMask::all()) {
+            Ok(_) => Ok(true),
+            Err(e) if e.is_not_found() => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn children(&self, name: &str) -> Result<Vec<SafeString>> {
+        let ds = self.dataset_by_name(name, DatasetType::Filesystem.into())?;
+        Ok(ds.get_child_filesystems()?.iter().map(|child| child.get_name()).collect())
+    }
+
+    fn snapshots(&self, name: &str) -> Result<Vec<SafeString>> {
+        let ds = self.dataset_by_name(name, DatasetType::Filesystem | DatasetType::Volume)?;
+        Ok(ds.get_snapshots_ordered()?.iter().map(|snap| snap.get_name()).collect())
+    }
+
+    fn create_filesystem(&self, name: &str, props: &[(&str, &str)]) -> Result<()> {
+        LibZfs::create_filesystem(self, name, props).map(drop)
+    }
+
+    fn create_snapshot(&self, name: &str) -> Result<()> {
+        self.create_snapshots(iter::once(name))
+    }
+
+    fn destroy(&self, name: &str) -> Result<()> {
+        self.dataset_by_name(name, DatasetTypeMask::all())?.destroy()
+    }
+
+    fn get_property(&self, name: &str, prop: &str) -> Result<String> {
+        self.dataset_by_name(name, DatasetTypeMask::all())?.get_property(prop)
+    }
+
+    fn set_property(&self, name: &str, prop: &str, value: &str) -> Result<()> {
+        self.dataset_by_name(name, DatasetTypeMask::all())?.set_property(prop, value)
+    }
+}
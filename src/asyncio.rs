@@ -0,0 +1,122 @@
+use crate::{AsSafeStr, Dataset, Error, Events, LibZfs, ReceiveOptions, Result, SendOptions};
+use crate::ZfsEvent;
+use crate::event::EVENT_POLL_INTERVAL;
+
+use futures_core::Stream;
+use std::io;
+use std::os::fd::{AsFd, OwnedFd};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use tokio::io::{AsyncRead, AsyncWrite, AsyncWriteExt};
+use tokio::net::unix::pipe;
+use tokio::sync::mpsc;
+
+/// How many decoded events can be waiting in an [`EventStream`] before the thread reading them
+/// stops to let the consumer catch up.
+const EVENT_STREAM_BUFFER: usize = 64;
+
+impl Dataset {
+    /// Send this snapshot as described by `options`, writing the stream to an async `writer`.
+    /// The send itself runs on its own thread, like [`send`](Self::send), and the stream is
+    /// passed to the writer through a pipe.
+    ///
+    /// Needs to run inside a tokio runtime with I/O enabled.
+    pub async fn send_to_async_writer<W>(&self, options: &SendOptions, mut writer: W) -> Result<()>
+        where W: AsyncWrite + Unpin,
+    {
+        let (reader, pipe_writer) = io::pipe().map_err(Error::Sys)?;
+        let mut reader = pipe::Receiver::from_owned_fd(reader.into()).map_err(Error::Sys)?;
+        let send = self.send_with_options(options, pipe_writer.into())?;
+        let copied = async {
+            tokio::io::copy(&mut reader, &mut writer).await?;
+            writer.flush().await
+        }.await;
+        // If copying failed, closing the pipe makes the send fail too, instead of blocking.
+        drop(reader);
+        // The send thread closes its end of the pipe just before finishing, so by now this
+        // doesn't block for long.
+        send.wait()?;
+        copied.map_err(Error::Sys)
+    }
+}
+
+impl LibZfs {
+    /// Receive a send stream read from an async `reader`, like [`receive`](Self::receive). The
+    /// receive itself runs on tokio's blocking thread pool, and the stream is passed to it through
+    /// a pipe.
+    ///
+    /// Needs to run inside a tokio runtime with I/O enabled.
+    pub async fn receive_from_async_reader<N, R>(
+        &self,
+        name: N,
+        mut reader: R,
+        options: &ReceiveOptions,
+    ) -> Result<()>
+        where N: AsSafeStr,
+              R: AsyncRead + Unpin,
+    {
+        let name = name.as_safe_str()?.into_owned();
+        let (pipe_reader, writer) = io::pipe().map_err(Error::Sys)?;
+        let mut writer = pipe::Sender::from_owned_fd(writer.into()).map_err(Error::Sys)?;
+
+        let libzfs = LibZfs { handle: self.handle.clone() };
+        let options = options.clone();
+        let pipe_reader = OwnedFd::from(pipe_reader);
+        let receive = tokio::task::spawn_blocking(move || {
+            libzfs.receive(name, pipe_reader.as_fd(), &options)
+        });
+
+        // Closing the pipe when done, or if copying fails, tells the receive where the stream
+        // ends.
+        let copied = tokio::io::copy(&mut reader, &mut writer).await;
+        drop(writer);
+        let received = receive.await
+            .unwrap_or_else(|_| Err(Error::Sys(io::Error::other("receive task panicked"))));
+        received?;
+        copied.map(|_| ()).map_err(Error::Sys)
+    }
+}
+
+/// ZFS events as an async stream, from [`LibZfs::event_stream`]. The stream never ends, except
+/// after yielding an error.
+pub struct EventStream {
+    rx: mpsc::Receiver<Result<ZfsEvent>>,
+}
+
+impl LibZfs {
+    /// Read the event stream asynchronously, like `zpool events -f`. See
+    /// [`events`](Self::events) for what `only_new` does. The events are read on a thread of
+    /// their own, which stops once the stream is dropped.
+    pub fn event_stream(&self, only_new: bool) -> Result<EventStream> {
+        let mut events = self.events(only_new)?;
+        let (tx, rx) = mpsc::channel(EVENT_STREAM_BUFFER);
+        thread::spawn(move || forward_events(&mut events, &tx));
+        Ok(EventStream { rx })
+    }
+}
+
+fn forward_events(events: &mut Events, tx: &mpsc::Sender<Result<ZfsEvent>>) {
+    while !tx.is_closed() {
+        match events.try_next() {
+            Ok(Some(event)) => {
+                if tx.blocking_send(Ok(event)).is_err() {
+                    return;
+                }
+            }
+            Ok(None) => thread::sleep(EVENT_POLL_INTERVAL),
+            Err(e) => {
+                let _ = tx.blocking_send(Err(e));
+                return;
+            }
+        }
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Result<ZfsEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.rx.poll_recv(cx)
+    }
+}
@@ -0,0 +1,129 @@
+use crate::{sys, Dataset, DatasetType, Error, LibZfs, LibZfsCore, NvList, Result, SafeString};
+use crate::error::{batch_errors, lzc_call};
+
+use std::collections::BTreeMap;
+use std::io;
+use std::ptr;
+
+/// A bookmark which couldn't be destroyed, and why.
+#[derive(Debug)]
+pub struct BookmarkError {
+    pub name: SafeString,
+    pub error: Error,
+}
+
+impl LibZfs {
+    /// Create a bookmark, given its full name (like `pool/fs#mark`), from a snapshot or another
+    /// bookmark, like [`LibZfsCore::create_bookmark`].
+    pub fn create_bookmark(&self, name: &str, source: &str) -> Result<()> {
+        LibZfsCore::new()?.create_bookmark(name, source)
+    }
+
+    /// Destroy bookmarks in bulk, given their full names (like `pool/fs#mark`), like
+    /// [`LibZfsCore::destroy_bookmarks`].
+    pub fn destroy_bookmarks<I, T>(&self, names: I) -> Result<Vec<BookmarkError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        LibZfsCore::new()?.destroy_bookmarks(names)
+    }
+}
+
+impl LibZfsCore {
+    /// Create a bookmark, given its full name (like `pool/fs#mark`), from a snapshot or another
+    /// bookmark of the same filesystem or volume, like `zfs bookmark`. A bookmark made from
+    /// another bookmark is a copy of it, so incremental sends can keep using it as a base after
+    /// the original snapshot is gone.
+    pub fn create_bookmark(&self, name: &str, source: &str) -> Result<()> {
+        if !name.contains('#') {
+            return Err(Error::invalid_input(format!("{:?} is not a bookmark name", name)));
+        }
+        if !source.contains(['@', '#']) {
+            return Err(Error::invalid_input(
+                format!("{:?} is not a snapshot or bookmark name", source)));
+        }
+        let mut nvl = NvList::new()?;
+        nvl.add_string(name, source)?;
+
+        let mut errlist = ptr::null_mut();
+        let ret = lzc_call!(unsafe { sys::lzc_bookmark(nvl.as_ptr(), &mut errlist) }, name);
+        if ret == 0 {
+            return Ok(());
+        }
+        let names = vec![SafeString::try_from(name)?];
+        let (_, e) = batch_errors("bookmark", names, ret, errlist).remove(0);
+        Err(e)
+    }
+
+    /// Destroy bookmarks in bulk, given their full names (like `pool/fs#mark`). Bookmarks which
+    /// don't exist are ignored.
+    ///
+    /// The bookmarks are destroyed in one batch per pool, and each batch is all-or-nothing: if
+    /// any bookmark in a pool can't be destroyed, none in that pool are. Returns the bookmarks
+    /// which weren't destroyed, along with the reason; an empty list means all went well.
+    pub fn destroy_bookmarks<I, T>(&self, names: I) -> Result<Vec<BookmarkError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        // libzfs_core requires all the bookmarks in one call to be in the same pool.
+        let mut by_pool = BTreeMap::<String, Vec<SafeString>>::new();
+        for name in names {
+            let name = name.as_ref();
+            if !name.contains('#') {
+                return Err(Error::invalid_input(format!("{:?} is not a bookmark name", name)));
+            }
+            let pool = name.split(['/', '#']).next().unwrap_or_default();
+            by_pool.entry(pool.to_owned()).or_default().push(SafeString::try_from(name)?);
+        }
+
+        let mut failed = vec![];
+        for names in by_pool.into_values() {
+            let mut nvl = NvList::new()?;
+            for name in &names {
+                nvl.add_boolean(name.as_ref())?;
+            }
+
+            let mut errlist = ptr::null_mut();
+            let ret = lzc_call!(unsafe {
+                sys::lzc_destroy_bookmarks(nvl.as_ptr(), &mut errlist)
+            }, format_args!("{} bookmarks", names.len()));
+            if ret == 0 {
+                continue;
+            }
+
+            failed.extend(batch_errors("destroy", names, ret, errlist).into_iter()
+                .map(|(name, error)| BookmarkError { name, error }));
+        }
+
+        Ok(failed)
+    }
+}
+
+impl Dataset {
+    /// Create a redaction bookmark of this snapshot, like `zfs redact`. Blocks which are changed
+    /// or removed in any of the redaction snapshots (which must be snapshots of clones of this
+    /// one) are left out when sending with it; see [`send_redacted`](Self::send_redacted).
+    ///
+    /// `bookmark` is the bookmark's short name, after the `#`.
+    pub fn redact<I, T>(&self, bookmark: &str, redaction_snapshots: I) -> Result<()>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        if self.get_type() != DatasetType::Snapshot {
+            return Err(Error::invalid_input(format!("{} is not a snapshot", self.name())));
+        }
+        let bookmark = SafeString::try_from(bookmark)?;
+        let mut snaps = NvList::new()?;
+        for name in redaction_snapshots {
+            snaps.add_boolean(name.as_ref())?;
+        }
+
+        let ret = lzc_call!(unsafe {
+            sys::lzc_redact(self.name().as_ptr(), bookmark.as_ptr(), snaps.as_ptr())
+        }, self.name());
+        match ret {
+            0 => Ok(()),
+            e => Err(Error::Sys(io::Error::from_raw_os_error(e)).context("redact", self.name())),
+        }
+    }
+}
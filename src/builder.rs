@@ -0,0 +1,166 @@
+use crate::{validate_user_property_name, Compression, Dataset, DatasetType, Error, LibZfs};
+use crate::{Mountpoint, PropertyValue, Result, SafeString, VolumeOptions};
+use crate::sys;
+
+/// Builds a new filesystem or volume: creates it with its properties, then mounts it, like
+/// `zfs create`. Start one with [`LibZfs::build_filesystem`] or [`LibZfs::build_volume`], like
+/// `libzfs.build_filesystem("tank/data").compression(Compression::Lz4).quota("100G").create()`.
+///
+/// Everything which can be checked without touching the pool, like the name and property
+/// names, is checked before anything is created.
+#[derive(Debug)]
+pub struct DatasetBuilder<'a> {
+    libzfs: &'a LibZfs,
+    name: String,
+    volume: Option<(u64, VolumeOptions)>,
+    props: Vec<(String, String)>,
+    mount: bool,
+}
+
+impl LibZfs {
+    /// Start building a filesystem with the given name.
+    pub fn build_filesystem(&self, name: &str) -> DatasetBuilder<'_> {
+        DatasetBuilder {
+            libzfs: self,
+            name: name.to_owned(),
+            volume: None,
+            props: vec![],
+            mount: true,
+        }
+    }
+
+    /// Start building a volume with the given name and size in bytes.
+    pub fn build_volume(&self, name: &str, volsize: u64) -> DatasetBuilder<'_> {
+        DatasetBuilder {
+            libzfs: self,
+            name: name.to_owned(),
+            volume: Some((volsize, VolumeOptions::default())),
+            props: vec![],
+            mount: false,
+        }
+    }
+}
+
+impl DatasetBuilder<'_> {
+    /// Set a native or user property, replacing any value given for it before.
+    pub fn property(mut self, name: &str, value: &str) -> Self {
+        self.props.retain(|(prop, _)| prop != name);
+        self.props.push((name.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Set an enumerated property from its Rust type, like [`Checksum`](crate::Checksum).
+    pub fn typed_property<T: PropertyValue>(self, value: &T) -> Self {
+        self.property(T::NAME, &value.to_string())
+    }
+
+    /// Set the `compression` property.
+    pub fn compression(self, compression: Compression) -> Self {
+        self.typed_property(&compression)
+    }
+
+    /// Set the `quota` property, in bytes or like `100G`.
+    pub fn quota(self, quota: &str) -> Self {
+        self.property("quota", quota)
+    }
+
+    /// Set the `refquota` property, in bytes or like `100G`.
+    pub fn refquota(self, refquota: &str) -> Self {
+        self.property("refquota", refquota)
+    }
+
+    /// Set the `reservation` property, in bytes or like `10G`.
+    pub fn reservation(self, reservation: &str) -> Self {
+        self.property("reservation", reservation)
+    }
+
+    /// Set the `recordsize` property of a filesystem, in bytes or like `1M`.
+    pub fn recordsize(self, recordsize: &str) -> Self {
+        self.property("recordsize", recordsize)
+    }
+
+    /// Set the `mountpoint` property of a filesystem: an absolute path, `legacy`, or `none`.
+    pub fn mountpoint(self, mountpoint: &str) -> Self {
+        self.property("mountpoint", mountpoint)
+    }
+
+    /// Whether to mount a new filesystem once it's created, if its `canmount` and `mountpoint`
+    /// properties allow. Defaults to true, like `zfs create`; unlike
+    /// [`LibZfs::create_filesystem`].
+    pub fn mount(mut self, mount: bool) -> Self {
+        self.mount = mount;
+        self
+    }
+
+    /// Set a volume's block size in bytes, like `zfs create -b`.
+    pub fn volblocksize(mut self, volblocksize: u64) -> Self {
+        if let Some((_, options)) = &mut self.volume {
+            options.volblocksize = Some(volblocksize);
+        }
+        self
+    }
+
+    /// Don't reserve space for a whole volume up front, like `zfs create -s`.
+    pub fn sparse(mut self, sparse: bool) -> Self {
+        if let Some((_, options)) = &mut self.volume {
+            options.sparse = sparse;
+        }
+        self
+    }
+
+    /// Check everything which can be checked without touching the pool: the name, that the
+    /// properties exist, and the mountpoint.
+    pub fn validate(&self) -> Result<()> {
+        let typ = if self.volume.is_some() { DatasetType::Volume } else { DatasetType::Filesystem };
+        self.libzfs.validate_dataset_name(&self.name, typ)?;
+        if self.volume.as_ref().is_some_and(|&(volsize, _)| volsize == 0) {
+            return Err(Error::invalid_input(format!("volume {} must have a size", self.name)));
+        }
+        for (name, value) in &self.props {
+            if name.contains(':') {
+                validate_user_property_name(name)?;
+                continue;
+            }
+            let cname = SafeString::try_from(name.as_str())?;
+            if unsafe { sys::zfs_name_to_prop(cname.as_ptr()) } == sys::zfs_prop_t::ZPROP_INVAL {
+                return Err(Error::invalid_input(format!("invalid property name {:?}", name)));
+            }
+            if name == "mountpoint"
+                && !(value.starts_with('/') || value == "legacy" || value == "none")
+            {
+                return Err(Error::invalid_input(format!(
+                    "mountpoint {:?} must be an absolute path, \"legacy\", or \"none\"", value)));
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the dataset can be created, create it, and mount it if it's a filesystem to be
+    /// mounted. If mounting fails, the filesystem is destroyed again, so either it's returned
+    /// or nothing is left behind.
+    pub fn create(self) -> Result<Dataset> {
+        self.validate()?;
+        let props = self.props.iter()
+            .map(|(name, value)| (name.as_str(), value.as_str()))
+            .collect::<Vec<_>>();
+        let ds = match &self.volume {
+            Some((volsize, options)) => {
+                self.libzfs.create_volume(self.name.as_str(), *volsize, options, &props)?
+            }
+            None => self.libzfs.create_filesystem(self.name.as_str(), &props)?,
+        };
+        if self.mount
+            && ds.get_type() == DatasetType::Filesystem
+            && ds.get_property("canmount")? == "on"
+            && matches!(ds.mountpoint()?, Mountpoint::Path(_))
+        {
+            if let Err(e) = ds.mount() {
+                // Best effort: the mount error is more useful to the caller than any error from
+                // cleaning up.
+                let _ = ds.destroy();
+                return Err(e);
+            }
+        }
+        Ok(ds)
+    }
+}
@@ -0,0 +1,176 @@
+use crate::{AsSafeStr, Dataset, Error, LibZfs, ReceiveOptions, Result, SendOptions, ZfsSend};
+
+use std::fs::File;
+use std::io::{self, Read, Write};
+use std::os::fd::{AsFd, AsRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+
+/// How often, in milliseconds, copier threads check whether they've been cancelled.
+const CANCEL_POLL_MS: i32 = 100;
+
+/// State shared between a cancellable operation's handle and its copier thread.
+#[derive(Default)]
+struct Shared {
+    cancelled: AtomicBool,
+    bytes: AtomicU64,
+}
+
+/// Copy everything from one fd to another until EOF, or until cancelled.
+fn copy_until_cancelled(from: OwnedFd, to: OwnedFd, shared: &Shared) -> io::Result<()> {
+    let mut from = File::from(from);
+    let mut to = File::from(to);
+    let mut buf = vec![0u8; 128 * 1024];
+    loop {
+        if shared.cancelled.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let mut pollfd = libc::pollfd { fd: from.as_raw_fd(), events: libc::POLLIN, revents: 0 };
+        match unsafe { libc::poll(&mut pollfd, 1, CANCEL_POLL_MS) } {
+            0 => continue,
+            n if n < 0 => {
+                let e = io::Error::last_os_error();
+                if e.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(e);
+            }
+            _ => (),
+        }
+        let n = match from.read(&mut buf) {
+            Ok(0) => return Ok(()),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        to.write_all(&buf[.. n])?;
+        shared.bytes.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
+
+fn join_copier(copier: JoinHandle<io::Result<()>>) -> Result<()> {
+    copier.join()
+        .unwrap_or_else(|_| Err(io::Error::other("copier thread panicked")))
+        .map_err(Error::Sys)
+}
+
+/// A send which can be cancelled, from [`Dataset::send_cancellable`].
+pub struct SendHandle<'a> {
+    send: ZfsSend<'a>,
+    copier: JoinHandle<io::Result<()>>,
+    shared: Arc<Shared>,
+}
+
+impl SendHandle<'_> {
+    /// How many bytes of the stream have been written out so far.
+    pub fn bytes_written(&self) -> u64 {
+        self.shared.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Whether the send has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.send.is_finished() && self.copier.is_finished()
+    }
+
+    /// Block until the send is done.
+    pub fn wait(self) -> Result<()> {
+        let copied = join_copier(self.copier);
+        self.send.wait()?;
+        copied
+    }
+
+    /// Stop the send, leaving the stream written so far incomplete. Returns how many bytes of it
+    /// were written.
+    ///
+    /// This waits for any write of the stream in progress to finish, so it can block if
+    /// whatever is reading the stream has stopped.
+    pub fn cancel(self) -> u64 {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+        // The copier closes its end of the pipe, which makes the send fail.
+        let _ = self.copier.join();
+        let _ = self.send.wait();
+        self.shared.bytes.load(Ordering::Relaxed)
+    }
+}
+
+/// A receive which can be cancelled, from [`LibZfs::receive_cancellable`].
+pub struct ReceiveHandle {
+    receive: JoinHandle<Result<()>>,
+    copier: JoinHandle<io::Result<()>>,
+    shared: Arc<Shared>,
+}
+
+impl ReceiveHandle {
+    /// How many bytes of the stream have been read so far.
+    pub fn bytes_read(&self) -> u64 {
+        self.shared.bytes.load(Ordering::Relaxed)
+    }
+
+    /// Whether the receive has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.receive.is_finished() && self.copier.is_finished()
+    }
+
+    /// Block until the receive is done.
+    pub fn wait(self) -> Result<()> {
+        let copied = join_copier(self.copier);
+        self.receive.join()
+            .unwrap_or_else(|_| Err(Error::Sys(io::Error::other("receive thread panicked"))))?;
+        copied
+    }
+
+    /// Stop the receive, returning how many bytes of the stream were read. Nothing is received,
+    /// unless the receive was [`resumable`](ReceiveOptions::resumable), in which case what was
+    /// received so far is kept, and can be resumed from the destination's
+    /// [`receive_resume_token`](Dataset::receive_resume_token).
+    pub fn cancel(self) -> u64 {
+        self.shared.cancelled.store(true, Ordering::Relaxed);
+        // The copier closes its end of the pipe, which ends the stream early.
+        let _ = self.copier.join();
+        let _ = self.receive.join();
+        self.shared.bytes.load(Ordering::Relaxed)
+    }
+}
+
+impl Dataset {
+    /// Start sending this snapshot as described by `options`, writing the stream to `fd`, in a
+    /// way that can be cancelled. The stream goes through a pipe and a copier thread, which
+    /// closes the pipe to stop the send when cancelled.
+    pub fn send_cancellable(&self, options: &SendOptions, fd: OwnedFd) -> Result<SendHandle<'_>> {
+        let (reader, writer) = io::pipe().map_err(Error::Sys)?;
+        let send = self.send_with_options(options, writer.into())?;
+        let shared = Arc::new(Shared::default());
+        let copier = {
+            let shared = shared.clone();
+            thread::spawn(move || copy_until_cancelled(reader.into(), fd, &shared))
+        };
+        Ok(SendHandle { send, copier, shared })
+    }
+}
+
+impl LibZfs {
+    /// Start receiving a send stream read from `fd` on a background thread, like
+    /// [`receive`](Self::receive), in a way that can be cancelled. The stream goes through a pipe
+    /// and a copier thread, which closes the pipe to stop the receive when cancelled.
+    pub fn receive_cancellable<N: AsSafeStr>(
+        &self,
+        name: N,
+        fd: OwnedFd,
+        options: &ReceiveOptions,
+    ) -> Result<ReceiveHandle> {
+        let name = name.as_safe_str()?.into_owned();
+        let (reader, writer) = io::pipe().map_err(Error::Sys)?;
+        let shared = Arc::new(Shared::default());
+        let copier = {
+            let shared = shared.clone();
+            thread::spawn(move || copy_until_cancelled(fd, writer.into(), &shared))
+        };
+
+        let libzfs = LibZfs { handle: self.handle.clone() };
+        let options = options.clone();
+        let reader = OwnedFd::from(reader);
+        let receive = thread::spawn(move || libzfs.receive(name, reader.as_fd(), &options));
+        Ok(ReceiveHandle { receive, copier, shared })
+    }
+}
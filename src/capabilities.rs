@@ -0,0 +1,52 @@
+use crate::sys;
+
+use std::ffi::CStr;
+
+/// Which optional parts of libzfs the running library has, so that a program built against
+/// one version can check before using something an older one lacks, and fall back or say why
+/// instead of failing with an unhelpful error.
+///
+/// This is about the userland library only; the kernel module, and each pool's enabled
+/// features, can still rule things out. For the program to load at all against a library
+/// missing some of the functions this crate uses, it has to be linked with lazy binding, like
+/// with `-C relro-level=partial`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Capabilities {
+    /// Getting and setting vdev properties (OpenZFS 2.2).
+    pub vdev_properties: bool,
+    /// Error scrubs, with [`ZPool::error_scrub`](crate::ZPool::error_scrub) (OpenZFS 2.2).
+    pub error_scrub: bool,
+    /// Block cloning (OpenZFS 2.2).
+    pub block_cloning: bool,
+    /// RAIDZ expansion, by attaching a disk to a raidz vdev (OpenZFS 2.3).
+    pub raidz_expansion: bool,
+    /// Pruning the dedup table, like `zpool ddtprune` (OpenZFS 2.3).
+    pub ddt_prune: bool,
+    /// Starting scrubs through libzfs_core's `lzc_scrub` (OpenZFS 2.3).
+    pub lzc_scrub: bool,
+}
+
+impl Capabilities {
+    /// Probe the loaded libzfs and libzfs_core for what they support.
+    pub fn detect() -> Capabilities {
+        Capabilities {
+            vdev_properties: has_function(c"zpool_get_vdev_prop"),
+            error_scrub: has_feature(c"com.delphix:head_errlog"),
+            block_cloning: has_feature(c"com.fudosecurity:block_cloning"),
+            raidz_expansion: has_feature(c"org.openzfs:raidz_expansion"),
+            ddt_prune: has_function(c"zpool_ddt_prune"),
+            lzc_scrub: has_function(c"lzc_scrub"),
+        }
+    }
+}
+
+/// Whether a function of this name is loaded into the process.
+fn has_function(name: &CStr) -> bool {
+    !unsafe { libc::dlsym(libc::RTLD_DEFAULT, name.as_ptr()) }.is_null()
+}
+
+/// Whether libzfs knows of the pool feature with this GUID.
+fn has_feature(guid: &CStr) -> bool {
+    unsafe { sys::zfeature_is_supported(guid.as_ptr()) != 0 }
+}
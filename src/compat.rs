@@ -0,0 +1,148 @@
+//! Shims over the differences between the OpenZFS releases this crate can be built against,
+//! from 2.0 on. The build script sets `zfs_2_1`, `zfs_2_2`, and so on when libzfs is at least
+//! that new.
+
+use crate::{sys, Error, NvList, Result, ZPool};
+#[cfg(zfs_2_2)]
+use crate::string::string_from_buf;
+
+#[cfg(zfs_2_2)]
+use std::mem;
+use std::ptr;
+use std::ffi::CStr;
+use std::io;
+#[cfg(not(zfs_2_3))]
+use std::os::raw::c_char;
+use std::os::raw::c_int;
+
+/// The error for something this build of the crate can't do, because the libzfs it was built
+/// against is older than `version`.
+#[cfg(not(zfs_2_2))]
+pub(crate) fn unsupported(what: &str, version: &str) -> Error {
+    Error::Sys(std::io::Error::new(std::io::ErrorKind::Unsupported,
+        format!("{} need OpenZFS {} or newer", what, version)))
+}
+
+/// Get a pool's `compatibility` property.
+#[cfg(zfs_2_1)]
+pub(crate) fn pool_compatibility(pool: &ZPool) -> Result<String> {
+    pool.get_prop_string(sys::zpool_prop_t::ZPOOL_PROP_COMPATIBILITY)
+}
+
+/// Before 2.1 there's no `compatibility` property, which is the same as it being `off`.
+#[cfg(not(zfs_2_1))]
+pub(crate) fn pool_compatibility(_pool: &ZPool) -> Result<String> {
+    Ok("off".to_owned())
+}
+
+/// Share a filesystem over every protocol whose property is set.
+pub(crate) unsafe fn zfs_share(zhp: *mut sys::zfs_handle_t) -> c_int {
+    // Before 2.2, this took no list of protocols.
+    #[cfg(zfs_2_2)]
+    return sys::zfs_share(zhp, ptr::null());
+    #[cfg(not(zfs_2_2))]
+    return sys::zfs_share(zhp);
+}
+
+/// Stop sharing a filesystem over any protocol.
+pub(crate) unsafe fn zfs_unshare(zhp: *mut sys::zfs_handle_t) -> c_int {
+    #[cfg(zfs_2_2)]
+    return sys::zfs_unshare(zhp, ptr::null(), ptr::null());
+    #[cfg(not(zfs_2_2))]
+    return sys::zfs_unshare(zhp);
+}
+
+/// Apply share changes made since the last commit, for every protocol.
+pub(crate) unsafe fn zfs_commit_shares() {
+    #[cfg(zfs_2_2)]
+    sys::zfs_commit_shares(ptr::null());
+    #[cfg(not(zfs_2_2))]
+    sys::zfs_commit_all_shares();
+}
+
+/// Add vdevs to a pool.
+pub(crate) unsafe fn zpool_add(zhp: *mut sys::zpool_handle_t, nvroot: *mut sys::nvlist_t) -> c_int {
+    // 2.3 added whether to refuse vdevs whose ashift differs from the pool's, which `zpool add`
+    // does unless told not to.
+    #[cfg(zfs_2_3)]
+    return sys::zpool_add(zhp, nvroot, 1);
+    #[cfg(not(zfs_2_3))]
+    return sys::zpool_add(zhp, nvroot);
+}
+
+/// Search for pools which could be imported, as `args` says. The caller must hold the handle's
+/// lock.
+pub(crate) unsafe fn zpool_search_import(
+    hdl: *mut sys::libzfs_handle_t,
+    args: &mut sys::importargs_t,
+) -> Result<NvList> {
+    // Since 2.2, this takes a handle of its own, which also keeps the text of any error.
+    #[cfg(zfs_2_2)]
+    {
+        let mut lpch: sys::libpc_handle_t = mem::zeroed();
+        lpch.lpc_lib_handle = hdl.cast();
+        lpch.lpc_ops = ptr::addr_of!(sys::libzfs_config_ops);
+        let pools = sys::zpool_search_import(&mut lpch, args);
+        if pools.is_null() {
+            if lpch.lpc_desc_active != 0 {
+                return Err(Error::invalid_data(string_from_buf(&lpch.lpc_desc)));
+            }
+            return Err(Error::Sys(io::Error::last_os_error()));
+        }
+        Ok(NvList::from_raw(pools))
+    }
+    // Before, the text of an error was only printed to stderr.
+    #[cfg(not(zfs_2_2))]
+    {
+        let ops = ptr::addr_of!(sys::libzfs_config_ops);
+        let pools = sys::zpool_search_import(hdl.cast(), args, ops);
+        if pools.is_null() {
+            return Err(Error::Sys(io::Error::last_os_error()));
+        }
+        Ok(NvList::from_raw(pools))
+    }
+}
+
+/// Long enough for any version string; libzfs uses 128.
+#[cfg(not(zfs_2_3))]
+const VERSION_BUF_LEN: usize = 128;
+
+/// Get the version of the ZFS userland tools and libraries.
+pub(crate) fn zfs_version_userland() -> String {
+    // Since 2.3, this returns a copy for the caller to free, rather than filling a buffer.
+    #[cfg(zfs_2_3)]
+    unsafe {
+        let raw = sys::zfs_version_userland();
+        let version = CStr::from_ptr(raw).to_string_lossy().into_owned();
+        libc::free(raw.cast());
+        version
+    }
+    #[cfg(not(zfs_2_3))]
+    {
+        let mut buf = [0 as c_char; VERSION_BUF_LEN];
+        unsafe { sys::zfs_version_userland(buf.as_mut_ptr(), buf.len() as c_int) };
+        unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy().into_owned()
+    }
+}
+
+/// Get the version of the ZFS kernel module, failing if it can't be read.
+pub(crate) fn zfs_version_kernel() -> io::Result<String> {
+    #[cfg(zfs_2_3)]
+    unsafe {
+        let raw = sys::zfs_version_kernel();
+        if raw.is_null() {
+            return Err(io::Error::last_os_error());
+        }
+        let version = CStr::from_ptr(raw).to_string_lossy().into_owned();
+        libc::free(raw.cast());
+        Ok(version)
+    }
+    #[cfg(not(zfs_2_3))]
+    {
+        let mut buf = [0 as c_char; VERSION_BUF_LEN];
+        if unsafe { sys::zfs_version_kernel(buf.as_mut_ptr(), buf.len() as c_int) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy().into_owned())
+    }
+}
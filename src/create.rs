@@ -0,0 +1,280 @@
+use crate::{sys, ztry, AsSafeStr, Dataset, DatasetType, DatasetTypeMask, Error, LibZfs, NvList};
+use crate::{NvListRef, Result, SafeString, ZfsError, PROP_BUF_LEN};
+use crate::string::string_from_buf;
+
+use std::os::raw::c_char;
+use std::path::Path;
+use std::ptr;
+
+/// Options for creating a volume.
+#[derive(Debug, Clone, Default)]
+pub struct VolumeOptions {
+    /// The block size in bytes; the volume size must be a multiple of it. Defaults to the
+    /// pool's default.
+    pub volblocksize: Option<u64>,
+    /// Don't reserve space for the whole volume up front, like `zfs create -s`.
+    pub sparse: bool,
+}
+
+impl LibZfs {
+    /// Create a filesystem with the given properties, like `zfs create`. Unlike the command, this
+    /// doesn't mount it.
+    pub fn create_filesystem<N>(&self, name: N, props: &[(&str, &str)]) -> Result<Dataset>
+        where N: AsSafeStr,
+    {
+        self.create(name, DatasetType::Filesystem, props_nvlist(props)?)
+    }
+
+    /// Create a volume of the given size in bytes with the given properties, like
+    /// `zfs create -V`.
+    pub fn create_volume<N: AsSafeStr>(
+        &self,
+        name: N,
+        volsize: u64,
+        options: &VolumeOptions,
+        props: &[(&str, &str)],
+    ) -> Result<Dataset> {
+        let name = name.as_safe_str()?;
+        let mut nvl = props_nvlist(props)?;
+        nvl.add_uint64("volsize", volsize)?;
+        if let Some(volblocksize) = options.volblocksize {
+            nvl.add_uint64("volblocksize", volblocksize)?;
+        }
+
+        if !options.sparse && !nvl.contains("refreservation") {
+            // Like the zfs command, reserve enough space for the volume to be completely
+            // overwritten, counting metadata and extra copies.
+            let pool_name = name.as_str().split('/').next().unwrap_or_default();
+            let pool = self.pool_by_name(pool_name)?;
+            let _lock = self.handle.lock();
+            let resv = unsafe {
+                sys::zvol_volsize_to_reservation(pool.handle, volsize, nvl.as_ptr())
+            };
+            nvl.add_uint64("refreservation", resv)?;
+        }
+
+        self.create(&*name, DatasetType::Volume, nvl)
+    }
+
+    /// Make sure a filesystem or volume exists with the given properties: create it if it's
+    /// missing, and otherwise set any of the properties which don't already have the given
+    /// values. Returns the dataset and whether anything was changed.
+    ///
+    /// Volumes need a `volsize` property, in bytes. An existing volume is grown to it, but never
+    /// shrunk.
+    pub fn ensure_dataset<N: AsSafeStr>(
+        &self,
+        name: N,
+        typ: DatasetType,
+        props: &[(&str, &str)],
+    ) -> Result<(Dataset, bool)> {
+        let name = name.as_safe_str()?;
+        let volsize = match typ {
+            DatasetType::Filesystem => None,
+            DatasetType::Volume => {
+                let (_, value) = props.iter()
+                    .find(|(prop, _)| *prop == "volsize")
+                    .ok_or_else(|| Error::invalid_input("volumes need a volsize property"))?;
+                Some(value.parse::<u64>()
+                    .map_err(|_| Error::invalid_input(format!("invalid volsize {:?}", value)))?)
+            }
+            _ => return Err(Error::invalid_input(format!("can't create a {:?}", typ))),
+        };
+
+        let ds = match self.dataset_by_name(&*name, DatasetTypeMask::all()) {
+            Ok(ds) => ds,
+            Err(e) if e.is_not_found() => {
+                let ds = match volsize {
+                    Some(volsize) => {
+                        let props = props.iter()
+                            .filter(|(prop, _)| *prop != "volsize")
+                            .copied()
+                            .collect::<Vec<_>>();
+                        self.create_volume(&*name, volsize, &VolumeOptions::default(), &props)?
+                    }
+                    None => self.create_filesystem(&*name, props)?,
+                };
+                return Ok((ds, true));
+            }
+            Err(e) => return Err(e),
+        };
+
+        if ds.get_type() != typ {
+            return Err(Error::invalid_input(
+                format!("{} already exists and is a {:?}", name, ds.get_type())));
+        }
+
+        let mut changed = false;
+        let mut differing = vec![];
+        for &(prop, value) in props {
+            if prop == "volsize" {
+                let volsize = volsize.unwrap_or_default();
+                if ds.volsize() < volsize {
+                    ds.set_volsize(volsize, false)?;
+                    changed = true;
+                }
+            } else {
+                let differs = match ds.get_property(prop) {
+                    Ok(current) => !same_value(prop, &current, value)?,
+                    Err(e) if e.is_not_found() => true,
+                    Err(e) => return Err(e),
+                };
+                if differs {
+                    differing.push((prop, value));
+                }
+            }
+        }
+        if !differing.is_empty() {
+            ds.set_properties(&differing)?;
+            changed = true;
+        }
+        Ok((ds, changed))
+    }
+
+    fn create<N: AsSafeStr>(&self, name: N, typ: DatasetType, props: NvList) -> Result<Dataset> {
+        let name = name.as_safe_str()?;
+        {
+            let _lock = self.handle.lock();
+            ztry!(unsafe {
+                sys::zfs_create(self.handle.raw(), name.as_ptr(), typ.into(), props.as_ptr())
+            }, self.handle.raw(), name);
+        }
+        self.dataset_by_name(&*name, DatasetTypeMask::from(typ))
+    }
+}
+
+impl Dataset {
+    /// Create a clone of this snapshot with the given properties, like `zfs clone`. Unlike the
+    /// command, this doesn't mount it.
+    pub fn create_clone<N: AsSafeStr>(&self, name: N, props: &[(&str, &str)]) -> Result<Dataset> {
+        let name = name.as_safe_str()?;
+        let props = props_nvlist(props)?;
+        {
+            let _lock = self.libzfs.lock();
+            ztry!(unsafe {
+                sys::zfs_clone(self.handle, name.as_ptr(), props.as_ptr())
+            }, self.libzfs.raw(), self.name());
+        }
+        let libzfs = self.libzfs();
+        libzfs.dataset_by_name(&*name, DatasetTypeMask::from(DatasetType::Filesystem))
+    }
+
+    /// Clone this snapshot into a new filesystem mounted at `mountpoint`, with the given
+    /// properties. If mounting fails, the clone is destroyed again, so either the mounted clone
+    /// is returned or nothing is left behind.
+    pub fn clone_and_mount<N, P>(&self, name: N, mountpoint: P, props: &[(&str, &str)])
+        -> Result<Dataset>
+        where N: AsSafeStr,
+              P: AsRef<Path>,
+    {
+        let mountpoint = mountpoint.as_ref().to_str()
+            .ok_or_else(|| Error::invalid_input("mountpoint is not valid UTF-8"))?;
+        let mut props = props.iter()
+            .filter(|(name, _)| *name != "mountpoint")
+            .copied()
+            .collect::<Vec<_>>();
+        props.push(("mountpoint", mountpoint));
+
+        let clone = self.create_clone(name, &props)?;
+        if let Err(e) = clone.mount() {
+            // Best effort: the mount error is more useful to the caller than any error from
+            // cleaning up.
+            let _ = clone.destroy();
+            return Err(e);
+        }
+        Ok(clone)
+    }
+
+    /// Get the snapshot this filesystem or volume was cloned from, if it's a clone. It stays
+    /// the origin, and so can't be destroyed, until the clone is destroyed or promoted.
+    pub fn origin(&self) -> Result<Option<Dataset>> {
+        let origin = {
+            let _lock = self.libzfs.lock();
+            let mut buf = vec![0 as c_char; PROP_BUF_LEN];
+            // This fails, rather than giving an empty value, for datasets with no origin.
+            let ret = unsafe {
+                sys::zfs_prop_get(
+                    self.handle,
+                    sys::zfs_prop_t::ZFS_PROP_ORIGIN,
+                    buf.as_mut_ptr(),
+                    buf.len(),
+                    ptr::null_mut(),
+                    ptr::null_mut(),
+                    0,
+                    1, // literal
+                )
+            };
+            if ret != 0 {
+                return Ok(None);
+            }
+            string_from_buf(&buf)
+        };
+        if origin.is_empty() {
+            return Ok(None);
+        }
+        let libzfs = self.libzfs();
+        libzfs.dataset_by_name(origin.as_str(), DatasetTypeMask::from(DatasetType::Snapshot))
+            .map(Some)
+    }
+
+    /// Get the filesystems and volumes cloned from this snapshot, like its `clones` property.
+    pub fn clones(&self) -> Result<Vec<Dataset>> {
+        if self.get_type() != DatasetType::Snapshot {
+            return Err(Error::invalid_input(format!("{} is not a snapshot", self.name())));
+        }
+        let names = {
+            let _lock = self.libzfs.lock();
+            // The nvlist belongs to the handle.
+            let clones = unsafe { sys::zfs_get_clones_nvl(self.handle) };
+            if clones.is_null() {
+                return Ok(vec![]);
+            }
+            unsafe { NvListRef::from_ptr(clones) }.iter()
+                .map(|pair| pair.name().into_owned())
+                .collect::<Vec<_>>()
+        };
+        let libzfs = self.libzfs();
+        names.iter()
+            .map(|name| libzfs.dataset_by_name(name.as_str(),
+                DatasetType::Filesystem | DatasetType::Volume))
+            .collect()
+    }
+}
+
+pub(crate) fn props_nvlist(props: &[(&str, &str)]) -> Result<NvList> {
+    let mut nvl = NvList::new()?;
+    for &(name, value) in props {
+        nvl.add_string(name, value)?;
+    }
+    Ok(nvl)
+}
+
+/// Whether a property's current value, as gotten, is the same as `value`, as it would be set:
+/// numbers are compared in full, so `100G` matches `107374182400`, and values with several
+/// names, like `on` and `yes`, by what they mean. Anything else is compared as given.
+fn same_value(prop: &str, current: &str, value: &str) -> Result<bool> {
+    let cprop = SafeString::try_from(prop)?;
+    let prop = unsafe { sys::zfs_name_to_prop(cprop.as_ptr()) };
+    if prop == sys::zfs_prop_t::ZPROP_INVAL {
+        return Ok(current == value);
+    }
+    let normalize = |value: &str| -> Result<Option<u64>> {
+        let cvalue = SafeString::try_from(value)?;
+        let mut num = 0;
+        let ret = match unsafe { sys::zfs_prop_get_type(prop) } {
+            sys::zprop_type_t::PROP_TYPE_INDEX => unsafe {
+                sys::zfs_prop_string_to_index(prop, cvalue.as_ptr(), &mut num)
+            },
+            // libzfs only uses the handle for error messages, which aren't wanted here.
+            sys::zprop_type_t::PROP_TYPE_NUMBER => unsafe {
+                sys::zfs_nicestrtonum(ptr::null_mut(), cvalue.as_ptr(), &mut num)
+            },
+            _ => return Ok(None),
+        };
+        Ok((ret == 0).then_some(num))
+    };
+    Ok(match (normalize(current)?, normalize(value)?) {
+        (Some(current), Some(value)) => current == value,
+        _ => current == value,
+    })
+}
@@ -0,0 +1,403 @@
+use crate::{sys, ztry, Dataset, DatasetType, Error, ListFilter, NvList, NvListRef, NvValue};
+use crate::{Result, ZfsError};
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::ptr;
+
+/// Who permissions are delegated to with `zfs allow`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Delegate {
+    /// A user, by ID, like `zfs allow -u`.
+    User(u32),
+    /// A group, by ID, like `zfs allow -g`.
+    Group(u32),
+    /// Everyone, like `zfs allow -e`.
+    Everyone,
+    /// Whoever creates a descendent filesystem, who gets these permissions on it, like
+    /// `zfs allow -c`.
+    Creator,
+    /// Not a grant to anyone, but the definition of a permission set, named like `@backup`,
+    /// like `zfs allow -s`.
+    Set(String),
+}
+
+/// Where delegated permissions apply, relative to the dataset they're set on.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum DelegationScope {
+    /// On the dataset itself, like `zfs allow -l`.
+    Local,
+    /// On its descendents, like `zfs allow -d`.
+    Descendent,
+}
+
+/// Permissions delegated on a dataset, like one line of `zfs allow`'s output.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Delegation {
+    /// Who they're delegated to.
+    pub who: Delegate,
+    /// Where they apply. Granting without `-l` or `-d` gives two delegations, one for each. This
+    /// is `None` for [`Delegate::Creator`] and [`Delegate::Set`].
+    pub scope: Option<DelegationScope>,
+    /// The permission names, like `snapshot` or `mount`, or property names, and permission sets,
+    /// named like `@backup`.
+    pub permissions: Vec<String>,
+}
+
+/// Who has what delegated permissions across a tree of datasets, from
+/// [`Dataset::delegation_audit`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DelegationAudit {
+    /// Whether the pool's `delegation` property is on. If it's off, none of the permissions
+    /// have any effect, and only root can administer the pool's datasets.
+    pub delegation_enabled: bool,
+    /// Each user, group, and everyone with any permissions in the tree, in order.
+    pub delegates: Vec<DelegateAudit>,
+}
+
+/// The permissions one user, group, or everyone has across a tree of datasets.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DelegateAudit {
+    /// Who has them: a [`Delegate::User`], [`Delegate::Group`], or [`Delegate::Everyone`].
+    pub who: Delegate,
+    /// Each dataset they have any permissions on, by name, in order.
+    pub datasets: Vec<(String, Vec<EffectivePermission>)>,
+}
+
+/// A permission someone has on a dataset, and where it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct EffectivePermission {
+    /// The permission name, or a property name.
+    pub permission: String,
+    /// The dataset it's delegated on: the dataset itself, or an ancestor.
+    pub granted_on: String,
+    /// Whether it's delegated locally on the dataset itself, or to descendents of an ancestor.
+    pub scope: DelegationScope,
+    /// The permission set it's delegated through, like `@backup`, if any.
+    pub via_set: Option<String>,
+}
+
+impl Dataset {
+    /// Work out who has what delegated permissions on this filesystem or volume and everything
+    /// under it, for auditing: the permissions delegated locally on each dataset, those
+    /// delegated to descendents on any of its ancestors, even above this one, and those in the
+    /// permission sets among them, each with where it comes from.
+    ///
+    /// Permissions for creators aren't included, since they belong to nobody until a
+    /// filesystem is created; once one is, they're delegated on it to its creator as usual.
+    pub fn delegation_audit(&self) -> Result<DelegationAudit> {
+        let mut delegates = BTreeMap::<Delegate, BTreeMap<String, Vec<_>>>::new();
+        for ds in self.list(&ListFilter::default())? {
+            // The delegations on the dataset and each of its ancestors, nearest first.
+            let levels = ds.delegation_levels()?;
+            for (depth, (granted_on, delegations)) in levels.iter().enumerate() {
+                let scope = if depth == 0 {
+                    DelegationScope::Local
+                } else {
+                    DelegationScope::Descendent
+                };
+                for delegation in delegations {
+                    if delegation.scope != Some(scope)
+                        || matches!(delegation.who, Delegate::Creator | Delegate::Set(_))
+                    {
+                        continue;
+                    }
+                    let effective = delegates.entry(delegation.who.clone())
+                        .or_default()
+                        .entry(ds.name().to_string())
+                        .or_default();
+                    for perm in &delegation.permissions {
+                        let mut add = |permission: String, via_set: Option<&String>| {
+                            effective.push(EffectivePermission {
+                                permission,
+                                granted_on: granted_on.clone(),
+                                scope,
+                                via_set: via_set.cloned(),
+                            });
+                        };
+                        if perm.starts_with('@') {
+                            for permission in expand_set(&levels[depth ..], perm) {
+                                add(permission, Some(perm));
+                            }
+                        } else {
+                            add(perm.clone(), None);
+                        }
+                    }
+                }
+            }
+        }
+
+        Ok(DelegationAudit {
+            delegation_enabled: self.get_pool().get_property("delegation")? == "on",
+            delegates: delegates.into_iter()
+                .map(|(who, datasets)| DelegateAudit {
+                    who,
+                    datasets: datasets.into_iter().filter(|(_, perms)| !perms.is_empty()).collect(),
+                })
+                .filter(|audit| !audit.datasets.is_empty())
+                .collect(),
+        })
+    }
+
+    /// Get the permissions delegated on this dataset itself with `zfs allow`, and the permission
+    /// sets defined on it, like the local part of `zfs allow`'s output. Those on its ancestors
+    /// aren't included, though their descendent ones apply here too.
+    pub fn delegated_permissions(&self) -> Result<Vec<Delegation>> {
+        let mut levels = self.delegation_levels()?;
+        Ok(levels.swap_remove(0).1)
+    }
+
+    /// Delegate permissions on this dataset, like `zfs allow`. Permission sets among them must be
+    /// defined on this dataset or an ancestor. Permissions for a user, group, or everyone with no
+    /// scope are delegated both locally and to descendents, as `zfs allow` does by default.
+    ///
+    /// To define a permission set, or add to one, use [`Delegate::Set`], or
+    /// [`define_permission_set`](Self::define_permission_set).
+    pub fn allow(&self, delegation: &Delegation) -> Result<()> {
+        if delegation.permissions.is_empty() {
+            return Err(Error::invalid_input("no permissions given to delegate"));
+        }
+        self.set_fsacl(false, &encode(delegation)?)
+    }
+
+    /// Take back permissions delegated on this dataset, like `zfs unallow`. If none are given,
+    /// everything delegated to who it is in that scope is taken back; for a [`Delegate::Set`],
+    /// that removes the set. With no scope, they're taken back both locally and from
+    /// descendents, like `zfs unallow` without `-l` or `-d`.
+    pub fn unallow(&self, delegation: &Delegation) -> Result<()> {
+        self.set_fsacl(true, &encode(delegation)?)
+    }
+
+    /// Take back permissions delegated on this dataset and on each filesystem and volume under
+    /// it, like `zfs unallow -r`. See [`unallow`](Self::unallow).
+    pub fn unallow_recursive(&self, delegation: &Delegation) -> Result<()> {
+        let acl = encode(delegation)?;
+        for ds in self.list(&ListFilter::default())? {
+            ds.set_fsacl(true, &acl)?;
+        }
+        Ok(())
+    }
+
+    /// Take back everything delegated on this dataset, in the given scope or in both, to every
+    /// user and group, everyone, and creators. Permission sets defined here are kept; remove
+    /// them with [`undefine_permission_set`](Self::undefine_permission_set).
+    pub fn unallow_all(&self, scope: Option<DelegationScope>) -> Result<()> {
+        let scope = match scope {
+            None => None,
+            Some(DelegationScope::Local) => Some('l'),
+            Some(DelegationScope::Descendent) => Some('d'),
+        };
+        // With no permissions under them, keys take back everything delegated under them.
+        let fsacl = self.fsacl()?;
+        let Some(own) = fsacl.lookup_nvlist(self.name().as_str()) else { return Ok(()) };
+        let mut acl = NvList::new()?;
+        for pair in own.iter() {
+            let key = pair.name();
+            let mut chars = key.chars();
+            let (Some(who), Some(key_scope)) = (chars.next(), chars.next()) else { continue };
+            if !who.eq_ignore_ascii_case(&'s') && (scope.is_none() || scope == Some(key_scope)) {
+                acl.add_boolean(&key)?;
+            }
+        }
+        if acl.iter().next().is_none() {
+            return Ok(());
+        }
+        self.set_fsacl(true, &acl)
+    }
+
+    /// Define a permission set on this dataset, named like `@backup`, like
+    /// `zfs allow -s @backup snapshot,send,hold`. If it's already defined here, the permissions
+    /// are added to it. It can then be delegated here or on any descendent by including its name
+    /// in a [`Delegation`]'s permissions. Sets may include other sets.
+    pub fn define_permission_set(&self, name: &str, permissions: &[&str]) -> Result<()> {
+        self.allow(&Delegation {
+            who: Delegate::Set(name.to_owned()),
+            scope: None,
+            permissions: permissions.iter().map(|&p| p.to_owned()).collect(),
+        })
+    }
+
+    /// Remove permissions from a permission set defined on this dataset, like
+    /// `zfs unallow -s @backup send`, or if none are given, remove the set altogether.
+    pub fn undefine_permission_set(&self, name: &str, permissions: &[&str]) -> Result<()> {
+        self.unallow(&Delegation {
+            who: Delegate::Set(name.to_owned()),
+            scope: None,
+            permissions: permissions.iter().map(|&p| p.to_owned()).collect(),
+        })
+    }
+
+    /// Get the permission sets which can be delegated on this dataset: those defined on it and
+    /// on its ancestors, by name. Where a name is defined more than once, the set has the
+    /// permissions of all its definitions, as ZFS checks each. A snapshot uses its
+    /// filesystem's.
+    pub fn permission_sets(&self) -> Result<BTreeMap<String, Vec<String>>> {
+        let mut sets = BTreeMap::new();
+        for (_, delegations) in self.delegation_levels()? {
+            for delegation in delegations {
+                if let Delegate::Set(set) = delegation.who {
+                    let perms: &mut Vec<String> = sets.entry(set).or_default();
+                    perms.extend(delegation.permissions);
+                    perms.sort();
+                    perms.dedup();
+                }
+            }
+        }
+        Ok(sets)
+    }
+
+    /// Delegate, or with `un`, take back, permissions on this dataset, given in libzfs's raw
+    /// form.
+    fn set_fsacl(&self, un: bool, acl: &NvList) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_set_fsacl(self.handle, un as sys::boolean_t, acl.as_ptr())
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Get the permissions delegated on this dataset, or a snapshot's filesystem, and on each of
+    /// its ancestors, nearest first, by name. Those with nothing delegated have no delegations.
+    pub(crate) fn delegation_levels(&self) -> Result<Vec<(String, Vec<Delegation>)>> {
+        let name = self.name();
+        let acl = match name.as_str().split_once(['@', '#']) {
+            Some((fs_name, _)) => self.libzfs()
+                .dataset_by_name(fs_name, DatasetType::Filesystem | DatasetType::Volume)?
+                .fsacl()?,
+            None => self.fsacl()?,
+        };
+        let mut levels = vec![];
+        let mut next = name.as_str().split(['@', '#']).next();
+        while let Some(name) = next {
+            levels.push((name.to_owned(), acl.lookup_nvlist(name).map_or_else(Vec::new, decode)));
+            next = name.rsplit_once('/').map(|(parent, _)| parent);
+        }
+        Ok(levels)
+    }
+
+    /// Get the permissions delegated on this dataset and its ancestors with `zfs allow`, in
+    /// libzfs's raw form: an nvlist under the name of each dataset with anything delegated on
+    /// it, of who-keys, each an nvlist of permission names; see [`decode`].
+    pub(crate) fn fsacl(&self) -> Result<NvList> {
+        let _lock = self.libzfs.lock();
+        let mut nvl = ptr::null_mut();
+        ztry!(unsafe { sys::zfs_get_fsacl(self.handle, &mut nvl) }, self.libzfs.raw(), self.name());
+        if nvl.is_null() {
+            NvList::new()
+        } else {
+            Ok(unsafe { NvList::from_raw(nvl) })
+        }
+    }
+}
+
+/// Decode libzfs's raw form of the permissions delegated on one dataset: the nvlist under its
+/// name in what [`Dataset::fsacl`] gets. Its keys are like `ul$1000`: who, where, and an ID.
+/// Who is upper case for the permission sets granted, and lower case for the rest; where is
+/// `l`ocal, `d`escendent, or `-` for creators and set definitions, like `s-$@backup`. Keys not
+/// understood are skipped.
+fn decode(acl: &NvListRef) -> Vec<Delegation> {
+    let mut delegations = BTreeMap::<_, Vec<String>>::new();
+    for pair in acl.iter() {
+        let key = pair.name();
+        let mut chars = key.chars();
+        let (Some(who), Some(scope), Some('$')) =
+            (chars.next(), chars.next(), chars.next()) else { continue };
+        let id = chars.as_str();
+        let who = match who.to_ascii_lowercase() {
+            'u' => match id.parse() { Ok(uid) => Delegate::User(uid), Err(_) => continue },
+            'g' => match id.parse() { Ok(gid) => Delegate::Group(gid), Err(_) => continue },
+            'e' => Delegate::Everyone,
+            'c' => Delegate::Creator,
+            's' => Delegate::Set(id.to_owned()),
+            _ => continue,
+        };
+        let scope = match scope {
+            'l' => Some(DelegationScope::Local),
+            'd' => Some(DelegationScope::Descendent),
+            '-' => None,
+            _ => continue,
+        };
+        let NvValue::NvList(perms) = pair.value() else { continue };
+        delegations.entry((who, scope))
+            .or_default()
+            .extend(perms.iter().map(|perm| perm.name().into_owned()));
+    }
+    delegations.into_iter()
+        .map(|((who, scope), mut permissions)| {
+            permissions.sort();
+            Delegation { who, scope, permissions }
+        })
+        .collect()
+}
+
+/// Encode permissions to delegate, or take back, in libzfs's raw form; see [`decode`]. With no
+/// permissions, each key maps to nothing, which takes back everything under it.
+fn encode(delegation: &Delegation) -> Result<NvList> {
+    let (who, id) = match &delegation.who {
+        Delegate::User(uid) => ('u', uid.to_string()),
+        Delegate::Group(gid) => ('g', gid.to_string()),
+        Delegate::Everyone => ('e', String::new()),
+        Delegate::Creator => ('c', String::new()),
+        Delegate::Set(name) if name.starts_with('@') => ('s', name.clone()),
+        Delegate::Set(name) => return Err(Error::invalid_input(format!(
+            "permission set name {:?} doesn't start with '@'", name))),
+    };
+    let scopes: &[char] = match (&delegation.who, delegation.scope) {
+        (Delegate::Creator | Delegate::Set(_), _) => &['-'],
+        (_, Some(DelegationScope::Local)) => &['l'],
+        (_, Some(DelegationScope::Descendent)) => &['d'],
+        (_, None) => &['l', 'd'],
+    };
+    // Permission sets go under the upper case key, the rest under the lower case one.
+    let (sets, perms): (Vec<&String>, Vec<&String>) =
+        delegation.permissions.iter().partition(|p| p.starts_with('@'));
+
+    let mut acl = NvList::new()?;
+    for scope in scopes {
+        for (who, perms) in [(who, &perms), (who.to_ascii_uppercase(), &sets)] {
+            let key = format!("{}{}${}", who, scope, id);
+            if delegation.permissions.is_empty() {
+                acl.add_boolean(&key)?;
+            } else if !perms.is_empty() {
+                let mut list = NvList::new()?;
+                for perm in perms {
+                    list.add_boolean(perm)?;
+                }
+                acl.add_nvlist(&key, &list)?;
+            }
+        }
+    }
+    Ok(acl)
+}
+
+/// The permissions in a permission set, given the delegations on the dataset it's delegated on
+/// and each of its ancestors, nearest first. Every definition of the set counts, as do those of
+/// any sets it includes.
+pub(crate) fn expand_set(levels: &[(String, Vec<Delegation>)], set: &str) -> BTreeSet<String> {
+    let mut perms = BTreeSet::new();
+    let mut seen = BTreeSet::new();
+    let mut pending = vec![set.to_owned()];
+    while let Some(set) = pending.pop() {
+        if !seen.insert(set.clone()) {
+            continue;
+        }
+        for delegation in levels.iter().flat_map(|(_, delegations)| delegations.iter()) {
+            if delegation.who != Delegate::Set(set.clone()) {
+                continue;
+            }
+            for perm in &delegation.permissions {
+                if perm.starts_with('@') {
+                    pending.push(perm.clone());
+                } else {
+                    perms.insert(perm.clone());
+                }
+            }
+        }
+    }
+    perms
+}
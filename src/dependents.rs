@@ -0,0 +1,89 @@
+use crate::{Dataset, DatasetType, Result, SafeString};
+
+use std::collections::HashSet;
+
+/// How a [`Dependent`] depends on the dataset it's listed under.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DependentRelation {
+    /// A filesystem or volume directly under a filesystem.
+    Child,
+    /// A snapshot of a filesystem or volume.
+    Snapshot,
+    /// A filesystem or volume cloned from a snapshot. It may be anywhere in the pool.
+    Clone,
+}
+
+/// A dataset which depends on another, along with everything which depends on it in turn, from
+/// [`Dataset::dependents`].
+#[derive(Debug, Clone)]
+pub struct Dependent {
+    pub dataset: Dataset,
+    pub dataset_type: DatasetType,
+    pub relation: DependentRelation,
+    /// For a filesystem or volume, its children and snapshots; for a snapshot, its clones.
+    pub dependents: Vec<Dependent>,
+}
+
+impl Dataset {
+    /// Get everything which depends on this dataset, as a tree: a filesystem's children and
+    /// snapshots, a volume's snapshots, and the clones of each snapshot, recursively. Snapshots
+    /// are listed oldest first.
+    ///
+    /// A clone which is also a descendant (a snapshot cloned into a child of its own filesystem)
+    /// shows up in both places.
+    pub fn dependents(&self) -> Result<Vec<Dependent>> {
+        let mut dependents = vec![];
+        match self.get_type() {
+            DatasetType::Snapshot => {
+                for clone in self.clones()? {
+                    dependents.push(Dependent::new(clone, DependentRelation::Clone)?);
+                }
+            }
+            DatasetType::Filesystem | DatasetType::Volume => {
+                for child in self.get_child_filesystems()? {
+                    dependents.push(Dependent::new(child, DependentRelation::Child)?);
+                }
+                for snap in self.get_snapshots_ordered()? {
+                    dependents.push(Dependent::new(snap, DependentRelation::Snapshot)?);
+                }
+            }
+            _ => (),
+        }
+        Ok(dependents)
+    }
+}
+
+impl Dependent {
+    fn new(dataset: Dataset, relation: DependentRelation) -> Result<Self> {
+        Ok(Dependent {
+            dataset_type: dataset.get_type(),
+            dependents: dataset.dependents()?,
+            dataset,
+            relation,
+        })
+    }
+
+    /// Flatten dependency trees into an order in which everything in them can be destroyed,
+    /// each dataset after everything which depends on it, listing each dataset only once.
+    pub fn destroy_order(dependents: &[Dependent]) -> Vec<&Dependent> {
+        fn visit<'a>(
+            dependent: &'a Dependent,
+            seen: &mut HashSet<SafeString>,
+            order: &mut Vec<&'a Dependent>,
+        ) {
+            for inner in &dependent.dependents {
+                visit(inner, seen, order);
+            }
+            if seen.insert(dependent.dataset.get_name()) {
+                order.push(dependent);
+            }
+        }
+
+        let mut seen = HashSet::new();
+        let mut order = vec![];
+        for dependent in dependents {
+            visit(dependent, &mut seen, &mut order);
+        }
+        order
+    }
+}
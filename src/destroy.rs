@@ -0,0 +1,71 @@
+use crate::{Dataset, DatasetType, Error, Plan, Result, SafeString};
+
+/// Options for [`Dataset::destroy_recursive`].
+#[derive(Debug, Clone, Default)]
+pub struct DestroyOptions {
+    /// Also destroy clones of snapshots being destroyed, and their descendants, even if they are
+    /// elsewhere in the pool, like `zfs destroy -R`. Otherwise, any such clones make the whole
+    /// operation fail before anything is destroyed, like `zfs destroy -r`.
+    pub clones: bool,
+    /// Don't destroy anything; only report what would be destroyed, like `zfs destroy -n`. See
+    /// also [`Dataset::plan_destroy`], which says more.
+    pub dry_run: bool,
+}
+
+impl Dataset {
+    /// Destroy this dataset along with all its descendants and snapshots, unmounting any
+    /// filesystems first. Returns the names of everything destroyed (or that would be, with
+    /// [`dry_run`](DestroyOptions::dry_run)), in the order they were destroyed.
+    ///
+    /// If an error occurs partway through, whatever was destroyed up to that point stays
+    /// destroyed.
+    pub fn destroy_recursive(&self, options: &DestroyOptions) -> Result<Vec<SafeString>> {
+        let mut destroyed = vec![];
+        for ds in self.destroy_targets(options)? {
+            let name = ds.get_name();
+            if !options.dry_run {
+                if ds.get_type() == DatasetType::Filesystem && ds.is_mounted() {
+                    ds.unmount()?;
+                }
+                ds.destroy()?;
+            }
+            destroyed.push(name);
+        }
+        Ok(destroyed)
+    }
+
+    /// Work out what [`destroy_recursive`](Self::destroy_recursive) would do with the given
+    /// options, without destroying anything: which datasets would be destroyed, which of them
+    /// are clones from elsewhere in the pool, and which filesystems would be unmounted. Fails
+    /// the same way it would if it can't be done.
+    pub fn plan_destroy(&self, options: &DestroyOptions) -> Result<Plan> {
+        let targets = self.destroy_targets(options)?;
+        let mut plan = Plan::default();
+        plan.destroy(&targets);
+        plan.clones_destroyed = targets.iter()
+            .filter(|ds| !self.is_ancestor_of(ds) && ds.name() != self.name())
+            .map(Dataset::get_name)
+            .collect();
+        Ok(plan)
+    }
+
+    /// Everything [`destroy_recursive`](Self::destroy_recursive) would destroy, in an order in
+    /// which it can be: everything after whatever depends on it, ending with this dataset.
+    pub(crate) fn destroy_targets(&self, options: &DestroyOptions) -> Result<Vec<Dataset>> {
+        let dependents = self.all_dependents()?;
+        if !options.clones {
+            if let Some(clone) = dependents.iter().find(|ds| !self.is_ancestor_of(ds)) {
+                return Err(Error::busy(
+                    format!("{} has dependent clone {}", self.name(), clone.name())));
+            }
+        }
+        Ok(dependents.into_iter().chain(Some(self.clone())).collect())
+    }
+
+    /// Whether a dataset is a descendant of this one, or a snapshot or bookmark of this one or
+    /// a descendant.
+    pub(crate) fn is_ancestor_of(&self, ds: &Dataset) -> bool {
+        ds.name().as_str().strip_prefix(self.name().as_str())
+            .is_some_and(|rest| rest.starts_with(['/', '@', '#']))
+    }
+}
@@ -0,0 +1,180 @@
+use crate::{sys, ztry, Dataset, Error, Result, SafeString, ZfsError};
+
+use std::ffi::OsString;
+use std::fs;
+use std::io::{self, Read};
+use std::os::fd::AsRawFd;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStringExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::thread;
+
+/// How a file changed between two snapshots.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DiffChange {
+    Added,
+    Removed,
+    /// The file's contents or metadata changed.
+    Modified,
+    /// The file was renamed or moved; this is the path it has now.
+    Renamed { to: PathBuf },
+}
+
+/// The kind of file a [`DiffEntry`] is about.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DiffFileType {
+    File,
+    Directory,
+    Symlink,
+    BlockDevice,
+    CharDevice,
+    Fifo,
+    Socket,
+    Door,
+    EventPort,
+    Unknown,
+}
+
+impl DiffFileType {
+    fn from_char(c: &str) -> Self {
+        match c {
+            "F" => DiffFileType::File,
+            "/" => DiffFileType::Directory,
+            "@" => DiffFileType::Symlink,
+            "B" => DiffFileType::BlockDevice,
+            "C" => DiffFileType::CharDevice,
+            "|" => DiffFileType::Fifo,
+            "=" => DiffFileType::Socket,
+            ">" => DiffFileType::Door,
+            "P" => DiffFileType::EventPort,
+            _ => DiffFileType::Unknown,
+        }
+    }
+}
+
+/// One changed file, from [`Dataset::diff_snapshots`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffEntry {
+    pub change: DiffChange,
+    /// The file's path (its old path, if it was renamed), under the filesystem's mountpoint.
+    pub path: PathBuf,
+    pub file_type: DiffFileType,
+    /// The file's inode number, if it could be found through the filesystem's `.zfs/snapshot`
+    /// directory.
+    pub inode: Option<u64>,
+    /// For a modified directory, how much its link count changed by, which is how many
+    /// subdirectories were added or removed.
+    pub link_change: Option<i64>,
+}
+
+impl Dataset {
+    /// List the files which changed in this filesystem between two of its snapshots, like
+    /// `zfs diff`. Snapshots can be given as `@snap` or in full as `fs@snap`. If `newer` is
+    /// `None`, the older snapshot is compared to the filesystem as it is now.
+    ///
+    /// The filesystem has to be mounted, and this needs root (or the `diff` permission).
+    pub fn diff_snapshots(&self, older: &str, newer: Option<&str>) -> Result<Vec<DiffEntry>> {
+        let older_c = SafeString::try_from(older)?;
+        let newer_c = newer.map(SafeString::try_from).transpose()?;
+        let flags = sys::diff_flags::ZFS_DIFF_PARSEABLE | sys::diff_flags::ZFS_DIFF_CLASSIFY;
+
+        let (mut reader, writer) = io::pipe().map_err(Error::Sys)?;
+        let read = thread::spawn(move || {
+            let mut output = vec![];
+            reader.read_to_end(&mut output).map(|_| output)
+        });
+        {
+            let _lock = self.libzfs.lock();
+            ztry!(unsafe {
+                sys::zfs_show_diffs(
+                    self.handle,
+                    writer.as_raw_fd(),
+                    older_c.as_ptr(),
+                    newer_c.as_ref().map_or(ptr::null(), |newer| newer.as_ptr()),
+                    flags as c_int,
+                )
+            }, self.libzfs.raw(), self.name());
+        }
+        drop(writer);
+        let output = read.join()
+            .unwrap_or_else(|_| Err(io::Error::other("diff reader panicked")))
+            .map_err(Error::Sys)?;
+
+        let mountpoint = self.mounted_at();
+        let snapshot_path = |snap: Option<&str>, path: &Path| -> Option<PathBuf> {
+            let mountpoint = mountpoint.as_ref()?;
+            let relative = path.strip_prefix(mountpoint).ok()?;
+            Some(match snap {
+                Some(snap) => {
+                    let short = snap.rsplit_once('@').map_or(snap, |(_, short)| short);
+                    mountpoint.join(".zfs/snapshot").join(short).join(relative)
+                }
+                None => path.to_owned(),
+            })
+        };
+        let inode = |snap, path: &Path| {
+            snapshot_path(snap, path)
+                .and_then(|path| fs::symlink_metadata(path).ok())
+                .map(|meta| meta.ino())
+        };
+
+        let mut entries = vec![];
+        for line in output.split(|&b| b == b'\n').filter(|line| !line.is_empty()) {
+            let line = String::from_utf8_lossy(line);
+            let cols = line.split('\t').collect::<Vec<_>>();
+            let [change, file_type, path, rest @ ..] = cols.as_slice() else {
+                return Err(Error::invalid_data(format!("unexpected zfs diff output: {}", line)));
+            };
+            let path = unmangle(path);
+            let (change, inode, link_change) = match *change {
+                "+" => (DiffChange::Added, inode(newer, &path), None),
+                "-" => (DiffChange::Removed, inode(Some(older), &path), None),
+                "M" => {
+                    let link_change = rest.first()
+                        .and_then(|delta| delta.strip_prefix('(')?.strip_suffix(')')?.parse().ok());
+                    (DiffChange::Modified, inode(newer, &path), link_change)
+                }
+                "R" => {
+                    let to = rest.first()
+                        .map(|to| unmangle(to))
+                        .ok_or_else(|| Error::invalid_data("zfs diff rename has no new path"))?;
+                    let inode = inode(newer, &to);
+                    (DiffChange::Renamed { to }, inode, None)
+                }
+                _ => return Err(Error::invalid_data(
+                    format!("unknown zfs diff change type {:?}", change))),
+            };
+            entries.push(DiffEntry {
+                change,
+                path,
+                file_type: DiffFileType::from_char(file_type),
+                inode,
+                link_change,
+            });
+        }
+        Ok(entries)
+    }
+}
+
+/// Undo the escaping `zfs diff` does of bytes in paths which aren't printable, or are
+/// whitespace or backslashes, which it writes as a backslash and four octal digits.
+fn unmangle(path: &str) -> PathBuf {
+    let mut bytes = vec![];
+    let mut rest = path.as_bytes();
+    while let Some((&b, after)) = rest.split_first() {
+        if b == b'\\' && after.len() >= 4 {
+            let octal = std::str::from_utf8(&after[..4]).ok()
+                .and_then(|digits| u8::from_str_radix(digits, 8).ok());
+            if let Some(byte) = octal {
+                bytes.push(byte);
+                rest = &after[4..];
+                continue;
+            }
+        }
+        bytes.push(b);
+        rest = after;
+    }
+    PathBuf::from(OsString::from_vec(bytes))
+}
@@ -0,0 +1,103 @@
+use crate::{Error, Events, LibZfs, NvListRef, Result, ZfsEvent};
+use crate::event::EVENT_POLL_INTERVAL;
+
+use std::io;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::thread::{self, JoinHandle};
+
+type EventHandler = Box<dyn FnMut(&ZfsEvent, &NvListRef) + Send>;
+
+/// Runs callbacks for ZFS events by their class, like a minimal zed.
+///
+/// Register handlers with [`on`](Self::on), then either feed it events with
+/// [`dispatch`](Self::dispatch) or have it read them itself on a thread of its own with
+/// [`run`](Self::run).
+#[derive(Default)]
+pub struct EventDispatcher {
+    handlers: Vec<(String, EventHandler)>,
+}
+
+impl EventDispatcher {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a handler for events of the given class, like `ereport.fs.zfs.checksum`. A class
+    /// ending in `*` matches every class starting with what comes before it, so
+    /// `ereport.fs.zfs.*` matches all ZFS fault reports and `*` matches everything. The handler
+    /// gets both the decoded event and its raw nvlist.
+    ///
+    /// Handlers run in the order they were registered, one event at a time.
+    pub fn on<F>(&mut self, class: &str, handler: F) -> &mut Self
+        where F: FnMut(&ZfsEvent, &NvListRef) + Send + 'static,
+    {
+        self.handlers.push((class.to_owned(), Box::new(handler)));
+        self
+    }
+
+    /// Run the handlers registered for an event's class. Events which can't be decoded (which
+    /// have no class) are ignored.
+    pub fn dispatch(&mut self, raw: &NvListRef) {
+        let Ok(event) = ZfsEvent::from_nvlist(raw) else {
+            return;
+        };
+        for (class, handler) in &mut self.handlers {
+            if class_matches(class, event.class()) {
+                handler(&event, raw);
+            }
+        }
+    }
+
+    /// Start reading the event stream and dispatching each event, on a thread of its own. See
+    /// [`LibZfs::events`] for what `only_new` does. Runs until the returned handle is stopped,
+    /// or reading an event fails.
+    pub fn run(self, libzfs: &LibZfs, only_new: bool) -> Result<DispatcherHandle> {
+        let events = libzfs.events(only_new)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let thread = {
+            let stop = stop.clone();
+            thread::spawn(move || self.dispatch_loop(events, &stop))
+        };
+        Ok(DispatcherHandle { stop, thread })
+    }
+
+    fn dispatch_loop(mut self, mut events: Events, stop: &AtomicBool) -> Result<()> {
+        while !stop.load(Ordering::Relaxed) {
+            match events.try_next_raw()? {
+                Some(raw) => self.dispatch(&raw),
+                None => thread::sleep(EVENT_POLL_INTERVAL),
+            }
+        }
+        Ok(())
+    }
+}
+
+fn class_matches(pattern: &str, class: &str) -> bool {
+    match pattern.strip_suffix('*') {
+        Some(prefix) => class.starts_with(prefix),
+        None => pattern == class,
+    }
+}
+
+/// A running [`EventDispatcher`], from [`EventDispatcher::run`].
+pub struct DispatcherHandle {
+    stop: Arc<AtomicBool>,
+    thread: JoinHandle<Result<()>>,
+}
+
+impl DispatcherHandle {
+    /// Whether the dispatcher is still running. It stops by itself only if reading an event
+    /// fails.
+    pub fn is_running(&self) -> bool {
+        !self.thread.is_finished()
+    }
+
+    /// Stop the dispatcher and wait for it to finish handling the current event, if any. Returns
+    /// the error which stopped it, if it had already stopped by itself.
+    pub fn stop(self) -> Result<()> {
+        self.stop.store(true, Ordering::Relaxed);
+        self.thread.join()
+            .unwrap_or_else(|_| Err(Error::Sys(io::Error::other("event handler panicked"))))
+    }
+}
@@ -0,0 +1,380 @@
+use crate::{sys, ztry, Dataset, Error, KeyFormat, KeyStatus, NvList, Result, SafeStr};
+use crate::{SafeString, ZfsError};
+use crate::error::lzc_call;
+
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::ops::{Deref, DerefMut};
+use std::os::raw::c_uint;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{self, Ordering};
+
+/// The length of the key which wraps a dataset's master key, in bytes.
+const WRAPPING_KEY_LEN: usize = 32;
+/// Passphrase length limits, from `sys/dsl_crypt.h`.
+const MIN_PASSPHRASE_LEN: usize = 8;
+const MAX_PASSPHRASE_LEN: usize = 512;
+/// PBKDF2 iterations for new passphrases, like `zfs change-key` uses by default.
+const DEFAULT_PBKDF2_ITERATIONS: u64 = 350000;
+/// `DCP_CMD_NEW_KEY`, from `sys/dsl_crypt.h`.
+const DCP_CMD_NEW_KEY: u64 = 2;
+
+/// The value of an encryption root's `keylocation` property: where `zfs load-key` gets its key
+/// from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum KeyLocation {
+    /// Asked for on the terminal.
+    Prompt,
+    /// Read from a file, given as a `file://` URI.
+    File(PathBuf),
+    /// Fetched from an `https://` or `http://` URI, given as it is.
+    Url(String),
+    /// The dataset isn't an encryption root, or isn't encrypted.
+    None,
+}
+
+impl KeyLocation {
+    fn parse(value: &str) -> KeyLocation {
+        match value {
+            "prompt" => KeyLocation::Prompt,
+            "none" | "-" | "" => KeyLocation::None,
+            _ => match value.strip_prefix("file://") {
+                Some(path) => KeyLocation::File(PathBuf::from(path)),
+                None => KeyLocation::Url(value.to_owned()),
+            },
+        }
+    }
+}
+
+impl fmt::Display for KeyLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeyLocation::Prompt => f.write_str("prompt"),
+            KeyLocation::File(path) => write!(f, "file://{}", path.display()),
+            KeyLocation::Url(url) => f.write_str(url),
+            KeyLocation::None => f.write_str("none"),
+        }
+    }
+}
+
+/// A key, from a [`KeyProvider`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeyMaterial {
+    /// The key as `zfs load-key` would read it from a file: a passphrase, 64 hex digits, or 32
+    /// raw bytes, depending on the `keyformat`. A newline at the end of a passphrase or hex key
+    /// is ignored.
+    Bytes(Vec<u8>),
+    /// Have libzfs get the key from this location, like `zfs load-key -L`. [`KeyLocation::None`]
+    /// means the dataset's own `keylocation`.
+    Location(KeyLocation),
+}
+
+/// Where to get a key from, for [`Dataset::load_key`] and [`Dataset::change_key`].
+///
+/// This is implemented for strings (a passphrase or hex key), bytes, paths (a file to read the
+/// key from), [`KeyLocation`], and closures which are given the dataset's name and return a
+/// passphrase or hex key, like by asking the user for it.
+pub trait KeyProvider {
+    /// Get the key for the given dataset.
+    fn key_material(&self, dataset: &SafeStr) -> Result<KeyMaterial>;
+}
+
+impl KeyProvider for str {
+    fn key_material(&self, _dataset: &SafeStr) -> Result<KeyMaterial> {
+        Ok(KeyMaterial::Bytes(self.as_bytes().to_vec()))
+    }
+}
+
+impl KeyProvider for String {
+    fn key_material(&self, dataset: &SafeStr) -> Result<KeyMaterial> {
+        self.as_str().key_material(dataset)
+    }
+}
+
+impl KeyProvider for [u8] {
+    fn key_material(&self, _dataset: &SafeStr) -> Result<KeyMaterial> {
+        Ok(KeyMaterial::Bytes(self.to_vec()))
+    }
+}
+
+impl KeyProvider for Vec<u8> {
+    fn key_material(&self, dataset: &SafeStr) -> Result<KeyMaterial> {
+        self.as_slice().key_material(dataset)
+    }
+}
+
+impl KeyProvider for Path {
+    fn key_material(&self, _dataset: &SafeStr) -> Result<KeyMaterial> {
+        fs::read(self).map(KeyMaterial::Bytes).map_err(Error::Sys)
+    }
+}
+
+impl KeyProvider for PathBuf {
+    fn key_material(&self, dataset: &SafeStr) -> Result<KeyMaterial> {
+        self.as_path().key_material(dataset)
+    }
+}
+
+impl KeyProvider for KeyLocation {
+    fn key_material(&self, _dataset: &SafeStr) -> Result<KeyMaterial> {
+        Ok(KeyMaterial::Location(self.clone()))
+    }
+}
+
+impl<F> KeyProvider for F
+    where F: Fn(&SafeStr) -> Result<String>,
+{
+    fn key_material(&self, dataset: &SafeStr) -> Result<KeyMaterial> {
+        self(dataset).map(|key| KeyMaterial::Bytes(key.into_bytes()))
+    }
+}
+
+/// Key bytes, which are zeroed when dropped, so they don't linger in freed memory.
+struct Secret(Vec<u8>);
+
+impl Deref for Secret {
+    type Target = Vec<u8>;
+    fn deref(&self) -> &Vec<u8> {
+        &self.0
+    }
+}
+
+impl DerefMut for Secret {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.0
+    }
+}
+
+impl Drop for Secret {
+    fn drop(&mut self) {
+        // Volatile writes, so they aren't optimized away as dead stores.
+        for byte in self.0.iter_mut() {
+            unsafe { ptr::write_volatile(byte, 0) };
+        }
+        atomic::compiler_fence(Ordering::SeqCst);
+    }
+}
+
+/// Turn a key as read from its source into the key which wraps the dataset's master key, like
+/// libzfs's `derive_key`: raw keys are used as they are, hex keys are decoded, and passphrases
+/// are put through PBKDF2-HMAC-SHA1 with the dataset's salt and iteration count.
+fn wrapping_key(format: &KeyFormat, key: &[u8], salt: u64, iters: u64) -> Result<Secret> {
+    let text = key.strip_suffix(b"\n").unwrap_or(key);
+    match format {
+        KeyFormat::Raw => {
+            if key.len() != WRAPPING_KEY_LEN {
+                return Err(Error::invalid_input(
+                    format!("raw keys must be {} bytes", WRAPPING_KEY_LEN)));
+            }
+            Ok(Secret(key.to_vec()))
+        }
+        KeyFormat::Hex => {
+            let digits = std::str::from_utf8(text).ok()
+                .filter(|s| s.len() == WRAPPING_KEY_LEN * 2)
+                .ok_or_else(|| Error::invalid_input(
+                    format!("hex keys must be {} hex digits", WRAPPING_KEY_LEN * 2)))?;
+            let mut wkey = Secret(Vec::with_capacity(WRAPPING_KEY_LEN));
+            for i in 0 .. WRAPPING_KEY_LEN {
+                wkey.push(u8::from_str_radix(&digits[i * 2 .. i * 2 + 2], 16)
+                    .map_err(|_| Error::invalid_input("hex keys must be all hex digits"))?);
+            }
+            Ok(wkey)
+        }
+        KeyFormat::Passphrase => {
+            if !(MIN_PASSPHRASE_LEN ..= MAX_PASSPHRASE_LEN).contains(&text.len()) {
+                return Err(Error::invalid_input(format!(
+                    "passphrases must be {} to {} bytes", MIN_PASSPHRASE_LEN, MAX_PASSPHRASE_LEN)));
+            }
+            let iters = u32::try_from(iters).map_err(|_| {
+                Error::invalid_data(format!("too many PBKDF2 iterations: {}", iters))
+            })?;
+            let mut wkey = Secret(vec![0u8; WRAPPING_KEY_LEN]);
+            // libzfs hashes the salt as it's laid out in memory, in the machine's byte order.
+            pbkdf2::pbkdf2_hmac::<sha1::Sha1>(text, &salt.to_ne_bytes(), iters, &mut wkey);
+            Ok(wkey)
+        }
+        _ => Err(Error::invalid_input(format!("unsupported key format {}", format))),
+    }
+}
+
+/// Read a random number for a new passphrase's salt.
+fn random_salt() -> Result<u64> {
+    let mut buf = [0u8; 8];
+    File::open("/dev/urandom")
+        .and_then(|mut f| f.read_exact(&mut buf))
+        .map_err(Error::Sys)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+impl Dataset {
+    /// Whether this dataset is encrypted, which is decided when it's created.
+    pub fn is_encrypted(&self) -> Result<bool> {
+        Ok(self.get_native_property(sys::zfs_prop_t::ZFS_PROP_ENCRYPTION)? != "off")
+    }
+
+    /// Get whether this dataset's key is loaded, so its data can be read and written.
+    pub fn key_status(&self) -> Result<KeyStatus> {
+        self.get_typed_property()
+    }
+
+    /// Get the format of the key of this dataset's encryption root.
+    pub fn key_format(&self) -> Result<KeyFormat> {
+        self.get_typed_property()
+    }
+
+    /// Get where this dataset's key is loaded from. Only encryption roots have one; for other
+    /// datasets it's [`KeyLocation::None`], and the key comes from their
+    /// [`encryption_root`](Self::encryption_root).
+    pub fn key_location(&self) -> Result<KeyLocation> {
+        let value = self.get_native_property(sys::zfs_prop_t::ZFS_PROP_KEYLOCATION)?;
+        Ok(KeyLocation::parse(&value))
+    }
+
+    /// Get the name of the dataset this one gets its key from, which may be itself, or `None` if
+    /// it isn't encrypted. Loading that dataset's key makes this one's data available too.
+    pub fn encryption_root(&self) -> Result<Option<SafeString>> {
+        let value = self.get_native_property(sys::zfs_prop_t::ZFS_PROP_ENCRYPTION_ROOT)?;
+        if value.is_empty() || value == "-" {
+            return Ok(None);
+        }
+        SafeString::try_from(value).map(Some)
+    }
+
+    /// Load the key of this encryption root, like `zfs load-key`, making its data and that of
+    /// the datasets which inherit its key available. The key is checked against the dataset's
+    /// `keyformat`; a wrong key fails with `EACCES`.
+    pub fn load_key<K: KeyProvider + ?Sized>(&self, key: &K) -> Result<()> {
+        self.load_key_impl(key, false)
+    }
+
+    /// Check that a key is right for this encryption root without loading it, like
+    /// `zfs load-key -n`.
+    pub fn check_key<K: KeyProvider + ?Sized>(&self, key: &K) -> Result<()> {
+        self.load_key_impl(key, true)
+    }
+
+    fn load_key_impl<K: KeyProvider + ?Sized>(&self, key: &K, noop: bool) -> Result<()> {
+        match self.encryption_root()? {
+            None => {
+                return Err(Error::invalid_input(format!("{} is not encrypted", self.name())));
+            }
+            Some(root) if root.as_str() != self.name().as_str() => {
+                return Err(Error::invalid_input(format!(
+                    "{} is not an encryption root; its key is loaded from {}", self.name(), root)));
+            }
+            Some(_) => (),
+        }
+
+        let bytes = match key.key_material(&self.name())? {
+            KeyMaterial::Location(location) => {
+                let alt = match location {
+                    KeyLocation::None => None,
+                    location => Some(SafeString::try_from(location.to_string())?),
+                };
+                let _lock = self.libzfs.lock();
+                ztry!(unsafe {
+                    sys::zfs_crypto_load_key(
+                        self.handle,
+                        noop as sys::boolean_t,
+                        alt.as_ref().map_or(ptr::null(), |alt| alt.as_ptr()) as _,
+                    )
+                }, self.libzfs.raw(), self.name());
+                return Ok(());
+            }
+            KeyMaterial::Bytes(bytes) => Secret(bytes),
+        };
+
+        let salt = self.get_int_property(sys::zfs_prop_t::ZFS_PROP_PBKDF2_SALT);
+        let iters = self.get_int_property(sys::zfs_prop_t::ZFS_PROP_PBKDF2_ITERS);
+        let mut wkey = wrapping_key(&self.key_format()?, &bytes, salt, iters)?;
+        let ret = lzc_call!(unsafe {
+            sys::lzc_load_key(
+                self.name().as_ptr(),
+                noop as sys::boolean_t,
+                wkey.as_mut_ptr(),
+                wkey.len() as c_uint,
+            )
+        }, self.name());
+        if ret != 0 {
+            return Err(Error::Sys(io::Error::from_raw_os_error(ret))
+                .context("load key for", self.name()));
+        }
+        Ok(())
+    }
+
+    /// Unload the key of this encryption root, like `zfs unload-key`. Its filesystems must be
+    /// unmounted, and its volumes not in use.
+    pub fn unload_key(&self) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe { sys::zfs_crypto_unload_key(self.handle) }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Change the key of this encrypted dataset, like `zfs change-key -o keyformat=...
+    /// -o keylocation=...`, giving the new key directly, or as a file to read it from. If the
+    /// dataset isn't an encryption root, it becomes one. Its current key must be loaded.
+    ///
+    /// Only the key which wraps the dataset's master key changes, so no data is rewritten. A new
+    /// passphrase gets a new random salt.
+    pub fn change_key<K: KeyProvider + ?Sized>(
+        &self,
+        key: &K,
+        format: KeyFormat,
+        location: &KeyLocation,
+    ) -> Result<()> {
+        let format_index = match format {
+            KeyFormat::Raw => sys::zfs_keyformat::ZFS_KEYFORMAT_RAW,
+            KeyFormat::Hex => sys::zfs_keyformat::ZFS_KEYFORMAT_HEX,
+            KeyFormat::Passphrase => sys::zfs_keyformat::ZFS_KEYFORMAT_PASSPHRASE,
+            _ => return Err(Error::invalid_input(format!("unsupported key format {}", format))),
+        };
+        match location {
+            KeyLocation::None => {
+                return Err(Error::invalid_input("an encryption root needs a key location"));
+            }
+            KeyLocation::File(path) if !path.is_absolute() => {
+                return Err(Error::invalid_input(
+                    format!("key file {} must be an absolute path", path.display())));
+            }
+            _ => (),
+        }
+        let bytes = Secret(match key.key_material(&self.name())? {
+            KeyMaterial::Bytes(bytes) => bytes,
+            KeyMaterial::Location(KeyLocation::File(path)) => fs::read(path).map_err(Error::Sys)?,
+            KeyMaterial::Location(_) => {
+                return Err(Error::invalid_input("a new key must be given directly or as a file"));
+            }
+        });
+
+        let (salt, iters) = if format == KeyFormat::Passphrase {
+            (random_salt()?, DEFAULT_PBKDF2_ITERATIONS)
+        } else {
+            (0, 0)
+        };
+        let mut wkey = wrapping_key(&format, &bytes, salt, iters)?;
+        let mut props = NvList::new()?;
+        props.add_uint64("keyformat", format_index as u64)?;
+        props.add_string("keylocation", &location.to_string())?;
+        if format == KeyFormat::Passphrase {
+            props.add_uint64("pbkdf2salt", salt)?;
+            props.add_uint64("pbkdf2iters", iters)?;
+        }
+        let ret = lzc_call!(unsafe {
+            sys::lzc_change_key(
+                self.name().as_ptr(),
+                DCP_CMD_NEW_KEY,
+                props.as_ptr(),
+                wkey.as_mut_ptr(),
+                wkey.len() as c_uint,
+            )
+        }, self.name());
+        if ret != 0 {
+            return Err(Error::Sys(io::Error::from_raw_os_error(ret))
+                .context("change key for", self.name()));
+        }
+        Ok(())
+    }
+}
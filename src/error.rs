@@ -1,41 +1,212 @@
 extern crate libzfs_sys as sys;
 
+use crate::{NvList, NvValue, SafeString};
+
+use std::collections::BTreeMap;
 use std::ffi::{CStr};
 use std::fmt;
+use std::io;
 use std::mem::transmute;
+use std::os::raw::c_int;
+
+use sys::zfs_error::*;
 
+/// Return the libzfs handle's last error if the call returned nonzero. With the `tracing`
+/// feature, the call is logged too, along with the target given as an optional third argument,
+/// which is only evaluated then.
 #[macro_export]
 macro_rules! ztry {
     ($stuff:expr, $lzfs:expr) => {
+        $crate::ztry!($stuff, $lzfs, "")
+    };
+    ($stuff:expr, $lzfs:expr, $target:expr) => {
         {
+            #[cfg(feature = "tracing")]
+            let start = std::time::Instant::now();
             let result = $stuff;
             if result != 0 {
                 let e = ZfsError::last_error($lzfs);
+                #[cfg(feature = "tracing")]
+                $crate::trace::ffi_call(stringify!($stuff), &$target, start, Some(&e));
                 if matches!(e.code, sys::zfs_error::EZFS_UNKNOWN | sys::zfs_error::EZFS_SUCCESS) {
-                    return Err(Error::Sys(std::io::Error::last_os_error()));
+                    // Tracing may have changed errno since, so use what was captured.
+                    return Err(Error::Sys(std::io::Error::from_raw_os_error(e.errno.unwrap_or(0))));
                 } else {
                     return Err(Error::Zfs(e));
                 }
             }
+            #[cfg(feature = "tracing")]
+            $crate::trace::ffi_call(stringify!($stuff), &$target, start, None);
             result
         }
     };
 }
 
+/// Make a libzfs_core call, which reports errors by returning an errno rather than through the
+/// libzfs handle, and evaluate to what it returned. With the `tracing` feature, the call is
+/// logged like those made through [`ztry!`], along with the target given as the second
+/// argument, which is only evaluated then.
+macro_rules! lzc_call {
+    ($stuff:expr, $target:expr) => {
+        {
+            #[cfg(feature = "tracing")]
+            let start = std::time::Instant::now();
+            let result = $stuff;
+            #[cfg(feature = "tracing")]
+            $crate::trace::lzc_call(stringify!($stuff), &$target, start, result);
+            result
+        }
+    };
+}
+pub(crate) use lzc_call;
+
 pub type Result<T> = ::std::result::Result<T, Error>;
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ZfsError {
+    /// The libzfs error code.
     pub code: sys::zfs_error,
+    /// libzfs's description of why the operation failed, like `dataset is busy`. Kept separate
+    /// from the action, so it can be matched on or shown alone.
     pub msg: String,
+    /// What libzfs was trying to do when it failed, like `cannot destroy 'tank/fs'`, if it said.
+    /// This is what the `zfs` and `zpool` commands print before the message.
+    pub action: String,
+    /// The errno behind the error, if known. libzfs doesn't record one, so for errors from
+    /// libzfs this is whatever errno the failed call left behind, which is usually the one from
+    /// the ioctl that failed, but not always.
+    pub errno: Option<i32>,
 }
 
 impl ZfsError {
     pub(crate) fn last_error(handle: *mut sys::libzfs_handle_t) -> Self {
-        let code: sys::zfs_error = unsafe { transmute(sys::libzfs_errno(handle)) };
+        let errno = io::Error::last_os_error().raw_os_error().filter(|&e| e != 0);
+        let code = zfs_error_from_raw(unsafe { sys::libzfs_errno(handle) });
         let msg_cstr = unsafe { CStr::from_ptr(sys::libzfs_error_description(handle)) };
         let msg = msg_cstr.to_string_lossy().into_owned();
-        ZfsError { code, msg }
+        let action_cstr = unsafe { CStr::from_ptr(sys::libzfs_error_action(handle)) };
+        let action = action_cstr.to_string_lossy().into_owned();
+        ZfsError { code, msg, action, errno }
+    }
+
+    /// The pool, dataset, or other object operated on doesn't exist.
+    pub fn is_not_found(&self) -> bool {
+        matches!(self.code, EZFS_NOENT)
+    }
+
+    /// The pool, dataset, or other object to be created already exists.
+    pub fn is_exists(&self) -> bool {
+        matches!(self.code, EZFS_EXISTS | EZFS_CHECKPOINT_EXISTS)
+    }
+
+    /// The object is in use, or some conflicting operation is in progress.
+    pub fn is_busy(&self) -> bool {
+        match self.code {
+            EZFS_BUSY
+            | EZFS_ACTIVE_POOL
+            | EZFS_ACTIVE_SPARE
+            | EZFS_RESILVERING
+            | EZFS_REBUILDING
+            | EZFS_SCRUBBING
+            | EZFS_INITIALIZING
+            | EZFS_TRIMMING
+            | EZFS_DEVRM_IN_PROGRESS
+            | EZFS_EXPORT_IN_PROGRESS
+            | EZFS_DISCARDING_CHECKPOINT => true,
+            #[cfg(zfs_2_2)]
+            EZFS_ERRORSCRUBBING => true,
+            _ => false,
+        }
+    }
+
+    /// The caller isn't allowed to do this.
+    pub fn is_permission(&self) -> bool {
+        match self.code {
+            EZFS_PERM | EZFS_NODELEGATION => true,
+            #[cfg(zfs_2_2)]
+            EZFS_NOT_USER_NAMESPACE => true,
+            _ => false,
+        }
+    }
+
+    /// The operation isn't supported by the pool, vdev, or kernel module.
+    pub fn is_not_supported(&self) -> bool {
+        match self.code {
+            EZFS_NOTSUP
+            | EZFS_BADVERSION
+            | EZFS_POOL_NOTSUP
+            | EZFS_VDEVNOTSUP
+            | EZFS_TRIM_NOTSUP
+            | EZFS_IOC_NOTSUPPORTED => true,
+            #[cfg(zfs_2_2)]
+            EZFS_VDEV_NOTSUP => true,
+            _ => false,
+        }
+    }
+
+    /// A name, property, or other argument was invalid.
+    pub fn is_invalid_argument(&self) -> bool {
+        matches!(self.code,
+            EZFS_BADPROP
+            | EZFS_PROPREADONLY
+            | EZFS_PROPTYPE
+            | EZFS_PROPNONINHERIT
+            | EZFS_BADTYPE
+            | EZFS_INVALIDNAME
+            | EZFS_NAMETOOLONG
+            | EZFS_TAGTOOLONG
+            | EZFS_POOL_INVALARG
+            | EZFS_BADWHO
+            | EZFS_BADPERM
+            | EZFS_BADPERMSET
+            | EZFS_WRONG_PARENT)
+    }
+
+    /// The pool or dataset is out of space, or the value would exceed a quota.
+    pub fn is_no_space(&self) -> bool {
+        matches!(self.code, EZFS_NOSPC | EZFS_PROPSPACE | EZFS_VOLTOOBIG | EZFS_VDEV_TOO_BIG)
+    }
+
+    /// The pool or dataset is read-only.
+    pub fn is_read_only(&self) -> bool {
+        matches!(self.code, EZFS_DSREADONLY | EZFS_POOLREADONLY)
+    }
+
+    /// The closest [`io::ErrorKind`] to this error, going by its code, or else its errno.
+    pub fn io_kind(&self) -> io::ErrorKind {
+        if self.is_not_found() {
+            io::ErrorKind::NotFound
+        } else if self.is_exists() {
+            io::ErrorKind::AlreadyExists
+        } else if self.is_busy() {
+            io::ErrorKind::ResourceBusy
+        } else if self.is_permission() {
+            io::ErrorKind::PermissionDenied
+        } else if self.is_not_supported() {
+            io::ErrorKind::Unsupported
+        } else if self.is_invalid_argument() {
+            io::ErrorKind::InvalidInput
+        } else if self.is_no_space() {
+            io::ErrorKind::StorageFull
+        } else if self.is_read_only() {
+            io::ErrorKind::ReadOnlyFilesystem
+        } else if let Some(errno) = self.errno {
+            io::Error::from_raw_os_error(errno).kind()
+        } else {
+            io::ErrorKind::Other
+        }
+    }
+}
+
+/// Convert a raw libzfs error code. Codes newer than the headers we were built against become
+/// `EZFS_UNKNOWN`.
+fn zfs_error_from_raw(raw: i32) -> sys::zfs_error {
+    let raw = raw as u32;
+    if raw == EZFS_SUCCESS as u32 || (EZFS_NOMEM as u32 ..= EZFS_UNKNOWN as u32).contains(&raw) {
+        // Safety: the codes from EZFS_NOMEM to EZFS_UNKNOWN are contiguous.
+        unsafe { transmute::<u32, sys::zfs_error>(raw) }
+    } else {
+        EZFS_UNKNOWN
     }
 }
 
@@ -43,7 +214,11 @@ impl ::std::error::Error for ZfsError {}
 
 impl fmt::Display for ZfsError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "ZFS error {:?}: {}", self.code, self.msg)
+        if self.action.is_empty() {
+            write!(f, "ZFS error {:?}: {}", self.code, self.msg)
+        } else {
+            write!(f, "ZFS error {:?}: {}: {}", self.code, self.action, self.msg)
+        }
     }
 }
 
@@ -51,6 +226,15 @@ impl fmt::Display for ZfsError {
 pub enum Error {
     Sys(::std::io::Error),
     Zfs(ZfsError),
+    /// Another error, along with what was being done and to which pool or dataset, for errors
+    /// which wouldn't say otherwise, like the errnos from operations done in bulk.
+    Context {
+        /// What was being done, like `snapshot` or `destroy`.
+        op: &'static str,
+        /// The name of the pool, dataset, snapshot, or bookmark it was being done to.
+        target: String,
+        source: Box<Error>,
+    },
 }
 
 impl ::std::error::Error for Error {
@@ -58,6 +242,7 @@ impl ::std::error::Error for Error {
         match self {
             Error::Sys(e) => Some(e),
             Error::Zfs(e) => Some(e),
+            Error::Context { source, .. } => Some(&**source),
         }
     }
 }
@@ -67,6 +252,22 @@ impl fmt::Display for Error {
         match *self {
             Error::Sys(ref e) => e.fmt(f),
             Error::Zfs(ref e) => e.fmt(f),
+            Error::Context { op, ref target, ref source } => {
+                write!(f, "cannot {} {}: {}", op, target, source)
+            }
+        }
+    }
+}
+
+/// Lets this crate's errors be returned from functions returning `io::Result`. A `ZfsError`
+/// becomes an `io::Error` of its [`io_kind`](ZfsError::io_kind), which it can be got back out
+/// of with `get_ref` and `downcast_ref`.
+impl From<Error> for io::Error {
+    fn from(e: Error) -> io::Error {
+        match e {
+            Error::Sys(e) => e,
+            Error::Zfs(e) => io::Error::new(e.io_kind(), e),
+            Error::Context { .. } => io::Error::new(e.io_kind(), e),
         }
     }
 }
@@ -76,3 +277,130 @@ impl From<ZfsError> for Error {
         Error::Zfs(z)
     }
 }
+
+impl Error {
+    /// Wrap this error with what was being done, and to what, when that isn't already clear.
+    pub fn context(self, op: &'static str, target: impl fmt::Display) -> Error {
+        Error::Context { op, target: target.to_string(), source: Box::new(self) }
+    }
+
+    /// The error underneath any context.
+    pub fn root(&self) -> &Error {
+        match self {
+            Error::Context { source, .. } => source.root(),
+            e => e,
+        }
+    }
+
+    /// The closest [`io::ErrorKind`] to this error.
+    fn io_kind(&self) -> io::ErrorKind {
+        match self {
+            Error::Sys(e) => e.kind(),
+            Error::Zfs(e) => e.io_kind(),
+            Error::Context { source, .. } => source.io_kind(),
+        }
+    }
+
+    /// Whether this is a "not found" error, either from libzfs or the OS.
+    pub fn is_not_found(&self) -> bool {
+        match self {
+            Error::Sys(e) => e.kind() == io::ErrorKind::NotFound,
+            Error::Zfs(e) => e.is_not_found(),
+            Error::Context { source, .. } => source.is_not_found(),
+        }
+    }
+
+    /// Whether this is an "already exists" error, either from libzfs or the OS.
+    pub fn is_exists(&self) -> bool {
+        match self {
+            Error::Sys(e) => e.kind() == io::ErrorKind::AlreadyExists,
+            Error::Zfs(e) => e.is_exists(),
+            Error::Context { source, .. } => source.is_exists(),
+        }
+    }
+
+    /// Whether this is a "busy" error, either from libzfs or the OS.
+    pub fn is_busy(&self) -> bool {
+        match self {
+            Error::Sys(e) => match e.raw_os_error() {
+                Some(errno) => errno == libc::EBUSY,
+                // One of ours, like from `Error::busy`.
+                None => e.kind() == io::ErrorKind::ResourceBusy,
+            },
+            Error::Zfs(e) => e.is_busy(),
+            Error::Context { source, .. } => source.is_busy(),
+        }
+    }
+
+    /// Whether this is a permission error, either from libzfs or the OS.
+    pub fn is_permission(&self) -> bool {
+        match self {
+            Error::Sys(e) => e.kind() == io::ErrorKind::PermissionDenied,
+            Error::Zfs(e) => e.is_permission(),
+            Error::Context { source, .. } => source.is_permission(),
+        }
+    }
+
+    /// An error for unexpected or malformed data, such as a config nvlist missing something.
+    pub(crate) fn invalid_data<E>(e: E) -> Self
+        where E: Into<Box<dyn ::std::error::Error + Send + Sync>>,
+    {
+        Error::Sys(::std::io::Error::new(::std::io::ErrorKind::InvalidData, e))
+    }
+
+    /// An error for bad arguments passed in by the caller.
+    pub(crate) fn invalid_input<E>(e: E) -> Self
+        where E: Into<Box<dyn ::std::error::Error + Send + Sync>>,
+    {
+        Error::Sys(::std::io::Error::new(::std::io::ErrorKind::InvalidInput, e))
+    }
+
+    /// An error for something which can't be done because of something else depending on or
+    /// using it.
+    pub(crate) fn busy<E>(e: E) -> Self
+        where E: Into<Box<dyn ::std::error::Error + Send + Sync>>,
+    {
+        Error::Sys(::std::io::Error::new(::std::io::ErrorKind::ResourceBusy, e))
+    }
+
+    /// An error for something the caller asked to create which already exists.
+    pub(crate) fn exists<E>(e: E) -> Self
+        where E: Into<Box<dyn ::std::error::Error + Send + Sync>>,
+    {
+        Error::Sys(::std::io::Error::new(::std::io::ErrorKind::AlreadyExists, e))
+    }
+
+    /// An error for something the caller asked for which doesn't exist.
+    pub(crate) fn not_found<E>(e: E) -> Self
+        where E: Into<Box<dyn ::std::error::Error + Send + Sync>>,
+    {
+        Error::Sys(::std::io::Error::new(::std::io::ErrorKind::NotFound, e))
+    }
+}
+
+/// Work out the error for each item of a libzfs_core batch operation `op` which failed with
+/// `ret`. The `errlist` has an int32 errno for each item which failed; the others failed only
+/// because the batch did, so they get the batch's error.
+pub(crate) fn batch_errors(
+    op: &'static str,
+    names: Vec<SafeString>,
+    ret: c_int,
+    errlist: *mut sys::nvlist_t,
+) -> Vec<(SafeString, Error)> {
+    let errlist = (!errlist.is_null()).then(|| unsafe { NvList::from_raw(errlist) });
+    let mut errnos = BTreeMap::new();
+    if let Some(errlist) = &errlist {
+        for pair in errlist.iter() {
+            if let NvValue::Int32(e) = pair.value() {
+                errnos.insert(pair.name().into_owned(), e);
+            }
+        }
+    }
+    names.into_iter()
+        .map(|name| {
+            let e = errnos.get(name.as_str()).copied().unwrap_or(ret);
+            let error = Error::Sys(io::Error::from_raw_os_error(e)).context(op, &name);
+            (name, error)
+        })
+        .collect()
+}
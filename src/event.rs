@@ -0,0 +1,290 @@
+use crate::{sys, Error, LibZfs, NvList, NvListRef, NvValue, Result, SafeString, VdevState};
+use crate::ZfsError;
+use crate::handle::LibZfsHandle;
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::path::PathBuf;
+use std::ptr;
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// `ZEVENT_SEEK_END`, which bindgen can't evaluate.
+pub(crate) const ZEVENT_SEEK_END: u64 = u64::MAX;
+
+/// How often [`Events`] checks for a new event while waiting.
+pub(crate) const EVENT_POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// The vdev an event is about.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct EventVdev {
+    pub guid: Option<u64>,
+    /// The device's path, for disks and files.
+    pub path: Option<PathBuf>,
+    /// The kind of vdev, like `disk` or `mirror`.
+    pub vdev_type: Option<String>,
+}
+
+impl EventVdev {
+    fn from_nvlist(nv: &NvListRef) -> Option<Self> {
+        let vdev = EventVdev {
+            guid: nv.lookup_uint64("vdev_guid"),
+            path: nv.lookup_string("vdev_path").map(|path| PathBuf::from(path.into_owned())),
+            vdev_type: nv.lookup_string("vdev_type").map(|t| t.into_owned()),
+        };
+        if vdev == EventVdev::default() {
+            None
+        } else {
+            Some(vdev)
+        }
+    }
+}
+
+/// A ZFS event, like `zpool events` shows, decoded from the nvlist the kernel posts. Only the
+/// commonly handled classes get their own variant; everything else is [`Other`](Self::Other).
+///
+/// `pool` is `None` for the rare events not tied to a pool, or if the name isn't valid UTF-8.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ZfsEvent {
+    /// A block read back with the wrong checksum (`ereport.fs.zfs.checksum`).
+    ChecksumError {
+        pool: Option<SafeString>,
+        vdev: Option<EventVdev>,
+        /// Where on the vdev, and how many bytes.
+        offset: Option<u64>,
+        size: Option<u64>,
+    },
+    /// A read or write to a vdev failed (`ereport.fs.zfs.io`).
+    IoError {
+        pool: Option<SafeString>,
+        vdev: Option<EventVdev>,
+        /// The errno the I/O failed with.
+        errno: Option<i32>,
+        offset: Option<u64>,
+        size: Option<u64>,
+    },
+    /// Data was lost because no copy of a block could be read (`ereport.fs.zfs.data`).
+    DataError {
+        pool: Option<SafeString>,
+        errno: Option<i32>,
+    },
+    /// A device was removed from the system (`resource.fs.zfs.removed`).
+    DeviceRemoved {
+        pool: Option<SafeString>,
+        vdev: Option<EventVdev>,
+    },
+    /// A vdev changed state, like going from online to faulted
+    /// (`resource.fs.zfs.statechange`).
+    VdevStateChanged {
+        pool: Option<SafeString>,
+        vdev: Option<EventVdev>,
+        state: VdevState,
+        previous: Option<VdevState>,
+    },
+    /// `sysevent.fs.zfs.scrub_start`
+    ScrubStarted { pool: Option<SafeString> },
+    /// `sysevent.fs.zfs.scrub_finish`
+    ScrubFinished { pool: Option<SafeString> },
+    /// `sysevent.fs.zfs.resilver_start`
+    ResilverStarted { pool: Option<SafeString> },
+    /// `sysevent.fs.zfs.resilver_finish`
+    ResilverFinished { pool: Option<SafeString> },
+    /// `sysevent.fs.zfs.pool_create`
+    PoolCreated { pool: Option<SafeString> },
+    /// `sysevent.fs.zfs.pool_import`
+    PoolImported { pool: Option<SafeString> },
+    /// `sysevent.fs.zfs.pool_destroy`
+    PoolDestroyed { pool: Option<SafeString> },
+    /// Any other class of event; the raw nvlist has the details.
+    Other {
+        class: String,
+        pool: Option<SafeString>,
+    },
+}
+
+impl ZfsEvent {
+    /// Decode an event from its raw nvlist.
+    pub fn from_nvlist(nv: &NvListRef) -> Result<Self> {
+        let class = nv.lookup_string("class")
+            .ok_or_else(|| Error::invalid_data("event has no class"))?;
+        // Ereports name the pool as "pool", and other events as "pool_name".
+        let pool = nv.lookup_string("pool")
+            .or_else(|| nv.lookup_string("pool_name"))
+            .and_then(|name| SafeString::try_from(name.as_ref()).ok());
+        let vdev = EventVdev::from_nvlist(nv);
+        let errno = lookup_int32(nv, "zio_err");
+        let offset = nv.lookup_uint64("zio_offset");
+        let size = nv.lookup_uint64("zio_size");
+        Ok(match class.as_ref() {
+            "ereport.fs.zfs.checksum" => ZfsEvent::ChecksumError { pool, vdev, offset, size },
+            "ereport.fs.zfs.io" => ZfsEvent::IoError { pool, vdev, errno, offset, size },
+            "ereport.fs.zfs.data" => ZfsEvent::DataError { pool, errno },
+            "resource.fs.zfs.removed" => ZfsEvent::DeviceRemoved { pool, vdev },
+            "resource.fs.zfs.statechange" => {
+                let state = |name| nv.lookup_uint64(name)
+                    .map(|s| VdevState::from_raw(s as sys::vdev_state::Type));
+                ZfsEvent::VdevStateChanged {
+                    pool,
+                    vdev,
+                    state: state("vdev_state").unwrap_or(VdevState::Unknown),
+                    previous: state("vdev_laststate"),
+                }
+            }
+            "sysevent.fs.zfs.scrub_start" => ZfsEvent::ScrubStarted { pool },
+            "sysevent.fs.zfs.scrub_finish" => ZfsEvent::ScrubFinished { pool },
+            "sysevent.fs.zfs.resilver_start" => ZfsEvent::ResilverStarted { pool },
+            "sysevent.fs.zfs.resilver_finish" => ZfsEvent::ResilverFinished { pool },
+            "sysevent.fs.zfs.pool_create" => ZfsEvent::PoolCreated { pool },
+            "sysevent.fs.zfs.pool_import" => ZfsEvent::PoolImported { pool },
+            "sysevent.fs.zfs.pool_destroy" => ZfsEvent::PoolDestroyed { pool },
+            _ => ZfsEvent::Other { class: class.into_owned(), pool },
+        })
+    }
+
+    /// The event's class, like `ereport.fs.zfs.checksum`.
+    pub fn class(&self) -> &str {
+        match self {
+            ZfsEvent::ChecksumError { .. } => "ereport.fs.zfs.checksum",
+            ZfsEvent::IoError { .. } => "ereport.fs.zfs.io",
+            ZfsEvent::DataError { .. } => "ereport.fs.zfs.data",
+            ZfsEvent::DeviceRemoved { .. } => "resource.fs.zfs.removed",
+            ZfsEvent::VdevStateChanged { .. } => "resource.fs.zfs.statechange",
+            ZfsEvent::ScrubStarted { .. } => "sysevent.fs.zfs.scrub_start",
+            ZfsEvent::ScrubFinished { .. } => "sysevent.fs.zfs.scrub_finish",
+            ZfsEvent::ResilverStarted { .. } => "sysevent.fs.zfs.resilver_start",
+            ZfsEvent::ResilverFinished { .. } => "sysevent.fs.zfs.resilver_finish",
+            ZfsEvent::PoolCreated { .. } => "sysevent.fs.zfs.pool_create",
+            ZfsEvent::PoolImported { .. } => "sysevent.fs.zfs.pool_import",
+            ZfsEvent::PoolDestroyed { .. } => "sysevent.fs.zfs.pool_destroy",
+            ZfsEvent::Other { class, .. } => class,
+        }
+    }
+
+    /// The pool the event is about, if any.
+    pub fn pool(&self) -> Option<&SafeString> {
+        match self {
+            ZfsEvent::ChecksumError { pool, .. }
+            | ZfsEvent::IoError { pool, .. }
+            | ZfsEvent::DataError { pool, .. }
+            | ZfsEvent::DeviceRemoved { pool, .. }
+            | ZfsEvent::VdevStateChanged { pool, .. }
+            | ZfsEvent::ScrubStarted { pool }
+            | ZfsEvent::ScrubFinished { pool }
+            | ZfsEvent::ResilverStarted { pool }
+            | ZfsEvent::ResilverFinished { pool }
+            | ZfsEvent::PoolCreated { pool }
+            | ZfsEvent::PoolImported { pool }
+            | ZfsEvent::PoolDestroyed { pool }
+            | ZfsEvent::Other { pool, .. } => pool.as_ref(),
+        }
+    }
+}
+
+/// When a raw event was posted, from its `time` field.
+pub fn event_time(nv: &NvListRef) -> Option<SystemTime> {
+    match nv.lookup_uint64_array("time")? {
+        [secs, nanos, ..] => Some(UNIX_EPOCH + Duration::new(*secs, *nanos as u32)),
+        _ => None,
+    }
+}
+
+fn lookup_int32(nv: &NvListRef, name: &str) -> Option<i32> {
+    nv.iter().find(|pair| pair.name() == name).and_then(|pair| match pair.value() {
+        NvValue::Int32(value) => Some(value),
+        _ => None,
+    })
+}
+
+/// A reader of the ZFS event stream, like `zpool events`, created by [`LibZfs::events`].
+/// Reading events needs root.
+///
+/// Each reader has its own position in the stream, so several can read the same events.
+/// Iterating waits for each next event, so the iterator never ends.
+pub struct Events {
+    libzfs: Arc<LibZfsHandle>,
+    /// `/dev/zfs`, which keeps this reader's position in the stream.
+    fd: OwnedFd,
+    dropped: u64,
+}
+
+impl LibZfs {
+    /// Start reading the event stream. If `only_new`, events already posted are skipped, like
+    /// `zpool events -f` does; otherwise reading starts from the oldest event the kernel still
+    /// has.
+    pub fn events(&self, only_new: bool) -> Result<Events> {
+        let fd = unsafe { libc::open(c"/dev/zfs".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return Err(Error::Sys(io::Error::last_os_error()));
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        if only_new {
+            let _lock = self.handle.lock();
+            if unsafe { sys::zpool_events_seek(self.handle.raw(), ZEVENT_SEEK_END, fd.as_raw_fd()) }
+                != 0
+            {
+                return Err(ZfsError::last_error(self.handle.raw()).into());
+            }
+        }
+        Ok(Events { libzfs: self.handle.clone(), fd, dropped: 0 })
+    }
+}
+
+impl Events {
+    /// Get the next event as its raw nvlist, or `None` if there are no more right now.
+    pub fn try_next_raw(&mut self) -> Result<Option<NvList>> {
+        let _lock = self.libzfs.lock();
+        let mut nvl = ptr::null_mut();
+        let mut dropped = 0;
+        let ret = unsafe {
+            sys::zpool_events_next(
+                self.libzfs.raw(),
+                &mut nvl,
+                &mut dropped,
+                sys::ZEVENT_NONBLOCK,
+                self.fd.as_raw_fd(),
+            )
+        };
+        self.dropped += dropped as u64;
+        if ret != 0 {
+            return Err(ZfsError::last_error(self.libzfs.raw()).into());
+        }
+        if nvl.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(unsafe { NvList::from_raw(nvl) }))
+    }
+
+    /// Get the next event, or `None` if there are no more right now.
+    pub fn try_next(&mut self) -> Result<Option<ZfsEvent>> {
+        match self.try_next_raw()? {
+            Some(nv) => ZfsEvent::from_nvlist(&nv).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Wait for the next event as its raw nvlist.
+    pub fn next_raw(&mut self) -> Result<NvList> {
+        // A blocking read would hold the libzfs lock for as long as it waits, so poll instead.
+        loop {
+            if let Some(nv) = self.try_next_raw()? {
+                return Ok(nv);
+            }
+            thread::sleep(EVENT_POLL_INTERVAL);
+        }
+    }
+
+    /// How many events were missed so far because the kernel's queue overflowed before they
+    /// could be read.
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+}
+
+impl Iterator for Events {
+    type Item = Result<ZfsEvent>;
+
+    fn next(&mut self) -> Option<Result<ZfsEvent>> {
+        Some(self.next_raw().and_then(|nv| ZfsEvent::from_nvlist(&nv)))
+    }
+}
@@ -0,0 +1,86 @@
+use crate::{sys, ztry, DatasetType, Error, MountBusy, Mountpoint, Result, SafeString, ZfsError};
+use crate::ZPool;
+use crate::mount::processes_using;
+
+use std::fmt;
+use std::io;
+use std::ptr;
+
+/// Options for [`ZPool::export`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Forcibly unmount filesystems which are in use, like `zpool export -f`.
+    pub force: bool,
+    /// Export without even writing out pending changes, like `zpool export -F`. Only meant for
+    /// testing and for pools whose devices are already gone; implies `force`.
+    pub hard_force: bool,
+}
+
+/// The error inside a failed export's [`io::Error`] when filesystems in the pool are in use.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExportBlocked {
+    /// The busy filesystems, and who is using them.
+    pub busy: Vec<(SafeString, MountBusy)>,
+}
+
+impl fmt::Display for ExportBlocked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "pool is busy:")?;
+        for (i, (name, busy)) in self.busy.iter().enumerate() {
+            write!(f, "{} {}: {}", if i == 0 { "" } else { ";" }, name, busy)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ExportBlocked {}
+
+impl ZPool {
+    /// Unmount and unshare all of the pool's filesystems, then export it, like `zpool export`.
+    ///
+    /// Unless forced, nothing is unmounted if any of the filesystems are in use; on Linux the
+    /// error then wraps an [`ExportBlocked`] listing them and the processes using them.
+    pub fn export(self, options: &ExportOptions) -> Result<()> {
+        let force = options.force || options.hard_force;
+        if !force && cfg!(target_os = "linux") {
+            let busy = self.busy_filesystems()?;
+            if !busy.is_empty() {
+                return Err(Error::Sys(io::Error::new(io::ErrorKind::ResourceBusy,
+                    ExportBlocked { busy })));
+            }
+        }
+
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zpool_disable_datasets(self.handle, force as sys::boolean_t)
+        }, self.libzfs.raw(), self.name());
+        if options.hard_force {
+            ztry!(unsafe {
+                sys::zpool_export_force(self.handle, ptr::null())
+            }, self.libzfs.raw(), self.name());
+        } else {
+            ztry!(unsafe {
+                sys::zpool_export(self.handle, force as sys::boolean_t, ptr::null())
+            }, self.libzfs.raw(), self.name());
+        }
+        Ok(())
+    }
+
+    /// Find the mounted filesystems in the pool which processes are using. Filesystems with
+    /// legacy mountpoints aren't checked.
+    fn busy_filesystems(&self) -> Result<Vec<(SafeString, MountBusy)>> {
+        let mut busy = vec![];
+        for ds in self.get_datasets()? {
+            if ds.get_type() != DatasetType::Filesystem || !ds.is_mounted() {
+                continue;
+            }
+            if let Mountpoint::Path(mountpoint) = ds.mountpoint()? {
+                let pids = processes_using(&mountpoint);
+                if !pids.is_empty() {
+                    busy.push((ds.get_name(), MountBusy { mountpoint, pids }));
+                }
+            }
+        }
+        Ok(busy)
+    }
+}
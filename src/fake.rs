@@ -0,0 +1,249 @@
+use crate::{DatasetName, DatasetType, Error, Result, SafeString, ZfsApi};
+
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+/// An in-memory stand-in for ZFS, implementing [`ZfsApi`], for unit-testing code without root
+/// privileges or real pools.
+///
+/// It keeps only names, types, and the properties which were set, and checks only what's
+/// needed for the operations to make sense: parents have to exist, names can't be reused, and
+/// anything with children or snapshots can't be destroyed. Properties set on a filesystem are
+/// inherited by everything under it, as if all properties were inheritable. `type` and `name`
+/// are always there; other native properties only have values once they're set.
+#[derive(Debug, Default)]
+pub struct FakeZfs {
+    state: Mutex<FakeState>,
+}
+
+#[derive(Debug, Default)]
+struct FakeState {
+    datasets: BTreeMap<DatasetName, FakeDataset>,
+    /// Stands in for the transaction group, to keep snapshots in order of creation.
+    txg: u64,
+}
+
+#[derive(Debug)]
+struct FakeDataset {
+    dataset_type: DatasetType,
+    createtxg: u64,
+    props: BTreeMap<String, String>,
+}
+
+impl FakeZfs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a pool, with its root filesystem.
+    pub fn add_pool(&self, name: &str) -> Result<()> {
+        let name = DatasetName::parse(name)?;
+        if !name.is_pool_root() {
+            return Err(Error::invalid_input(format!("{:?} is not a pool name", name.to_string())));
+        }
+        self.state.lock().unwrap().insert(name, DatasetType::Filesystem, &[])
+    }
+}
+
+impl FakeState {
+    fn insert(&mut self, name: DatasetName, dataset_type: DatasetType, props: &[(&str, &str)])
+        -> Result<()>
+    {
+        if self.datasets.contains_key(&name) {
+            return Err(Error::exists(format!("{} already exists", name)));
+        }
+        if let Some(parent) = name.parent() {
+            self.get(&parent)?;
+        }
+        self.txg += 1;
+        let dataset = FakeDataset {
+            dataset_type,
+            createtxg: self.txg,
+            props: props.iter().map(|&(k, v)| (k.to_owned(), v.to_owned())).collect(),
+        };
+        self.datasets.insert(name, dataset);
+        Ok(())
+    }
+
+    fn get(&self, name: &DatasetName) -> Result<&FakeDataset> {
+        self.datasets.get(name).ok_or_else(|| Error::not_found(format!("{} does not exist", name)))
+    }
+}
+
+impl ZfsApi for FakeZfs {
+    fn pool_names(&self) -> Result<Vec<SafeString>> {
+        self.state.lock().unwrap().datasets.keys()
+            .filter(|name| name.is_pool_root())
+            .map(|name| SafeString::try_from(name.to_string()))
+            .collect()
+    }
+
+    fn exists(&self, name: &str) -> Result<bool> {
+        let name = DatasetName::parse(name)?;
+        Ok(self.state.lock().unwrap().datasets.contains_key(&name))
+    }
+
+    fn children(&self, name: &str) -> Result<Vec<SafeString>> {
+        let name = DatasetName::parse(name)?;
+        let state = self.state.lock().unwrap();
+        state.get(&name)?;
+        state.datasets.keys()
+            .filter(|child| child.suffix().is_none() && child.parent().as_ref() == Some(&name))
+            .map(|child| SafeString::try_from(child.to_string()))
+            .collect()
+    }
+
+    fn snapshots(&self, name: &str) -> Result<Vec<SafeString>> {
+        let name = DatasetName::parse(name)?;
+        let state = self.state.lock().unwrap();
+        state.get(&name)?;
+        let mut snapshots = state.datasets.iter()
+            .filter(|(snap, _)| snap.snapshot().is_some() && snap.filesystem() == name)
+            .collect::<Vec<_>>();
+        snapshots.sort_by_key(|(_, snap)| snap.createtxg);
+        snapshots.into_iter()
+            .map(|(snap, _)| SafeString::try_from(snap.to_string()))
+            .collect()
+    }
+
+    fn create_filesystem(&self, name: &str, props: &[(&str, &str)]) -> Result<()> {
+        let name = DatasetName::parse(name)?;
+        if name.suffix().is_some() {
+            return Err(Error::invalid_input(format!("{} is not a filesystem name", name)));
+        }
+        self.state.lock().unwrap().insert(name, DatasetType::Filesystem, props)
+    }
+
+    fn create_snapshot(&self, name: &str) -> Result<()> {
+        let name = DatasetName::parse(name)?;
+        if name.snapshot().is_none() {
+            return Err(Error::invalid_input(format!("{} is not a snapshot name", name)));
+        }
+        self.state.lock().unwrap().insert(name, DatasetType::Snapshot, &[])
+    }
+
+    fn destroy(&self, name: &str) -> Result<()> {
+        let name = DatasetName::parse(name)?;
+        let mut state = self.state.lock().unwrap();
+        state.get(&name)?;
+        if state.datasets.keys().any(|other| other.parent().as_ref() == Some(&name)) {
+            return Err(Error::busy(format!("{} has children or snapshots", name)));
+        }
+        state.datasets.remove(&name);
+        Ok(())
+    }
+
+    fn get_property(&self, name: &str, prop: &str) -> Result<String> {
+        let name = DatasetName::parse(name)?;
+        let state = self.state.lock().unwrap();
+        let dataset = state.get(&name)?;
+        match prop {
+            "name" => return Ok(name.to_string()),
+            "type" => return Ok(match dataset.dataset_type {
+                DatasetType::Snapshot => "snapshot",
+                _ => "filesystem",
+            }.to_owned()),
+            _ => (),
+        }
+        let mut next = Some(name);
+        while let Some(name) = next {
+            if let Some(value) = state.get(&name)?.props.get(prop) {
+                return Ok(value.clone());
+            }
+            next = name.parent();
+        }
+        Err(Error::not_found(format!("property {:?} is not set", prop)))
+    }
+
+    fn set_property(&self, name: &str, prop: &str, value: &str) -> Result<()> {
+        let name = DatasetName::parse(name)?;
+        let mut state = self.state.lock().unwrap();
+        let dataset = state.datasets.get_mut(&name)
+            .ok_or_else(|| Error::not_found(format!("{} does not exist", name)))?;
+        dataset.props.insert(prop.to_owned(), value.to_owned());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: Result<Vec<SafeString>>) -> Vec<String> {
+        names.unwrap().into_iter().map(String::from).collect()
+    }
+
+    #[test]
+    fn pools() {
+        let zfs = FakeZfs::new();
+        zfs.add_pool("tank").unwrap();
+        zfs.add_pool("backup").unwrap();
+        assert_eq!(names(zfs.pool_names()), ["backup", "tank"]);
+        assert!(zfs.add_pool("tank").unwrap_err().is_exists());
+        assert!(zfs.add_pool("tank/fs").is_err());
+    }
+
+    #[test]
+    fn create_needs_parent() {
+        let zfs = FakeZfs::new();
+        zfs.add_pool("tank").unwrap();
+        assert!(zfs.create_filesystem("tank/a/b", &[]).unwrap_err().is_not_found());
+        zfs.create_filesystem("tank/a", &[]).unwrap();
+        zfs.create_filesystem("tank/a/b", &[]).unwrap();
+        assert!(zfs.create_filesystem("tank/a", &[]).unwrap_err().is_exists());
+        assert!(zfs.create_filesystem("tank/a@snap", &[]).is_err());
+        assert!(zfs.exists("tank/a/b").unwrap());
+        assert!(!zfs.exists("tank/c").unwrap());
+        assert_eq!(names(zfs.children("tank")), ["tank/a"]);
+        assert_eq!(names(zfs.children("tank/a")), ["tank/a/b"]);
+    }
+
+    #[test]
+    fn snapshots_in_creation_order() {
+        let zfs = FakeZfs::new();
+        zfs.add_pool("tank").unwrap();
+        zfs.create_snapshot("tank@b").unwrap();
+        zfs.create_snapshot("tank@a").unwrap();
+        assert!(zfs.create_snapshot("tank").is_err());
+        assert!(zfs.create_snapshot("tank/fs@a").unwrap_err().is_not_found());
+        assert_eq!(names(zfs.snapshots("tank")), ["tank@b", "tank@a"]);
+        assert!(names(zfs.children("tank")).is_empty());
+    }
+
+    #[test]
+    fn destroy_needs_no_dependents() {
+        let zfs = FakeZfs::new();
+        zfs.add_pool("tank").unwrap();
+        zfs.create_filesystem("tank/fs", &[]).unwrap();
+        zfs.create_snapshot("tank/fs@snap").unwrap();
+        assert!(zfs.destroy("tank").unwrap_err().is_busy());
+        assert!(zfs.destroy("tank/fs").unwrap_err().is_busy());
+        zfs.destroy("tank/fs@snap").unwrap();
+        zfs.destroy("tank/fs").unwrap();
+        assert!(!zfs.exists("tank/fs").unwrap());
+        assert!(zfs.destroy("tank/fs").unwrap_err().is_not_found());
+    }
+
+    #[test]
+    fn properties_are_inherited() {
+        let zfs = FakeZfs::new();
+        zfs.add_pool("tank").unwrap();
+        zfs.create_filesystem("tank/a", &[("atime", "off")]).unwrap();
+        zfs.create_filesystem("tank/a/b", &[]).unwrap();
+        zfs.create_snapshot("tank/a/b@snap").unwrap();
+        zfs.set_property("tank", "compression", "lz4").unwrap();
+
+        assert_eq!(zfs.get_property("tank/a/b@snap", "compression").unwrap(), "lz4");
+        assert_eq!(zfs.get_property("tank/a/b", "atime").unwrap(), "off");
+        assert!(zfs.get_property("tank", "atime").unwrap_err().is_not_found());
+
+        zfs.set_property("tank/a/b", "compression", "zstd").unwrap();
+        assert_eq!(zfs.get_property("tank/a/b", "compression").unwrap(), "zstd");
+        assert_eq!(zfs.get_property("tank/a", "compression").unwrap(), "lz4");
+
+        assert_eq!(zfs.get_property("tank/a", "name").unwrap(), "tank/a");
+        assert_eq!(zfs.get_property("tank/a", "type").unwrap(), "filesystem");
+        assert_eq!(zfs.get_property("tank/a/b@snap", "type").unwrap(), "snapshot");
+        assert!(zfs.set_property("tank/c", "atime", "on").unwrap_err().is_not_found());
+    }
+}
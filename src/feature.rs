@@ -0,0 +1,410 @@
+use crate::{sys, ztry, Error, Result, SafeString, ZfsError, ZPool};
+use crate::compat;
+use crate::handle::init_prop_tables;
+use crate::string::string_from_buf;
+
+use std::convert::Infallible;
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::c_char;
+use std::ptr;
+use std::str::FromStr;
+
+/// Pools older than this on-disk version predate feature flags and need a version upgrade
+/// before any features can be enabled.
+const SPA_VERSION_FEATURES: u64 = 5000;
+
+/// Information about a pool feature known to the running libzfs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FeatureInfo {
+    /// User-facing name, as used in `feature@<name>` properties.
+    pub name: String,
+    /// On-disk identifier, e.g. `org.open-zfs:large_blocks`.
+    pub guid: String,
+    pub description: String,
+    /// Pools with this feature active can still be imported read-only by software which
+    /// doesn't understand it.
+    pub readonly_compatible: bool,
+    /// The feature becomes active as soon as it is enabled, rather than on first use.
+    pub activate_on_enable: bool,
+}
+
+impl FeatureInfo {
+    /// Which feature this is.
+    pub fn feature(&self) -> Feature {
+        Feature::from_str(&self.name).unwrap_or_else(|e| match e {})
+    }
+
+    fn from_sys(fi: &sys::zfeature_info_t) -> Self {
+        let s = |p: *const c_char| if p.is_null() {
+            String::new()
+        } else {
+            unsafe { CStr::from_ptr(p) }.to_string_lossy().into_owned()
+        };
+        let flag = |f: sys::zfeature_flags| (fi.fi_flags.0 & f.0) != 0;
+        FeatureInfo {
+            name: s(fi.fi_uname),
+            guid: s(fi.fi_guid),
+            description: s(fi.fi_desc),
+            readonly_compatible: flag(sys::zfeature_flags::ZFEATURE_FLAG_READONLY_COMPAT),
+            activate_on_enable: flag(sys::zfeature_flags::ZFEATURE_FLAG_ACTIVATE_ON_ENABLE),
+        }
+    }
+}
+
+/// libzfs's table of every feature it knows, filled in first if it hasn't been yet.
+fn feature_table() -> &'static [sys::zfeature_info_t] {
+    init_prop_tables();
+    unsafe { &*ptr::addr_of!(sys::spa_feature_table) }
+}
+
+/// Get all features supported by both libzfs and the running kernel module.
+pub(crate) fn supported_features() -> Vec<(usize, FeatureInfo)> {
+    feature_table().iter()
+        .enumerate()
+        .filter(|(_, fi)| fi.fi_zfs_mod_supported != 0)
+        .map(|(i, fi)| (i, FeatureInfo::from_sys(fi)))
+        .collect()
+}
+
+/// Get all features supported by both libzfs and the running kernel module, in the order
+/// `zpool upgrade -v` lists them.
+pub fn known_features() -> Vec<FeatureInfo> {
+    supported_features().into_iter().map(|(_, feature)| feature).collect()
+}
+
+/// Load a `compatibility` property value (a comma-separated list of compatibility files, or
+/// `off` / `legacy`) into a per-feature table of which features are allowed, indexed the same
+/// as `spa_feature_table`.
+#[cfg(zfs_2_1)]
+pub(crate) fn load_compat(compat: &str) -> Result<Vec<bool>> {
+    let mut allowed = vec![0 as sys::boolean_t; feature_table().len()];
+    let mut report = vec![0 as c_char; 1024];
+    let compat = SafeString::try_from(compat)?;
+    let status = unsafe {
+        sys::zpool_load_compat(
+            compat.as_ptr(),
+            allowed.as_mut_ptr(),
+            report.as_mut_ptr(),
+            report.len(),
+        )
+    };
+    match status {
+        sys::zpool_compat_status_t::ZPOOL_COMPATIBILITY_OK
+            | sys::zpool_compat_status_t::ZPOOL_COMPATIBILITY_WARNTOKEN => {
+            Ok(allowed.into_iter().map(|b| b != 0).collect())
+        }
+        _ => {
+            Err(Error::invalid_data(string_from_buf(&report)))
+        }
+    }
+}
+
+/// Before OpenZFS 2.1 there was no `compatibility` property, so every feature is allowed.
+#[cfg(not(zfs_2_1))]
+pub(crate) fn load_compat(_compat: &str) -> Result<Vec<bool>> {
+    Ok(vec![true; feature_table().len()])
+}
+
+/// Get the features which a `compatibility` property value allows: the features listed in all
+/// of the given compatibility files (like `grub2` or `openzfs-2.1-linux`, from
+/// `/usr/share/zfs/compatibility.d`), or every feature for `off`, or none for `legacy`.
+pub fn compatible_features(compatibility: &str) -> Result<Vec<FeatureInfo>> {
+    let allowed = load_compat(compatibility)?;
+    Ok(supported_features().into_iter()
+        .filter(|(idx, _)| allowed[*idx])
+        .map(|(_, feature)| feature)
+        .collect())
+}
+
+/// A feature which a pool upgrade would enable.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedFeature {
+    pub feature: FeatureInfo,
+    /// Whether the pool's `compatibility` property allows enabling this feature. Features which
+    /// are not allowed are left disabled by [`ZPool::upgrade`].
+    pub allowed: bool,
+}
+
+impl PlannedFeature {
+    /// Whether enabling this feature immediately prevents software that lacks it from importing
+    /// the pool read-write (or at all, if it is not read-only compatible).
+    pub fn breaks_older_imports(&self) -> bool {
+        self.allowed && self.feature.activate_on_enable
+    }
+}
+
+/// What [`ZPool::upgrade`] would do, as reported by [`ZPool::upgrade_plan`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UpgradePlan {
+    /// If the pool predates feature flags, its current on-disk version. Upgrading converts it to
+    /// a feature-flags pool, which older software cannot import at all.
+    pub legacy_version: Option<u64>,
+    /// The pool's `compatibility` property.
+    pub compatibility: String,
+    /// Features which are supported but currently disabled on the pool.
+    pub features: Vec<PlannedFeature>,
+}
+
+impl UpgradePlan {
+    /// Whether the upgrade would change anything at all.
+    pub fn is_empty(&self) -> bool {
+        self.legacy_version.is_none() && !self.features.iter().any(|f| f.allowed)
+    }
+
+    /// Features that the upgrade would actually enable.
+    pub fn to_enable(&self) -> impl Iterator<Item = &FeatureInfo> {
+        self.features.iter().filter(|f| f.allowed).map(|f| &f.feature)
+    }
+
+    /// Whether the upgrade would make the pool unimportable by older software right away, as
+    /// opposed to only once newly-enabled features are first used.
+    pub fn breaks_older_imports(&self) -> bool {
+        self.legacy_version.is_some() || self.features.iter().any(|f| f.breaks_older_imports())
+    }
+}
+
+impl ZPool {
+    /// Work out which features `upgrade()` would enable on this pool, without changing anything.
+    pub fn upgrade_plan(&self) -> Result<UpgradePlan> {
+        let version = self.version();
+        let legacy_version = if version < SPA_VERSION_FEATURES { Some(version) } else { None };
+
+        let compatibility = compat::pool_compatibility(self)?;
+        let allowed = load_compat(&compatibility)?;
+
+        let mut features = vec![];
+        for (idx, feature) in supported_features() {
+            if self.get_feature_state(&feature.name)? != FeatureState::Disabled {
+                continue;
+            }
+            features.push(PlannedFeature {
+                feature,
+                allowed: allowed[idx],
+            });
+        }
+
+        Ok(UpgradePlan { legacy_version, compatibility, features })
+    }
+
+    /// Upgrade the pool: convert it to feature flags if needed, and enable all supported features
+    /// permitted by its `compatibility` property. Returns the plan that was carried out.
+    pub fn upgrade(&self) -> Result<UpgradePlan> {
+        let _lock = self.libzfs.lock();
+        let plan = self.upgrade_plan()?;
+        if plan.legacy_version.is_some() {
+            ztry!(unsafe {
+                sys::zpool_upgrade(self.handle, SPA_VERSION_FEATURES)
+            }, self.libzfs.raw(), self.name());
+        }
+        for feature in plan.to_enable() {
+            let prop = SafeString::try_from(format!("feature@{}", feature.name))?;
+            ztry!(unsafe {
+                sys::zpool_set_prop(self.handle, prop.as_ptr(), c"enabled".as_ptr())
+            }, self.libzfs.raw(), self.name());
+        }
+        Ok(plan)
+    }
+
+    /// Get every supported feature along with its state on this pool, like the `feature@`
+    /// entries of `zpool get all`.
+    pub fn features(&self) -> Result<Vec<PoolFeature>> {
+        supported_features().into_iter()
+            .map(|(_, info)| {
+                let state = self.get_feature_state(&info.name)?;
+                Ok(PoolFeature { info, state })
+            })
+            .collect()
+    }
+
+    /// Enable a single feature, given as a [`Feature`] or by its short name (e.g.
+    /// `"zstd_compress"`), like
+    /// `zpool set feature@zstd_compress=enabled`, along with any features it depends on. Doing
+    /// this to a feature which is already enabled or active does nothing.
+    ///
+    /// Like any feature, once it becomes active, software which doesn't support it may no longer
+    /// be able to import the pool, and it can't be disabled again. Features not allowed by the
+    /// pool's `compatibility` property are refused.
+    pub fn enable_feature<F: AsRef<str>>(&self, feature: F) -> Result<()> {
+        let name = feature.as_ref();
+        let prop = format!("feature@{}", name);
+        self.validate_property(&prop, "enabled")?;
+        if self.get_feature_state(name)? != FeatureState::Disabled {
+            return Ok(());
+        }
+        self.set_property(&prop, "enabled")
+    }
+
+    /// Get the features enabled on this pool which the given `compatibility` value doesn't allow.
+    /// If this is not empty, setting the pool's `compatibility` to it won't make the pool
+    /// importable by the software it describes.
+    pub fn incompatible_features(&self, compatibility: &str) -> Result<Vec<FeatureInfo>> {
+        let allowed = load_compat(compatibility)?;
+        let mut incompatible = vec![];
+        for (idx, feature) in supported_features() {
+            if !allowed[idx] && self.get_feature_state(&feature.name)? != FeatureState::Disabled {
+                incompatible.push(feature);
+            }
+        }
+        Ok(incompatible)
+    }
+
+    /// Whether the pool predates feature flags, so no features can be enabled until it's
+    /// upgraded.
+    pub(crate) fn is_legacy_version(&self) -> bool {
+        self.version() < SPA_VERSION_FEATURES
+    }
+
+    fn version(&self) -> u64 {
+        let _lock = self.libzfs.lock();
+        unsafe {
+            sys::zpool_get_prop_int(self.handle, sys::zpool_prop_t::ZPOOL_PROP_VERSION, ptr::null_mut())
+        }
+    }
+
+    /// Get the state of a pool feature, given as a [`Feature`] or by its short name (e.g.
+    /// `"large_blocks"`).
+    pub fn get_feature_state<F: AsRef<str>>(&self, feature: F) -> Result<FeatureState> {
+        let _lock = self.libzfs.lock();
+        let prop = SafeString::try_from(format!("feature@{}", feature.as_ref()))?;
+        let mut buf = vec![0 as c_char; 64];
+        ztry!(unsafe {
+            sys::zpool_prop_get_feature(self.handle, prop.as_ptr(), buf.as_mut_ptr(), buf.len())
+        }, self.libzfs.raw(), self.name());
+        match string_from_buf(&buf).as_str() {
+            "disabled" => Ok(FeatureState::Disabled),
+            "enabled" => Ok(FeatureState::Enabled),
+            "active" => Ok(FeatureState::Active),
+            other => Err(Error::invalid_data(format!("unknown feature state {:?}", other))),
+        }
+    }
+}
+
+/// A feature and its state on a pool, from [`ZPool::features`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PoolFeature {
+    pub info: FeatureInfo,
+    pub state: FeatureState,
+}
+
+/// The state of a pool feature.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FeatureState {
+    Disabled,
+    /// Enabled, but not yet in use on disk.
+    Enabled,
+    /// In use on disk; software without this feature can't (fully) import the pool.
+    Active,
+}
+
+impl fmt::Display for FeatureState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            FeatureState::Disabled => "disabled",
+            FeatureState::Enabled => "enabled",
+            FeatureState::Active => "active",
+        })
+    }
+}
+
+macro_rules! features {
+    ( $( $variant:ident => $name:expr, )* ) => {
+        /// A pool feature, by its short name, as in `feature@<name>` properties. The variants are
+        /// the features of OpenZFS up to 2.3; see `zpool-features(7)` for what each one does.
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum Feature {
+            $( $variant, )*
+            /// A feature added by a newer version of ZFS.
+            Other(String),
+        }
+
+        impl Feature {
+            /// The feature's short name, like `zstd_compress`.
+            pub fn name(&self) -> &str {
+                match self {
+                    $( Feature::$variant => $name, )*
+                    Feature::Other(name) => name,
+                }
+            }
+        }
+
+        impl FromStr for Feature {
+            type Err = Infallible;
+            fn from_str(s: &str) -> std::result::Result<Feature, Infallible> {
+                Ok(match s {
+                    $( $name => Feature::$variant, )*
+                    _ => Feature::Other(s.to_owned()),
+                })
+            }
+        }
+    }
+}
+
+features! {
+        AsyncDestroy => "async_destroy",
+        EmptyBpobj => "empty_bpobj",
+        Lz4Compress => "lz4_compress",
+        MultiVdevCrashDump => "multi_vdev_crash_dump",
+        SpacemapHistogram => "spacemap_histogram",
+        EnabledTxg => "enabled_txg",
+        HoleBirth => "hole_birth",
+        ExtensibleDataset => "extensible_dataset",
+        EmbeddedData => "embedded_data",
+        Bookmarks => "bookmarks",
+        FilesystemLimits => "filesystem_limits",
+        LargeBlocks => "large_blocks",
+        LargeDnode => "large_dnode",
+        Sha512 => "sha512",
+        Skein => "skein",
+        Edonr => "edonr",
+        UserobjAccounting => "userobj_accounting",
+        Encryption => "encryption",
+        ProjectQuota => "project_quota",
+        DeviceRemoval => "device_removal",
+        ObsoleteCounts => "obsolete_counts",
+        ZpoolCheckpoint => "zpool_checkpoint",
+        SpacemapV2 => "spacemap_v2",
+        AllocationClasses => "allocation_classes",
+        ResilverDefer => "resilver_defer",
+        BookmarkV2 => "bookmark_v2",
+        RedactionBookmarks => "redaction_bookmarks",
+        RedactedDatasets => "redacted_datasets",
+        BookmarkWritten => "bookmark_written",
+        LogSpacemap => "log_spacemap",
+        Livelist => "livelist",
+        DeviceRebuild => "device_rebuild",
+        ZstdCompress => "zstd_compress",
+        Draid => "draid",
+        Zilsaxattr => "zilsaxattr",
+        HeadErrlog => "head_errlog",
+        Blake3 => "blake3",
+        BlockCloning => "block_cloning",
+        VdevZapsV2 => "vdev_zaps_v2",
+        RedactionListSpill => "redaction_list_spill",
+        RaidzExpansion => "raidz_expansion",
+        FastDedup => "fast_dedup",
+        Longname => "longname",
+        LargeMicrozap => "large_microzap",
+}
+
+impl Feature {
+    /// Information about this feature, if it's supported by both libzfs and the running kernel
+    /// module.
+    pub fn info(&self) -> Option<FeatureInfo> {
+        supported_features().into_iter()
+            .map(|(_, info)| info)
+            .find(|info| info.name == self.name())
+    }
+}
+
+impl AsRef<str> for Feature {
+    fn as_ref(&self) -> &str {
+        self.name()
+    }
+}
+
+impl fmt::Display for Feature {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{sys, Error, Result};
+
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::sync::{Arc, Condvar, Mutex, Once, PoisonError};
+use std::thread::{self, ThreadId};
+
+/// `libzfs_init` and `libzfs_fini` (re)initialize process-wide state such as the property
+/// tables, so they must never run concurrently.
+static INIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Fill in libzfs's property and feature tables, for looking things up in them without a
+/// library handle. `libzfs_init` does this too, but they may be wanted before one is opened.
+pub(crate) fn init_prop_tables() {
+    static TABLES: Once = Once::new();
+    TABLES.call_once(|| {
+        let _init = INIT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        unsafe {
+            sys::zfs_prop_init();
+            sys::zpool_prop_init();
+            sys::zpool_feature_init();
+        }
+    });
+}
+
+/// A libzfs library handle, shared by the `LibZfs` that opened it and every pool and dataset
+/// opened through it.
+///
+/// libzfs is not thread-safe: the library handle holds mutable state (the last error, the
+/// mnttab cache, ...) and every pool and dataset handle refers back to it. So all use of the
+/// handle, and of anything opened through it, must happen while holding its lock.
+pub(crate) struct LibZfsHandle {
+    raw: *mut sys::libzfs_handle_t,
+    lock: ReentrantLock,
+}
+
+// Safety: all access to the raw handle is serialized by the lock.
+unsafe impl Send for LibZfsHandle {}
+unsafe impl Sync for LibZfsHandle {}
+
+impl LibZfsHandle {
+    pub fn init() -> Result<Arc<Self>> {
+        let _init = INIT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        let raw = unsafe { sys::libzfs_init() };
+        if raw.is_null() {
+            Err(Error::Sys(io::Error::last_os_error()))
+        } else {
+            // Errors are returned with their text, so they shouldn't also go to stderr. libzfs
+            // leaves this off, but the zfs command turns it on, so make sure.
+            unsafe { sys::libzfs_print_on_error(raw, 0) };
+            Ok(Arc::new(LibZfsHandle { raw, lock: ReentrantLock::default() }))
+        }
+    }
+
+    /// Take ownership of a raw handle from `libzfs_init`.
+    ///
+    /// # Safety
+    /// `raw` must be a valid handle which nothing else will close.
+    pub unsafe fn from_raw(raw: *mut sys::libzfs_handle_t) -> Arc<Self> {
+        Arc::new(LibZfsHandle { raw, lock: ReentrantLock::default() })
+    }
+
+    /// Give up ownership of the raw handle, without closing it.
+    pub fn into_raw(self) -> *mut sys::libzfs_handle_t {
+        let raw = self.raw;
+        std::mem::forget(self);
+        raw
+    }
+
+    /// Get the raw handle. Only use it while holding the lock.
+    pub fn raw(&self) -> *mut sys::libzfs_handle_t {
+        self.raw
+    }
+
+    /// Take the lock, which must be held while calling into libzfs with this handle or any pool
+    /// or dataset handle opened through it.
+    pub fn lock(&self) -> LockGuard<'_> {
+        self.lock.lock()
+    }
+}
+
+impl Drop for LibZfsHandle {
+    fn drop(&mut self) {
+        let _init = INIT_LOCK.lock().unwrap_or_else(PoisonError::into_inner);
+        unsafe { sys::libzfs_fini(self.raw) };
+    }
+}
+
+impl fmt::Debug for LibZfsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("LibZfsHandle").field(&self.raw).finish()
+    }
+}
+
+/// A lock which the thread holding it can take again. It needs to be reentrant because libzfs
+/// calls back into our code while iterating, and those callbacks make further libzfs calls.
+#[derive(Default)]
+struct ReentrantLock {
+    state: Mutex<LockState>,
+    released: Condvar,
+}
+
+#[derive(Default)]
+struct LockState {
+    owner: Option<ThreadId>,
+    count: usize,
+}
+
+impl ReentrantLock {
+    fn lock(&self) -> LockGuard<'_> {
+        let me = thread::current().id();
+        let mut state = self.state.lock().unwrap_or_else(PoisonError::into_inner);
+        loop {
+            match state.owner {
+                None => {
+                    state.owner = Some(me);
+                    break;
+                }
+                Some(owner) if owner == me => break,
+                Some(_) => {
+                    state = self.released.wait(state).unwrap_or_else(PoisonError::into_inner);
+                }
+            }
+        }
+        state.count += 1;
+        LockGuard { lock: self, _not_send: PhantomData }
+    }
+}
+
+pub(crate) struct LockGuard<'a> {
+    lock: &'a ReentrantLock,
+    // The guard must be released on the thread which took it.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> Drop for LockGuard<'a> {
+    fn drop(&mut self) {
+        let mut state = self.lock.state.lock().unwrap_or_else(PoisonError::into_inner);
+        state.count -= 1;
+        if state.count == 0 {
+            state.owner = None;
+            self.lock.released.notify_one();
+        }
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{sys, ztry, Error, NvList, NvListRef, NvValue, Result, ZfsError, ZPool};
+
+use std::ptr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// One entry in a pool's history, from [`ZPool::history`].
+#[derive(Debug, Clone)]
+pub struct HistoryRecord {
+    pub time: SystemTime,
+    /// The user ID of whoever ran the command, for commands.
+    pub uid: Option<u32>,
+    /// The host the command ran on, for commands.
+    pub hostname: Option<String>,
+    /// The zone the command ran in, on systems with zones.
+    pub zone: Option<String>,
+    pub event: HistoryEvent,
+}
+
+/// What a [`HistoryRecord`] is about.
+#[derive(Debug, Clone)]
+pub enum HistoryEvent {
+    /// A command run by a user, like `zfs snapshot tank/fs@now`. These are what `zpool history`
+    /// shows by default.
+    Command(String),
+    /// A change ZFS made internally, which `zpool history -i` shows, like `snapshot` or `set`.
+    Internal {
+        /// Like `snapshot`, `set`, or `destroy`.
+        name: String,
+        /// The transaction group the change was made in.
+        txg: Option<u64>,
+        /// The dataset changed, if any.
+        dataset: Option<String>,
+        dataset_id: Option<u64>,
+        /// More about the change, like `compression=2`.
+        details: String,
+    },
+    /// An ioctl issued by a command, which `zpool history -i` also shows.
+    Ioctl {
+        /// Like `zfs_ioc_snapshot`.
+        ioctl: String,
+        /// The ioctl's arguments and results, if they were logged.
+        input: Option<NvList>,
+        output: Option<NvList>,
+        /// The errno the ioctl failed with, if it did.
+        errno: Option<i64>,
+        /// How long the ioctl took.
+        elapsed: Option<Duration>,
+    },
+    /// An internal event in the format from before OpenZFS 0.6.3, identified by number.
+    LegacyInternal {
+        event: u64,
+        txg: Option<u64>,
+        details: String,
+    },
+    /// A record this crate doesn't know how to read.
+    Unknown(NvList),
+}
+
+impl HistoryRecord {
+    fn from_nvlist(nv: &NvListRef) -> Self {
+        let string = |name| nv.lookup_string(name).map(|s| s.into_owned());
+        let event = if let Some(command) = string("history command") {
+            HistoryEvent::Command(command)
+        } else if let Some(name) = string("internal_name") {
+            HistoryEvent::Internal {
+                name,
+                txg: nv.lookup_uint64("history txg"),
+                dataset: string("dsname"),
+                dataset_id: nv.lookup_uint64("dsid"),
+                details: string("history internal str").unwrap_or_default(),
+            }
+        } else if let Some(ioctl) = string("ioctl") {
+            HistoryEvent::Ioctl {
+                ioctl,
+                input: nv.lookup_nvlist("in_nvl").map(NvListRef::to_owned),
+                output: nv.lookup_nvlist("out_nvl").map(NvListRef::to_owned),
+                errno: lookup_int64(nv, "errno"),
+                elapsed: lookup_int64(nv, "elapsed_ns")
+                    .map(|ns| Duration::from_nanos(ns as u64)),
+            }
+        } else if let Some(event) = nv.lookup_uint64("history internal event") {
+            HistoryEvent::LegacyInternal {
+                event,
+                txg: nv.lookup_uint64("history txg"),
+                details: string("history internal str").unwrap_or_default(),
+            }
+        } else {
+            HistoryEvent::Unknown(nv.to_owned())
+        };
+        HistoryRecord {
+            time: UNIX_EPOCH + Duration::from_secs(nv.lookup_uint64("history time").unwrap_or(0)),
+            uid: nv.lookup_uint64("history who").map(|uid| uid as u32),
+            hostname: string("history hostname"),
+            zone: string("history zone"),
+            event,
+        }
+    }
+}
+
+fn lookup_int64(nv: &NvListRef, name: &str) -> Option<i64> {
+    nv.iter().find(|pair| pair.name() == name).and_then(|pair| match pair.value() {
+        NvValue::Int64(value) => Some(value),
+        NvValue::UInt64(value) => Some(value as i64),
+        _ => None,
+    })
+}
+
+impl ZPool {
+    /// Get the pool's history: every command which changed it, and the internal changes and
+    /// ioctls they caused, oldest first, like `zpool history -il`. Only the most recent history
+    /// is kept, since the on-disk log has a fixed size and wraps around.
+    pub fn history(&self) -> Result<Vec<HistoryRecord>> {
+        let _lock = self.libzfs.lock();
+        let mut records = vec![];
+        let mut offset = 0;
+        loop {
+            let mut nvhis = ptr::null_mut();
+            let mut eof: sys::boolean_t = 0;
+            ztry!(unsafe {
+                sys::zpool_get_history(self.handle, &mut nvhis, &mut offset, &mut eof)
+            }, self.libzfs.raw(), self.name());
+            if !nvhis.is_null() {
+                let nvhis = unsafe { NvList::from_raw(nvhis) };
+                for record in nvhis.lookup_nvlist_array("history_record").unwrap_or_default() {
+                    records.push(HistoryRecord::from_nvlist(record));
+                }
+            }
+            if eof != 0 {
+                return Ok(records);
+            }
+        }
+    }
+}
@@ -0,0 +1,154 @@
+use crate::{sys, Dataset, DatasetType, Error, LibZfs, LibZfsCore, NvList, NvValue, Result};
+use crate::{SafeStr, SafeString, SnapshotError};
+use crate::error::{batch_errors, lzc_call};
+use crate::snapshot::snapshots_by_pool;
+
+use std::fs::OpenOptions;
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd, OwnedFd};
+use std::ptr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Open a file descriptor to pass to [`LibZfs::hold`] so the holds are released when it's
+/// closed, including when the process exits or crashes.
+pub fn hold_cleanup_fd() -> Result<OwnedFd> {
+    let file = OpenOptions::new().read(true).write(true).open("/dev/zfs").map_err(Error::Sys)?;
+    Ok(file.into())
+}
+
+impl LibZfs {
+    /// Place a hold with the given tag on each of the given snapshots, like
+    /// [`LibZfsCore::hold`].
+    pub fn hold<I, T>(&self, snapshots: I, tag: &str, cleanup_fd: Option<BorrowedFd<'_>>)
+        -> Result<Vec<SnapshotError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        LibZfsCore::new()?.hold(snapshots, tag, cleanup_fd)
+    }
+
+    /// Release the hold with the given tag from each of the given snapshots, like
+    /// [`LibZfsCore::release`].
+    pub fn release<I, T>(&self, snapshots: I, tag: &str) -> Result<Vec<SnapshotError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        LibZfsCore::new()?.release(snapshots, tag)
+    }
+}
+
+impl LibZfsCore {
+    /// Place a hold with the given tag on each of the given snapshots (full names, like
+    /// `pool/fs@snap`), like `zfs hold`, so they can't be destroyed until it's released.
+    /// Snapshots which don't exist are skipped and reported as failed.
+    ///
+    /// With a `cleanup_fd` from [`hold_cleanup_fd`], the holds are released automatically when
+    /// it's closed, so they don't outlive the process placing them.
+    ///
+    /// The holds are placed in one batch per pool, and each batch is all-or-nothing apart from
+    /// missing snapshots. Returns the snapshots which weren't held, along with the reason; an
+    /// empty list means all went well.
+    pub fn hold<I, T>(&self, snapshots: I, tag: &str, cleanup_fd: Option<BorrowedFd<'_>>)
+        -> Result<Vec<SnapshotError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        let cleanup_fd = cleanup_fd.map(|fd| fd.as_raw_fd()).unwrap_or(-1);
+        let mut failed = vec![];
+        for names in snapshots_by_pool(snapshots)? {
+            let mut nvl = NvList::new()?;
+            for name in &names {
+                nvl.add_string(name.as_str(), tag)?;
+            }
+
+            let mut errlist = ptr::null_mut();
+            let ret = lzc_call!(unsafe {
+                sys::lzc_hold(nvl.as_ptr(), cleanup_fd, &mut errlist)
+            }, format_args!("{} snapshots", names.len()));
+            if ret == 0 {
+                // Missing snapshots are only reported in the errlist.
+                let errlist = (!errlist.is_null()).then(|| unsafe { NvList::from_raw(errlist) });
+                for pair in errlist.iter().flat_map(|errlist| errlist.iter()) {
+                    if let NvValue::Int32(e) = pair.value() {
+                        let name = SafeString::try_from(pair.name().as_ref())?;
+                        let error = Error::Sys(io::Error::from_raw_os_error(e))
+                            .context("hold", &name);
+                        failed.push(SnapshotError { name, error });
+                    }
+                }
+                continue;
+            }
+
+            failed.extend(batch_errors("hold", names, ret, errlist).into_iter()
+                .map(|(name, error)| SnapshotError { name, error }));
+        }
+        Ok(failed)
+    }
+
+    /// Release the hold with the given tag from each of the given snapshots, like `zfs release`.
+    ///
+    /// The holds are released in one batch per pool, and each batch is all-or-nothing. Returns
+    /// the snapshots whose hold wasn't released, along with the reason; an empty list means all
+    /// went well.
+    pub fn release<I, T>(&self, snapshots: I, tag: &str) -> Result<Vec<SnapshotError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        let mut failed = vec![];
+        for names in snapshots_by_pool(snapshots)? {
+            let mut nvl = NvList::new()?;
+            for name in &names {
+                let mut tags = NvList::new()?;
+                tags.add_boolean(tag)?;
+                nvl.add_nvlist(name.as_str(), &tags)?;
+            }
+
+            let mut errlist = ptr::null_mut();
+            let ret = lzc_call!(unsafe {
+                sys::lzc_release(nvl.as_ptr(), &mut errlist)
+            }, format_args!("{} snapshots", names.len()));
+            if ret == 0 {
+                continue;
+            }
+
+            failed.extend(batch_errors("release", names, ret, errlist).into_iter()
+                .map(|(name, error)| SnapshotError { name, error }));
+        }
+        Ok(failed)
+    }
+
+    /// Get the user holds on a snapshot (full name, like `pool/fs@snap`), like
+    /// [`Dataset::holds`].
+    pub fn holds(&self, snapshot: &SafeStr) -> Result<Vec<(String, SystemTime)>> {
+        let mut nvl = ptr::null_mut();
+        let ret = lzc_call!(unsafe { sys::lzc_get_holds(snapshot.as_ptr(), &mut nvl) }, snapshot);
+        if ret != 0 {
+            return Err(Error::Sys(io::Error::from_raw_os_error(ret))
+                .context("get holds of", snapshot));
+        }
+        let nvl = unsafe { NvList::from_raw(nvl) };
+
+        let mut holds = nvl.iter()
+            .filter_map(|pair| match pair.value() {
+                NvValue::UInt64(secs) => {
+                    Some((pair.name().into_owned(), UNIX_EPOCH + Duration::from_secs(secs)))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        holds.sort();
+        Ok(holds)
+    }
+}
+
+impl Dataset {
+    /// Get the user holds on this snapshot, like `zfs holds`, as pairs of the hold's tag and when
+    /// it was placed, sorted by tag. A snapshot can't be destroyed while it has any holds.
+    pub fn holds(&self) -> Result<Vec<(String, SystemTime)>> {
+        if self.get_type() != DatasetType::Snapshot {
+            return Err(Error::invalid_input(format!("{} is not a snapshot", self.name())));
+        }
+
+        LibZfsCore::new()?.holds(&self.name())
+    }
+}
@@ -0,0 +1,329 @@
+use crate::{sys, ztry, AsSafeStr, Error, LibZfs, NvList, NvListRef, NvValue, Result, SafeStr};
+use crate::{SafeString, ZfsError, ZPool, ZPoolState};
+use crate::compat;
+use crate::vdev::{vdev_tree, visit_leaves};
+
+use std::ffi::CString;
+use std::fs::File;
+use std::io;
+use std::mem;
+use std::os::fd::AsRawFd;
+use std::os::raw::{c_char, c_int};
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Options for [`LibZfs::import_pool`].
+#[derive(Debug, Clone, Default)]
+pub struct ImportOptions {
+    /// Mount all of the pool's filesystems under this directory instead of `/`, like
+    /// `zpool import -R`. This is only for the lifetime of the import, and also stops the pool
+    /// from being added to the cache file, so it isn't imported automatically on the next boot.
+    pub altroot: Option<PathBuf>,
+    /// Import the pool under this name instead, like `zpool import <pool> <newpool>`. The new
+    /// name is stored on disk.
+    pub new_name: Option<SafeString>,
+    /// Import the pool even if a separate intent log device is missing, like `zpool import -m`.
+    /// Any synchronous writes not yet replayed from the missing log are lost. Afterwards,
+    /// [`ZPool::missing_log_devices`] lists the log devices which were left out.
+    pub missing_log: bool,
+}
+
+/// A pool which could be imported, as found by [`LibZfs::find_importable_pools`].
+#[derive(Debug, Clone)]
+pub struct ImportablePool {
+    pub name: SafeString,
+    pub guid: u64,
+    /// Usually `Exported`; `PotentiallyActive` means it may be in use by another system.
+    pub state: ZPoolState,
+    /// Paths of devices which the pool needs but which weren't found.
+    pub missing_devices: Vec<PathBuf>,
+    /// The pool's full config, including its vdev tree.
+    pub config: NvList,
+}
+
+impl ImportablePool {
+    fn from_config(config: &NvListRef) -> Result<Self> {
+        let name = config.lookup_string("name")
+            .ok_or_else(|| Error::invalid_data("pool config has no name"))?;
+        let guid = config.lookup_uint64("pool_guid")
+            .ok_or_else(|| Error::invalid_data("pool config has no GUID"))?;
+        let state = config.lookup_uint64("state")
+            .ok_or_else(|| Error::invalid_data("pool config has no state"))?;
+        Ok(ImportablePool {
+            name: SafeString::try_from(name.as_ref())?,
+            guid,
+            state: ZPoolState::from_raw(state as sys::pool_state_t),
+            missing_devices: missing_devices(config, false)?,
+            config: config.to_owned(),
+        })
+    }
+}
+
+/// Whether and how a pool which can't be imported as it is could be recovered by rewinding it
+/// to an earlier transaction group, like `zpool import -F`. This is the same information
+/// `zpool import -F -n` and libzfs's `zpool_explain_recover` give, from the load information in
+/// a pool's config.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RecoveryInfo {
+    /// The time of the transaction group the pool would be rewound to.
+    pub rewind_to: SystemTime,
+    /// About how much of the most recent changes would be discarded, irreversibly.
+    pub data_loss: Duration,
+    /// How many persistent user data errors would remain after rewinding, if known. Any of
+    /// these would need the files restored, or the pool restored from backup, to fix.
+    pub data_errors: Option<u64>,
+}
+
+impl RecoveryInfo {
+    /// Read the recovery information from a pool config, like that of an
+    /// [`ImportablePool`]. Returns `None` if there isn't any, in which case recovery by
+    /// rewinding isn't possible, or wasn't tried.
+    pub fn from_config(config: &NvListRef) -> Option<RecoveryInfo> {
+        let rewind = config.lookup_nvlist("load_info")?.lookup_nvlist("rewind_info")?;
+        let rewind_to = rewind.lookup_uint64("rewind_txg_ts").filter(|&ts| ts != 0)?;
+        let data_loss = rewind.lookup_int64("seconds_of_rewind").unwrap_or_default();
+        Some(RecoveryInfo {
+            rewind_to: UNIX_EPOCH + Duration::from_secs(rewind_to),
+            data_loss: Duration::from_secs(data_loss.max(0) as u64),
+            data_errors: rewind.lookup_uint64("verify_data_errors")
+                .filter(|&errors| errors != u64::MAX),
+        })
+    }
+}
+
+impl ImportablePool {
+    /// Get the information on recovering this pool by rewinding it, if there is any.
+    pub fn recovery_info(&self) -> Option<RecoveryInfo> {
+        RecoveryInfo::from_config(&self.config)
+    }
+}
+
+impl ZPool {
+    /// Get the information on recovering this pool by rewinding it, if there is any: for a pool
+    /// which was opened but is faulted, like `zpool clear -F -n`.
+    pub fn recovery_info(&self) -> Result<Option<RecoveryInfo>> {
+        let config = self.get_config()?;
+        Ok(RecoveryInfo::from_config(&config))
+    }
+}
+
+/// What the ZFS label on a device says, from [`read_label`].
+#[derive(Debug, Clone)]
+pub struct DeviceLabel {
+    /// The pool the device belongs to. Spares and cache devices don't record one.
+    pub pool_name: Option<SafeString>,
+    pub pool_guid: Option<u64>,
+    /// The GUID of the device itself within the pool.
+    pub guid: u64,
+    /// The transaction group the label was last written in.
+    pub txg: Option<u64>,
+    pub state: ZPoolState,
+    /// The host which last had the pool imported, if known.
+    pub hostname: Option<String>,
+    /// How many of the device's four label copies were readable and valid.
+    pub num_labels: u32,
+    /// The whole label, including the vdev tree of the device's top-level vdev.
+    pub config: NvList,
+}
+
+/// Read the ZFS label from a device or file, like `zdb -l`, without importing anything. Returns
+/// `None` if it has no valid label, meaning it isn't (or is no longer) part of a pool.
+pub fn read_label<P: AsRef<Path>>(device: P) -> Result<Option<DeviceLabel>> {
+    let file = File::open(device).map_err(Error::Sys)?;
+    let mut config = ptr::null_mut();
+    let mut num_labels = 0;
+    if unsafe { sys::zpool_read_label(file.as_raw_fd(), &mut config, &mut num_labels) } != 0 {
+        return Err(Error::Sys(io::Error::last_os_error()));
+    }
+    if config.is_null() {
+        return Ok(None);
+    }
+    let config = unsafe { NvList::from_raw(config) };
+
+    let state = config.lookup_uint64("state")
+        .ok_or_else(|| Error::invalid_data("label has no state"))?;
+    let guid = config.lookup_uint64("guid")
+        .ok_or_else(|| Error::invalid_data("label has no GUID"))?;
+    let pool_name = config.lookup_string("name")
+        .map(|name| SafeString::try_from(name.as_ref()))
+        .transpose()?;
+    Ok(Some(DeviceLabel {
+        pool_name,
+        pool_guid: config.lookup_uint64("pool_guid"),
+        guid,
+        txg: config.lookup_uint64("txg"),
+        state: ZPoolState::from_raw(state as sys::pool_state_t),
+        hostname: config.lookup_string("hostname").map(|name| name.into_owned()),
+        num_labels: num_labels as u32,
+        config,
+    }))
+}
+
+impl LibZfs {
+    /// Import an exported pool by name, like `zpool import -N`: it is found by scanning the
+    /// usual devices, and its filesystems are not mounted.
+    ///
+    /// Fails if more than one importable pool has the name; use
+    /// [`import_pool_by_guid`](Self::import_pool_by_guid) to pick one.
+    pub fn import_pool<N: AsSafeStr>(&self, name: N, options: &ImportOptions) -> Result<ZPool> {
+        let name = name.as_safe_str()?;
+        let found = self.search_import(Some(&name), None, &[])?;
+        let config = only_config(&found)?
+            .ok_or_else(|| Error::not_found(format!("no importable pool named {}", name)))?;
+        self.import_config(config, options)
+    }
+
+    /// Import an exported pool by its GUID, like `zpool import -N <guid>`. This picks out one
+    /// pool when several with the same name are visible.
+    pub fn import_pool_by_guid(&self, guid: u64, options: &ImportOptions) -> Result<ZPool> {
+        let found = self.search_import(None, Some(guid), &[])?;
+        let config = only_config(&found)?
+            .ok_or_else(|| Error::not_found(format!("no importable pool with GUID {}", guid)))?;
+        self.import_config(config, options)
+    }
+
+    /// Scan for pools which could be imported, like `zpool import` with no pool name. Devices
+    /// are searched for in the given directories (or device files), or in the usual places if
+    /// none are given.
+    pub fn find_importable_pools<P: AsRef<Path>>(&self, dirs: &[P]) -> Result<Vec<ImportablePool>> {
+        let dirs = dirs.iter().map(AsRef::as_ref).collect::<Vec<_>>();
+        let found = self.search_import(None, None, &dirs)?;
+        found.iter()
+            .filter_map(|pair| match pair.value() {
+                NvValue::NvList(config) => Some(ImportablePool::from_config(config)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Import a pool found by [`find_importable_pools`](Self::find_importable_pools).
+    pub fn import_found_pool(&self, pool: &ImportablePool, options: &ImportOptions)
+        -> Result<ZPool>
+    {
+        self.import_config(&pool.config, options)
+    }
+
+    fn import_config(&self, config: &NvListRef, options: &ImportOptions) -> Result<ZPool> {
+        let name = match &options.new_name {
+            Some(new_name) => new_name.clone(),
+            None => config.lookup_string("name")
+                .ok_or_else(|| Error::invalid_data("pool config has no name"))
+                .and_then(|name| SafeString::try_from(name.as_ref()))?,
+        };
+
+        let mut props = NvList::new()?;
+        if let Some(altroot) = &options.altroot {
+            let altroot = altroot.to_str()
+                .ok_or_else(|| Error::invalid_input("altroot is not valid UTF-8"))?;
+            props.add_string("altroot", altroot)?;
+            props.add_string("cachefile", "none")?;
+        }
+
+        let mut flags = sys::ZFS_IMPORT_NORMAL;
+        if options.missing_log {
+            flags |= sys::ZFS_IMPORT_MISSING_LOG;
+        }
+
+        let _lock = self.handle.lock();
+        ztry!(unsafe {
+            sys::zpool_import_props(
+                self.handle.raw(),
+                config.as_ptr(),
+                options.new_name.as_ref().map_or(ptr::null(), |name| name.as_ptr()),
+                if props.is_empty() { ptr::null_mut() } else { props.as_ptr() },
+                flags as c_int,
+            )
+        }, self.handle.raw());
+        self.pool_by_name(&name)
+    }
+
+    /// Find the configs of pools which could be imported, keyed by pool name, optionally only
+    /// looking for ones with the given name or GUID, and optionally only looking in the given
+    /// directories.
+    fn search_import(&self, name: Option<&SafeStr>, guid: Option<u64>, dirs: &[&Path])
+        -> Result<NvList>
+    {
+        let dirs = dirs.iter()
+            .map(|dir| CString::new(dir.as_os_str().as_bytes()))
+            .collect::<std::result::Result<Vec<_>, _>>()
+            .map_err(Error::invalid_input)?;
+        // libzfs doesn't modify these, despite the type.
+        let mut dir_ptrs = dirs.iter()
+            .map(|dir| dir.as_ptr() as *mut c_char)
+            .collect::<Vec<_>>();
+
+        let mut args: sys::importargs_t = unsafe { mem::zeroed() };
+        if let Some(name) = name {
+            args.poolname = name.as_ptr();
+        }
+        args.guid = guid.unwrap_or(0);
+        if !dir_ptrs.is_empty() {
+            args.path = dir_ptrs.as_mut_ptr();
+            args.paths = dir_ptrs.len() as c_int;
+        }
+
+        let _lock = self.handle.lock();
+        unsafe { compat::zpool_search_import(self.handle.raw(), &mut args) }
+    }
+}
+
+/// Find the paths of leaf devices in a pool config which couldn't be opened, optionally only
+/// under log vdevs.
+fn missing_devices(config: &NvListRef, logs_only: bool) -> Result<Vec<PathBuf>> {
+    let mut missing = vec![];
+    let top_level = vdev_tree(config)?.lookup_nvlist_array("children").unwrap_or_default();
+    for vdev in top_level {
+        if logs_only && vdev.lookup_uint64("is_log") != Some(1) {
+            continue;
+        }
+        visit_leaves(vdev, &mut |leaf| {
+            let cant_open = leaf.lookup_struct::<sys::vdev_stat_t>("vdev_stats")
+                .is_some_and(|vs| vs.vs_state == sys::vdev_state::VDEV_STATE_CANT_OPEN as u64);
+            if cant_open {
+                if let Some(path) = leaf.lookup_string("path") {
+                    missing.push(PathBuf::from(path.into_owned()));
+                }
+            }
+        });
+    }
+    Ok(missing)
+}
+
+/// Get the one pool config out of the results of a search, if any.
+fn only_config(found: &NvListRef) -> Result<Option<&NvListRef>> {
+    let mut configs = found.iter().filter_map(|pair| match pair.value() {
+        NvValue::NvList(config) => Some(config),
+        _ => None,
+    });
+    let config = configs.next();
+    if configs.next().is_some() {
+        return Err(Error::invalid_input(
+            "more than one importable pool matches; import by GUID instead"));
+    }
+    Ok(config)
+}
+
+impl ZPool {
+    /// The directory the pool's filesystems are mounted under, if it was imported or created
+    /// with an altroot. Mountpoints reported by
+    /// [`Dataset::mountpoint`](crate::Dataset::mountpoint) already include it.
+    pub fn altroot(&self) -> Result<Option<PathBuf>> {
+        let altroot = self.get_prop_string(sys::zpool_prop_t::ZPOOL_PROP_ALTROOT)?;
+        if altroot.is_empty() || altroot == "-" {
+            Ok(None)
+        } else {
+            Ok(Some(PathBuf::from(altroot)))
+        }
+    }
+
+    /// Paths of log devices which couldn't be opened, such as those left out by an import with
+    /// [`ImportOptions::missing_log`]. While any are missing, synchronous writes go to the main
+    /// pool devices instead.
+    pub fn missing_log_devices(&self) -> Result<Vec<PathBuf>> {
+        let config = self.get_config()?;
+        missing_devices(&config, true)
+    }
+}
@@ -0,0 +1,193 @@
+use crate::{sys, Dataset, DatasetType, LibZfs, Result, SafeString, ScanStatus, Vdev, ZPool};
+use crate::ZPoolState;
+
+use std::collections::BTreeMap;
+use std::ptr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// A plain-data summary of a dataset, detached from any libzfs handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatasetInfo {
+    pub name: SafeString,
+    pub dataset_type: DatasetType,
+    pub guid: u64,
+    /// Creation time, in seconds since the epoch.
+    pub creation: u64,
+    pub used: u64,
+    pub available: u64,
+    pub referenced: u64,
+    /// Any extra properties asked for, in their parsable form.
+    pub properties: BTreeMap<String, String>,
+}
+
+/// How a dataset's space is used, in bytes, like the columns of `zfs list -o space`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpaceBreakdown {
+    pub available: u64,
+    /// The total of all the `used_by_*` fields.
+    pub used: u64,
+    pub used_by_snapshots: u64,
+    pub used_by_dataset: u64,
+    pub used_by_refreservation: u64,
+    pub used_by_children: u64,
+}
+
+/// A plain-data summary of a pool, detached from any libzfs handle.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolInfo {
+    pub name: SafeString,
+    pub guid: u64,
+    pub state: ZPoolState,
+    /// Like `"ONLINE"` or `"DEGRADED"`.
+    pub health: String,
+    pub size: u64,
+    pub allocated: u64,
+    pub free: u64,
+    /// Any extra properties asked for, in their parsable form.
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Everything a monitoring exporter typically reports, gathered in one go by
+/// [`LibZfs::metrics_snapshot`].
+#[derive(Debug, Clone)]
+pub struct MetricsSnapshot {
+    /// When the snapshot was taken.
+    pub time: SystemTime,
+    pub pools: Vec<PoolMetrics>,
+}
+
+/// One pool's part of a [`MetricsSnapshot`].
+#[derive(Debug, Clone)]
+pub struct PoolMetrics {
+    /// Health, capacity, and identity.
+    pub info: PoolInfo,
+    /// How fragmented the free space is, as a percentage, if known.
+    pub fragmentation: Option<u64>,
+    /// The current or most recent scrub or resilver, if there has been one.
+    pub scan: Option<ScanStatus>,
+    /// The vdev tree, with each vdev's state and I/O and error counters.
+    pub vdevs: Vec<Vdev>,
+    /// The pool's filesystems and volumes. Empty if the pool is unavailable.
+    pub datasets: Vec<DatasetMetrics>,
+}
+
+/// One dataset's part of a [`MetricsSnapshot`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DatasetMetrics {
+    pub name: SafeString,
+    pub dataset_type: DatasetType,
+    pub referenced: u64,
+    pub logicalused: u64,
+    pub space: SpaceBreakdown,
+}
+
+impl LibZfs {
+    /// Gather the health, capacity, scan status, and vdev counters of every imported pool, and
+    /// the space used by every filesystem and volume in them. This does one pass over each pool
+    /// rather than the many separate calls it would take to get the same through the other
+    /// methods, so it's suited to being called on every scrape by a metrics exporter.
+    pub fn metrics_snapshot(&self) -> Result<MetricsSnapshot> {
+        let time = SystemTime::now();
+        let pools = self.get_zpools()?
+            .iter()
+            .map(ZPool::metrics)
+            .collect::<Result<Vec<_>>>()?;
+        Ok(MetricsSnapshot { time, pools })
+    }
+}
+
+impl ZPool {
+    fn metrics(&self) -> Result<PoolMetrics> {
+        let fragmentation = {
+            let _lock = self.libzfs.lock();
+            unsafe {
+                sys::zpool_get_prop_int(
+                    self.handle,
+                    sys::zpool_prop_t::ZPOOL_PROP_FRAGMENTATION,
+                    ptr::null_mut(),
+                )
+            }
+        };
+        let datasets = if self.get_state() == ZPoolState::Unavailable {
+            vec![]
+        } else {
+            self.get_datasets()?
+                .iter()
+                .map(|dataset| DatasetMetrics {
+                    name: dataset.get_name(),
+                    dataset_type: dataset.get_type(),
+                    referenced: dataset.referenced(),
+                    logicalused: dataset.logicalused(),
+                    space: dataset.space_breakdown(),
+                })
+                .collect()
+        };
+        let config = self.get_config()?;
+        Ok(PoolMetrics {
+            info: self.info(&[])?,
+            // libzfs reports u64::MAX when it can't be worked out.
+            fragmentation: Some(fragmentation).filter(|&frag| frag != u64::MAX),
+            scan: self.scan_status_in(&config)?,
+            vdevs: self.vdevs_in(&config)?,
+            datasets,
+        })
+    }
+}
+
+impl Dataset {
+    /// Summarize this dataset, along with the values of the given extra properties.
+    pub fn info(&self, props: &[&str]) -> Result<DatasetInfo> {
+        let _lock = self.libzfs.lock();
+        let mut properties = BTreeMap::new();
+        for &prop in props {
+            properties.insert(prop.to_owned(), self.get_property(prop)?);
+        }
+        Ok(DatasetInfo {
+            name: self.get_name(),
+            dataset_type: self.get_type(),
+            guid: self.guid(),
+            creation: self.creation().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+            used: self.used(),
+            available: self.available(),
+            referenced: self.referenced(),
+            properties,
+        })
+    }
+
+    /// Get a breakdown of the space used by this dataset.
+    pub fn space_breakdown(&self) -> SpaceBreakdown {
+        SpaceBreakdown {
+            available: self.available(),
+            used: self.used(),
+            used_by_snapshots: self.get_int_property(sys::zfs_prop_t::ZFS_PROP_USEDSNAP),
+            used_by_dataset: self.get_int_property(sys::zfs_prop_t::ZFS_PROP_USEDDS),
+            used_by_refreservation: self.get_int_property(sys::zfs_prop_t::ZFS_PROP_USEDREFRESERV),
+            used_by_children: self.get_int_property(sys::zfs_prop_t::ZFS_PROP_USEDCHILD),
+        }
+    }
+}
+
+impl ZPool {
+    /// Summarize this pool, along with the values of the given extra properties.
+    pub fn info(&self, props: &[&str]) -> Result<PoolInfo> {
+        let _lock = self.libzfs.lock();
+        let int = |prop| unsafe { sys::zpool_get_prop_int(self.handle, prop, ptr::null_mut()) };
+        let mut properties = BTreeMap::new();
+        for &prop in props {
+            properties.insert(prop.to_owned(), self.get_property(prop)?);
+        }
+        Ok(PoolInfo {
+            name: self.get_name(),
+            guid: int(sys::zpool_prop_t::ZPOOL_PROP_GUID),
+            state: self.get_state(),
+            health: self.get_prop_string(sys::zpool_prop_t::ZPOOL_PROP_HEALTH)?,
+            size: int(sys::zpool_prop_t::ZPOOL_PROP_SIZE),
+            allocated: int(sys::zpool_prop_t::ZPOOL_PROP_ALLOCATED),
+            free: int(sys::zpool_prop_t::ZPOOL_PROP_FREE),
+            properties,
+        })
+    }
+}
@@ -0,0 +1,25 @@
+use crate::{sys, ztry, Dataset, Error, Result, ZfsError};
+
+use std::os::raw::c_int;
+
+impl Dataset {
+    /// Attach this filesystem to a FreeBSD jail, given its ID, like `zfs jail`, so that the jail
+    /// can mount and manage it and its descendants. Its `jailed` property must be on; see
+    /// [`set_delegated`](Self::set_delegated).
+    pub fn jail(&self, jail_id: i32) -> Result<()> {
+        self.set_jailed(jail_id, true)
+    }
+
+    /// Detach this filesystem from a FreeBSD jail, like `zfs unjail`.
+    pub fn unjail(&self, jail_id: i32) -> Result<()> {
+        self.set_jailed(jail_id, false)
+    }
+
+    fn set_jailed(&self, jail_id: i32, attach: bool) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_jail(self.handle, jail_id as c_int, attach as c_int)
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+}
@@ -0,0 +1,570 @@
+use crate::{sys, ztry, Error, LibZfs, NvList, Result, SafeString, ZfsError, ZPool};
+use crate::create::props_nvlist;
+use crate::vdev::{leaf_nvlist, refs, root_nvlist};
+
+use std::collections::BTreeSet;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+/// A top-level vdev to create, as part of a [`PoolLayout`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VdevLayout {
+    /// A single device or file, with no redundancy of its own.
+    Disk(PathBuf),
+    /// Devices which each hold a copy of the same data, like `mirror sda sdb`.
+    Mirror(Vec<PathBuf>),
+    /// Devices with data and `parity` devices' worth of parity spread across them, like
+    /// `raidz2 sda sdb sdc sdd`. `parity` is 1, 2, or 3.
+    Raidz { parity: u8, devices: Vec<PathBuf> },
+}
+
+impl VdevLayout {
+    /// The devices making up the vdev.
+    pub fn devices(&self) -> &[PathBuf] {
+        match self {
+            VdevLayout::Disk(device) => std::slice::from_ref(device),
+            VdevLayout::Mirror(devices) | VdevLayout::Raidz { devices, .. } => devices,
+        }
+    }
+
+    /// The kind of vdev, as `zpool create` names it, like `mirror` or `raidz2`.
+    pub fn kind(&self) -> String {
+        match self {
+            VdevLayout::Disk(_) => "disk".to_owned(),
+            VdevLayout::Mirror(_) => "mirror".to_owned(),
+            VdevLayout::Raidz { parity, .. } => format!("raidz{}", parity),
+        }
+    }
+
+    /// Check the vdev has enough devices for its kind.
+    fn validate(&self) -> Result<()> {
+        match self {
+            VdevLayout::Disk(_) => Ok(()),
+            VdevLayout::Mirror(devices) if devices.len() < 2 => Err(Error::invalid_input(
+                "a mirror needs at least 2 devices")),
+            VdevLayout::Mirror(_) => Ok(()),
+            VdevLayout::Raidz { parity, .. } if !(1..=3).contains(parity) => Err(
+                Error::invalid_input(format!("raidz parity must be 1 to 3, not {}", parity))),
+            VdevLayout::Raidz { parity, devices } if devices.len() <= *parity as usize => Err(
+                Error::invalid_input(format!(
+                    "raidz{} needs at least {} devices", parity, *parity as usize + 1))),
+            VdevLayout::Raidz { .. } => Ok(()),
+        }
+    }
+
+    /// Build the vdev's config, to go in the root of a vdev tree.
+    fn nvlist(&self) -> Result<NvList> {
+        let (vdev_type, devices) = match self {
+            VdevLayout::Disk(device) => return leaf_nvlist(device),
+            VdevLayout::Mirror(devices) => ("mirror", devices),
+            VdevLayout::Raidz { devices, .. } => ("raidz", devices),
+        };
+        let leaves = devices.iter()
+            .map(|device| leaf_nvlist(device))
+            .collect::<Result<Vec<_>>>()?;
+        let mut nvl = NvList::new()?;
+        nvl.add_string("type", vdev_type)?;
+        if let VdevLayout::Raidz { parity, .. } = self {
+            nvl.add_uint64("nparity", u64::from(*parity))?;
+        }
+        nvl.add_nvlist_array("children", &refs(&leaves))?;
+        Ok(nvl)
+    }
+}
+
+impl fmt::Display for VdevLayout {
+    /// Format the vdev the way `zpool create` takes it, like `mirror /dev/sda /dev/sdb`.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !matches!(self, VdevLayout::Disk(_)) {
+            write!(f, "{} ", self.kind())?;
+        }
+        write_devices(f, self.devices())
+    }
+}
+
+/// The vdevs to create a pool from, like the vdev arguments of `zpool create`. Build one up
+/// like `PoolLayout::new().mirror(["/dev/sda", "/dev/sdb"]).log_mirror(["/dev/nvme0n1",
+/// "/dev/nvme1n1"]).cache(["/dev/nvme2n1"])`, and pass it to [`LibZfs::create_pool`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolLayout {
+    /// The data vdevs, which the pool's data is striped across.
+    pub data: Vec<VdevLayout>,
+    /// Intent log (SLOG) vdevs, like `log`.
+    pub logs: Vec<VdevLayout>,
+    /// Vdevs for metadata and small blocks, like `special`.
+    pub special: Vec<VdevLayout>,
+    /// Vdevs for the deduplication table, like `dedup`.
+    pub dedup: Vec<VdevLayout>,
+    /// Cache (L2ARC) devices, like `cache`.
+    pub cache: Vec<PathBuf>,
+    /// Hot spares, like `spare`.
+    pub spares: Vec<PathBuf>,
+}
+
+impl PoolLayout {
+    pub fn new() -> Self {
+        PoolLayout::default()
+    }
+
+    /// Add a data vdev.
+    pub fn add_data(mut self, vdev: VdevLayout) -> Self {
+        self.data.push(vdev);
+        self
+    }
+
+    /// Add a single device as a data vdev.
+    pub fn disk<P: AsRef<Path>>(self, device: P) -> Self {
+        self.add_data(VdevLayout::Disk(device.as_ref().to_owned()))
+    }
+
+    /// Add a mirror of the given devices as a data vdev.
+    pub fn mirror<I, P>(self, devices: I) -> Self
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>,
+    {
+        self.add_data(VdevLayout::Mirror(paths(devices)))
+    }
+
+    /// Add a RAIDZ vdev of the given devices with `parity` (1 to 3) as a data vdev.
+    pub fn raidz<I, P>(self, parity: u8, devices: I) -> Self
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>,
+    {
+        self.add_data(VdevLayout::Raidz { parity, devices: paths(devices) })
+    }
+
+    /// Add an intent log vdev. Logs can be single devices or mirrors.
+    pub fn add_log(mut self, vdev: VdevLayout) -> Self {
+        self.logs.push(vdev);
+        self
+    }
+
+    /// Add a single device as an intent log vdev.
+    pub fn log<P: AsRef<Path>>(self, device: P) -> Self {
+        self.add_log(VdevLayout::Disk(device.as_ref().to_owned()))
+    }
+
+    /// Add a mirror of the given devices as an intent log vdev.
+    pub fn log_mirror<I, P>(self, devices: I) -> Self
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>,
+    {
+        self.add_log(VdevLayout::Mirror(paths(devices)))
+    }
+
+    /// Add a special allocation class vdev, for metadata and small blocks. Losing it loses the
+    /// pool, so it should be as redundant as the data vdevs.
+    pub fn add_special(mut self, vdev: VdevLayout) -> Self {
+        self.special.push(vdev);
+        self
+    }
+
+    /// Add a mirror of the given devices as a special allocation class vdev.
+    pub fn special_mirror<I, P>(self, devices: I) -> Self
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>,
+    {
+        self.add_special(VdevLayout::Mirror(paths(devices)))
+    }
+
+    /// Add a vdev for the deduplication table. Losing it loses the pool, so it should be as
+    /// redundant as the data vdevs.
+    pub fn add_dedup(mut self, vdev: VdevLayout) -> Self {
+        self.dedup.push(vdev);
+        self
+    }
+
+    /// Add a mirror of the given devices as a deduplication table vdev.
+    pub fn dedup_mirror<I, P>(self, devices: I) -> Self
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>,
+    {
+        self.add_dedup(VdevLayout::Mirror(paths(devices)))
+    }
+
+    /// Add cache (L2ARC) devices.
+    pub fn cache<I, P>(mut self, devices: I) -> Self
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>,
+    {
+        self.cache.extend(paths(devices));
+        self
+    }
+
+    /// Add hot spares.
+    pub fn spare<I, P>(mut self, devices: I) -> Self
+        where I: IntoIterator<Item = P>,
+              P: AsRef<Path>,
+    {
+        self.spares.extend(paths(devices));
+        self
+    }
+
+    /// Check for layouts which can't be created: no data vdevs, too few devices for a mirror
+    /// or RAIDZ, RAIDZ logs, or a device used more than once.
+    pub fn validate(&self) -> Result<()> {
+        if self.data.is_empty() {
+            return Err(Error::invalid_input("a pool needs at least one data vdev"));
+        }
+        for vdev in self.data.iter().chain(&self.logs).chain(&self.special).chain(&self.dedup) {
+            vdev.validate()?;
+        }
+        if self.logs.iter().any(|vdev| matches!(vdev, VdevLayout::Raidz { .. })) {
+            return Err(Error::invalid_input("log vdevs can't be RAIDZ"));
+        }
+        let mut seen = BTreeSet::new();
+        if let Some(device) = self.devices().find(|device| !seen.insert(*device)) {
+            return Err(Error::invalid_input(format!(
+                "{} is used more than once", device.display())));
+        }
+        Ok(())
+    }
+
+    /// Find what's allowed but probably a mistake, which `zpool create` refuses without `-f`:
+    /// data, special, and dedup vdevs which aren't all of the same kind and width, so the
+    /// pool is only as redundant as the weakest of them. Returns a description of each.
+    pub fn warnings(&self) -> Vec<String> {
+        let mut warnings = vec![];
+        let mut vdevs = self.data.iter().chain(&self.special).chain(&self.dedup);
+        let Some(first) = vdevs.next() else { return warnings };
+        for vdev in vdevs {
+            if vdev.kind() != first.kind() {
+                warnings.push(format!(
+                    "mismatched replication level: both {} and {} vdevs", first.kind(),
+                    vdev.kind()));
+            } else if vdev.devices().len() != first.devices().len() {
+                warnings.push(format!(
+                    "mismatched replication level: both {}-way and {}-way {} vdevs",
+                    first.devices().len(), vdev.devices().len(), vdev.kind()));
+            }
+        }
+        warnings.dedup();
+        warnings
+    }
+
+    /// The vdevs of a section of a vdev spec. The cache and spare sections never have any, so
+    /// they get the data vdevs, to be left alone.
+    fn vdevs_mut(&mut self, section: Section) -> &mut Vec<VdevLayout> {
+        match section {
+            Section::Data | Section::Cache | Section::Spare => &mut self.data,
+            Section::Log => &mut self.logs,
+            Section::Special => &mut self.special,
+            Section::Dedup => &mut self.dedup,
+        }
+    }
+
+    /// Every device in the layout.
+    fn devices(&self) -> impl Iterator<Item = &PathBuf> {
+        self.data.iter().chain(&self.logs).chain(&self.special).chain(&self.dedup)
+            .flat_map(VdevLayout::devices)
+            .chain(&self.cache)
+            .chain(&self.spares)
+    }
+
+    /// Build the root of the vdev tree for `zpool_create`.
+    pub(crate) fn nvlist(&self) -> Result<NvList> {
+        let mut top_level = vec![];
+        for vdev in &self.data {
+            top_level.push(vdev.nvlist()?);
+        }
+        for vdev in &self.logs {
+            let mut nvl = vdev.nvlist()?;
+            nvl.add_uint64("is_log", 1)?;
+            nvl.add_string("alloc_bias", "log")?;
+            top_level.push(nvl);
+        }
+        for (bias, vdevs) in [("special", &self.special), ("dedup", &self.dedup)] {
+            for vdev in vdevs {
+                let mut nvl = vdev.nvlist()?;
+                nvl.add_string("alloc_bias", bias)?;
+                top_level.push(nvl);
+            }
+        }
+        let mut nvroot = root_nvlist(&refs(&top_level))?;
+        for (name, devices) in [("l2cache", &self.cache), ("spares", &self.spares)] {
+            if !devices.is_empty() {
+                let leaves = devices.iter()
+                    .map(|device| leaf_nvlist(device))
+                    .collect::<Result<Vec<_>>>()?;
+                nvroot.add_nvlist_array(name, &refs(&leaves))?;
+            }
+        }
+        Ok(nvroot)
+    }
+}
+
+/// A section of a `zpool create` vdev spec.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Section {
+    Data,
+    Log,
+    Special,
+    Dedup,
+    Cache,
+    Spare,
+}
+
+impl FromStr for PoolLayout {
+    type Err = Error;
+
+    /// Parse the vdev arguments of `zpool create`, like
+    /// `mirror sda sdb log nvme0n1 cache nvme1n1`. Devices given by name alone, like `sda`, are
+    /// taken to be in `/dev`; files need their full path. A mirror or RAIDZ vdev takes every
+    /// device up to the next keyword. dRAID isn't supported.
+    ///
+    /// The layout isn't [validated](PoolLayout::validate); creating a pool from it does that.
+    fn from_str(s: &str) -> Result<PoolLayout> {
+        let mut layout = PoolLayout::new();
+        let mut section = Section::Data;
+        // The mirror or RAIDZ vdev devices are being added to, if any.
+        let mut group = None;
+        for word in s.split_whitespace() {
+            let next_group = match word {
+                "mirror" => Some(VdevLayout::Mirror(vec![])),
+                "raidz" | "raidz1" => Some(VdevLayout::Raidz { parity: 1, devices: vec![] }),
+                "raidz2" => Some(VdevLayout::Raidz { parity: 2, devices: vec![] }),
+                "raidz3" => Some(VdevLayout::Raidz { parity: 3, devices: vec![] }),
+                _ if word.starts_with("draid") => return Err(Error::invalid_input(format!(
+                    "{:?}: dRAID vdevs aren't supported", word))),
+                _ => None,
+            };
+            if let Some(next_group) = next_group {
+                if matches!(section, Section::Cache | Section::Spare) {
+                    return Err(Error::invalid_input(format!(
+                        "{:?}: cache devices and spares can't be grouped", word)));
+                }
+                layout.vdevs_mut(section).extend(group.replace(next_group));
+                continue;
+            }
+
+            let next_section = match word {
+                "log" | "logs" => Some(Section::Log),
+                "special" => Some(Section::Special),
+                "dedup" => Some(Section::Dedup),
+                "cache" => Some(Section::Cache),
+                "spare" | "spares" => Some(Section::Spare),
+                _ => None,
+            };
+            if let Some(next_section) = next_section {
+                layout.vdevs_mut(section).extend(group.take());
+                section = next_section;
+                continue;
+            }
+
+            let device = if word.starts_with('/') {
+                PathBuf::from(word)
+            } else {
+                Path::new("/dev").join(word)
+            };
+            match (&mut group, section) {
+                (Some(VdevLayout::Mirror(devices) | VdevLayout::Raidz { devices, .. }), _) => {
+                    devices.push(device);
+                }
+                (_, Section::Cache) => layout.cache.push(device),
+                (_, Section::Spare) => layout.spares.push(device),
+                _ => layout.vdevs_mut(section).push(VdevLayout::Disk(device)),
+            }
+        }
+        layout.vdevs_mut(section).extend(group);
+        Ok(layout)
+    }
+}
+
+impl fmt::Display for PoolLayout {
+    /// Format the layout as the vdev arguments of `zpool create`, which can be parsed back. In
+    /// each section, single devices come before mirror and RAIDZ vdevs, since otherwise they'd
+    /// be taken as part of the one before.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut sep = "";
+        for (keyword, vdevs) in [
+            ("", &self.data),
+            ("special ", &self.special),
+            ("dedup ", &self.dedup),
+            ("log ", &self.logs),
+        ] {
+            if !vdevs.is_empty() {
+                write!(f, "{}{}", sep, keyword)?;
+                let (disks, groups): (Vec<_>, Vec<_>) =
+                    vdevs.iter().partition(|vdev| matches!(vdev, VdevLayout::Disk(_)));
+                for (i, vdev) in disks.into_iter().chain(groups).enumerate() {
+                    write!(f, "{}{}", if i == 0 { "" } else { " " }, vdev)?;
+                }
+                sep = " ";
+            }
+        }
+        for (keyword, devices) in [("cache ", &self.cache), ("spare ", &self.spares)] {
+            if !devices.is_empty() {
+                write!(f, "{}{}", sep, keyword)?;
+                write_devices(f, devices)?;
+                sep = " ";
+            }
+        }
+        Ok(())
+    }
+}
+
+fn write_devices(f: &mut fmt::Formatter, devices: &[PathBuf]) -> fmt::Result {
+    for (i, device) in devices.iter().enumerate() {
+        write!(f, "{}{}", if i == 0 { "" } else { " " }, device.display())?;
+    }
+    Ok(())
+}
+
+fn paths<I, P>(devices: I) -> Vec<PathBuf>
+    where I: IntoIterator<Item = P>,
+          P: AsRef<Path>,
+{
+    devices.into_iter().map(|device| device.as_ref().to_owned()).collect()
+}
+
+/// Options for [`LibZfs::create_pool`].
+#[derive(Debug, Clone, Default)]
+pub struct CreatePoolOptions {
+    /// Create the pool even if its layout has [warnings](PoolLayout::warnings), like
+    /// `zpool create -f`.
+    pub force: bool,
+    /// Pool properties, like `zpool create -o`.
+    pub properties: Vec<(String, String)>,
+    /// Properties of the pool's root filesystem, like `zpool create -O`.
+    pub filesystem_properties: Vec<(String, String)>,
+}
+
+impl LibZfs {
+    /// Create a pool with the given layout, like `zpool create`. The layout is checked first,
+    /// and unless `force` is set, its warnings are errors too. Unlike the command, this doesn't
+    /// partition whole disks, or check whether devices are already in use.
+    pub fn create_pool(&self, name: &str, layout: &PoolLayout, options: &CreatePoolOptions)
+        -> Result<ZPool>
+    {
+        layout.validate()?;
+        let warnings = layout.warnings();
+        if !options.force && !warnings.is_empty() {
+            return Err(Error::invalid_input(warnings.join("; ")));
+        }
+        let cname = SafeString::try_from(name)?;
+        let nvroot = layout.nvlist()?;
+        fn as_refs(props: &[(String, String)]) -> Vec<(&str, &str)> {
+            props.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect()
+        }
+        let props = props_nvlist(&as_refs(&options.properties))?;
+        let fs_props = props_nvlist(&as_refs(&options.filesystem_properties))?;
+        {
+            let _lock = self.handle.lock();
+            ztry!(unsafe {
+                sys::zpool_create(
+                    self.handle.raw(),
+                    cname.as_ptr(),
+                    nvroot.as_ptr(),
+                    props.as_ptr(),
+                    fs_props.as_ptr(),
+                )
+            }, self.handle.raw(), name);
+        }
+        self.pool_by_name(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dev(name: &str) -> PathBuf {
+        Path::new("/dev").join(name)
+    }
+
+    fn devs(names: &[&str]) -> Vec<PathBuf> {
+        names.iter().map(|name| dev(name)).collect()
+    }
+
+    #[test]
+    fn parse_groups() {
+        let layout = "mirror sda sdb raidz2 sdc sdd sde sdf".parse::<PoolLayout>().unwrap();
+        assert_eq!(layout.data, [
+            VdevLayout::Mirror(devs(&["sda", "sdb"])),
+            VdevLayout::Raidz { parity: 2, devices: devs(&["sdc", "sdd", "sde", "sdf"]) },
+        ]);
+        assert!(layout.logs.is_empty() && layout.cache.is_empty() && layout.spares.is_empty());
+
+        let layout = "raidz sda sdb raidz1 sdc sdd raidz3 sde sdf sdg sdh".parse::<PoolLayout>()
+            .unwrap();
+        let parities = layout.data.iter()
+            .map(|vdev| match vdev {
+                VdevLayout::Raidz { parity, .. } => *parity,
+                _ => 0,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(parities, [1, 1, 3]);
+    }
+
+    #[test]
+    fn parse_sections() {
+        let layout = "sda /tmp/file log mirror nvme0n1 nvme1n1 special mirror sdb sdc \
+            dedup sdd cache nvme2n1 nvme3n1 spare sde"
+            .parse::<PoolLayout>().unwrap();
+        assert_eq!(layout, PoolLayout {
+            data: vec![VdevLayout::Disk(dev("sda")), VdevLayout::Disk("/tmp/file".into())],
+            logs: vec![VdevLayout::Mirror(devs(&["nvme0n1", "nvme1n1"]))],
+            special: vec![VdevLayout::Mirror(devs(&["sdb", "sdc"]))],
+            dedup: vec![VdevLayout::Disk(dev("sdd"))],
+            cache: devs(&["nvme2n1", "nvme3n1"]),
+            spares: devs(&["sde"]),
+        });
+
+        // A section keyword ends the group before it, and "logs" and "spares" are accepted.
+        let layout = "mirror sda sdb logs sdc spares sdd sde".parse::<PoolLayout>().unwrap();
+        assert_eq!(layout, PoolLayout::new()
+            .mirror(["/dev/sda", "/dev/sdb"])
+            .log("/dev/sdc")
+            .spare(["/dev/sdd", "/dev/sde"]));
+    }
+
+    #[test]
+    fn parse_malformed() {
+        assert!("draid2:4d sda sdb sdc sdd sde".parse::<PoolLayout>().is_err());
+        assert!("sda cache mirror sdb sdc".parse::<PoolLayout>().is_err());
+        assert!("sda spare raidz sdb sdc".parse::<PoolLayout>().is_err());
+
+        // Parsing doesn't validate, but what it gives can't be created.
+        for spec in [
+            "",
+            "log sda",
+            "mirror sda",
+            "raidz2 sda sdb",
+            "sda sda",
+            "sda log raidz sdb sdc",
+        ] {
+            let layout = spec.parse::<PoolLayout>().unwrap();
+            assert!(layout.validate().is_err(), "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        let layout = PoolLayout::new()
+            .disk("/tmp/file")
+            .mirror(["/dev/sda", "/dev/sdb"])
+            .raidz(1, ["/dev/sdc", "/dev/sdd", "/dev/sde"])
+            .special_mirror(["/dev/sdf", "/dev/sdg"])
+            .dedup_mirror(["/dev/sdh", "/dev/sdi"])
+            .log_mirror(["/dev/nvme0n1", "/dev/nvme1n1"])
+            .cache(["/dev/nvme2n1"])
+            .spare(["/dev/sdj", "/dev/sdk"]);
+        let spec = layout.to_string();
+        assert_eq!(spec, "/tmp/file mirror /dev/sda /dev/sdb raidz1 /dev/sdc /dev/sdd /dev/sde \
+            special mirror /dev/sdf /dev/sdg dedup mirror /dev/sdh /dev/sdi \
+            log mirror /dev/nvme0n1 /dev/nvme1n1 cache /dev/nvme2n1 spare /dev/sdj /dev/sdk");
+        assert_eq!(spec.parse::<PoolLayout>().unwrap(), layout);
+
+        // Single devices move ahead of groups, so they aren't parsed as part of them.
+        let layout = PoolLayout::new().mirror(["/dev/sda", "/dev/sdb"]).disk("/dev/sdc");
+        let spec = layout.to_string();
+        assert_eq!(spec, "/dev/sdc mirror /dev/sda /dev/sdb");
+        let parsed = spec.parse::<PoolLayout>().unwrap();
+        assert_eq!(parsed, PoolLayout::new().disk("/dev/sdc").mirror(["/dev/sda", "/dev/sdb"]));
+
+        assert_eq!(PoolLayout::new().to_string(), "");
+    }
+}
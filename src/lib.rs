@@ -3,80 +3,238 @@
 
 use libzfs_sys as sys;
 
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::fs;
 use std::io;
-use std::marker::PhantomData;
 use std::os::fd::{AsRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
 use std::os::raw::{c_char, c_void};
+use std::path::{Path, PathBuf};
 use std::ptr;
+use std::sync::{Arc, Mutex, PoisonError};
 use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 mod string;
+mod api;
+#[cfg(feature = "async")]
+mod asyncio;
+mod bookmark;
+mod builder;
+mod cancel;
+mod capabilities;
+mod compat;
+mod create;
+mod delegation;
+mod dependents;
+mod destroy;
+mod diff;
+mod dispatch;
+mod encryption;
 mod error;
-
-pub use string::SafeString;
+mod event;
+mod export;
+mod fake;
+mod feature;
+mod handle;
+mod history;
+mod hold;
+mod import;
+mod info;
+#[cfg(target_os = "freebsd")]
+mod jail;
+mod layout;
+mod list;
+mod lzc;
+mod mount;
+mod name;
+mod nvpair;
+#[cfg(feature = "rayon")]
+mod parallel;
+mod plan;
+mod privilege;
+mod project;
+mod promote;
+mod property;
+mod propvalue;
+mod raw;
+mod receive;
+mod rename;
+mod replicate;
+mod retention;
+mod retry;
+mod rollback;
+mod scan;
+mod send;
+mod share;
+mod snapname;
+mod snapshot;
+mod stats;
+mod status;
+mod supervisor;
+#[cfg(feature = "tracing")]
+mod trace;
+mod vdev;
+mod userspace;
+mod version;
+mod wait;
+mod watch;
+mod zone;
+mod zvol;
+
+pub use string::{AsSafeStr, BorrowedName, SafeStr, SafeString};
+#[cfg(feature = "async")]
+pub use asyncio::EventStream;
+pub use api::ZfsApi;
+pub use bookmark::BookmarkError;
+pub use builder::DatasetBuilder;
+pub use cancel::{ReceiveHandle, SendHandle};
+pub use capabilities::Capabilities;
+pub use create::*;
+pub use delegation::{Delegate, DelegateAudit, Delegation, DelegationAudit, DelegationScope};
+pub use delegation::EffectivePermission;
+pub use dependents::*;
+pub use destroy::*;
+pub use diff::*;
+pub use dispatch::{DispatcherHandle, EventDispatcher};
+pub use encryption::{KeyLocation, KeyMaterial, KeyProvider};
 pub use error::*;
+pub use event::*;
+pub use export::*;
+pub use fake::FakeZfs;
+pub use feature::*;
+pub use history::*;
+pub use hold::hold_cleanup_fd;
+pub use import::{read_label, DeviceLabel, ImportablePool, ImportOptions, RecoveryInfo};
+pub use info::*;
+pub use layout::{CreatePoolOptions, PoolLayout, VdevLayout};
+pub use list::*;
+pub use lzc::LibZfsCore;
+pub use mount::*;
+pub use name::*;
+pub use property::{validate_user_property_name, PoolPropertyError, Property, PropertySource};
+pub use plan::Plan;
+pub use project::FileProject;
+#[cfg(target_os = "linux")]
+pub use project::{get_project, set_project};
+pub use promote::PromotePlan;
+pub use propvalue::*;
+pub use scan::*;
+pub use receive::{ReceiveOptions, ResumeToken};
+pub use rename::RenameOptions;
+pub use replicate::{ReplicateOptions, ReplicationPlan, ReplicationStep};
+pub use retention::{RetentionPlan, RetentionPolicy};
+pub use retry::RetryPolicy;
+pub use rollback::RollbackOptions;
+pub use send::SendOptions;
+pub use share::ShareError;
+pub use snapname::SnapshotNamer;
+pub use snapshot::{SnapshotError, SnapshotInfo};
+pub use stats::*;
+pub use status::{ErrorLogEntry, PoolStatusReport};
+pub use nvpair::{NvList, NvListRef, NvPair, NvPairs, NvValue};
+pub use supervisor::*;
+pub use vdev::{InitializeState, RaidzExpansionStatus, TrimState, Vdev, VdevInitializeStatus};
+pub use vdev::{FoundVdev, VdevClass, VdevState, VdevTrimStatus};
+pub use userspace::{SpaceOwner, SpaceUsage};
+pub use version::{ZfsVersion, ZfsVersions};
+pub use wait::*;
+pub use watch::*;
+
+use handle::LibZfsHandle;
 
 /// Flags for ZFS send operations.
 pub use sys::lzc_send_flags as ZfsSendFlags;
 
+/// Size of buffers for property values; matches libzfs's `ZFS_MAXPROPLEN`.
+const PROP_BUF_LEN: usize = 4096;
+
+/// The entry point to libzfs.
+///
+/// Pools and datasets opened through a `LibZfs` share ownership of the underlying library
+/// handle, so they stay valid even if the `LibZfs` itself is dropped first.
+///
+/// `LibZfs`, and the pools and datasets opened through it, can be shared between threads. libzfs
+/// itself is not thread-safe, so calls made through the same `LibZfs` are serialized by an
+/// internal lock; for parallelism, use a separate `LibZfs` per thread.
 #[derive(Debug)]
 pub struct LibZfs {
-    handle: *mut sys::libzfs_handle_t,
+    handle: Arc<LibZfsHandle>,
 }
 
 impl LibZfs {
     pub fn new() -> Result<Self> {
-        let handle = unsafe { sys::libzfs_init() };
-        if handle.is_null() {
-            Err(Error::Sys(std::io::Error::last_os_error()))
-        } else {
-            Ok(LibZfs { handle })
-        }
+        LibZfsHandle::init().map(|handle| LibZfs { handle })
     }
 
-    pub fn pool_by_name(&self, name: &SafeString) -> Result<ZPool> {
-        let handle = unsafe { sys::zpool_open(self.handle, name.as_ptr()) };
-        self.ptr_or_err(handle).map(|handle| ZPool { libzfs: self.handle, handle })
+    /// Have libzfs print each error to stderr as it happens, the way the `zfs` and `zpool`
+    /// commands do. It's off by default: the same text is in the returned
+    /// [`ZfsError`](crate::ZfsError), as its `action` and `msg`.
+    pub fn set_print_on_error(&self, enable: bool) {
+        let _lock = self.handle.lock();
+        unsafe { sys::libzfs_print_on_error(self.handle.raw(), enable as sys::boolean_t) };
     }
 
-    pub fn dataset_by_name(&self, name: &SafeString, types: DatasetTypeMask) -> Result<Dataset> {
-        let handle = unsafe { sys::zfs_open(self.handle, name.as_ptr(), types.0 as i32) };
-        self.ptr_or_err(handle).map(|handle| Dataset { libzfs: self.handle, handle })
+    pub fn pool_by_name<N: AsSafeStr>(&self, name: N) -> Result<ZPool> {
+        let name = name.as_safe_str()?;
+        let _lock = self.handle.lock();
+        let handle = unsafe { sys::zpool_open(self.handle.raw(), name.as_ptr()) };
+        self.ptr_or_err(handle)
+            .map(|handle| ZPool { libzfs: self.handle.clone(), handle, owned: true })
     }
 
-    pub fn create_snapshots<I, T>(&self, names: I) -> Result<()>
-        where I: Iterator<Item = T>,
-              T: AsRef<str>,
+    pub fn dataset_by_name<N>(&self, name: N, types: DatasetTypeMask) -> Result<Dataset>
+        where N: AsSafeStr,
     {
-        let nvl = self.build_nvlist(names)?;
+        let name = name.as_safe_str()?;
+        let _lock = self.handle.lock();
+        let handle = unsafe { sys::zfs_open(self.handle.raw(), name.as_ptr(), types.0 as i32) };
+        self.ptr_or_err(handle).map(|handle| Dataset { libzfs: self.handle.clone(), handle })
+    }
 
-        // Need to check if empty, otherwise it segfaults.
-        let ret = match unsafe { sys::nvlist_empty(nvl) } {
-            0 => if 0 != unsafe { sys::zfs_snapshot_nvl(self.handle, nvl, std::ptr::null_mut()) } {
-                self.get_last_error()
-            } else {
-                Ok(())
-            },
-            _ => Ok(()),
+    /// Find the mounted filesystem which contains the given path, like `/var/lib/whatever`. The
+    /// path can be anywhere inside the filesystem, not just its mountpoint.
+    pub fn dataset_by_mountpoint<P: AsRef<Path>>(&self, path: P) -> Result<Dataset> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())
+            .map_err(Error::invalid_input)?;
+        let _lock = self.handle.lock();
+        let handle = unsafe {
+            sys::zfs_path_to_zhandle(
+                self.handle.raw(),
+                path.as_ptr(),
+                sys::zfs_type_t::ZFS_TYPE_FILESYSTEM,
+            )
         };
+        self.ptr_or_err(handle).map(|handle| Dataset { libzfs: self.handle.clone(), handle })
+    }
 
-        unsafe { sys::nvlist_free(nvl) };
-
-        ret
+    /// Find the mounted filesystem which contains the given path, like
+    /// [`dataset_by_mountpoint`](Self::dataset_by_mountpoint), along with where the path is
+    /// relative to the root of the filesystem. Symlinks in the path are resolved first.
+    pub fn resolve_path<P: AsRef<Path>>(&self, path: P) -> Result<(Dataset, PathBuf)> {
+        let path = fs::canonicalize(path).map_err(Error::Sys)?;
+        let dataset = self.dataset_by_mountpoint(&path)?;
+        let relative = dataset.mounted_at()
+            .and_then(|root| path.strip_prefix(root).ok().map(Path::to_owned))
+            .ok_or_else(|| Error::not_found(
+                format!("{} is not under where {} is mounted", path.display(), dataset.name())))?;
+        Ok((dataset, relative))
     }
 
-    pub fn destroy_snapshots<I, T>(&self, names: I) -> Result<()>
+    pub fn create_snapshots<I, T>(&self, names: I) -> Result<()>
         where I: Iterator<Item = T>,
               T: AsRef<str>,
     {
+        let _lock = self.handle.lock();
         let nvl = self.build_nvlist(names)?;
 
         // Need to check if empty, otherwise it segfaults.
         let ret = match unsafe { sys::nvlist_empty(nvl) } {
-            0 => match unsafe { sys::zfs_destroy_snaps_nvl(self.handle, nvl, 0) } {
-                0 => Ok(()),
-                _ => self.get_last_error(),
+            0 => if 0 != unsafe { sys::zfs_snapshot_nvl(self.handle.raw(), nvl, std::ptr::null_mut()) } {
+                self.get_last_error()
+            } else {
+                Ok(())
             },
             _ => Ok(()),
         };
@@ -97,9 +255,14 @@ impl LibZfs {
         }
 
         for name in names {
-            let mut cstr = name.as_ref().to_owned();
-            cstr.push('\0');
-            unsafe { sys::fnvlist_add_boolean(nvl, cstr.as_ptr() as *const _) };
+            let cstr = match SafeString::try_from(name.as_ref()) {
+                Ok(cstr) => cstr,
+                Err(e) => {
+                    unsafe { sys::nvlist_free(nvl) };
+                    return Err(e);
+                }
+            };
+            unsafe { sys::fnvlist_add_boolean(nvl, cstr.as_ptr()) };
         }
 
         Ok(nvl)
@@ -107,28 +270,29 @@ impl LibZfs {
 
     pub fn get_zpools(&self) -> Result<Vec<ZPool>> {
         struct Context {
-            libzfs: *mut sys::libzfs_handle_t,
+            libzfs: Arc<LibZfsHandle>,
             pools: Vec<ZPool>,
         }
 
         extern "C" fn zpool_iter_collect(handle: *mut sys::zpool_handle_t, context: *mut c_void) -> i32 {
             let ctx = unsafe { &mut *(context as *mut Context) };
-            ctx.pools.push(ZPool { libzfs: ctx.libzfs, handle });
+            ctx.pools.push(ZPool { libzfs: ctx.libzfs.clone(), handle, owned: true });
             0
         }
 
+        let _lock = self.handle.lock();
         let mut ctx = Context {
-            libzfs: self.handle,
+            libzfs: self.handle.clone(),
             pools: vec![],
         };
 
         ztry!(unsafe {
             sys::zpool_iter(
-                self.handle,
+                self.handle.raw(),
                 Some(zpool_iter_collect),
                 &mut ctx as *mut _ as *mut c_void,
             )
-        }, self.handle);
+        }, self.handle.raw());
 
         Ok(ctx.pools)
     }
@@ -142,7 +306,7 @@ impl LibZfs {
     }
 
     fn get_last_error<T>(&self) -> Result<T> {
-        let zfs_err = ZfsError::last_error(self.handle);
+        let zfs_err = ZfsError::last_error(self.handle.raw());
         // TODO: is this valid? should we do this on EZFS_SUCCESS instead / in addition?
         if zfs_err.code != sys::zfs_error::EZFS_UNKNOWN {
             Err(Error::Zfs(zfs_err))
@@ -152,46 +316,52 @@ impl LibZfs {
     }
 }
 
-impl Drop for LibZfs {
-    fn drop(&mut self) {
-        unsafe {
-            sys::libzfs_fini(self.handle);
-        }
-    }
-}
-
-#[derive(Debug)]
 pub struct ZPool {
-    libzfs: *mut sys::libzfs_handle_t,
+    libzfs: Arc<LibZfsHandle>,
     handle: *mut sys::zpool_handle_t,
+    /// Whether the pool handle is ours to close. Handles borrowed from a dataset belong to the
+    /// library handle, which closes them itself.
+    owned: bool,
 }
 
+// Safety: the pool handle is only used while holding the libzfs handle's lock.
+unsafe impl Send for ZPool {}
+unsafe impl Sync for ZPool {}
+
 impl ZPool {
     pub fn get_state(&self) -> ZPoolState {
+        let _lock = self.libzfs.lock();
         // this is defined as returning an int, though it really returns a pool_state_t.
         let raw: i32 = unsafe { sys::zpool_get_state(self.handle) };
-        ZPoolState::from(raw as sys::pool_state_t)
+        ZPoolState::from_raw(raw as sys::pool_state_t)
     }
 
     pub fn get_name(&self) -> SafeString {
+        self.name().to_owned()
+    }
+
+    /// Borrow the name of this pool, without copying it. The library handle stays locked
+    /// while it's borrowed.
+    pub fn name(&self) -> BorrowedName<'_> {
+        let lock = self.libzfs.lock();
         let cstr = unsafe { CStr::from_ptr(sys::zpool_get_name(self.handle)) };
-        let utf8_verified = cstr.to_str().expect("invalid UTF8 in pool name");
-        SafeString::from(utf8_verified.to_owned())
+        BorrowedName::new(SafeStr::from_cstr(cstr).expect("invalid UTF8 in pool name"), lock)
     }
 
     pub fn get_datasets(&self) -> Result<Vec<Dataset>> {
+        let _lock = self.libzfs.lock();
         let pool_name = self.get_name();
 
         let root_handle = unsafe {
-            sys::zfs_open(self.libzfs, pool_name.as_ptr(), sys::zfs_type_t::ZFS_TYPE_FILESYSTEM as i32)
+            sys::zfs_open(self.libzfs.raw(), pool_name.as_ptr(), sys::zfs_type_t::ZFS_TYPE_FILESYSTEM as i32)
         };
         if root_handle.is_null() {
-            return Err(ZfsError::last_error(self.libzfs).into());
+            return Err(ZfsError::last_error(self.libzfs.raw()).into());
         }
 
         let mut ctx = ZfsIterCollectContext {
-            libzfs: self.libzfs,
-            vec: vec![Dataset { libzfs: self.libzfs, handle: root_handle }],
+            libzfs: self.libzfs.clone(),
+            vec: vec![Dataset { libzfs: self.libzfs.clone(), handle: root_handle }],
         };
 
         ztry!(unsafe {
@@ -201,7 +371,7 @@ impl ZPool {
                 Some(zfs_iter_collect),
                 &mut ctx as *mut _ as *mut c_void,
             )
-        }, self.libzfs);
+        }, self.libzfs.raw(), self.name());
 
         ctx.vec.retain(|ds| {
             let typ = ds.get_type();
@@ -209,52 +379,170 @@ impl ZPool {
         });
         Ok(ctx.vec)
     }
+
+    /// Get a copy of the pool's current configuration, with freshly updated stats.
+    pub fn get_config(&self) -> Result<NvList> {
+        let _lock = self.libzfs.lock();
+        let mut missing = 0;
+        ztry!(unsafe {
+            sys::zpool_refresh_stats(self.handle, &mut missing)
+        }, self.libzfs.raw(), self.name());
+        let config = unsafe { sys::zpool_get_config(self.handle, ptr::null_mut()) };
+        if config.is_null() {
+            return Err(Error::invalid_data("pool has no config"));
+        }
+        Ok(unsafe { NvListRef::from_ptr(config) }.to_owned())
+    }
+
+    pub(crate) fn get_prop_string(&self, prop: sys::zpool_prop_t::Type) -> Result<String> {
+        self.get_prop(prop, false)
+    }
+
+    /// Get a property's value in its parsable form, like `zpool get -p`: numbers in full,
+    /// rather than abbreviated.
+    pub(crate) fn get_prop_parsable(&self, prop: sys::zpool_prop_t::Type) -> Result<String> {
+        self.get_prop(prop, true)
+    }
+
+    fn get_prop(&self, prop: sys::zpool_prop_t::Type, literal: bool) -> Result<String> {
+        let _lock = self.libzfs.lock();
+        let mut buf = vec![0 as c_char; PROP_BUF_LEN];
+        ztry!(unsafe {
+            sys::zpool_get_prop(
+                self.handle,
+                prop,
+                buf.as_mut_ptr(),
+                buf.len(),
+                ptr::null_mut(), // source
+                literal as sys::boolean_t,
+            )
+        }, self.libzfs.raw(), self.name());
+        Ok(string::string_from_buf(&buf))
+    }
+}
+
+impl fmt::Debug for ZPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ZPool")
+            .field("name", &self.name())
+            .field("state", &self.get_state())
+            .finish()
+    }
+}
+
+impl fmt::Display for ZPool {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.name().fmt(f)
+    }
 }
 
 impl Drop for ZPool {
     fn drop(&mut self) {
+        if !self.owned {
+            return;
+        }
+        let _lock = self.libzfs.lock();
         unsafe {
             sys::zpool_close(self.handle);
         }
     }
 }
 
-#[derive(Debug)]
 pub struct Dataset {
-    libzfs: *mut sys::libzfs_handle_t,
+    libzfs: Arc<LibZfsHandle>,
     handle: *mut sys::zfs_handle_t,
 }
 
+// Safety: the dataset handle is only used while holding the libzfs handle's lock.
+unsafe impl Send for Dataset {}
+unsafe impl Sync for Dataset {}
+
 impl Dataset {
     /// Get the type of this dataset.
     pub fn get_type(&self) -> DatasetType {
-        DatasetType::from(unsafe { sys::zfs_get_type(self.handle) })
+        let _lock = self.libzfs.lock();
+        DatasetType::from_raw(unsafe { sys::zfs_get_type(self.handle) })
     }
 
     /// Get the name of this dataset.
     pub fn get_name(&self) -> SafeString {
+        self.name().to_owned()
+    }
+
+    /// Borrow the name of this dataset, without copying it. The library handle stays locked
+    /// while it's borrowed.
+    pub fn name(&self) -> BorrowedName<'_> {
+        let lock = self.libzfs.lock();
         let cstr = unsafe { CStr::from_ptr(sys::zfs_get_name(self.handle)) };
-        let utf8_verified = cstr.to_str().expect("invalid UTF8 in dataset name");
-        SafeString::from(utf8_verified.to_owned())
+        BorrowedName::new(SafeStr::from_cstr(cstr).expect("invalid UTF8 in dataset name"), lock)
     }
 
     /// Get the pool this dataset belongs to.
     pub fn get_pool(&self) -> ZPool {
+        let _lock = self.libzfs.lock();
         let handle = unsafe { sys::zfs_get_pool_handle(self.handle) };
-        ZPool { libzfs: self.libzfs, handle }
+        ZPool { libzfs: self.libzfs.clone(), handle, owned: false }
+    }
+
+    /// The library handle this dataset was opened through.
+    pub(crate) fn libzfs(&self) -> LibZfs {
+        LibZfs { handle: self.libzfs.clone() }
     }
 
     /// Get the name of the pool this dataset belongs to.
     pub fn get_pool_name(&self) -> SafeString {
+        let _lock = self.libzfs.lock();
         let cstr = unsafe { CStr::from_ptr(sys::zfs_get_pool_name(self.handle)) };
-        let utf8_verified = cstr.to_str().expect("invalid UTF8 in pool name");
-        SafeString::from(utf8_verified.to_owned())
+        SafeString::try_from(cstr).expect("invalid UTF8 in pool name")
+    }
+
+    /// Get the dataset's GUID, which identifies it regardless of renames, and tells apart
+    /// datasets which had the same name at different times.
+    pub fn guid(&self) -> u64 {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_GUID)
+    }
+
+    /// Get the transaction group in which the dataset was created. Snapshots of the same dataset
+    /// taken later always have a higher createtxg.
+    pub fn createtxg(&self) -> u64 {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_CREATETXG)
+    }
+
+    /// Get the time the dataset was created.
+    pub fn creation(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.get_int_property(sys::zfs_prop_t::ZFS_PROP_CREATION))
+    }
+
+    /// Get the space used by the dataset and its descendants, in bytes.
+    pub fn used(&self) -> u64 {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_USED)
+    }
+
+    /// Get the space available to the dataset and its children, in bytes.
+    pub fn available(&self) -> u64 {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_AVAILABLE)
+    }
+
+    /// Get the amount of data accessible by this dataset, in bytes.
+    pub fn referenced(&self) -> u64 {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_REFERENCED)
+    }
+
+    /// Get the space used by the dataset and its descendants before compression, in bytes.
+    pub fn logicalused(&self) -> u64 {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_LOGICALUSED)
+    }
+
+    pub(crate) fn get_int_property(&self, prop: sys::zfs_prop_t::Type) -> u64 {
+        let _lock = self.libzfs.lock();
+        unsafe { sys::zfs_prop_get_int(self.handle, prop) }
     }
 
     /// Get all snapshots of this dataset.
     pub fn get_snapshots(&self) -> Result<Vec<Dataset>> {
+        let _lock = self.libzfs.lock();
         let mut ctx = ZfsIterCollectContext {
-            libzfs: self.libzfs,
+            libzfs: self.libzfs.clone(),
             vec: vec![],
         };
         ztry!(unsafe {
@@ -266,14 +554,15 @@ impl Dataset {
                 0, // min_txg: none
                 0, // max_txg: none
             )
-        }, self.libzfs);
+        }, self.libzfs.raw(), self.name());
         Ok(ctx.vec)
     }
 
     /// Get all snapshots of this dataset, ordered by creation time (oldest first).
     pub fn get_snapshots_ordered(&self) -> Result<Vec<Dataset>> {
+        let _lock = self.libzfs.lock();
         let mut ctx = ZfsIterCollectContext {
-            libzfs: self.libzfs,
+            libzfs: self.libzfs.clone(),
             vec: vec![],
         };
         ztry!(unsafe {
@@ -284,52 +573,73 @@ impl Dataset {
                 0, // min_txg: none
                 0, // max_txg: none
             )
-        }, self.libzfs);
+        }, self.libzfs.raw(), self.name());
         Ok(ctx.vec)
     }
 
     /// Execute a callback function for each snapshot of this dataset.
-    pub fn foreach_snapshot(&self, callback: Box<dyn FnMut(Dataset)>) -> Result<()> {
+    pub fn foreach_snapshot<F>(&self, callback: F) -> Result<()>
+        where F: FnMut(Dataset),
+    {
+        let _lock = self.libzfs.lock();
         let mut ctx = ZfsIterCallbackContext {
-            libzfs: self.libzfs,
+            libzfs: self.libzfs.clone(),
             callback,
         };
         ztry!(unsafe {
             sys::zfs_iter_snapshots(
                 self.handle,
                 0,
-                Some(zfs_iter_callback),
+                Some(zfs_iter_callback::<F>),
                 &mut ctx as *mut _ as *mut c_void,
                 0,
                 0,
             )
-        }, self.libzfs);
+        }, self.libzfs.raw(), self.name());
         Ok(())
     }
 
     /// Execute a callback function for each snapshot of this dataset, ordered by creation time
     /// (oldest first).
-    pub fn foreach_snapshot_ordered(&self, callback: Box<dyn FnMut(Dataset)>) -> Result<()> {
+    pub fn foreach_snapshot_ordered<F>(&self, callback: F) -> Result<()>
+        where F: FnMut(Dataset),
+    {
+        let _lock = self.libzfs.lock();
         let mut ctx = ZfsIterCallbackContext {
-            libzfs: self.libzfs,
+            libzfs: self.libzfs.clone(),
             callback,
         };
         ztry!(unsafe {
             sys::zfs_iter_snapshots_sorted(
                 self.handle,
-                Some(zfs_iter_callback),
+                Some(zfs_iter_callback::<F>),
                 &mut ctx as *mut _ as *mut c_void,
                 0,
                 0,
             )
-        }, self.libzfs);
+        }, self.libzfs.raw(), self.name());
         Ok(())
     }
 
+    /// Boxed-callback version of [`foreach_snapshot`](Self::foreach_snapshot), for
+    /// compatibility.
+    #[deprecated(note = "use foreach_snapshot, which takes any closure without boxing")]
+    pub fn foreach_snapshot_boxed(&self, callback: Box<dyn FnMut(Dataset)>) -> Result<()> {
+        self.foreach_snapshot(callback)
+    }
+
+    /// Boxed-callback version of [`foreach_snapshot_ordered`](Self::foreach_snapshot_ordered),
+    /// for compatibility.
+    #[deprecated(note = "use foreach_snapshot_ordered, which takes any closure without boxing")]
+    pub fn foreach_snapshot_ordered_boxed(&self, callback: Box<dyn FnMut(Dataset)>) -> Result<()> {
+        self.foreach_snapshot_ordered(callback)
+    }
+
     /// Get all direct descendent filesystems under this one.
     pub fn get_child_filesystems(&self) -> Result<Vec<Dataset>> {
+        let _lock = self.libzfs.lock();
         let mut ctx = ZfsIterCollectContext {
-            libzfs: self.libzfs,
+            libzfs: self.libzfs.clone(),
             vec: vec![],
         };
         ztry!(unsafe {
@@ -338,14 +648,36 @@ impl Dataset {
                 Some(zfs_iter_collect),
                 &mut ctx as *mut _ as *mut c_void,
             )
-        }, self.libzfs);
+        }, self.libzfs.raw(), self.name());
         Ok(ctx.vec)
     }
 
+    /// Get this filesystem or volume and all the filesystems and volumes under it, parents
+    /// before children. Unlike [`dependents`](Self::dependents), this leaves out snapshots and
+    /// clones.
+    pub(crate) fn self_and_descendants(&self) -> Result<Vec<Dataset>> {
+        let mut datasets = vec![self.clone()];
+        let mut i = 0;
+        while i < datasets.len() {
+            let children = datasets[i].get_child_filesystems()?;
+            datasets.extend(children);
+            i += 1;
+        }
+        Ok(datasets)
+    }
+
     /// Get all child datasets of this one, recursively, of all types (snapshot, filesystem, etc.).
+    #[deprecated(note = "use dependents, which says how each dataset depends on this one")]
     pub fn get_all_dependents(&self) -> Result<Vec<Dataset>> {
+        self.all_dependents()
+    }
+
+    /// Get everything which depends on this dataset, recursively, in an order in which they can
+    /// be destroyed, including clones elsewhere in the pool.
+    pub(crate) fn all_dependents(&self) -> Result<Vec<Dataset>> {
+        let _lock = self.libzfs.lock();
         let mut ctx = ZfsIterCollectContext {
-            libzfs: self.libzfs,
+            libzfs: self.libzfs.clone(),
             vec: vec![],
         };
         ztry!(unsafe {
@@ -355,54 +687,142 @@ impl Dataset {
                 Some(zfs_iter_collect),
                 &mut ctx as *mut _ as *mut c_void,
             )
-        }, self.libzfs);
+        }, self.libzfs.raw(), self.name());
         Ok(ctx.vec)
     }
 
+    /// Destroy this dataset, like `zfs destroy`. It must not have any children, snapshots, or
+    /// clones, and must not be mounted.
+    pub fn destroy(self) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe { sys::zfs_destroy(self.handle, 0) }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
     pub fn get_send_space(&self, from_fq: Option<&SafeString>, flags: ZfsSendFlags) -> Result<u64> {
-        let name: *const c_char = unsafe { sys::zfs_get_name(self.handle) };
-        let from: *const c_char = from_fq.map(|s| s.as_ptr()).unwrap_or(ptr::null());
-        let mut space = 0u64;
-        ztry!(unsafe { sys::lzc_send_space(name, from, flags, &mut space as *mut _) }, self.libzfs);
-        Ok(space)
+        self.estimate_send_size(from_fq.map(|s| &**s), flags)
+    }
+
+    /// Estimate how many bytes sending this snapshot would write, incrementally from the
+    /// snapshot or bookmark `from_fq` if given, like `zfs send -nP`. Nothing is sent. The flags
+    /// matter, since compressed and raw streams are smaller.
+    pub fn estimate_send_size(&self, from_fq: Option<&SafeStr>, flags: ZfsSendFlags) -> Result<u64> {
+        LibZfsCore::new()?.estimate_send_size(&self.name(), from_fq, flags)
     }
 
+    /// Send this snapshot as a stream written to `fd`, like `zfs send`, incrementally from the
+    /// snapshot or bookmark `from_fq` if given. Runs on a background thread.
+    ///
+    /// With [`LZC_SEND_FLAG_RAW`](ZfsSendFlags::LZC_SEND_FLAG_RAW), an encrypted dataset's
+    /// blocks are sent still encrypted, so its key doesn't need to be loaded, and the receiving
+    /// side can't read them without it.
     pub fn send(
         &self,
         from_fq: Option<SafeString>,
         fd: OwnedFd,
         flags: ZfsSendFlags,
     ) -> Result<ZfsSend<'_>> {
-        // Best-effort attempt to set a big buffer size in case fd is a pipe.
-        let _ = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETPIPE_SZ, 1_048_576_i32) };
+        self.send_impl(vec![(from_fq, self.get_name())], None, fd, flags)
+    }
+
+    /// Send this snapshot incrementally from the earlier snapshot `from_fq` of the same dataset,
+    /// including every snapshot in between, like `zfs send -I`. This is written as a series of
+    /// incremental streams one after another, each of which needs its own call to
+    /// [`LibZfs::receive`] on the receiving side.
+    ///
+    /// [`ZfsSend::progress`] only works while the last stream is being sent.
+    pub fn send_intermediates(
+        &self,
+        from_fq: SafeString,
+        fd: OwnedFd,
+        flags: ZfsSendFlags,
+    ) -> Result<ZfsSend<'_>> {
+        let libzfs = self.libzfs();
+        let name = self.get_name();
+        let from = libzfs.dataset_by_name(&from_fq, DatasetType::Snapshot.into())?;
+        let parent = |name: &SafeStr| name.as_str().split_once('@').map(|(fs, _)| fs.to_owned());
+        let fs = match (parent(&name), parent(&from.name())) {
+            (Some(fs), Some(from_fs)) if fs == from_fs => fs,
+            _ => return Err(Error::invalid_input(format!(
+                "{} is not an earlier snapshot of the same dataset as {}", from_fq, name))),
+        };
+        let (from_txg, to_txg) = (from.createtxg(), self.createtxg());
+        if from_txg >= to_txg {
+            return Err(Error::invalid_input(format!("{} is not older than {}", from_fq, name)));
+        }
 
-        // Safety is ensured by tying the lifetime of the ZfsSend holding the thread to the
-        // lifetime of the dataset, which holds the libzfs handle.
-        struct Wrap(*mut sys::libzfs_handle);
-        unsafe impl Send for Wrap {}
-        impl Wrap {
-            pub fn ptr(&self) -> *mut sys::libzfs_handle {
-                self.0
+        let fs = libzfs.dataset_by_name(fs, DatasetType::Filesystem | DatasetType::Volume)?;
+        let mut steps = vec![];
+        let mut prev = from_fq;
+        for snap in fs.get_snapshots_ordered()? {
+            let txg = snap.createtxg();
+            if txg > from_txg && txg <= to_txg {
+                let snap = snap.get_name();
+                steps.push((Some(prev), snap.clone()));
+                prev = snap;
             }
         }
-        let w = Wrap(self.libzfs);
+        self.send_impl(steps, None, fd, flags)
+    }
+
+    /// Send this snapshot redacted with the given redaction bookmark (full name, like
+    /// `pool/fs#book`), like `zfs send --redact`: blocks which were changed or removed in the
+    /// bookmark's redaction snapshots are left out of the stream.
+    pub fn send_redacted(
+        &self,
+        from_fq: Option<SafeString>,
+        redaction_bookmark: SafeString,
+        fd: OwnedFd,
+        flags: ZfsSendFlags,
+    ) -> Result<ZfsSend<'_>> {
+        self.send_impl(vec![(from_fq, self.get_name())], Some(redaction_bookmark), fd, flags)
+    }
+
+    /// Send each `(from, to)` snapshot pair in turn on a background thread.
+    fn send_impl(
+        &self,
+        steps: Vec<(Option<SafeString>, SafeString)>,
+        redaction_bookmark: Option<SafeString>,
+        fd: OwnedFd,
+        flags: ZfsSendFlags,
+    ) -> Result<ZfsSend<'_>> {
+        // Best-effort attempt to set a big buffer size in case fd is a pipe. Only Linux lets
+        // pipe buffers be resized.
+        #[cfg(target_os = "linux")]
+        let _ = unsafe { libc::fcntl(fd.as_raw_fd(), libc::F_SETPIPE_SZ, 1_048_576_i32) };
 
-        let fqname = self.get_name();
+        // Only the thread closes the fd, so it stays open while the thread uses it.
+        let raw_fd = fd.as_raw_fd();
+        let fd = Arc::new(Mutex::new(Some(fd)));
+        let thread_fd = Arc::clone(&fd);
         let thread = thread::spawn(move || {
-            ztry!(unsafe {
-                sys::lzc_send(
-                    fqname.as_ptr(),
-                    from_fq.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()),
-                    fd.as_raw_fd(),
-                    flags,
-                )
-            }, w.ptr());
-            drop(fd);
-            Ok(())
+            let send = || {
+                for (from_fq, fqname) in steps {
+                    let ret = lzc_call!(unsafe {
+                        sys::lzc_send_redacted(
+                            fqname.as_ptr(),
+                            from_fq.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()),
+                            raw_fd,
+                            flags,
+                            redaction_bookmark.as_ref().map(|s| s.as_ptr()).unwrap_or(ptr::null()),
+                        )
+                    }, fqname);
+                    if ret != 0 {
+                        return Err(Error::Sys(io::Error::from_raw_os_error(ret)));
+                    }
+                }
+                Ok(())
+            };
+            let result = send();
+            // Close the stream, so whatever's reading it sees the end, but not while
+            // ZfsSend::progress is using it.
+            thread_fd.lock().unwrap_or_else(PoisonError::into_inner).take();
+            result
         });
 
         Ok(ZfsSend {
-            _dataset: PhantomData,
+            dataset: self,
+            fd,
             thread,
         })
     }
@@ -418,11 +838,45 @@ impl std::fmt::Display for ZfsSendPanicked {
 impl std::error::Error for ZfsSendPanicked {}
 
 pub struct ZfsSend<'a> {
-    _dataset: PhantomData<&'a Dataset>,
+    dataset: &'a Dataset,
+    /// The fd being sent to, until the send is done and the thread closes it.
+    fd: Arc<Mutex<Option<OwnedFd>>>,
     thread: JoinHandle<Result<()>>,
 }
 
+/// How far along a send is, as reported by [`ZfsSend::progress`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct SendProgress {
+    pub bytes_written: u64,
+    pub blocks_visited: u64,
+}
+
 impl<'a> ZfsSend<'a> {
+    /// Check how far the send has got. This only works while it is still running.
+    pub fn progress(&self) -> Result<SendProgress> {
+        let fd = self.fd.lock().unwrap_or_else(PoisonError::into_inner);
+        let fd = fd.as_ref().ok_or_else(|| Error::invalid_input("the send has finished"))?;
+        let _lock = self.dataset.libzfs.lock();
+        let mut progress = SendProgress { bytes_written: 0, blocks_visited: 0 };
+        let ret = unsafe {
+            sys::zfs_send_progress(
+                self.dataset.handle,
+                fd.as_raw_fd(),
+                &mut progress.bytes_written,
+                &mut progress.blocks_visited,
+            )
+        };
+        if ret != 0 {
+            return Err(Error::Sys(io::Error::from_raw_os_error(ret)));
+        }
+        Ok(progress)
+    }
+
+    /// Whether the send has finished, successfully or not.
+    pub fn is_finished(&self) -> bool {
+        self.thread.is_finished()
+    }
+
     /// Blocks the current thread until the send is done, whether successful or failure.
     pub fn wait(self) -> Result<()> {
         self.thread.join()
@@ -431,36 +885,80 @@ impl<'a> ZfsSend<'a> {
 }
 
 struct ZfsIterCollectContext {
-    libzfs: *mut sys::libzfs_handle_t,
+    libzfs: Arc<LibZfsHandle>,
     vec: Vec<Dataset>,
 }
 
 extern "C" fn zfs_iter_collect(handle: *mut sys::zfs_handle_t, context: *mut c_void) -> i32 {
     let ctx = unsafe { &mut *(context as *mut ZfsIterCollectContext) };
-    ctx.vec.push(Dataset { libzfs: ctx.libzfs, handle });
+    ctx.vec.push(Dataset { libzfs: ctx.libzfs.clone(), handle });
     0
 }
 
-struct ZfsIterCallbackContext {
-    libzfs: *mut sys::libzfs_handle_t,
-    callback: Box<dyn FnMut(Dataset)>,
+struct ZfsIterCallbackContext<F> {
+    libzfs: Arc<LibZfsHandle>,
+    callback: F,
 }
 
-extern "C" fn zfs_iter_callback(handle: *mut sys::zfs_handle_t, context: *mut c_void) -> i32 {
-    let ctx = unsafe { &mut *(context as *mut ZfsIterCallbackContext) };
-    (ctx.callback)(Dataset { libzfs: ctx.libzfs, handle });
+extern "C" fn zfs_iter_callback<F: FnMut(Dataset)>(
+    handle: *mut sys::zfs_handle_t,
+    context: *mut c_void,
+) -> i32 {
+    let ctx = unsafe { &mut *(context as *mut ZfsIterCallbackContext<F>) };
+    (ctx.callback)(Dataset { libzfs: ctx.libzfs.clone(), handle });
     0
 }
 
 impl Clone for Dataset {
     fn clone(&self) -> Self {
+        let _lock = self.libzfs.lock();
         let handle = unsafe { sys::zfs_handle_dup(self.handle) };
-        Dataset { libzfs: self.libzfs, handle }
+        Dataset { libzfs: self.libzfs.clone(), handle }
+    }
+}
+
+/// Sort snapshots oldest first, the same order as
+/// [`get_snapshots_ordered`](Dataset::get_snapshots_ordered): by createtxg, and then by name for
+/// snapshots taken together (like by `zfs snapshot -r`).
+pub fn sort_snapshots(snapshots: &mut [Dataset]) {
+    snapshots.sort_by_cached_key(|snap| (snap.createtxg(), snap.get_name()));
+}
+
+/// Datasets are equal if they have the same type and GUID, i.e. they're handles to the same
+/// dataset. The type matters because a bookmark has the GUID of the snapshot it was made from.
+impl PartialEq for Dataset {
+    fn eq(&self, other: &Dataset) -> bool {
+        (self.get_type(), self.guid()) == (other.get_type(), other.guid())
+    }
+}
+
+impl Eq for Dataset {}
+
+impl std::hash::Hash for Dataset {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.get_type().hash(state);
+        self.guid().hash(state);
+    }
+}
+
+impl fmt::Debug for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Dataset")
+            .field("name", &self.name())
+            .field("type", &self.get_type())
+            .finish()
+    }
+}
+
+impl fmt::Display for Dataset {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.name().fmt(f)
     }
 }
 
 impl Drop for Dataset {
     fn drop(&mut self) {
+        let _lock = self.libzfs.lock();
         unsafe {
             sys::zfs_close(self.handle);
         }
@@ -483,27 +981,47 @@ macro_rules! translate_enum {
         // to join a path and an identifier with '::' in a macro. :(
         use $sys_name::*;
 
-        #[derive(Debug, Copy, Clone, PartialEq, Eq)]
-        #[repr($repr)]
+        #[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub enum $new_name {
-            $($new = ($sys as $repr),)*
+            $($new,)*
+            /// A value added by a newer version of libzfs than this crate knows about.
+            Unknown($repr),
         }
 
-        impl From<$repr> for $new_name {
-            fn from(raw: $repr) -> $new_name {
+        impl $new_name {
+            /// Convert a raw value, mapping any this crate doesn't know about to `Unknown`.
+            pub fn from_raw(raw: $repr) -> $new_name {
                 use $new_name::*;
                 match raw {
                     $(
                         $sys => $new
                     ),*,
-                    _ => panic!("unknown {} variant: {}", stringify!($sys_name), raw)
+                    _ => Unknown(raw),
+                }
+            }
+        }
+
+        impl TryFrom<$repr> for $new_name {
+            type Error = Error;
+            fn try_from(raw: $repr) -> Result<$new_name> {
+                match $new_name::from_raw(raw) {
+                    $new_name::Unknown(_) => Err(Error::invalid_data(
+                        format!("unknown {} variant: {}", stringify!($sys_name), raw))),
+                    val => Ok(val),
                 }
             }
         }
 
         impl From<$new_name> for $repr {
             fn from(val: $new_name) -> $repr {
-                unsafe { std::mem::transmute(val) }
+                use $new_name::*;
+                match val {
+                    $(
+                        $new => $sys
+                    ),*,
+                    Unknown(raw) => raw,
+                }
             }
         }
     }
@@ -538,12 +1056,70 @@ translate_enum! {
     }
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// A set of dataset types, for looking up or listing datasets of any of them. Make one from a
+/// `DatasetType`, by combining types with `|`, or from one of the constants.
+#[derive(Copy, Clone, PartialEq, Eq)]
 pub struct DatasetTypeMask(u32);
 
 impl DatasetTypeMask {
+    /// Every type.
+    pub const ALL: Self = DatasetTypeMask(u32::MAX);
+
+    /// No types at all.
+    pub const NONE: Self = DatasetTypeMask(0);
+
+    /// Filesystems and volumes: the types which can have children and snapshots.
+    pub const FILESYSTEM_AND_VOLUME: Self = DatasetTypeMask(
+        sys::zfs_type_t::ZFS_TYPE_FILESYSTEM | sys::zfs_type_t::ZFS_TYPE_VOLUME);
+
+    /// Filesystems, volumes, and snapshots, like the `zfs` command's idea of a dataset.
+    pub const DATASETS: Self = DatasetTypeMask(
+        sys::zfs_type_t::ZFS_TYPE_FILESYSTEM
+        | sys::zfs_type_t::ZFS_TYPE_VOLUME
+        | sys::zfs_type_t::ZFS_TYPE_SNAPSHOT);
+
+    /// The types this crate knows of, in the order [`iter`](Self::iter) gives them.
+    const KNOWN: [DatasetType; 5] = [
+        DatasetType::Filesystem,
+        DatasetType::Snapshot,
+        DatasetType::Volume,
+        DatasetType::Pool,
+        DatasetType::Bookmark,
+    ];
+
     pub fn all() -> Self {
-        DatasetTypeMask(std::u32::MAX)
+        Self::ALL
+    }
+
+    /// Whether the given type is in the set.
+    pub fn contains(self, t: DatasetType) -> bool {
+        let bits: u32 = t.into();
+        bits != 0 && self.0 & bits == bits
+    }
+
+    /// Whether the set has no types in it.
+    pub fn is_empty(self) -> bool {
+        self.iter().next().is_none()
+    }
+
+    /// The types in the set. Only types this crate knows of are included, even in
+    /// [`ALL`](Self::ALL).
+    pub fn iter(self) -> impl Iterator<Item = DatasetType> {
+        Self::KNOWN.into_iter().filter(move |&t| self.contains(t))
+    }
+}
+
+/// Shows the types in the set, like `DatasetTypeMask(Filesystem | Volume)`.
+impl std::fmt::Debug for DatasetTypeMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.write_str("DatasetTypeMask(")?;
+        for (i, t) in self.iter().enumerate() {
+            if i > 0 {
+                f.write_str(" | ")?;
+            }
+            write!(f, "{:?}", t)?;
+        }
+        f.write_str(")")
     }
 }
 
@@ -560,6 +1136,34 @@ impl std::ops::BitOr for DatasetType {
     }
 }
 
+impl std::ops::BitOr for DatasetTypeMask {
+    type Output = DatasetTypeMask;
+    fn bitor(self, rhs: DatasetTypeMask) -> Self::Output {
+        DatasetTypeMask(self.0 | rhs.0)
+    }
+}
+
+impl std::ops::BitAnd for DatasetTypeMask {
+    type Output = DatasetTypeMask;
+    fn bitand(self, rhs: DatasetTypeMask) -> Self::Output {
+        DatasetTypeMask(self.0 & rhs.0)
+    }
+}
+
+impl std::ops::BitAnd<DatasetType> for DatasetTypeMask {
+    type Output = DatasetTypeMask;
+    fn bitand(self, rhs: DatasetType) -> Self::Output {
+        DatasetTypeMask(self.0 & Into::<u32>::into(rhs))
+    }
+}
+
+impl std::ops::Not for DatasetTypeMask {
+    type Output = DatasetTypeMask;
+    fn not(self) -> Self::Output {
+        DatasetTypeMask(!self.0)
+    }
+}
+
 impl std::ops::BitOr<DatasetType> for DatasetTypeMask {
     type Output = DatasetTypeMask;
     fn bitor(self, rhs: DatasetType) -> Self::Output {
@@ -0,0 +1,258 @@
+use crate::{sys, ztry, zfs_iter_collect, Dataset, DatasetType, DatasetTypeMask, Error, LibZfs};
+use crate::{NvListRef, Result, SafeString, ZfsError, ZfsIterCollectContext, ZPoolState};
+
+use std::os::raw::{c_int, c_void};
+
+/// What a bulk listing does when reading an individual dataset fails.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ListMode {
+    /// Stop and return the first error.
+    Strict,
+    /// Record the error in that dataset's row and carry on. On busy pools, datasets (especially
+    /// snapshots) can be destroyed partway through a listing, and this keeps that from failing
+    /// the whole thing. Datasets which vanish, or which can't be looked into for lack of
+    /// permission, while finding what to list are skipped, along with everything under them.
+    Lenient,
+}
+
+impl ListMode {
+    /// Carry on past an error from walking the tree, as if nothing was found, if this mode
+    /// allows it.
+    pub(crate) fn tolerate<T: Default>(self, result: Result<T>) -> Result<T> {
+        match result {
+            Err(e) if self == ListMode::Lenient && (e.is_not_found() || e.is_permission()) => {
+                Ok(T::default())
+            }
+            result => result,
+        }
+    }
+}
+
+/// Which datasets [`Dataset::list`] and [`LibZfs::list_datasets`] return.
+#[derive(Debug, Clone)]
+pub struct ListFilter {
+    /// The types of dataset to return, like `zfs list -t`. Snapshots and bookmarks are only
+    /// looked for at all if asked for here. Defaults to filesystems and volumes.
+    pub types: DatasetTypeMask,
+    /// How many levels below the starting point to go, like `zfs list -d`: 0 is only the
+    /// starting dataset itself, and 1 adds its children and its own snapshots. `None` means no
+    /// limit.
+    pub max_depth: Option<usize>,
+    /// Only return datasets whose properties have these values, like `("mounted", "yes")`.
+    /// Values are compared in their parsable form. Datasets which don't have the property at
+    /// all don't match.
+    pub properties: Vec<(String, String)>,
+}
+
+impl Default for ListFilter {
+    fn default() -> Self {
+        ListFilter {
+            types: DatasetType::Filesystem | DatasetType::Volume,
+            max_depth: None,
+            properties: vec![],
+        }
+    }
+}
+
+impl ListFilter {
+    fn wants(&self, typ: DatasetType) -> bool {
+        self.types.0 & u32::from(typ) != 0
+    }
+
+    pub(crate) fn matches(&self, ds: &Dataset) -> bool {
+        self.wants(ds.get_type())
+            && self.properties.iter()
+                .all(|(name, value)| ds.get_property(name).is_ok_and(|v| &v == value))
+    }
+}
+
+/// One row of a bulk property listing.
+#[derive(Debug)]
+pub struct PropertyRow {
+    pub name: SafeString,
+    /// The requested property values, in the order they were asked for, or the error hit while
+    /// reading them. Native properties which don't apply to the dataset's type, like `volsize`
+    /// for a filesystem, and user properties which aren't set on it are `None`.
+    pub values: Result<Vec<Option<String>>>,
+}
+
+impl LibZfs {
+    /// List datasets in all imported pools, like `zfs list`, returning only those which pass the
+    /// filter, sorted by name. Depth is counted from each pool's root filesystem. Pools which are
+    /// unavailable are skipped.
+    pub fn list_datasets(&self, filter: &ListFilter) -> Result<Vec<Dataset>> {
+        let mut found = vec![];
+        for pool in self.get_zpools()? {
+            if pool.get_state() == ZPoolState::Unavailable {
+                continue;
+            }
+            let root = self.dataset_by_name(pool.name(), DatasetType::Filesystem.into())?;
+            root.list_into(filter, 0, ListMode::Strict, &mut found)?;
+            if filter.matches(&root) {
+                found.push(root);
+            }
+        }
+        found.sort_by(|a, b| a.name().cmp(&b.name()));
+        Ok(found)
+    }
+}
+
+impl Dataset {
+    /// List this dataset and those under it, like `zfs list -r`, returning only those which pass
+    /// the filter, sorted by name.
+    ///
+    /// The filter is applied while walking the tree: datasets which don't pass are let go
+    /// immediately, snapshots and bookmarks are only listed if the filter asks for them, and
+    /// nothing deeper than `max_depth` is visited.
+    pub fn list(&self, filter: &ListFilter) -> Result<Vec<Dataset>> {
+        self.list_with_mode(filter, ListMode::Strict)
+    }
+
+    /// Like [`list`](Self::list), but with what to do about errors from walking the tree.
+    fn list_with_mode(&self, filter: &ListFilter, mode: ListMode) -> Result<Vec<Dataset>> {
+        let mut found = vec![];
+        self.list_into(filter, 0, mode, &mut found)?;
+        if filter.matches(self) {
+            found.push(self.clone());
+        }
+        found.sort_by(|a, b| a.name().cmp(&b.name()));
+        Ok(found)
+    }
+
+    /// Add whatever passes the filter under this dataset, which is at the given depth.
+    pub(crate) fn list_into(
+        &self,
+        filter: &ListFilter,
+        depth: usize,
+        mode: ListMode,
+        found: &mut Vec<Dataset>,
+    ) -> Result<()> {
+        for child in self.list_level(filter, depth, mode, found)? {
+            child.list_into(filter, depth + 1, mode, found)?;
+            if filter.matches(&child) {
+                found.push(child);
+            }
+        }
+        Ok(())
+    }
+
+    /// Add this dataset's snapshots and bookmarks which pass the filter, and return its children,
+    /// if the filter goes deeper than the depth this dataset is at.
+    pub(crate) fn list_level(
+        &self,
+        filter: &ListFilter,
+        depth: usize,
+        mode: ListMode,
+        found: &mut Vec<Dataset>,
+    ) -> Result<Vec<Dataset>> {
+        if filter.max_depth.is_some_and(|max| depth >= max)
+            || !matches!(self.get_type(), DatasetType::Filesystem | DatasetType::Volume)
+        {
+            return Ok(vec![]);
+        }
+        if filter.wants(DatasetType::Snapshot) {
+            let snapshots = mode.tolerate(self.get_snapshots())?;
+            found.extend(snapshots.into_iter().filter(|snap| filter.matches(snap)));
+        }
+        if filter.wants(DatasetType::Bookmark) {
+            let bookmarks = mode.tolerate(self.bookmarks())?;
+            found.extend(bookmarks.into_iter().filter(|bm| filter.matches(bm)));
+        }
+        mode.tolerate(self.get_child_filesystems())
+    }
+
+    pub(crate) fn bookmarks(&self) -> Result<Vec<Dataset>> {
+        let _lock = self.libzfs.lock();
+        let mut ctx = ZfsIterCollectContext {
+            libzfs: self.libzfs.clone(),
+            vec: vec![],
+        };
+        ztry!(unsafe {
+            sys::zfs_iter_bookmarks(
+                self.handle,
+                Some(zfs_iter_collect),
+                &mut ctx as *mut _ as *mut c_void,
+            )
+        }, self.libzfs.raw(), self.name());
+        Ok(ctx.vec)
+    }
+
+    /// Read the given properties from this dataset and all its descendants of the given types,
+    /// like `zfs list -r -p -t <types> -o <props>`. Rows are sorted by dataset name. See
+    /// [`bulk_properties`] for how the properties are read, and [`ListMode`] for what happens
+    /// when something goes wrong.
+    pub fn list_properties(
+        &self,
+        props: &[&str],
+        types: DatasetTypeMask,
+        mode: ListMode,
+    ) -> Result<Vec<PropertyRow>> {
+        let datasets = self.list_with_mode(&ListFilter { types, ..ListFilter::default() }, mode)?;
+        bulk_properties(&datasets, props, mode)
+    }
+}
+
+/// A property name, looked up once for a whole bulk listing.
+enum PropSpec<'a> {
+    Native(sys::zfs_prop_t::Type),
+    User(&'a str),
+}
+
+/// Read the given properties from each of the given datasets, returning a row for each, in the
+/// same order. Property names are looked up once up front rather than once per dataset, and
+/// each dataset's values are read from the properties its handle already has cached, without
+/// asking the kernel again.
+pub fn bulk_properties(datasets: &[Dataset], props: &[&str], mode: ListMode)
+    -> Result<Vec<PropertyRow>>
+{
+    let specs = props.iter()
+        .map(|&name| {
+            let cname = SafeString::try_from(name)?;
+            let prop = unsafe { sys::zfs_name_to_prop(cname.as_ptr()) };
+            if prop != sys::zfs_prop_t::ZPROP_INVAL {
+                Ok(PropSpec::Native(prop))
+            } else if unsafe { sys::zfs_prop_user(cname.as_ptr()) } != 0 {
+                Ok(PropSpec::User(name))
+            } else {
+                Err(Error::invalid_input(format!("invalid property name {:?}", name)))
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    let mut rows = vec![];
+    for ds in datasets {
+        let values = ds.read_props(&specs);
+        let values = match (mode, values) {
+            (ListMode::Strict, Err(e)) => return Err(e),
+            (_, values) => values,
+        };
+        rows.push(PropertyRow { name: ds.get_name(), values });
+    }
+    Ok(rows)
+}
+
+impl Dataset {
+    fn read_props(&self, specs: &[PropSpec]) -> Result<Vec<Option<String>>> {
+        let _lock = self.libzfs.lock();
+        let typ = unsafe { sys::zfs_get_type(self.handle) };
+        let mut user_props = None;
+        specs.iter()
+            .map(|spec| match *spec {
+                PropSpec::Native(prop) => {
+                    if unsafe { sys::zfs_prop_valid_for_type(prop as c_int, typ, 0) } == 0 {
+                        return Ok(None);
+                    }
+                    self.get_native_property(prop).map(Some)
+                }
+                PropSpec::User(name) => {
+                    let user_props = *user_props.get_or_insert_with(|| unsafe {
+                        NvListRef::from_ptr(sys::zfs_get_user_props(self.handle))
+                    });
+                    Ok(user_props.lookup_nvlist(name)
+                        .and_then(|prop| prop.lookup_string("value"))
+                        .map(|value| value.into_owned()))
+                }
+            })
+            .collect()
+    }
+}
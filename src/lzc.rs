@@ -0,0 +1,130 @@
+use crate::{sys, Error, NvList, Result, SafeStr, SafeString, SnapshotError, ZfsSendFlags};
+use crate::create::props_nvlist;
+use crate::error::{batch_errors, lzc_call};
+use crate::snapshot::snapshots_by_pool;
+
+use std::io;
+use std::os::fd::{AsRawFd, BorrowedFd};
+use std::os::raw::c_char;
+use std::ptr;
+use std::sync::Arc;
+
+/// Operations done through `libzfs_core` alone, without a libzfs handle.
+///
+/// Everything here works on datasets by name and reports errors by errno, so there's no lock
+/// to take and no libzfs global state involved: calls from many threads at once go straight to
+/// the kernel in parallel. That makes this a better fit than [`LibZfs`](crate::LibZfs) for
+/// daemons which mostly snapshot, hold, send, and receive. In exchange, nothing is mounted or
+/// unmounted, and errors have only an errno, not libzfs's description.
+///
+/// The equivalent [`LibZfs`](crate::LibZfs) methods (like [`hold`](crate::LibZfs::hold)) use
+/// this under the hood.
+#[derive(Clone)]
+pub struct LibZfsCore {
+    _init: Arc<CoreInit>,
+}
+
+struct CoreInit;
+
+impl Drop for CoreInit {
+    fn drop(&mut self) {
+        unsafe { sys::libzfs_core_fini() };
+    }
+}
+
+impl std::fmt::Debug for LibZfsCore {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("LibZfsCore")
+    }
+}
+
+impl LibZfsCore {
+    /// Open `/dev/zfs`, unless this process already has it open through `libzfs_core`.
+    pub fn new() -> Result<Self> {
+        match unsafe { sys::libzfs_core_init() } {
+            0 => Ok(LibZfsCore { _init: Arc::new(CoreInit) }),
+            e => Err(Error::Sys(io::Error::from_raw_os_error(e))),
+        }
+    }
+
+    /// Create snapshots in bulk, given their full names (like `pool/fs@snap`), all with the given
+    /// properties, like `zfs snapshot`.
+    ///
+    /// The snapshots are created in one batch per pool, and each batch is atomic: either all of
+    /// a pool's snapshots are created, at the same moment, or none are. Returns the snapshots
+    /// which weren't created, along with the reason; an empty list means all went well.
+    pub fn snapshot<I, T>(&self, names: I, props: &[(&str, &str)])
+        -> Result<Vec<SnapshotError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        let props = props_nvlist(props)?;
+        let mut failed = vec![];
+        for names in snapshots_by_pool(names)? {
+            let mut nvl = NvList::new()?;
+            for name in &names {
+                nvl.add_boolean(name.as_ref())?;
+            }
+
+            let mut errlist = ptr::null_mut();
+            let ret = lzc_call!(unsafe {
+                sys::lzc_snapshot(nvl.as_ptr(), props.as_ptr(), &mut errlist)
+            }, format_args!("{} snapshots", names.len()));
+            if ret == 0 {
+                continue;
+            }
+
+            failed.extend(batch_errors("snapshot", names, ret, errlist).into_iter()
+                .map(|(name, error)| SnapshotError { name, error }));
+        }
+        Ok(failed)
+    }
+
+    /// Destroy a filesystem or volume, like `zfs destroy`. It must not have any children,
+    /// snapshots, or clones, and must not be mounted, since nothing here unmounts it.
+    pub fn destroy(&self, name: &str) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        match lzc_call!(unsafe { sys::lzc_destroy(name.as_ptr()) }, name) {
+            0 => Ok(()),
+            e => Err(Error::Sys(io::Error::from_raw_os_error(e)).context("destroy", name)),
+        }
+    }
+
+    /// Send the snapshot `snapshot` (full name) as a stream written to `fd`, like `zfs send`,
+    /// incrementally from the snapshot or bookmark `from` if given. Blocks until the whole
+    /// stream has been written.
+    pub fn send(
+        &self,
+        snapshot: &SafeStr,
+        from: Option<&SafeStr>,
+        fd: BorrowedFd<'_>,
+        flags: ZfsSendFlags,
+    ) -> Result<()> {
+        let from: *const c_char = from.map(|s| s.as_ptr()).unwrap_or(ptr::null());
+        match lzc_call!(unsafe {
+            sys::lzc_send(snapshot.as_ptr(), from, fd.as_raw_fd(), flags)
+        }, snapshot) {
+            0 => Ok(()),
+            e => Err(Error::Sys(io::Error::from_raw_os_error(e)).context("send", snapshot)),
+        }
+    }
+
+    /// Estimate how many bytes [`send`](Self::send) would write, like
+    /// [`Dataset::estimate_send_size`](crate::Dataset::estimate_send_size).
+    pub fn estimate_send_size(
+        &self,
+        snapshot: &SafeStr,
+        from: Option<&SafeStr>,
+        flags: ZfsSendFlags,
+    ) -> Result<u64> {
+        let from: *const c_char = from.map(|s| s.as_ptr()).unwrap_or(ptr::null());
+        let mut space = 0u64;
+        match lzc_call!(unsafe {
+            sys::lzc_send_space(snapshot.as_ptr(), from, flags, &mut space)
+        }, snapshot) {
+            0 => Ok(space),
+            e => Err(Error::Sys(io::Error::from_raw_os_error(e))
+                .context("estimate send size of", snapshot)),
+        }
+    }
+}
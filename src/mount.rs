@@ -0,0 +1,344 @@
+use crate::{sys, ztry, Dataset, DatasetType, Error, LibZfs, Result, SafeString, ZfsError};
+
+use std::ffi::{CStr, CString, OsStr};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::os::raw::c_int;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::ptr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// libzfs's mount flag for mounting over a non-empty directory, from libspl's `sys/mount.h`.
+const MS_OVERLAY: c_int = sys::LIBZFS_MS_OVERLAY as c_int;
+
+/// The value of a filesystem's `mountpoint` property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mountpoint {
+    Path(PathBuf),
+    /// Mounted with mount(8) and /etc/fstab rather than by ZFS.
+    Legacy,
+    /// Never mounted.
+    None,
+}
+
+impl Mountpoint {
+    /// The path, if ZFS mounts the filesystem itself, rather than it being `legacy` or `none`.
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Mountpoint::Path(path) => Some(path),
+            Mountpoint::Legacy | Mountpoint::None => None,
+        }
+    }
+
+    /// Whether the filesystem is mounted with mount(8) and /etc/fstab rather than by ZFS.
+    pub fn is_legacy(&self) -> bool {
+        *self == Mountpoint::Legacy
+    }
+}
+
+/// Options for [`Dataset::mount_with_options`]. Each one which is set overrides the matching
+/// property for this mount only, like the temporary properties of `zfs mount -o`; the rest come
+/// from the properties as usual.
+#[derive(Debug, Clone, Default)]
+pub struct MountOptions {
+    /// Overrides `readonly`.
+    pub read_only: Option<bool>,
+    /// Overrides `exec`.
+    pub exec: Option<bool>,
+    /// Overrides `setuid`.
+    pub setuid: Option<bool>,
+    /// Overrides `devices`.
+    pub devices: Option<bool>,
+    /// Overrides `atime`.
+    pub atime: Option<bool>,
+    /// Overrides `relatime`.
+    pub relatime: Option<bool>,
+    /// Mount even if the mountpoint directory isn't empty, hiding what's in it, like
+    /// `zfs mount -O`.
+    pub overlay: bool,
+}
+
+/// Formats the property overrides as a mount(8) option string, like `ro,noexec`.
+impl fmt::Display for MountOptions {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let opts = [
+            (self.read_only, "ro", "rw"),
+            (self.exec, "exec", "noexec"),
+            (self.setuid, "suid", "nosuid"),
+            (self.devices, "dev", "nodev"),
+            (self.atime, "atime", "noatime"),
+            (self.relatime, "relatime", "norelatime"),
+        ];
+        let opts = opts.iter()
+            .filter_map(|&(value, on, off)| value.map(|v| if v { on } else { off }))
+            .collect::<Vec<_>>();
+        f.write_str(&opts.join(","))
+    }
+}
+
+/// Options for [`Dataset::unmount_with_options`].
+#[derive(Debug, Clone, Default)]
+pub struct UnmountOptions {
+    /// Unmount even if the filesystem is busy, like `zfs unmount -f`.
+    pub force: bool,
+    /// Detach the filesystem now, and finish unmounting it once it's no longer busy, like
+    /// `umount -l`. Only Linux can do this.
+    pub lazy: bool,
+}
+
+/// The error inside a failed unmount's [`io::Error`] when the filesystem is busy.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountBusy {
+    pub mountpoint: PathBuf,
+    /// The processes with files, directories, or their root open under the mountpoint, as far
+    /// as could be found.
+    pub pids: Vec<u32>,
+}
+
+impl fmt::Display for MountBusy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} is busy", self.mountpoint.display())?;
+        if !self.pids.is_empty() {
+            let pids = self.pids.iter().map(u32::to_string).collect::<Vec<_>>();
+            write!(f, " (in use by pid {})", pids.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MountBusy {}
+
+/// Find the processes using anything under a path, by looking at their open files, working
+/// directory, and root in /proc.
+pub(crate) fn processes_using(path: &Path) -> Vec<u32> {
+    let uses = |link: PathBuf| fs::read_link(link).is_ok_and(|target| target.starts_with(path));
+    let Ok(procs) = fs::read_dir("/proc") else {
+        return vec![];
+    };
+    let mut pids = procs
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let pid = entry.file_name().to_str()?.parse::<u32>().ok()?;
+            let dir = entry.path();
+            let using = ["cwd", "root", "exe"].iter().any(|link| uses(dir.join(link)))
+                || fs::read_dir(dir.join("fd")).is_ok_and(|fds| {
+                    fds.filter_map(|fd| fd.ok()).any(|fd| uses(fd.path()))
+                });
+            using.then_some(pid)
+        })
+        .collect::<Vec<_>>();
+    pids.sort();
+    pids
+}
+
+/// A filesystem which couldn't be mounted, and why.
+#[derive(Debug)]
+pub struct MountError {
+    pub name: SafeString,
+    pub error: Error,
+}
+
+impl LibZfs {
+    /// Turn libzfs's cache of the mount table on or off. With it on, checking whether
+    /// filesystems are mounted (and where) reads `/proc/mounts` (or the platform's equivalent)
+    /// once and then answers from the cache, instead of reading it again for every check.
+    ///
+    /// Mounts and unmounts done through this handle keep the cache up to date, but others, such
+    /// as by other processes, aren't noticed until
+    /// [`invalidate_mnttab_cache`](Self::invalidate_mnttab_cache) is called.
+    pub fn set_mnttab_cache(&self, enable: bool) {
+        let _lock = self.handle.lock();
+        unsafe { sys::libzfs_mnttab_cache(self.handle.raw(), enable as sys::boolean_t) };
+    }
+
+    /// Throw away the cached mount table, so it's read again the next time it's needed.
+    pub fn invalidate_mnttab_cache(&self) {
+        let _lock = self.handle.lock();
+        unsafe {
+            sys::libzfs_mnttab_fini(self.handle.raw());
+            sys::libzfs_mnttab_init(self.handle.raw());
+        }
+    }
+}
+
+impl Dataset {
+    /// Whether this filesystem is currently mounted.
+    pub fn is_mounted(&self) -> bool {
+        let _lock = self.libzfs.lock();
+        unsafe { sys::zfs_is_mounted(self.handle, ptr::null_mut()) != 0 }
+    }
+
+    /// Where this filesystem is actually mounted, if it is. Unlike
+    /// [`mountpoint`](Self::mountpoint), this is known for `legacy` mountpoints too.
+    pub fn mounted_at(&self) -> Option<PathBuf> {
+        let _lock = self.libzfs.lock();
+        let mut raw = ptr::null_mut();
+        if unsafe { sys::zfs_is_mounted(self.handle, &mut raw) } == 0 || raw.is_null() {
+            return None;
+        }
+        let path = PathBuf::from(OsStr::from_bytes(unsafe { CStr::from_ptr(raw) }.to_bytes()));
+        unsafe { libc::free(raw.cast()) };
+        Some(path)
+    }
+
+    /// Get this filesystem's `mountpoint` property. It may not actually be mounted there (or at
+    /// all); see [`is_mounted`](Self::is_mounted).
+    pub fn mountpoint(&self) -> Result<Mountpoint> {
+        let value = self.get_native_property(sys::zfs_prop_t::ZFS_PROP_MOUNTPOINT)?;
+        Ok(match value.as_str() {
+            "legacy" => Mountpoint::Legacy,
+            "none" => Mountpoint::None,
+            _ => Mountpoint::Path(PathBuf::from(value)),
+        })
+    }
+
+    /// Mount this filesystem at its `mountpoint`, like `zfs mount`.
+    pub fn mount(&self) -> Result<()> {
+        self.mount_with_options(&MountOptions::default())
+    }
+
+    /// Mount this filesystem at its `mountpoint` with the given options, like `zfs mount -o`.
+    ///
+    /// Fails if `mountpoint` is `legacy` or `none`, rather than quietly doing nothing like libzfs
+    /// does; use [`mount_at`](Self::mount_at) for those.
+    pub fn mount_with_options(&self, options: &MountOptions) -> Result<()> {
+        match self.mountpoint()? {
+            Mountpoint::Path(_) => (),
+            Mountpoint::Legacy => return Err(Error::invalid_input(format!(
+                "{} has a legacy mountpoint; use mount_at or mount(8)", self.name()))),
+            Mountpoint::None => return Err(Error::invalid_input(format!(
+                "{} has no mountpoint; use mount_at", self.name()))),
+        }
+        let opts = SafeString::try_from(options.to_string())?;
+        let flags = if options.overlay { MS_OVERLAY } else { 0 };
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_mount(self.handle, opts.as_ptr(), flags)
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Mount this filesystem at the given path, whatever its `mountpoint` property says, the
+    /// same way libzfs mounts filesystems itself. This is how to mount one with a `legacy` or
+    /// `none` mountpoint without going through mount(8).
+    pub fn mount_at<P: AsRef<Path>>(&self, path: P, options: &MountOptions) -> Result<()> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())
+            .map_err(Error::invalid_input)?;
+        let opts = SafeString::try_from(options.to_string())?;
+        let flags = if options.overlay { MS_OVERLAY } else { 0 };
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_mount_at(self.handle, opts.as_ptr(), flags, path.as_ptr())
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Unmount this filesystem, like `zfs unmount`.
+    pub fn unmount(&self) -> Result<()> {
+        self.unmount_with_options(&UnmountOptions::default())
+    }
+
+    /// Unmount this filesystem with the given options.
+    ///
+    /// If it's busy, on Linux the error wraps a [`MountBusy`] listing the processes using it.
+    pub fn unmount_with_options(&self, options: &UnmountOptions) -> Result<()> {
+        let mut flags = 0;
+        if options.force {
+            flags |= libc::MNT_FORCE;
+        }
+        if options.lazy {
+            #[cfg(target_os = "linux")]
+            {
+                flags |= libc::MNT_DETACH;
+            }
+            #[cfg(not(target_os = "linux"))]
+            return Err(Error::Sys(io::Error::new(io::ErrorKind::Unsupported,
+                "lazy unmounts are only supported on Linux")));
+        }
+
+        let mountpoint = self.mountpoint().ok();
+        let _lock = self.libzfs.lock();
+        let result = (|| {
+            ztry!(unsafe {
+                sys::zfs_unmount(self.handle, ptr::null(), flags)
+            }, self.libzfs.raw(), self.name());
+            Ok(())
+        })();
+        match (result, mountpoint) {
+            (Err(e), Some(Mountpoint::Path(path))) if e.is_busy() && cfg!(target_os = "linux") => {
+                let pids = processes_using(&path);
+                Err(Error::Sys(io::Error::new(io::ErrorKind::ResourceBusy,
+                    MountBusy { mountpoint: path, pids })))
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// Mount this filesystem and all filesystems under it which should be mounted and aren't
+    /// already, like `zfs mount -a` limited to this subtree. Filesystems are mounted after any
+    /// whose mountpoint contains or is the same as theirs, and otherwise in parallel across
+    /// `threads` threads, each with its own [`LibZfs`]. Carries on past filesystems which can't
+    /// be mounted, and returns them along with the reason; an empty list means all went well.
+    pub fn mount_all(&self, threads: usize) -> Result<Vec<MountError>> {
+        let mut todo = vec![];
+        for ds in self.self_and_descendants()? {
+            if ds.get_type() != DatasetType::Filesystem
+                || ds.is_mounted()
+                || ds.get_property("canmount")? != "on"
+            {
+                continue;
+            }
+            if let Mountpoint::Path(path) = ds.mountpoint()? {
+                todo.push((path, ds.get_name()));
+            }
+        }
+
+        // Sorting by mountpoint puts each one after all those containing it, so its depth is one
+        // more than that of the nearest one before it which contains it. Filesystems with the
+        // same mountpoint count as containing each other, so they go in successive waves, in
+        // name order, rather than racing to mount in the same place.
+        todo.sort();
+        let mut waves: Vec<Vec<SafeString>> = vec![];
+        let mut depths: Vec<(&PathBuf, usize)> = vec![];
+        for (path, name) in &todo {
+            let depth = depths.iter().rev()
+                .find(|(other, _)| path.starts_with(other))
+                .map(|(_, depth)| depth + 1)
+                .unwrap_or(0);
+            depths.push((path, depth));
+            if waves.len() <= depth {
+                waves.resize_with(depth + 1, Vec::new);
+            }
+            waves[depth].push(name.clone());
+        }
+
+        // The lock on each LibZfs would serialize the mounts, so each thread needs its own.
+        let handles = (0 .. threads.max(1))
+            .map(|_| LibZfs::new())
+            .collect::<Result<Vec<_>>>()?;
+        let failed = Mutex::new(vec![]);
+        for wave in waves {
+            let next = AtomicUsize::new(0);
+            thread::scope(|scope| {
+                for libzfs in handles.iter().take(wave.len()) {
+                    scope.spawn(|| {
+                        while let Some(name) = wave.get(next.fetch_add(1, Ordering::Relaxed)) {
+                            let mounted = libzfs
+                                .dataset_by_name(name, DatasetType::Filesystem.into())
+                                .and_then(|ds| ds.mount());
+                            if let Err(error) = mounted {
+                                let mut failed = failed.lock().unwrap();
+                                failed.push(MountError { name: name.clone(), error });
+                            }
+                        }
+                    });
+                }
+            });
+        }
+        Ok(failed.into_inner().unwrap())
+    }
+}
@@ -0,0 +1,420 @@
+use crate::{sys, AsSafeStr, DatasetType, Error, LibZfs, Result, SafeStr, SafeString};
+
+use std::borrow::Cow;
+use std::fmt;
+use std::str::FromStr;
+
+/// Why a pool or dataset name is invalid.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum NameError {
+    Empty,
+    TooLong,
+    /// The name contains a character which isn't allowed, like a space.
+    InvalidChar(char),
+    LeadingSlash,
+    TrailingSlash,
+    /// Two slashes in a row.
+    EmptyComponent,
+    /// More than one `@` or `#`.
+    MultipleDelimiters,
+    /// A pool name must start with a letter.
+    NoLetter,
+    /// The pool name is reserved, like `mirror` or `raidz`.
+    Reserved,
+    /// The pool name looks like a disk device name, like `c0t0d0`.
+    DiskLike,
+    /// A component is `.`.
+    SelfRef,
+    /// A component is `..`.
+    ParentRef,
+    /// A snapshot name is missing its `@`.
+    MissingAt,
+    /// A bookmark name is missing its `#`.
+    MissingPound,
+    /// The name is of a different type of dataset than the one asked for, e.g. it names a
+    /// snapshot when a filesystem was wanted.
+    WrongType,
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NameError::Empty => f.write_str("name is empty"),
+            NameError::TooLong => f.write_str("name is too long"),
+            NameError::InvalidChar(c) => write!(f, "invalid character {:?} in name", c),
+            NameError::LeadingSlash => f.write_str("leading slash in name"),
+            NameError::TrailingSlash => f.write_str("trailing slash in name"),
+            NameError::EmptyComponent => f.write_str("empty component in name"),
+            NameError::MultipleDelimiters => f.write_str("more than one '@' or '#' in name"),
+            NameError::NoLetter => f.write_str("pool name must begin with a letter"),
+            NameError::Reserved => f.write_str("name is reserved"),
+            NameError::DiskLike => f.write_str("pool name looks like a disk name"),
+            NameError::SelfRef => f.write_str("'.' is not allowed as a name component"),
+            NameError::ParentRef => f.write_str("'..' is not allowed as a name component"),
+            NameError::MissingAt => f.write_str("snapshot name is missing '@'"),
+            NameError::MissingPound => f.write_str("bookmark name is missing '#'"),
+            NameError::WrongType => f.write_str("name is for a different type of dataset"),
+        }
+    }
+}
+
+impl std::error::Error for NameError {}
+
+impl From<NameError> for Error {
+    fn from(e: NameError) -> Error {
+        Error::invalid_input(e)
+    }
+}
+
+impl LibZfs {
+    /// Check whether a name is valid for a pool (with `DatasetType::Pool`) or a dataset of the
+    /// given type, without touching any pools.
+    pub fn validate_dataset_name(&self, name: &str, typ: DatasetType)
+        -> std::result::Result<(), NameError>
+    {
+        validate_name(name, typ)
+    }
+}
+
+/// Check a name for [`LibZfs::validate_dataset_name`]. The checks are from libzfs's common code,
+/// and don't need a handle.
+fn validate_name(name: &str, typ: DatasetType) -> std::result::Result<(), NameError> {
+    if name.is_empty() {
+        return Err(NameError::Empty);
+    }
+    let cname = SafeString::try_from(name)
+        .map_err(|_| NameError::InvalidChar('\0'))?;
+
+    let mut why = 0;
+    let mut what = 0;
+    let ret = unsafe {
+        if typ == DatasetType::Pool {
+            sys::pool_namecheck(cname.as_ptr(), &mut why, &mut what)
+        } else {
+            sys::dataset_namecheck(cname.as_ptr(), &mut why, &mut what)
+        }
+    };
+    if ret != 0 {
+        return Err(namecheck_error(why, what as u8 as char));
+    }
+
+    let (has_at, has_pound) = (name.contains('@'), name.contains('#'));
+    match typ {
+        DatasetType::Filesystem | DatasetType::Volume if has_at || has_pound => {
+            Err(NameError::WrongType)
+        }
+        DatasetType::Snapshot if !has_at => Err(NameError::MissingAt),
+        DatasetType::Bookmark if !has_pound => Err(NameError::MissingPound),
+        _ => Ok(()),
+    }
+}
+
+fn namecheck_error(why: sys::namecheck_err_t::Type, what: char) -> NameError {
+    use sys::namecheck_err_t::*;
+    match why {
+        NAME_ERR_LEADING_SLASH => NameError::LeadingSlash,
+        NAME_ERR_EMPTY_COMPONENT => NameError::EmptyComponent,
+        NAME_ERR_TRAILING_SLASH => NameError::TrailingSlash,
+        NAME_ERR_INVALCHAR => NameError::InvalidChar(what),
+        NAME_ERR_MULTIPLE_DELIMITERS => NameError::MultipleDelimiters,
+        NAME_ERR_NOLETTER => NameError::NoLetter,
+        NAME_ERR_RESERVED => NameError::Reserved,
+        NAME_ERR_DISKLIKE => NameError::DiskLike,
+        NAME_ERR_TOOLONG => NameError::TooLong,
+        NAME_ERR_SELF_REF => NameError::SelfRef,
+        NAME_ERR_PARENT_REF => NameError::ParentRef,
+        NAME_ERR_NO_AT => NameError::MissingAt,
+        NAME_ERR_NO_POUND => NameError::MissingPound,
+        _ => NameError::InvalidChar(what),
+    }
+}
+
+/// The part of a dataset name after the filesystem or volume path, if any.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum NameSuffix {
+    /// `@name`
+    Snapshot(String),
+    /// `#name`
+    Bookmark(String),
+}
+
+/// A dataset name split into its parts: `pool/fs/child@snap` has pool `pool`, path
+/// `["fs", "child"]`, and snapshot `snap`.
+///
+/// Parsing only checks the structure of the name; use
+/// [`LibZfs::validate_dataset_name`] to check the characters and lengths too.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct DatasetName {
+    pool: String,
+    path: Vec<String>,
+    suffix: Option<NameSuffix>,
+}
+
+impl DatasetName {
+    pub fn parse(name: &str) -> std::result::Result<DatasetName, NameError> {
+        if name.is_empty() {
+            return Err(NameError::Empty);
+        }
+        if name.starts_with('/') {
+            return Err(NameError::LeadingSlash);
+        }
+
+        let (fs, suffix) = match name.find(['@', '#']) {
+            Some(idx) => {
+                let (fs, rest) = name.split_at(idx);
+                let (delim, part) = rest.split_at(1);
+                if part.contains(['@', '#']) {
+                    return Err(NameError::MultipleDelimiters);
+                }
+                if part.is_empty() {
+                    return Err(NameError::EmptyComponent);
+                }
+                if part.contains('/') {
+                    return Err(NameError::InvalidChar('/'));
+                }
+                let suffix = match delim {
+                    "@" => NameSuffix::Snapshot(part.to_owned()),
+                    _ => NameSuffix::Bookmark(part.to_owned()),
+                };
+                (fs, Some(suffix))
+            }
+            None => (name, None),
+        };
+
+        if fs.ends_with('/') {
+            return Err(NameError::TrailingSlash);
+        }
+        let mut components = fs.split('/');
+        let pool = components.next().unwrap_or_default();
+        if pool.is_empty() {
+            return Err(NameError::EmptyComponent);
+        }
+        let path = components.map(str::to_owned).collect::<Vec<_>>();
+        if path.iter().any(String::is_empty) {
+            return Err(NameError::EmptyComponent);
+        }
+
+        Ok(DatasetName { pool: pool.to_owned(), path, suffix })
+    }
+
+    pub fn pool(&self) -> &str {
+        &self.pool
+    }
+
+    /// The components of the filesystem or volume path below the pool.
+    pub fn path(&self) -> &[String] {
+        &self.path
+    }
+
+    pub fn suffix(&self) -> Option<&NameSuffix> {
+        self.suffix.as_ref()
+    }
+
+    pub fn snapshot(&self) -> Option<&str> {
+        match &self.suffix {
+            Some(NameSuffix::Snapshot(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn bookmark(&self) -> Option<&str> {
+        match &self.suffix {
+            Some(NameSuffix::Bookmark(s)) => Some(s),
+            _ => None,
+        }
+    }
+
+    /// Whether this names a pool's root filesystem.
+    pub fn is_pool_root(&self) -> bool {
+        self.path.is_empty() && self.suffix.is_none()
+    }
+
+    /// The filesystem or volume part of the name, without any snapshot or bookmark.
+    pub fn filesystem(&self) -> DatasetName {
+        DatasetName {
+            pool: self.pool.clone(),
+            path: self.path.clone(),
+            suffix: None,
+        }
+    }
+
+    /// The name of the parent: the filesystem for a snapshot or bookmark, or the filesystem
+    /// one level up otherwise. The pool root has no parent.
+    pub fn parent(&self) -> Option<DatasetName> {
+        if self.suffix.is_some() {
+            return Some(self.filesystem());
+        }
+        let mut path = self.path.clone();
+        path.pop()?;
+        Some(DatasetName { pool: self.pool.clone(), path, suffix: None })
+    }
+
+    /// The name of a child filesystem or volume of this one.
+    pub fn child(&self, name: &str) -> DatasetName {
+        let mut path = self.path.clone();
+        path.push(name.to_owned());
+        DatasetName { pool: self.pool.clone(), path, suffix: None }
+    }
+
+    /// The name of a snapshot of this filesystem or volume.
+    pub fn with_snapshot(&self, snapshot: &str) -> DatasetName {
+        DatasetName {
+            suffix: Some(NameSuffix::Snapshot(snapshot.to_owned())),
+            ..self.filesystem()
+        }
+    }
+
+    /// The name of a bookmark of this filesystem or volume.
+    pub fn with_bookmark(&self, bookmark: &str) -> DatasetName {
+        DatasetName {
+            suffix: Some(NameSuffix::Bookmark(bookmark.to_owned())),
+            ..self.filesystem()
+        }
+    }
+}
+
+impl fmt::Display for DatasetName {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.pool)?;
+        for component in &self.path {
+            write!(f, "/{}", component)?;
+        }
+        match &self.suffix {
+            Some(NameSuffix::Snapshot(s)) => write!(f, "@{}", s),
+            Some(NameSuffix::Bookmark(s)) => write!(f, "#{}", s),
+            None => Ok(()),
+        }
+    }
+}
+
+impl FromStr for DatasetName {
+    type Err = NameError;
+    fn from_str(s: &str) -> std::result::Result<DatasetName, NameError> {
+        DatasetName::parse(s)
+    }
+}
+
+impl AsSafeStr for DatasetName {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>> {
+        SafeString::try_from(self.to_string()).map(Cow::Owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_parts() {
+        let name = DatasetName::parse("tank").unwrap();
+        assert_eq!(name.pool(), "tank");
+        assert!(name.path().is_empty());
+        assert!(name.is_pool_root());
+        assert_eq!(name.parent(), None);
+
+        let name = DatasetName::parse("tank/fs/child").unwrap();
+        assert_eq!(name.pool(), "tank");
+        assert_eq!(name.path(), ["fs", "child"]);
+        assert_eq!(name.suffix(), None);
+        assert_eq!(name.parent().unwrap().to_string(), "tank/fs");
+
+        let name = DatasetName::parse("tank/fs@snap").unwrap();
+        assert_eq!(name.path(), ["fs"]);
+        assert_eq!(name.snapshot(), Some("snap"));
+        assert_eq!(name.bookmark(), None);
+        assert!(!name.is_pool_root());
+        assert_eq!(name.parent().unwrap().to_string(), "tank/fs");
+
+        let name = DatasetName::parse("tank#mark").unwrap();
+        assert!(name.path().is_empty());
+        assert_eq!(name.bookmark(), Some("mark"));
+        assert_eq!(name.snapshot(), None);
+        assert_eq!(name.filesystem().to_string(), "tank");
+    }
+
+    #[test]
+    fn parse_errors() {
+        for (name, err) in [
+            ("", NameError::Empty),
+            ("/tank", NameError::LeadingSlash),
+            ("tank/", NameError::TrailingSlash),
+            ("tank/fs/@snap", NameError::TrailingSlash),
+            ("tank//fs", NameError::EmptyComponent),
+            ("@snap", NameError::EmptyComponent),
+            ("tank@", NameError::EmptyComponent),
+            ("tank#", NameError::EmptyComponent),
+            ("tank@a@b", NameError::MultipleDelimiters),
+            ("tank@a#b", NameError::MultipleDelimiters),
+            ("tank@snap/fs", NameError::InvalidChar('/')),
+        ] {
+            assert_eq!(DatasetName::parse(name), Err(err), "{:?}", name);
+            assert_eq!(name.parse::<DatasetName>(), Err(err), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn display_roundtrip() {
+        for name in ["tank", "tank/fs", "tank/a/b/c", "tank@snap", "tank/fs@snap", "tank/fs#mark"] {
+            assert_eq!(DatasetName::parse(name).unwrap().to_string(), name);
+        }
+
+        let fs = DatasetName::parse("tank/fs").unwrap();
+        assert_eq!(fs.child("child").to_string(), "tank/fs/child");
+        assert_eq!(fs.with_snapshot("snap").to_string(), "tank/fs@snap");
+        assert_eq!(fs.with_bookmark("mark").with_snapshot("snap").to_string(), "tank/fs@snap");
+        assert_eq!(fs.child("child").parent(), Some(fs));
+    }
+
+    #[test]
+    fn validate_datasets() {
+        for (name, typ) in [
+            ("tank", DatasetType::Filesystem),
+            ("tank/fs-1_a.b:c d", DatasetType::Filesystem),
+            ("tank/vol", DatasetType::Volume),
+            ("tank/fs@snap", DatasetType::Snapshot),
+            ("tank/fs#mark", DatasetType::Bookmark),
+        ] {
+            assert_eq!(validate_name(name, typ), Ok(()), "{:?}", name);
+        }
+
+        let long = format!("tank/{}", "a".repeat(251));
+        assert_eq!(validate_name(&long[..255], DatasetType::Filesystem), Ok(()));
+        for (name, typ, err) in [
+            ("", DatasetType::Filesystem, NameError::Empty),
+            (long.as_str(), DatasetType::Filesystem, NameError::TooLong),
+            ("tank/f!s", DatasetType::Filesystem, NameError::InvalidChar('!')),
+            ("tank/fs\0", DatasetType::Filesystem, NameError::InvalidChar('\0')),
+            ("/tank", DatasetType::Filesystem, NameError::LeadingSlash),
+            ("tank/", DatasetType::Filesystem, NameError::TrailingSlash),
+            ("tank//fs", DatasetType::Filesystem, NameError::EmptyComponent),
+            ("tank/./fs", DatasetType::Filesystem, NameError::SelfRef),
+            ("tank/..", DatasetType::Filesystem, NameError::ParentRef),
+            ("tank@a@b", DatasetType::Snapshot, NameError::MultipleDelimiters),
+            ("tank/fs@snap", DatasetType::Filesystem, NameError::WrongType),
+            ("tank/fs#mark", DatasetType::Volume, NameError::WrongType),
+            ("tank/fs", DatasetType::Snapshot, NameError::MissingAt),
+            ("tank/fs@snap", DatasetType::Bookmark, NameError::MissingPound),
+        ] {
+            assert_eq!(validate_name(name, typ), Err(err), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn validate_pools() {
+        assert_eq!(validate_name("tank", DatasetType::Pool), Ok(()));
+        assert_eq!(validate_name("mirrored", DatasetType::Pool), Ok(()));
+        for (name, err) in [
+            ("tank/fs", NameError::InvalidChar('/')),
+            ("tank@snap", NameError::InvalidChar('@')),
+            ("1tank", NameError::NoLetter),
+            ("_tank", NameError::NoLetter),
+            ("mirror", NameError::Reserved),
+            ("raidz", NameError::Reserved),
+            ("c0t0d0", NameError::DiskLike),
+        ] {
+            assert_eq!(validate_name(name, DatasetType::Pool), Err(err), "{:?}", name);
+        }
+        let long = "a".repeat(300);
+        assert_eq!(validate_name(&long, DatasetType::Pool), Err(NameError::TooLong));
+    }
+}
@@ -0,0 +1,390 @@
+use crate::{sys, Error, Result, SafeString};
+
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::fmt;
+use std::io;
+use std::marker::PhantomData;
+use std::mem::{self, MaybeUninit};
+use std::ops::Deref;
+use std::os::raw::c_char;
+use std::ptr;
+use std::slice;
+
+/// An owned name-value list, as used by libzfs for configs, property lists, and the like. Freed
+/// when dropped.
+pub struct NvList {
+    raw: *mut sys::nvlist_t,
+}
+
+impl NvList {
+    /// Create a new, empty list with unique names.
+    pub fn new() -> Result<Self> {
+        let mut raw = ptr::null_mut();
+        match unsafe { sys::nvlist_alloc(&mut raw, sys::NV_UNIQUE_NAME, 0) } {
+            0 => Ok(NvList { raw }),
+            e => Err(Error::Sys(io::Error::from_raw_os_error(e))),
+        }
+    }
+
+    /// Take ownership of a raw nvlist.
+    ///
+    /// # Safety
+    /// The pointer must be a valid nvlist which nothing else will free.
+    pub unsafe fn from_raw(raw: *mut sys::nvlist_t) -> Self {
+        NvList { raw }
+    }
+
+    /// Give up ownership of the nvlist, returning the raw pointer, which the caller must free.
+    pub fn into_raw(self) -> *mut sys::nvlist_t {
+        let raw = self.raw;
+        mem::forget(self);
+        raw
+    }
+
+    pub fn add_boolean(&mut self, name: &str) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        unsafe { sys::fnvlist_add_boolean(self.raw, name.as_ptr()) };
+        Ok(())
+    }
+
+    pub fn add_boolean_value(&mut self, name: &str, value: bool) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        unsafe { sys::fnvlist_add_boolean_value(self.raw, name.as_ptr(), value as sys::boolean_t) };
+        Ok(())
+    }
+
+    pub fn add_uint64(&mut self, name: &str, value: u64) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        unsafe { sys::fnvlist_add_uint64(self.raw, name.as_ptr(), value) };
+        Ok(())
+    }
+
+    pub fn add_string(&mut self, name: &str, value: &str) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        let value = SafeString::try_from(value)?;
+        unsafe { sys::fnvlist_add_string(self.raw, name.as_ptr(), value.as_ptr()) };
+        Ok(())
+    }
+
+    /// Add a copy of another list as a nested list.
+    pub fn add_nvlist(&mut self, name: &str, value: &NvListRef) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        unsafe { sys::fnvlist_add_nvlist(self.raw, name.as_ptr(), value.as_ptr()) };
+        Ok(())
+    }
+
+    /// Add copies of other lists as a nested list array.
+    pub fn add_nvlist_array(&mut self, name: &str, values: &[&NvListRef]) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        let ptrs: Vec<*const sys::nvlist_t> = values.iter().map(|v| v.as_ptr() as *const _).collect();
+        unsafe {
+            sys::fnvlist_add_nvlist_array(self.raw, name.as_ptr(), ptrs.as_ptr(), ptrs.len() as sys::uint_t)
+        };
+        Ok(())
+    }
+
+    pub fn add_string_array(&mut self, name: &str, values: &[&str]) -> Result<()> {
+        let name = SafeString::try_from(name)?;
+        let values = values.iter()
+            .map(|&v| SafeString::try_from(v))
+            .collect::<Result<Vec<SafeString>>>()?;
+        let ptrs: Vec<*const c_char> = values.iter().map(|v| v.as_ptr()).collect();
+        unsafe {
+            sys::fnvlist_add_string_array(self.raw, name.as_ptr(), ptrs.as_ptr(), ptrs.len() as sys::uint_t)
+        };
+        Ok(())
+    }
+}
+
+impl Deref for NvList {
+    type Target = NvListRef;
+    fn deref(&self) -> &NvListRef {
+        unsafe { NvListRef::from_ptr(self.raw) }
+    }
+}
+
+impl Clone for NvList {
+    fn clone(&self) -> Self {
+        self.deref().to_owned()
+    }
+}
+
+impl Drop for NvList {
+    fn drop(&mut self) {
+        unsafe { sys::nvlist_free(self.raw) };
+    }
+}
+
+impl fmt::Debug for NvList {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.deref().fmt(f)
+    }
+}
+
+/// A borrowed name-value list, such as a pool config owned by a libzfs handle.
+pub struct NvListRef {
+    _opaque: [u8; 0],
+}
+
+impl NvListRef {
+    /// Borrow a raw nvlist.
+    ///
+    /// # Safety
+    /// The pointer must be a valid nvlist which outlives the returned reference.
+    pub unsafe fn from_ptr<'a>(raw: *mut sys::nvlist_t) -> &'a NvListRef {
+        &*(raw as *const NvListRef)
+    }
+
+    pub fn as_ptr(&self) -> *mut sys::nvlist_t {
+        self as *const NvListRef as *mut sys::nvlist_t
+    }
+
+    /// Make an owned copy of this list.
+    pub fn to_owned(&self) -> NvList {
+        let mut raw = ptr::null_mut();
+        let ret = unsafe { sys::nvlist_dup(self.as_ptr(), &mut raw, 0) };
+        assert_eq!(ret, 0, "nvlist_dup failed");
+        NvList { raw }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        unsafe { sys::nvlist_empty(self.as_ptr()) != 0 }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        // A name with a NUL in it can't be in the list.
+        let Ok(name) = SafeString::try_from(name) else {
+            return false;
+        };
+        unsafe { sys::nvlist_exists(self.as_ptr(), name.as_ptr()) != 0 }
+    }
+
+    pub fn lookup_uint64(&self, name: &str) -> Option<u64> {
+        let name = SafeString::try_from(name).ok()?;
+        let mut value = 0u64;
+        match unsafe { sys::nvlist_lookup_uint64(self.as_ptr(), name.as_ptr(), &mut value) } {
+            0 => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn lookup_int64(&self, name: &str) -> Option<i64> {
+        let name = SafeString::try_from(name).ok()?;
+        let mut value = 0i64;
+        match unsafe { sys::nvlist_lookup_int64(self.as_ptr(), name.as_ptr(), &mut value) } {
+            0 => Some(value),
+            _ => None,
+        }
+    }
+
+    pub fn lookup_string(&self, name: &str) -> Option<Cow<'_, str>> {
+        let name = SafeString::try_from(name).ok()?;
+        let mut value = ptr::null();
+        match unsafe { sys::nvlist_lookup_string(self.as_ptr(), name.as_ptr(), &mut value) } {
+            0 => Some(unsafe { CStr::from_ptr(value) }.to_string_lossy()),
+            _ => None,
+        }
+    }
+
+    pub fn lookup_nvlist(&self, name: &str) -> Option<&NvListRef> {
+        let name = SafeString::try_from(name).ok()?;
+        let mut value = ptr::null_mut();
+        match unsafe { sys::nvlist_lookup_nvlist(self.as_ptr(), name.as_ptr(), &mut value) } {
+            0 => Some(unsafe { NvListRef::from_ptr(value) }),
+            _ => None,
+        }
+    }
+
+    pub fn lookup_nvlist_array(&self, name: &str) -> Option<Vec<&NvListRef>> {
+        let name = SafeString::try_from(name).ok()?;
+        let mut values = ptr::null_mut();
+        let mut len = 0;
+        match unsafe {
+            sys::nvlist_lookup_nvlist_array(self.as_ptr(), name.as_ptr(), &mut values, &mut len)
+        } {
+            0 => Some(nvlist_array(values, len)),
+            _ => None,
+        }
+    }
+
+    pub fn lookup_uint64_array(&self, name: &str) -> Option<&[u64]> {
+        let name = SafeString::try_from(name).ok()?;
+        let mut values = ptr::null_mut();
+        let mut len = 0;
+        match unsafe {
+            sys::nvlist_lookup_uint64_array(self.as_ptr(), name.as_ptr(), &mut values, &mut len)
+        } {
+            0 => Some(raw_slice(values, len)),
+            _ => None,
+        }
+    }
+
+    /// Read a uint64 array holding a C stats struct (like `vdev_stat_t`). Older kernels may
+    /// provide fewer fields than we know about; any missing trailing fields are zero.
+    pub(crate) fn lookup_struct<T: Copy>(&self, name: &str) -> Option<T> {
+        let arr = self.lookup_uint64_array(name)?;
+        let mut out = MaybeUninit::<T>::zeroed();
+        let len = mem::size_of::<T>().min(mem::size_of_val(arr));
+        unsafe {
+            ptr::copy_nonoverlapping(arr.as_ptr() as *const u8, out.as_mut_ptr() as *mut u8, len);
+            Some(out.assume_init())
+        }
+    }
+
+    /// Iterate over the name-value pairs in the list.
+    pub fn iter(&self) -> NvPairs<'_> {
+        NvPairs {
+            list: self,
+            cur: ptr::null_mut(),
+        }
+    }
+}
+
+impl fmt::Debug for NvListRef {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_map()
+            .entries(self.iter().map(|pair| (pair.name(), pair.value())))
+            .finish()
+    }
+}
+
+fn nvlist_array<'a>(values: *mut *mut sys::nvlist_t, len: sys::uint_t) -> Vec<&'a NvListRef> {
+    raw_slice(values, len)
+        .iter()
+        .map(|&p| unsafe { NvListRef::from_ptr(p) })
+        .collect()
+}
+
+fn raw_slice<'a, T>(ptr: *mut T, len: sys::uint_t) -> &'a [T] {
+    if len == 0 || ptr.is_null() {
+        &[]
+    } else {
+        unsafe { slice::from_raw_parts(ptr, len as usize) }
+    }
+}
+
+/// Iterator over the pairs in an nvlist.
+pub struct NvPairs<'a> {
+    list: &'a NvListRef,
+    cur: *mut sys::nvpair_t,
+}
+
+impl<'a> Iterator for NvPairs<'a> {
+    type Item = NvPair<'a>;
+    fn next(&mut self) -> Option<NvPair<'a>> {
+        self.cur = unsafe { sys::nvlist_next_nvpair(self.list.as_ptr(), self.cur) };
+        if self.cur.is_null() {
+            None
+        } else {
+            Some(NvPair { raw: self.cur, _list: PhantomData })
+        }
+    }
+}
+
+/// A single name-value pair in an nvlist.
+#[derive(Copy, Clone)]
+pub struct NvPair<'a> {
+    raw: *mut sys::nvpair_t,
+    _list: PhantomData<&'a NvListRef>,
+}
+
+impl<'a> NvPair<'a> {
+    pub fn name(&self) -> Cow<'a, str> {
+        unsafe { CStr::from_ptr(sys::nvpair_name(self.raw)) }.to_string_lossy()
+    }
+
+    pub fn value(&self) -> NvValue<'a> {
+        use sys::data_type_t::*;
+        let raw = self.raw;
+        unsafe {
+            match sys::nvpair_type(raw) {
+                DATA_TYPE_BOOLEAN => NvValue::Boolean,
+                DATA_TYPE_BOOLEAN_VALUE => {
+                    let mut v = 0;
+                    sys::nvpair_value_boolean_value(raw, &mut v);
+                    NvValue::BooleanValue(v != 0)
+                }
+                DATA_TYPE_INT32 => {
+                    let mut v = 0;
+                    sys::nvpair_value_int32(raw, &mut v);
+                    NvValue::Int32(v)
+                }
+                DATA_TYPE_UINT32 => {
+                    let mut v = 0;
+                    sys::nvpair_value_uint32(raw, &mut v);
+                    NvValue::UInt32(v)
+                }
+                DATA_TYPE_INT64 => {
+                    let mut v = 0;
+                    sys::nvpair_value_int64(raw, &mut v);
+                    NvValue::Int64(v)
+                }
+                DATA_TYPE_UINT64 => {
+                    let mut v = 0;
+                    sys::nvpair_value_uint64(raw, &mut v);
+                    NvValue::UInt64(v)
+                }
+                DATA_TYPE_STRING => {
+                    let mut v = ptr::null();
+                    sys::nvpair_value_string(raw, &mut v);
+                    NvValue::String(CStr::from_ptr(v).to_string_lossy())
+                }
+                DATA_TYPE_NVLIST => {
+                    let mut v = ptr::null_mut();
+                    sys::nvpair_value_nvlist(raw, &mut v);
+                    NvValue::NvList(NvListRef::from_ptr(v))
+                }
+                DATA_TYPE_UINT64_ARRAY => {
+                    let mut v = ptr::null_mut();
+                    let mut len = 0;
+                    sys::nvpair_value_uint64_array(raw, &mut v, &mut len);
+                    NvValue::UInt64Array(raw_slice(v, len))
+                }
+                DATA_TYPE_STRING_ARRAY => {
+                    let mut v = ptr::null_mut();
+                    let mut len = 0;
+                    sys::nvpair_value_string_array(raw, &mut v, &mut len);
+                    NvValue::StringArray(raw_slice(v, len).iter()
+                        .map(|&s| CStr::from_ptr(s).to_string_lossy())
+                        .collect())
+                }
+                DATA_TYPE_NVLIST_ARRAY => {
+                    let mut v = ptr::null_mut();
+                    let mut len = 0;
+                    sys::nvpair_value_nvlist_array(raw, &mut v, &mut len);
+                    NvValue::NvListArray(nvlist_array(v, len))
+                }
+                other => NvValue::Other(other),
+            }
+        }
+    }
+}
+
+impl<'a> fmt::Debug for NvPair<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("NvPair")
+            .field(&self.name())
+            .field(&self.value())
+            .finish()
+    }
+}
+
+/// The value of an nvlist pair.
+#[derive(Debug, Clone)]
+pub enum NvValue<'a> {
+    /// A name with no value; its presence alone is the information.
+    Boolean,
+    BooleanValue(bool),
+    Int32(i32),
+    UInt32(u32),
+    Int64(i64),
+    UInt64(u64),
+    String(Cow<'a, str>),
+    NvList(&'a NvListRef),
+    UInt64Array(&'a [u64]),
+    StringArray(Vec<Cow<'a, str>>),
+    NvListArray(Vec<&'a NvListRef>),
+    /// Some other type which isn't decoded here.
+    Other(sys::data_type_t::Type),
+}
@@ -0,0 +1,41 @@
+use crate::{Dataset, DatasetType, SafeString};
+
+/// What a destructive operation would do, from one of the `plan_` methods, like
+/// [`Dataset::plan_destroy`] or [`Dataset::plan_rollback`]. Working this out doesn't change
+/// anything, so it can be shown to a user for confirmation first, like `zfs destroy -nv`.
+///
+/// It reflects the state of things when it was made; by the time the operation actually runs,
+/// things may have changed.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Plan {
+    /// Datasets which would be destroyed, in the order they would be.
+    pub destroyed: Vec<SafeString>,
+    /// Of those, the clones elsewhere in the pool which would be destroyed because the snapshot
+    /// they were cloned from would be, along with everything under them.
+    pub clones_destroyed: Vec<SafeString>,
+    /// Filesystems which would be unmounted: to be destroyed, or to be mounted again
+    /// afterwards.
+    pub unmounted: Vec<SafeString>,
+    /// Filesystems and volumes which would be rolled back, losing every change since their most
+    /// recent remaining snapshot.
+    pub rolled_back: Vec<SafeString>,
+    /// Datasets which would be renamed, with their new names.
+    pub renamed: Vec<(SafeString, SafeString)>,
+    /// Datasets which would be created.
+    pub created: Vec<SafeString>,
+}
+
+impl Plan {
+    /// Add datasets which would be destroyed, noting the mounted filesystems among them.
+    pub(crate) fn destroy<'a, I>(&mut self, datasets: I)
+        where I: IntoIterator<Item = &'a Dataset>,
+    {
+        for ds in datasets {
+            if ds.get_type() == DatasetType::Filesystem && ds.is_mounted() {
+                self.unmounted.push(ds.get_name());
+            }
+            self.destroyed.push(ds.get_name());
+        }
+    }
+}
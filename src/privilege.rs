@@ -0,0 +1,82 @@
+use crate::delegation::expand_set;
+use crate::{sys, Dataset, Delegate, DelegationScope, Error, LibZfs, Result, ZfsError};
+
+use std::os::raw::c_int;
+use std::ptr;
+
+impl LibZfs {
+    /// Check up front whether this process is allowed to do `op` to `dataset`, so that a missing
+    /// permission is reported clearly before starting, rather than as a bare `EZFS_PERM` from
+    /// partway through. `op` is a permission name as used by `zfs allow`, like `snapshot`,
+    /// `destroy`, `mount`, `send`, or `receive`, or the name of a property to be set.
+    ///
+    /// Root may do anything. Anyone else needs the permission delegated with `zfs allow` to
+    /// their user, one of their groups, or everyone, either locally on the dataset (or a
+    /// snapshot's filesystem) or for descendents on an ancestor, directly or through a
+    /// permission set; and the pool's `delegation` property has to be on. Many operations need
+    /// more than one permission (creating a filesystem needs `create` and `mount`), so check
+    /// each.
+    ///
+    /// A failure is a permission error (see [`Error::is_permission`]) saying what's missing.
+    /// Passing doesn't guarantee the operation will succeed.
+    pub fn check_privileges(&self, op: &str, dataset: &Dataset) -> Result<()> {
+        let uid = unsafe { libc::geteuid() };
+        if uid == 0 {
+            return Ok(());
+        }
+        let denied = |code, msg: String| Err(Error::Zfs(ZfsError {
+            code,
+            msg,
+            action: format!("cannot {} {}", op, dataset.name()),
+            errno: Some(libc::EPERM),
+        }));
+        if dataset.get_pool().get_property("delegation")? != "on" {
+            return denied(sys::zfs_error::EZFS_NODELEGATION, format!(
+                "delegated administration is disabled on pool {}; only root can do this",
+                dataset.get_pool_name()));
+        }
+
+        // Snapshots and bookmarks use their filesystem's permissions. Those of it and each of
+        // its ancestors, nearest first.
+        let levels = dataset.delegation_levels()?;
+
+        let groups = current_groups();
+        for (depth, (_, delegations)) in levels.iter().enumerate() {
+            // Local permissions apply on the dataset itself, descendent ones below it.
+            let scope = if depth == 0 {
+                DelegationScope::Local
+            } else {
+                DelegationScope::Descendent
+            };
+            for delegation in delegations {
+                let applies = delegation.scope == Some(scope) && match delegation.who {
+                    Delegate::User(id) => id == uid,
+                    Delegate::Group(id) => groups.contains(&id),
+                    Delegate::Everyone => true,
+                    _ => false,
+                };
+                let allows = |perm: &String| if perm.starts_with('@') {
+                    expand_set(&levels[depth ..], perm).contains(op)
+                } else {
+                    perm == op
+                };
+                if applies && delegation.permissions.iter().any(allows) {
+                    return Ok(());
+                }
+            }
+        }
+        denied(sys::zfs_error::EZFS_PERM, format!(
+            "user {} has not been allowed '{}' on {} or its ancestors; it needs root, or \
+            `zfs allow {}`", uid, op, levels[0].0, op))
+    }
+}
+
+/// The effective group ID and supplementary groups of this process.
+fn current_groups() -> Vec<libc::gid_t> {
+    let count = unsafe { libc::getgroups(0, ptr::null_mut()) }.max(0);
+    let mut groups = vec![0; count as usize];
+    let count = unsafe { libc::getgroups(count as c_int, groups.as_mut_ptr()) };
+    groups.truncate(count.max(0) as usize);
+    groups.push(unsafe { libc::getegid() });
+    groups
+}
@@ -0,0 +1,167 @@
+use crate::{sys, ztry, Dataset, Error, Result, SafeString, SpaceOwner, ZfsError};
+
+#[cfg(target_os = "linux")]
+use std::fs::{self, File};
+#[cfg(target_os = "linux")]
+use std::io;
+#[cfg(target_os = "linux")]
+use std::os::fd::AsRawFd;
+#[cfg(target_os = "linux")]
+use std::os::unix::fs::MetadataExt;
+#[cfg(target_os = "linux")]
+use std::path::Path;
+
+// Project quotas account for and limit space by project ID, which is set on files and
+// directories with `zfs project` (or `chattr -p`), rather than by who owns them. A directory
+// with the inherit flag set passes its project ID on to everything created in it, so a tree can
+// be made into a project with its own quota, whoever writes there.
+
+impl Dataset {
+    /// How much space the files with the given project ID take up in this filesystem, in bytes,
+    /// like the `projectused@<id>` property.
+    pub fn project_used(&self, project: u32) -> Result<u64> {
+        self.userquota_int(&format!("projectused@{}", project))
+    }
+
+    /// How many files and directories (objects) have the given project ID in this filesystem,
+    /// like the `projectobjused@<id>` property.
+    pub fn project_objects_used(&self, project: u32) -> Result<u64> {
+        self.userquota_int(&format!("projectobjused@{}", project))
+    }
+
+    /// The limit on the space the files with the given project ID may take up, in bytes, like
+    /// the `projectquota@<id>` property, or `None` if there isn't one.
+    pub fn project_quota(&self, project: u32) -> Result<Option<u64>> {
+        let quota = self.userquota_int(&format!("projectquota@{}", project))?;
+        Ok((quota != 0).then_some(quota))
+    }
+
+    /// The limit on how many files and directories may have the given project ID, like the
+    /// `projectobjquota@<id>` property, or `None` if there isn't one.
+    pub fn project_objects_quota(&self, project: u32) -> Result<Option<u64>> {
+        let quota = self.userquota_int(&format!("projectobjquota@{}", project))?;
+        Ok((quota != 0).then_some(quota))
+    }
+
+    /// Set or remove the limit on the space the files with the given project ID may take up in
+    /// this filesystem, in bytes, like `zfs set projectquota@<id>=`.
+    pub fn set_project_quota(&self, project: u32, quota: Option<u64>) -> Result<()> {
+        self.set_space_quota(SpaceOwner::Project, project, quota)
+    }
+
+    /// Set or remove the limit on how many files and directories may have the given project ID
+    /// in this filesystem, like `zfs set projectobjquota@<id>=`.
+    pub fn set_project_objects_quota(&self, project: u32, quota: Option<u64>) -> Result<()> {
+        self.set_objects_quota(SpaceOwner::Project, project, quota)
+    }
+
+    /// Get the value of a user, group, or project space property, like `userused@alice` or
+    /// `projectquota@5`. Quotas which aren't set are 0.
+    pub(crate) fn userquota_int(&self, prop: &str) -> Result<u64> {
+        let prop = SafeString::try_from(prop)?;
+        let mut value = 0u64;
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_prop_get_userquota_int(self.handle, prop.as_ptr(), &mut value)
+        }, self.libzfs.raw(), self.name());
+        Ok(value)
+    }
+}
+
+/// The project a file or directory belongs to, like `zfs project` shows.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct FileProject {
+    pub id: u32,
+    /// For a directory, whether files and directories created in it get its project ID, like
+    /// `zfs project -s`.
+    pub inherit: bool,
+}
+
+/// Linux's `struct fsxattr`, for the ioctls which get and set project IDs.
+#[cfg(target_os = "linux")]
+#[repr(C)]
+#[derive(Default)]
+struct FsXattr {
+    xflags: u32,
+    extsize: u32,
+    nextents: u32,
+    projid: u32,
+    cowextsize: u32,
+    pad: [u8; 8],
+}
+
+#[cfg(target_os = "linux")]
+const FS_IOC_FSGETXATTR: u32 = 0x801c581f;
+#[cfg(target_os = "linux")]
+const FS_IOC_FSSETXATTR: u32 = 0x401c5820;
+#[cfg(target_os = "linux")]
+const FS_XFLAG_PROJINHERIT: u32 = 0x200;
+
+#[cfg(target_os = "linux")]
+fn get_xattr(file: &File) -> io::Result<FsXattr> {
+    let mut attr = FsXattr::default();
+    if unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FSGETXATTR as _, &mut attr) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(attr)
+}
+
+/// Get the project of a file or directory in a ZFS filesystem, like `zfs project`. Only
+/// supported on Linux.
+#[cfg(target_os = "linux")]
+pub fn get_project<P: AsRef<Path>>(path: P) -> Result<FileProject> {
+    let attr = get_xattr(&File::open(path).map_err(Error::Sys)?).map_err(Error::Sys)?;
+    Ok(FileProject { id: attr.projid, inherit: attr.xflags & FS_XFLAG_PROJINHERIT != 0 })
+}
+
+/// Put a file or directory in a ZFS filesystem into a project, like `zfs project -p <id>`, and
+/// with `inherit`, set a directory to pass its project on to files created in it (`-s`). With
+/// `recursive`, do the same to everything under a directory, staying within its filesystem and
+/// skipping anything other than files and directories, like `-r`. Only supported on Linux.
+///
+/// Setting the ID to 0 without `inherit` takes things back out of any project, like
+/// `zfs project -C -k`.
+#[cfg(target_os = "linux")]
+pub fn set_project<P: AsRef<Path>>(path: P, project: FileProject, recursive: bool) -> Result<()> {
+    let path = path.as_ref();
+    let metadata = fs::symlink_metadata(path).map_err(Error::Sys)?;
+    set_project_on(path, project).map_err(Error::Sys)?;
+    if recursive && metadata.is_dir() {
+        set_project_under(path, metadata.dev(), project)?;
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_project_under(dir: &Path, dev: u64, project: FileProject) -> Result<()> {
+    for entry in fs::read_dir(dir).map_err(Error::Sys)? {
+        let path = entry.map_err(Error::Sys)?.path();
+        let metadata = fs::symlink_metadata(&path).map_err(Error::Sys)?;
+        if metadata.dev() != dev || !(metadata.is_dir() || metadata.is_file()) {
+            continue;
+        }
+        set_project_on(&path, project)
+            .map_err(|e| Error::Sys(e).context("set the project of", path.display()))?;
+        if metadata.is_dir() {
+            set_project_under(&path, dev, project)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(target_os = "linux")]
+fn set_project_on(path: &Path, project: FileProject) -> io::Result<()> {
+    let file = File::open(path)?;
+    let mut attr = get_xattr(&file)?;
+    attr.projid = project.id;
+    // Only directories can pass their project on.
+    if project.inherit && file.metadata()?.is_dir() {
+        attr.xflags |= FS_XFLAG_PROJINHERIT;
+    } else {
+        attr.xflags &= !FS_XFLAG_PROJINHERIT;
+    }
+    if unsafe { libc::ioctl(file.as_raw_fd(), FS_IOC_FSSETXATTR as _, &attr) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
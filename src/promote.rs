@@ -0,0 +1,82 @@
+use crate::{sys, ztry, Dataset, DatasetType, Error, Result, SafeString, ZfsError};
+
+use std::collections::HashSet;
+
+/// What [`Dataset::promote`] would do, from [`Dataset::plan_promote`], to show for confirmation
+/// first. Promoting a clone swaps it with the filesystem or volume it was cloned from: the
+/// origin snapshot, and every snapshot before it, move to the clone, and the old origin becomes
+/// a clone of it instead.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PromotePlan {
+    /// The clone to be promoted.
+    pub promoted: SafeString,
+    /// Its origin afterwards: that of the filesystem or volume it was cloned from, if that was
+    /// a clone too.
+    pub new_origin: Option<SafeString>,
+    /// Snapshots which would move to the promoted clone, oldest first, with their new names.
+    pub moved: Vec<(SafeString, SafeString)>,
+    /// Filesystems and volumes whose origin would be renamed by the move, with their new
+    /// origin. The one the clone was cloned from comes first.
+    pub new_origins: Vec<(SafeString, SafeString)>,
+    /// Snapshots the clone already has with the same names as ones which would move. Promoting
+    /// fails until they're renamed or destroyed.
+    pub conflicts: Vec<SafeString>,
+}
+
+impl Dataset {
+    /// Promote this clone, like `zfs promote`, so that it no longer depends on the filesystem
+    /// or volume it was cloned from, which then depends on it instead. See
+    /// [`plan_promote`](Self::plan_promote) for what moves where. Open any affected datasets
+    /// again to see their new names and origins.
+    pub fn promote(&self) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe { sys::zfs_promote(self.handle) }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Work out what [`promote`](Self::promote) would do, without changing anything: which
+    /// snapshots would move to this clone, and which datasets' origins would change.
+    pub fn plan_promote(&self) -> Result<PromotePlan> {
+        let Some(origin) = self.origin()? else {
+            return Err(Error::invalid_input(format!("{} is not a clone", self.name())));
+        };
+        let origin_name = origin.get_name();
+        let (origin_fs, origin_short) = origin_name.as_str().split_once('@')
+            .ok_or_else(|| Error::invalid_data(format!("bad origin {}", origin.name())))?;
+        let libzfs = self.libzfs();
+        let origin_fs =
+            libzfs.dataset_by_name(origin_fs, DatasetType::Filesystem | DatasetType::Volume)?;
+        let renamed = |short: &str| SafeString::try_from(format!("{}@{}", self.name(), short));
+
+        let own = self.snapshot_infos()?.into_iter()
+            .filter_map(|snap| snap.name.as_str().split_once('@').map(|(_, s)| s.to_owned()))
+            .collect::<HashSet<_>>();
+        let mut plan = PromotePlan {
+            promoted: self.get_name(),
+            new_origin: origin_fs.origin()?.map(|o| o.get_name()),
+            moved: vec![],
+            new_origins: vec![(origin_fs.get_name(), renamed(origin_short)?)],
+            conflicts: vec![],
+        };
+        let origin_txg = origin.createtxg();
+        for snap in origin_fs.get_snapshots_ordered()? {
+            if snap.createtxg() > origin_txg {
+                continue;
+            }
+            let snap_name = snap.name();
+            let (_, short) = snap_name.as_str().split_once('@').unwrap_or_default();
+            let new_name = renamed(short)?;
+            if own.contains(short) {
+                plan.conflicts.push(new_name.clone());
+            }
+            for clone in snap.clones()? {
+                if clone.name() != self.name() {
+                    plan.new_origins.push((clone.get_name(), new_name.clone()));
+                }
+            }
+            plan.moved.push((snap.get_name(), new_name));
+        }
+        Ok(plan)
+    }
+}
@@ -0,0 +1,546 @@
+use crate::{sys, ztry, Dataset, Error, NvListRef, NvValue, Result, SafeString, ZfsError, ZPool};
+use crate::PROP_BUF_LEN;
+use crate::compat;
+use crate::create::props_nvlist;
+use crate::feature::{load_compat, supported_features};
+use crate::string::string_from_buf;
+
+use std::ffi::CStr;
+use std::fmt;
+use std::os::raw::{c_char, c_int, c_void};
+use std::ptr;
+
+/// The `source` a received user property has in the user props nvlist.
+const ZPROP_SOURCE_VAL_RECVD: &str = "$recvd";
+
+/// The range of sector sizes a pool's `ashift` can be set to, as powers of two.
+const ASHIFT_MIN: u64 = 9;
+const ASHIFT_MAX: u64 = 16;
+
+/// The longest a pool's `comment` can be.
+const ZPROP_MAX_COMMENT: usize = 32;
+
+/// Where a property's value comes from, like the `source` column of `zfs get`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertySource {
+    /// The property isn't settable, like `used`.
+    None,
+    Default,
+    /// Set only for the current mount, with a mount option.
+    Temporary,
+    Local,
+    /// Inherited from the named ancestor.
+    Inherited(SafeString),
+    /// Set by a `zfs receive`.
+    Received,
+}
+
+/// A property's value and where it comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+    pub name: String,
+    /// The value in its parsable form, as printed by `zfs get -p`.
+    pub value: String,
+    pub source: PropertySource,
+}
+
+impl Dataset {
+    /// Get the value of a native property (like `"compression"`) or user property (like
+    /// `"com.example:role"`), in its parsable form, as printed by `zfs get -p`.
+    pub fn get_property(&self, name: &str) -> Result<String> {
+        self.get_property_with_source(name).map(|prop| prop.value)
+    }
+
+    /// Get the value of a property along with where it came from, like `zfs get -o value,source`.
+    pub fn get_property_with_source(&self, name: &str) -> Result<Property> {
+        let _lock = self.libzfs.lock();
+        let cname = SafeString::try_from(name)?;
+        let prop = unsafe { sys::zfs_name_to_prop(cname.as_ptr()) };
+        if prop != sys::zfs_prop_t::ZPROP_INVAL {
+            return self.get_native_property_with_source(prop);
+        }
+
+        if unsafe { sys::zfs_prop_user(cname.as_ptr()) } == 0 {
+            return Err(Error::invalid_input(format!("invalid property name {:?}", name)));
+        }
+        let user_props = unsafe { NvListRef::from_ptr(sys::zfs_get_user_props(self.handle)) };
+        user_props.lookup_nvlist(name)
+            .and_then(|prop| self.user_property(name, prop))
+            .ok_or_else(|| Error::not_found(format!("user property {:?} is not set", name)))
+    }
+
+    /// Get every property of this dataset, native ones first, then user properties, like
+    /// `zfs get all`.
+    pub fn properties(&self) -> Result<Vec<Property>> {
+        extern "C" fn collect(prop: c_int, context: *mut c_void) -> c_int {
+            let props = unsafe { &mut *(context as *mut Vec<sys::zfs_prop_t::Type>) };
+            props.push(prop as sys::zfs_prop_t::Type);
+            sys::ZPROP_CONT
+        }
+
+        let _lock = self.libzfs.lock();
+        let mut native = vec![];
+        unsafe {
+            sys::zfs_prop_iter(
+                Some(collect),
+                &mut native as *mut _ as *mut c_void,
+                0, // show_all: skip hidden properties
+                1, // ordered
+                sys::zfs_get_type(self.handle),
+            )
+        };
+
+        // Like `zfs get all`, leave out properties which don't apply, like `mounted` on an
+        // unmounted filesystem.
+        let mut props = native.into_iter()
+            .filter_map(|prop| self.get_native_property_with_source(prop).ok())
+            .collect::<Vec<_>>();
+
+        let user_props = unsafe { NvListRef::from_ptr(sys::zfs_get_user_props(self.handle)) };
+        for pair in user_props.iter() {
+            if let NvValue::NvList(prop) = pair.value() {
+                props.extend(self.user_property(&pair.name(), prop));
+            }
+        }
+        Ok(props)
+    }
+
+    /// Get the value of a user property, like `com.example:role`, or `None` if it isn't set
+    /// here or on any ancestor.
+    pub fn get_user_property(&self, name: &str) -> Result<Option<String>> {
+        validate_user_property_name(name)?;
+        match self.get_property(name) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Set a user property, like `com.example:role`, on this dataset or snapshot.
+    pub fn set_user_property(&self, name: &str, value: &str) -> Result<()> {
+        validate_user_property_name(name)?;
+        self.set_property(name, value)
+    }
+
+    /// Remove a user property from this dataset or snapshot, like `zfs inherit`. Afterwards it
+    /// has the value set on the nearest ancestor, if any.
+    pub fn clear_user_property(&self, name: &str) -> Result<()> {
+        validate_user_property_name(name)?;
+        self.inherit_property(name, false)
+    }
+
+    /// Clear a native or user property set on this dataset, like `zfs inherit`, so it takes the
+    /// value set on the nearest ancestor, or the default if none has one. With `recursive`, it's
+    /// cleared on every filesystem and volume under this one too, like `zfs inherit -r`, parents
+    /// before children.
+    ///
+    /// Properties which aren't inherited, like `quota`, can't be cleared this way, and fail with
+    /// `EZFS_PROPNONINHERIT`; set them to their default value instead.
+    pub fn inherit_property(&self, name: &str, recursive: bool) -> Result<()> {
+        self.inherit(name, false, recursive)
+    }
+
+    /// Clear a native or user property set on this dataset, like `zfs inherit -S`, going back
+    /// to the value it was last received with, if it was received with one, or else inheriting
+    /// it like [`inherit_property`](Self::inherit_property). This undoes local changes to a
+    /// replicated dataset's properties. With `recursive`, this is done on every filesystem and
+    /// volume under this one too.
+    pub fn revert_to_received(&self, name: &str, recursive: bool) -> Result<()> {
+        self.inherit(name, true, recursive)
+    }
+
+    fn inherit(&self, name: &str, received: bool, recursive: bool) -> Result<()> {
+        let cname = SafeString::try_from(name)?;
+        let datasets = if recursive { self.self_and_descendants()? } else { vec![self.clone()] };
+        for ds in datasets {
+            let _lock = ds.libzfs.lock();
+            ztry!(unsafe {
+                sys::zfs_prop_inherit(
+                    ds.handle,
+                    cname.as_ptr(),
+                    received as sys::boolean_t,
+                )
+            }, ds.libzfs.raw(), ds.name());
+        }
+        Ok(())
+    }
+
+    /// Read a user property out of its entry in the user props nvlist.
+    pub(crate) fn user_property(&self, name: &str, prop: &NvListRef) -> Option<Property> {
+        let value = prop.lookup_string("value")?.into_owned();
+        let source = match prop.lookup_string("source") {
+            Some(src) if src == ZPROP_SOURCE_VAL_RECVD => PropertySource::Received,
+            Some(src) if src == self.name().as_str() => PropertySource::Local,
+            Some(src) => PropertySource::Inherited(SafeString::try_from(src.as_ref()).ok()?),
+            None => PropertySource::Local,
+        };
+        Some(Property { name: name.to_owned(), value, source })
+    }
+
+    /// Set a native or user property, like `zfs set`.
+    pub fn set_property(&self, name: &str, value: &str) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        let cname = SafeString::try_from(name)?;
+        let cvalue = SafeString::try_from(value)?;
+        ztry!(unsafe {
+            sys::zfs_prop_set(self.handle, cname.as_ptr(), cvalue.as_ptr())
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Set several native or user properties in one call, like `zfs set a=1 b=2`. They're all
+    /// checked before any is set, and then most are set together in one transaction, so either
+    /// all or none of them change. A few, like quotas and reservations, are set one at a time
+    /// first, so if setting the rest fails, those may already have changed.
+    pub fn set_properties(&self, props: &[(&str, &str)]) -> Result<()> {
+        let nvl = props_nvlist(props)?;
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_prop_set_list(self.handle, nvl.as_ptr())
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    pub(crate) fn get_native_property(&self, prop: sys::zfs_prop_t::Type) -> Result<String> {
+        self.get_native_property_with_source(prop).map(|prop| prop.value)
+    }
+
+    pub(crate) fn get_native_property_with_source(&self, prop: sys::zfs_prop_t::Type)
+        -> Result<Property>
+    {
+        use sys::zprop_source_t::*;
+        let _lock = self.libzfs.lock();
+        let mut buf = vec![0 as c_char; PROP_BUF_LEN];
+        let mut src = 0;
+        let mut statbuf = vec![0 as c_char; PROP_BUF_LEN];
+        ztry!(unsafe {
+            sys::zfs_prop_get(
+                self.handle,
+                prop,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut src,
+                statbuf.as_mut_ptr(),
+                statbuf.len(),
+                1, // literal
+            )
+        }, self.libzfs.raw(), self.name());
+        let source = match src {
+            ZPROP_SRC_DEFAULT => PropertySource::Default,
+            ZPROP_SRC_TEMPORARY => PropertySource::Temporary,
+            ZPROP_SRC_LOCAL => PropertySource::Local,
+            ZPROP_SRC_RECEIVED => PropertySource::Received,
+            ZPROP_SRC_INHERITED => {
+                PropertySource::Inherited(SafeString::try_from(string_from_buf(&statbuf))?)
+            }
+            _ => PropertySource::None,
+        };
+        let name = unsafe { CStr::from_ptr(sys::zfs_prop_to_name(prop)) };
+        Ok(Property {
+            name: name.to_string_lossy().into_owned(),
+            value: string_from_buf(&buf),
+            source,
+        })
+    }
+}
+
+/// Check that a name is a valid user property name: `module:name`, made of lowercase letters,
+/// digits, and `-_.:`.
+pub fn validate_user_property_name(name: &str) -> Result<()> {
+    if !name.contains(':') {
+        return Err(Error::invalid_input(
+            format!("user property name {:?} must be of the form module:name", name)));
+    }
+    if let Some(c) = name.chars().find(|&c| {
+        !(c.is_ascii_lowercase() || c.is_ascii_digit() || "-_.:".contains(c))
+    }) {
+        return Err(Error::invalid_input(
+            format!("invalid character {:?} in user property name {:?}", c, name)));
+    }
+    let cname = SafeString::try_from(name)?;
+    if unsafe { sys::zfs_prop_user(cname.as_ptr()) } == 0 {
+        return Err(Error::invalid_input(format!("invalid user property name {:?}", name)));
+    }
+    Ok(())
+}
+
+/// Why a pool property can't be set to a value, as found by [`ZPool::validate_property`]. This
+/// is the error inside the [`io::Error`] that [`ZPool::set_property`] returns for these cases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PoolPropertyError {
+    /// There's no pool property or feature by this name.
+    Unknown { name: String },
+    /// The property can't be set, like `size`.
+    ReadOnly { name: String },
+    /// The property can only be set when the pool is created or imported, like `altroot`.
+    SetOnce { name: String },
+    /// The value isn't valid for the property; `expected` describes what would be.
+    BadValue { name: String, value: String, expected: String },
+    /// The feature isn't allowed by the pool's `compatibility` property.
+    NotCompatible { name: String, compatibility: String },
+    /// The pool's on-disk version is too old to support the property or feature; it needs
+    /// [`ZPool::upgrade`] first.
+    NeedsUpgrade { name: String },
+}
+
+impl fmt::Display for PoolPropertyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PoolPropertyError::Unknown { name } => write!(f, "invalid pool property {:?}", name),
+            PoolPropertyError::ReadOnly { name } => write!(f, "{:?} is read-only", name),
+            PoolPropertyError::SetOnce { name } => {
+                write!(f, "{:?} can only be set at creation or import time", name)
+            }
+            PoolPropertyError::BadValue { name, value, expected } => {
+                write!(f, "bad value {:?} for {:?}: expected {}", value, name, expected)
+            }
+            PoolPropertyError::NotCompatible { name, compatibility } => {
+                write!(f, "{:?} is not allowed by compatibility={:?}", name, compatibility)
+            }
+            PoolPropertyError::NeedsUpgrade { name } => {
+                write!(f, "pool must be upgraded to support {:?}", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for PoolPropertyError {}
+
+impl ZPool {
+    /// Get the value of a native pool property (like `"ashift"`), in its parsable form, as
+    /// printed by `zpool get -p`.
+    pub fn get_property(&self, name: &str) -> Result<String> {
+        let cname = SafeString::try_from(name)?;
+        let prop = unsafe { sys::zpool_name_to_prop(cname.as_ptr()) };
+        if prop == sys::zpool_prop_t::ZPOOL_PROP_INVAL {
+            return Err(Error::invalid_input(format!("invalid pool property name {:?}", name)));
+        }
+        self.get_prop_parsable(prop)
+    }
+
+    /// Get every property of this pool, like `zpool get all`: native properties first, then a
+    /// `feature@<name>` entry for each feature this libzfs supports, then an
+    /// `unsupported@<guid>` entry for each feature in use on the pool which it doesn't.
+    pub fn properties(&self) -> Result<Vec<Property>> {
+        extern "C" fn collect(prop: c_int, context: *mut c_void) -> c_int {
+            let props = unsafe { &mut *(context as *mut Vec<sys::zpool_prop_t::Type>) };
+            props.push(prop as sys::zpool_prop_t::Type);
+            sys::ZPROP_CONT
+        }
+
+        let _lock = self.libzfs.lock();
+        let mut native = vec![];
+        unsafe {
+            sys::zprop_iter(
+                Some(collect),
+                &mut native as *mut _ as *mut c_void,
+                0, // show_all: skip hidden properties
+                1, // ordered
+                sys::zfs_type_t::ZFS_TYPE_POOL,
+            )
+        };
+
+        // Like `zpool get all`, leave out properties which can't be read, like `expandsize` on
+        // pools whose devices can't grow.
+        let mut props = native.into_iter()
+            .filter_map(|prop| self.get_native_property_with_source(prop).ok())
+            .collect::<Vec<_>>();
+
+        let supported = supported_features();
+        for (_, feature) in &supported {
+            let value = self.get_feature_state(&feature.name)?;
+            props.push(Property {
+                name: format!("feature@{}", feature.name),
+                value: value.to_string(),
+                source: PropertySource::Local,
+            });
+        }
+
+        let stats = unsafe { sys::zpool_get_features(self.handle) };
+        if !stats.is_null() {
+            for pair in unsafe { NvListRef::from_ptr(stats) }.iter() {
+                let guid = pair.name();
+                if supported.iter().any(|(_, feature)| feature.guid == guid) {
+                    continue;
+                }
+                let name = SafeString::try_from(format!("unsupported@{}", guid))?;
+                let mut buf = vec![0 as c_char; 64];
+                ztry!(unsafe {
+                    sys::zpool_prop_get_feature(
+                        self.handle,
+                        name.as_ptr(),
+                        buf.as_mut_ptr(),
+                        buf.len(),
+                    )
+                }, self.libzfs.raw(), self.name());
+                props.push(Property {
+                    name: name.into(),
+                    value: string_from_buf(&buf),
+                    source: PropertySource::Local,
+                });
+            }
+        }
+        Ok(props)
+    }
+
+    /// Check whether a pool property (or `feature@<name>`) could be set to the given value,
+    /// without setting it, so that bad input can be reported before trying. The error for a
+    /// value which won't do wraps a [`PoolPropertyError`] saying why.
+    ///
+    /// This catches what can be known up front; setting the property can still fail for other
+    /// reasons.
+    pub fn validate_property(&self, name: &str, value: &str) -> Result<()> {
+        let bad_value = |expected: &str| Error::invalid_input(PoolPropertyError::BadValue {
+            name: name.to_owned(),
+            value: value.to_owned(),
+            expected: expected.to_owned(),
+        });
+
+        let cname = SafeString::try_from(name)?;
+        let cvalue = SafeString::try_from(value)?;
+        let prop = unsafe { sys::zpool_name_to_prop(cname.as_ptr()) };
+        if prop == sys::zpool_prop_t::ZPOOL_PROP_INVAL {
+            let idx = name.strip_prefix("feature@").and_then(|feature| {
+                supported_features().into_iter()
+                    .find(|(_, info)| info.name == feature)
+                    .map(|(idx, _)| idx)
+            });
+            let Some(idx) = idx else {
+                return Err(Error::invalid_input(
+                    PoolPropertyError::Unknown { name: name.to_owned() }));
+            };
+            if self.is_legacy_version() {
+                return Err(Error::invalid_input(
+                    PoolPropertyError::NeedsUpgrade { name: name.to_owned() }));
+            }
+            // Features can't be disabled once enabled.
+            if value != "enabled" {
+                return Err(bad_value("\"enabled\""));
+            }
+            let compatibility = compat::pool_compatibility(self)?;
+            if !load_compat(&compatibility)?[idx] {
+                return Err(Error::invalid_input(
+                    PoolPropertyError::NotCompatible { name: name.to_owned(), compatibility }));
+            }
+            return Ok(());
+        }
+
+        if unsafe { sys::zpool_prop_readonly(prop) } != 0 {
+            return Err(Error::invalid_input(PoolPropertyError::ReadOnly { name: name.to_owned() }));
+        }
+        if unsafe { sys::zpool_prop_setonce(prop) } != 0 {
+            return Err(Error::invalid_input(PoolPropertyError::SetOnce { name: name.to_owned() }));
+        }
+
+        let _lock = self.libzfs.lock();
+        match unsafe { sys::zpool_prop_get_type(prop) } {
+            sys::zprop_type_t::PROP_TYPE_INDEX => {
+                let mut index = 0;
+                let ret = unsafe {
+                    sys::zpool_prop_string_to_index(prop, cvalue.as_ptr(), &mut index)
+                };
+                if ret != 0 {
+                    let values = unsafe { sys::zpool_prop_values(prop) };
+                    return Err(bad_value(&if values.is_null() {
+                        "one of the property's values".to_owned()
+                    } else {
+                        unsafe { CStr::from_ptr(values) }.to_string_lossy().into_owned()
+                    }));
+                }
+            }
+            sys::zprop_type_t::PROP_TYPE_NUMBER => {
+                let mut num = 0;
+                // libzfs only uses the handle for error messages, which we make ourselves.
+                let ret = unsafe {
+                    sys::zfs_nicestrtonum(ptr::null_mut(), cvalue.as_ptr(), &mut num)
+                };
+                if ret != 0 {
+                    return Err(bad_value("a number"));
+                }
+                if prop == sys::zpool_prop_t::ZPOOL_PROP_ASHIFT
+                    && num != 0 && !(ASHIFT_MIN..=ASHIFT_MAX).contains(&num)
+                {
+                    return Err(bad_value(&format!("0, or {} to {}", ASHIFT_MIN, ASHIFT_MAX)));
+                }
+            }
+            _ => {
+                if let Some(expected) = self.check_string_property(prop, value) {
+                    return Err(bad_value(&expected));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Check the value of a string property which libzfs places limits on, returning what's
+    /// expected if it doesn't fit them.
+    fn check_string_property(&self, prop: sys::zpool_prop_t::Type, value: &str) -> Option<String> {
+        match prop {
+            sys::zpool_prop_t::ZPOOL_PROP_BOOTFS => {
+                let pool = self.name();
+                let pool = pool.as_str();
+                let in_pool = value == pool
+                    || value.strip_prefix(pool).is_some_and(|rest| rest.starts_with('/'));
+                (!value.is_empty() && !in_pool).then(|| format!("a filesystem in {}", pool))
+            }
+            sys::zpool_prop_t::ZPOOL_PROP_CACHEFILE => {
+                let ok = value.is_empty() || value == "none" || value.starts_with('/');
+                (!ok).then(|| "an absolute path, or \"none\"".to_owned())
+            }
+            #[cfg(zfs_2_1)]
+            sys::zpool_prop_t::ZPOOL_PROP_COMPATIBILITY => {
+                load_compat(value).err().map(|e| {
+                    format!("\"off\", \"legacy\", or a list of compatibility files ({})", e)
+                })
+            }
+            sys::zpool_prop_t::ZPOOL_PROP_COMMENT => {
+                let ok = value.len() <= ZPROP_MAX_COMMENT
+                    && value.chars().all(|c| c.is_ascii() && !c.is_ascii_control());
+                (!ok).then(|| format!("at most {} printable ASCII characters", ZPROP_MAX_COMMENT))
+            }
+            _ => None,
+        }
+    }
+
+    /// Set a native pool property or enable a feature, like `zpool set`. The value is checked
+    /// first with [`validate_property`](Self::validate_property).
+    pub fn set_property(&self, name: &str, value: &str) -> Result<()> {
+        self.validate_property(name, value)?;
+        let _lock = self.libzfs.lock();
+        let cname = SafeString::try_from(name)?;
+        let cvalue = SafeString::try_from(value)?;
+        ztry!(unsafe {
+            sys::zpool_set_prop(self.handle, cname.as_ptr(), cvalue.as_ptr())
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    fn get_native_property_with_source(&self, prop: sys::zpool_prop_t::Type) -> Result<Property> {
+        use sys::zprop_source_t::*;
+        let _lock = self.libzfs.lock();
+        let mut buf = vec![0 as c_char; PROP_BUF_LEN];
+        let mut src = 0;
+        ztry!(unsafe {
+            sys::zpool_get_prop(
+                self.handle,
+                prop,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut src,
+                1, // literal
+            )
+        }, self.libzfs.raw(), self.name());
+        let source = match src {
+            ZPROP_SRC_DEFAULT => PropertySource::Default,
+            ZPROP_SRC_TEMPORARY => PropertySource::Temporary,
+            ZPROP_SRC_LOCAL => PropertySource::Local,
+            _ => PropertySource::None,
+        };
+        let name = unsafe { CStr::from_ptr(sys::zpool_prop_to_name(prop)) };
+        Ok(Property {
+            name: name.to_string_lossy().into_owned(),
+            value: string_from_buf(&buf),
+            source,
+        })
+    }
+}
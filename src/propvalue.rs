@@ -0,0 +1,402 @@
+use crate::{sys, Dataset, Error, Result, ZPool};
+use crate::handle::init_prop_tables;
+
+use std::ffi::{CStr, CString};
+use std::fmt;
+use std::ptr;
+use std::str::FromStr;
+
+/// A typed value of a native property with a fixed set of values, like `compression`.
+///
+/// Each type has an `Other` variant for values added by newer versions of ZFS.
+pub trait PropertyValue: FromStr<Err = Error> + fmt::Display {
+    /// The property's name.
+    const NAME: &'static str;
+}
+
+/// A typed value of a native pool property with a fixed set of values, like `failmode`.
+pub trait PoolPropertyValue: FromStr<Err = Error> + fmt::Display {
+    /// The property's name.
+    const NAME: &'static str;
+}
+
+impl Dataset {
+    /// Get the value of an enumerated property as its Rust type, like
+    /// `ds.get_typed_property::<Compression>()`.
+    pub fn get_typed_property<T: PropertyValue>(&self) -> Result<T> {
+        self.get_property(T::NAME)?.parse()
+    }
+
+    /// Set an enumerated property from its Rust type.
+    pub fn set_typed_property<T: PropertyValue>(&self, value: &T) -> Result<()> {
+        self.set_property(T::NAME, &value.to_string())
+    }
+}
+
+impl ZPool {
+    /// Get the value of an enumerated pool property as its Rust type, like
+    /// `pool.get_typed_property::<FailMode>()`.
+    pub fn get_typed_property<T: PoolPropertyValue>(&self) -> Result<T> {
+        self.get_property(T::NAME)?.parse()
+    }
+
+    /// Set an enumerated pool property from its Rust type.
+    pub fn set_typed_property<T: PoolPropertyValue>(&self, value: &T) -> Result<()> {
+        self.set_property(T::NAME, &value.to_string())
+    }
+}
+
+/// The libzfs table which an enumerated property's values are named in.
+#[derive(Clone, Copy)]
+enum Table {
+    Dataset(sys::zfs_prop_t::Type),
+    Pool(sys::zpool_prop_t::Type),
+}
+
+impl Table {
+    /// What libzfs calls the value with this index, if it knows of it.
+    fn name(self, index: u64) -> Option<&'static str> {
+        init_prop_tables();
+        let mut name = ptr::null();
+        let ret = match self {
+            Table::Dataset(prop) => unsafe {
+                sys::zfs_prop_index_to_string(prop, index, &mut name)
+            },
+            Table::Pool(prop) => unsafe {
+                sys::zpool_prop_index_to_string(prop, index, &mut name)
+            },
+        };
+        if ret != 0 || name.is_null() {
+            return None;
+        }
+        unsafe { CStr::from_ptr(name) }.to_str().ok()
+    }
+
+    /// The index of the value libzfs calls `name` (or one of its other names, like `full` for
+    /// `geom`), if it knows of it.
+    fn index(self, name: &str) -> Option<u64> {
+        init_prop_tables();
+        let name = CString::new(name).ok()?;
+        let mut index = 0;
+        let ret = match self {
+            Table::Dataset(prop) => unsafe {
+                sys::zfs_prop_string_to_index(prop, name.as_ptr(), &mut index)
+            },
+            Table::Pool(prop) => unsafe {
+                sys::zpool_prop_string_to_index(prop, name.as_ptr(), &mut index)
+            },
+        };
+        (ret == 0).then_some(index)
+    }
+}
+
+/// Define an enum for a property whose values are in a libzfs table. Each variant is given by
+/// its index in the table, which is the value of the corresponding C enum; what they're called
+/// comes from libzfs, so it parses and prints them exactly like the `zfs` and `zpool` commands.
+/// A variant the libzfs linked against doesn't know about prints as its index, which it will
+/// refuse to set.
+macro_rules! property_enum {
+    (
+        $(#[$attr:meta])*
+        name: $name:ident,
+        property: $prop:expr,
+        table: $table:ident($sys:ident),
+        impl: $trait:ident,
+        values: {
+            $( $(#[$vattr:meta])* $variant:ident = $index:literal, )*
+        }
+    ) => {
+        $(#[$attr])*
+        #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+        pub enum $name {
+            $( $(#[$vattr])* $variant, )*
+            Other(String),
+        }
+
+        impl $name {
+            const TABLE: Table = property_enum!(@table $table($sys));
+        }
+
+        impl FromStr for $name {
+            type Err = Error;
+            fn from_str(s: &str) -> Result<$name> {
+                Ok(match $name::TABLE.index(s) {
+                    $( Some($index) => $name::$variant, )*
+                    _ => $name::Other(s.to_owned()),
+                })
+            }
+        }
+
+        impl fmt::Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+                let index: u64 = match self {
+                    $( $name::$variant => $index, )*
+                    $name::Other(s) => return f.write_str(s),
+                };
+                match $name::TABLE.name(index) {
+                    Some(name) => f.write_str(name),
+                    None => write!(f, "{}", index),
+                }
+            }
+        }
+
+        impl $trait for $name {
+            const NAME: &'static str = $prop;
+        }
+    };
+    (@table Dataset($sys:ident)) => { Table::Dataset(sys::zfs_prop_t::$sys) };
+    (@table Pool($sys:ident)) => { Table::Pool(sys::zpool_prop_t::$sys) };
+}
+
+property_enum! {
+    /// The `checksum` property. The indices are `enum zio_checksum`'s.
+    name: Checksum,
+    property: "checksum",
+    table: Dataset(ZFS_PROP_CHECKSUM),
+    impl: PropertyValue,
+    values: {
+        On = 1,
+        Off = 2,
+        Fletcher2 = 6,
+        Fletcher4 = 7,
+        Sha256 = 8,
+        NoParity = 10,
+        Sha512 = 11,
+        Skein = 12,
+        Edonr = 13,
+        Blake3 = 14,
+    }
+}
+
+property_enum! {
+    /// The `sync` property. The indices are `zfs_sync_type_t`'s.
+    name: SyncPolicy,
+    property: "sync",
+    table: Dataset(ZFS_PROP_SYNC),
+    impl: PropertyValue,
+    values: {
+        Standard = 0,
+        Always = 1,
+        Disabled = 2,
+    }
+}
+
+property_enum! {
+    /// The `atime` property.
+    name: Atime,
+    property: "atime",
+    table: Dataset(ZFS_PROP_ATIME),
+    impl: PropertyValue,
+    values: {
+        Off = 0,
+        On = 1,
+    }
+}
+
+property_enum! {
+    /// The `redundant_metadata` property. The indices are `zfs_redundant_metadata_type_t`'s.
+    name: RedundantMetadata,
+    property: "redundant_metadata",
+    table: Dataset(ZFS_PROP_REDUNDANT_METADATA),
+    impl: PropertyValue,
+    values: {
+        All = 0,
+        Most = 1,
+        Some = 2,
+        None = 3,
+    }
+}
+
+property_enum! {
+    /// The `snapdev` property of a volume: whether its snapshots get device nodes.
+    name: Snapdev,
+    property: "snapdev",
+    table: Dataset(ZFS_PROP_SNAPDEV),
+    impl: PropertyValue,
+    values: {
+        Hidden = 0,
+        Visible = 1,
+    }
+}
+
+property_enum! {
+    /// The `volmode` property of a volume: how it is exposed to the operating system. The
+    /// indices are `zfs_volmode_t`'s.
+    name: Volmode,
+    property: "volmode",
+    table: Dataset(ZFS_PROP_VOLMODE),
+    impl: PropertyValue,
+    values: {
+        /// Use the `zvol_volmode` module parameter.
+        Default = 0,
+        /// A full block device, which can be partitioned (a GEOM provider, on FreeBSD). Also
+        /// called `full`.
+        Geom = 1,
+        /// A plain device node, without partitions.
+        Dev = 2,
+        /// Not exposed at all.
+        None = 3,
+    }
+}
+
+property_enum! {
+    /// The `keystatus` property of an encrypted dataset: whether its key is loaded. The indices
+    /// are `zfs_keystatus_t`'s.
+    name: KeyStatus,
+    property: "keystatus",
+    table: Dataset(ZFS_PROP_KEYSTATUS),
+    impl: PropertyValue,
+    values: {
+        /// The dataset isn't encrypted.
+        None = 0,
+        Unavailable = 1,
+        Available = 2,
+    }
+}
+
+property_enum! {
+    /// The `keyformat` property of an encryption root: what its key is. The indices are
+    /// `zfs_keyformat_t`'s.
+    name: KeyFormat,
+    property: "keyformat",
+    table: Dataset(ZFS_PROP_KEYFORMAT),
+    impl: PropertyValue,
+    values: {
+        /// The dataset isn't encrypted.
+        None = 0,
+        /// 32 bytes.
+        Raw = 1,
+        /// 64 hex digits.
+        Hex = 2,
+        /// A passphrase of 8 to 512 bytes, from which the key is derived with PBKDF2.
+        Passphrase = 3,
+    }
+}
+
+property_enum! {
+    /// The `failmode` pool property: what happens when the pool can't be written to. The indices
+    /// are `ZIO_FAILURE_MODE_*`.
+    name: FailMode,
+    property: "failmode",
+    table: Pool(ZPOOL_PROP_FAILUREMODE),
+    impl: PoolPropertyValue,
+    values: {
+        /// Block I/O until the devices come back.
+        Wait = 0,
+        /// Fail new writes with EIO, but allow reads from healthy devices.
+        Continue = 1,
+        /// Panic the system.
+        Panic = 2,
+    }
+}
+
+/// The `compression` property. Levels of `None` mean the algorithm's default level.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Compression {
+    On,
+    Off,
+    Lzjb,
+    Gzip(Option<u8>),
+    Zle,
+    Lz4,
+    Zstd(Option<u8>),
+    ZstdFast(Option<u16>),
+    Other(String),
+}
+
+impl FromStr for Compression {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Compression> {
+        fn level<T: FromStr>(s: &str, prefix: &str) -> Option<Option<T>> {
+            match s.strip_prefix(prefix)? {
+                "" => Some(None),
+                rest => rest.strip_prefix('-')?.parse().ok().map(Some),
+            }
+        }
+        Ok(match s {
+            "on" => Compression::On,
+            "off" => Compression::Off,
+            "lzjb" => Compression::Lzjb,
+            "zle" => Compression::Zle,
+            "lz4" => Compression::Lz4,
+            _ => {
+                if let Some(l) = level(s, "gzip") {
+                    Compression::Gzip(l)
+                } else if let Some(l) = level(s, "zstd-fast") {
+                    Compression::ZstdFast(l)
+                } else if let Some(l) = level(s, "zstd") {
+                    Compression::Zstd(l)
+                } else {
+                    Compression::Other(s.to_owned())
+                }
+            }
+        })
+    }
+}
+
+impl fmt::Display for Compression {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Compression::On => f.write_str("on"),
+            Compression::Off => f.write_str("off"),
+            Compression::Lzjb => f.write_str("lzjb"),
+            Compression::Gzip(None) => f.write_str("gzip"),
+            Compression::Gzip(Some(l)) => write!(f, "gzip-{}", l),
+            Compression::Zle => f.write_str("zle"),
+            Compression::Lz4 => f.write_str("lz4"),
+            Compression::Zstd(None) => f.write_str("zstd"),
+            Compression::Zstd(Some(l)) => write!(f, "zstd-{}", l),
+            Compression::ZstdFast(None) => f.write_str("zstd-fast"),
+            Compression::ZstdFast(Some(l)) => write!(f, "zstd-fast-{}", l),
+            Compression::Other(s) => f.write_str(s),
+        }
+    }
+}
+
+impl PropertyValue for Compression {
+    const NAME: &'static str = "compression";
+}
+
+/// The `dedup` property.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Dedup {
+    Off,
+    /// Dedup with the given checksum (or the default one), and optionally verify that blocks
+    /// with matching checksums really are the same.
+    On {
+        checksum: Option<Checksum>,
+        verify: bool,
+    },
+}
+
+impl FromStr for Dedup {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Dedup> {
+        let (checksum, verify) = match s {
+            "off" => return Ok(Dedup::Off),
+            "on" => (None, false),
+            "verify" => (None, true),
+            _ => match s.strip_suffix(",verify") {
+                Some(checksum) => (Some(checksum.parse()?), true),
+                None => (Some(s.parse()?), false),
+            },
+        };
+        Ok(Dedup::On { checksum, verify })
+    }
+}
+
+impl fmt::Display for Dedup {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Dedup::Off => f.write_str("off"),
+            Dedup::On { checksum: None, verify: false } => f.write_str("on"),
+            Dedup::On { checksum: None, verify: true } => f.write_str("verify"),
+            Dedup::On { checksum: Some(c), verify: false } => c.fmt(f),
+            Dedup::On { checksum: Some(c), verify: true } => write!(f, "{},verify", c),
+        }
+    }
+}
+
+impl PropertyValue for Dedup {
+    const NAME: &'static str = "dedup";
+}
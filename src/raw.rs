@@ -0,0 +1,110 @@
+use crate::{sys, Dataset, LibZfs, ZPool};
+use crate::handle::LibZfsHandle;
+
+use std::mem::ManuallyDrop;
+use std::ptr;
+use std::sync::Arc;
+
+// These let applications call libzfs functions this crate doesn't wrap yet, while still using
+// it to open and close things. libzfs isn't thread-safe, so any such calls have to be made
+// inside `with_lock`, unless nothing else could be using the same library handle at the time.
+
+impl LibZfs {
+    /// Get the raw libzfs handle, without giving up ownership of it.
+    pub fn as_raw(&self) -> *mut sys::libzfs_handle_t {
+        self.handle.raw()
+    }
+
+    /// Give up ownership of the raw libzfs handle, so that it isn't closed, and the caller has to
+    /// `libzfs_fini` it. This only works while no pools or datasets opened through this `LibZfs`
+    /// are still around, since they share it; otherwise, it's given back.
+    pub fn into_raw(self) -> Result<*mut sys::libzfs_handle_t, LibZfs> {
+        Arc::try_unwrap(self.handle)
+            .map(LibZfsHandle::into_raw)
+            .map_err(|handle| LibZfs { handle })
+    }
+
+    /// Take ownership of a raw libzfs handle, from `libzfs_init` or
+    /// [`into_raw`](Self::into_raw). It's closed when this `LibZfs`, and everything opened
+    /// through it, is dropped.
+    ///
+    /// # Safety
+    /// `raw` must be a valid libzfs handle which nothing else will use or close afterward.
+    pub unsafe fn from_raw(raw: *mut sys::libzfs_handle_t) -> LibZfs {
+        LibZfs { handle: LibZfsHandle::from_raw(raw) }
+    }
+
+    /// Run `f` while holding the lock which serializes use of this library handle, and of
+    /// everything opened through it, so that it can make libzfs calls with raw handles.
+    pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _lock = self.handle.lock();
+        f()
+    }
+}
+
+impl ZPool {
+    /// Get the raw pool handle, without giving up ownership of it.
+    pub fn as_raw(&self) -> *mut sys::zpool_handle_t {
+        self.handle
+    }
+
+    /// Give up ownership of the raw pool handle, so that it isn't closed, and the caller has to
+    /// `zpool_close` it, before the `LibZfs` it came from is gone. Pools got from a dataset's
+    /// [`get_pool`](Dataset::get_pool) belong to the library handle, and mustn't be closed.
+    pub fn into_raw(self) -> *mut sys::zpool_handle_t {
+        let this = ManuallyDrop::new(self);
+        // Safety: the field is read only once and `this` is never dropped.
+        drop(unsafe { ptr::read(&this.libzfs) });
+        this.handle
+    }
+
+    /// Take ownership of a raw pool handle, opened through the given `LibZfs`'s library handle.
+    /// It's closed when the `ZPool` is dropped.
+    ///
+    /// # Safety
+    /// `raw` must be a valid pool handle opened through `libzfs`, which nothing else will use
+    /// or close afterward.
+    pub unsafe fn from_raw(libzfs: &LibZfs, raw: *mut sys::zpool_handle_t) -> ZPool {
+        ZPool { libzfs: libzfs.handle.clone(), handle: raw, owned: true }
+    }
+
+    /// Run `f` while holding the lock which serializes use of this pool's library handle, so
+    /// that it can make libzfs calls with raw handles.
+    pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _lock = self.libzfs.lock();
+        f()
+    }
+}
+
+impl Dataset {
+    /// Get the raw dataset handle, without giving up ownership of it.
+    pub fn as_raw(&self) -> *mut sys::zfs_handle_t {
+        self.handle
+    }
+
+    /// Give up ownership of the raw dataset handle, so that it isn't closed, and the caller has
+    /// to `zfs_close` it, before the `LibZfs` it came from is gone.
+    pub fn into_raw(self) -> *mut sys::zfs_handle_t {
+        let this = ManuallyDrop::new(self);
+        // Safety: the field is read only once and `this` is never dropped.
+        drop(unsafe { ptr::read(&this.libzfs) });
+        this.handle
+    }
+
+    /// Take ownership of a raw dataset handle, opened through the given `LibZfs`'s library
+    /// handle. It's closed when the `Dataset` is dropped.
+    ///
+    /// # Safety
+    /// `raw` must be a valid dataset handle opened through `libzfs`, which nothing else will use
+    /// or close afterward.
+    pub unsafe fn from_raw(libzfs: &LibZfs, raw: *mut sys::zfs_handle_t) -> Dataset {
+        Dataset { libzfs: libzfs.handle.clone(), handle: raw }
+    }
+
+    /// Run `f` while holding the lock which serializes use of this dataset's library handle, so
+    /// that it can make libzfs calls with raw handles.
+    pub fn with_lock<R>(&self, f: impl FnOnce() -> R) -> R {
+        let _lock = self.libzfs.lock();
+        f()
+    }
+}
@@ -0,0 +1,500 @@
+use crate::{sys, AsSafeStr, Dataset, DatasetType, DatasetTypeMask, Error, LibZfs, Mountpoint};
+use crate::{LibZfsCore, NvList, Plan, Result, SafeString, ZfsError};
+use crate::error::lzc_call;
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::mem::ManuallyDrop;
+use std::os::fd::{AsFd, AsRawFd, BorrowedFd, FromRawFd};
+use std::ptr;
+use std::thread;
+
+/// The magic number at the start of every send stream; see `sys/zfs_ioctl.h`.
+const DMU_BACKUP_MAGIC: u64 = 0x2f5bacbac;
+/// Stream feature flag for raw (encrypted) sends.
+const DMU_BACKUP_FEATURE_RAW: u64 = 1 << 24;
+/// Stream header type of a replication stream, like from `zfs send -R`.
+const DMU_COMPOUNDSTREAM: u64 = 2;
+/// Size of a `dmu_replay_record_t`, which starts every stream with its BEGIN record.
+const REPLAY_RECORD_LEN: usize = 312;
+/// Where `drr_begin.drr_toname` is in the BEGIN record.
+const TONAME_OFFSET: usize = 56;
+
+/// The BEGIN record which starts a send stream.
+struct BeginRecord {
+    buf: [u64; REPLAY_RECORD_LEN / 8],
+}
+
+impl BeginRecord {
+    fn read(fd: BorrowedFd<'_>) -> Result<Self> {
+        // Read through a File, but don't let it close the caller's fd.
+        let mut file = ManuallyDrop::new(unsafe { File::from_raw_fd(fd.as_raw_fd()) });
+        let mut buf = [0u64; REPLAY_RECORD_LEN / 8];
+        let bytes = unsafe {
+            std::slice::from_raw_parts_mut(buf.as_mut_ptr() as *mut u8, REPLAY_RECORD_LEN)
+        };
+        file.read_exact(bytes).map_err(Error::Sys)?;
+
+        let record = BeginRecord { buf };
+        if record.magic() != DMU_BACKUP_MAGIC {
+            return Err(Error::invalid_data("input is not a ZFS send stream"));
+        }
+        if record.header_type() == DMU_COMPOUNDSTREAM {
+            return Err(Error::invalid_input("replication streams (zfs send -R) aren't supported"));
+        }
+        Ok(record)
+    }
+
+    /// Whether the stream was written on a machine of the other endianness.
+    fn byteswapped(&self) -> bool {
+        self.buf[1].swap_bytes() == DMU_BACKUP_MAGIC
+    }
+
+    fn magic(&self) -> u64 {
+        if self.byteswapped() { self.buf[1].swap_bytes() } else { self.buf[1] }
+    }
+
+    fn versioninfo(&self) -> u64 {
+        if self.byteswapped() { self.buf[2].swap_bytes() } else { self.buf[2] }
+    }
+
+    fn header_type(&self) -> u64 {
+        self.versioninfo() & 0x3
+    }
+
+    fn is_raw(&self) -> bool {
+        (self.versioninfo() >> 2) & DMU_BACKUP_FEATURE_RAW != 0
+    }
+
+    /// Whether the stream is incremental from an earlier snapshot, rather than a full one.
+    fn is_incremental(&self) -> bool {
+        // drr_fromguid, which is zero for a full stream
+        self.buf[6] != 0
+    }
+
+    /// The full name of the snapshot receiving this stream into `name` would create: `name`
+    /// itself if it's a snapshot, or else the stream's snapshot's short name under it.
+    fn snapshot_name<N: AsSafeStr>(&self, name: N) -> Result<SafeString> {
+        let name = name.as_safe_str()?;
+        if name.as_str().contains('@') {
+            return Ok(name.into_owned());
+        }
+        let toname = self.toname();
+        let (_, snap) = toname.split_once('@').ok_or_else(|| {
+            Error::invalid_data(format!("bad snapshot name {:?} in stream", toname))
+        })?;
+        SafeString::try_from(format!("{}@{}", name, snap))
+    }
+
+    /// The name of the snapshot the stream was sent from.
+    fn toname(&self) -> String {
+        let bytes = unsafe {
+            std::slice::from_raw_parts(self.buf.as_ptr() as *const u8, REPLAY_RECORD_LEN)
+        };
+        let toname = &bytes[TONAME_OFFSET ..];
+        let len = toname.iter().position(|&b| b == 0).unwrap_or(toname.len());
+        String::from_utf8_lossy(&toname[.. len]).into_owned()
+    }
+}
+
+/// Options for [`LibZfs::receive`].
+#[derive(Debug, Clone, Default)]
+pub struct ReceiveOptions {
+    /// Roll the destination back to its most recent snapshot first, discarding any changes made
+    /// since, like `zfs receive -F`.
+    pub force: bool,
+    /// If the receive is interrupted, keep what was received so far, so the send can be resumed
+    /// from the destination's `receive_resume_token`, like `zfs receive -s`.
+    pub resumable: bool,
+    /// Don't mount the received filesystem, like `zfs receive -u`.
+    pub no_mount: bool,
+}
+
+/// The state of an interrupted resumable receive, parsed from a `receive_resume_token`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ResumeToken {
+    /// The token itself, to pass to the sending side.
+    pub token: String,
+    /// The snapshot being sent.
+    pub toname: String,
+    pub toguid: u64,
+    /// For an incremental send, the GUID of the snapshot it's from.
+    pub fromguid: Option<u64>,
+    /// The object the send got up to.
+    pub object: u64,
+    /// The offset in that object the send got up to.
+    pub offset: u64,
+    /// How many bytes had been received.
+    pub bytes: u64,
+    /// Whether the stream had embedded data, large blocks, compressed blocks, or raw blocks;
+    /// the resumed send needs the same.
+    pub embedok: bool,
+    pub largeblockok: bool,
+    pub compressok: bool,
+    pub rawok: bool,
+}
+
+impl LibZfs {
+    /// Receive a send stream read from `reader`, like [`receive`](Self::receive). Blocks until
+    /// the receive is done.
+    pub fn receive_from_reader<N, R>(&self, name: N, mut reader: R, options: &ReceiveOptions)
+        -> Result<()>
+        where N: AsSafeStr,
+              R: Read + Send,
+    {
+        let (pipe_reader, mut pipe_writer) = io::pipe().map_err(Error::Sys)?;
+        thread::scope(|scope| {
+            let copier = scope.spawn(move || io::copy(&mut reader, &mut pipe_writer));
+            let received = self.receive(name, pipe_reader.as_fd(), options);
+            // If receiving failed, closing the pipe makes copying fail too, instead of blocking.
+            drop(pipe_reader);
+            let copied = copier.join().unwrap_or_else(|_| {
+                Err(io::Error::other("receive copier thread panicked"))
+            });
+            received?;
+            copied.map(|_| ()).map_err(Error::Sys)
+        })
+    }
+
+    /// Parse a `receive_resume_token` property value.
+    pub fn parse_resume_token(&self, token: &str) -> Result<ResumeToken> {
+        let ctoken = SafeString::try_from(token)?;
+        let nvl = {
+            let _lock = self.handle.lock();
+            let nvl = unsafe {
+                sys::zfs_send_resume_token_to_nvlist(self.handle.raw(), ctoken.as_ptr())
+            };
+            if nvl.is_null() {
+                return Err(Error::Zfs(ZfsError::last_error(self.handle.raw())));
+            }
+            unsafe { NvList::from_raw(nvl) }
+        };
+
+        let uint64 = |name| nvl.lookup_uint64(name)
+            .ok_or_else(|| Error::invalid_data(format!("resume token has no {}", name)));
+        Ok(ResumeToken {
+            token: token.to_owned(),
+            toname: nvl.lookup_string("toname")
+                .ok_or_else(|| Error::invalid_data("resume token has no toname"))?
+                .into_owned(),
+            toguid: uint64("toguid")?,
+            fromguid: nvl.lookup_uint64("fromguid"),
+            object: uint64("object")?,
+            offset: uint64("offset")?,
+            bytes: nvl.lookup_uint64("bytes").unwrap_or(0),
+            embedok: nvl.contains("embedok"),
+            largeblockok: nvl.contains("largeblockok"),
+            compressok: nvl.contains("compressok"),
+            rawok: nvl.contains("rawok"),
+        })
+    }
+
+    /// Receive a send stream read from `fd`, like `zfs receive`, creating the snapshot `name`.
+    /// If `name` is a filesystem or volume rather than a snapshot, the snapshot gets the same
+    /// short name it had on the sending side. Unless told otherwise, a received filesystem is
+    /// then mounted, if its `canmount` and `mountpoint` properties allow.
+    ///
+    /// Raw streams of encrypted datasets are received as they are, without needing the key. A
+    /// raw incremental stream is only accepted if it was sent from the same encrypted snapshot
+    /// that `name`'s dataset has; otherwise this fails with `EZFS_BADSTREAM`.
+    pub fn receive<N: AsSafeStr>(&self, name: N, fd: BorrowedFd<'_>, options: &ReceiveOptions)
+        -> Result<()>
+    {
+        let snapname = LibZfsCore::new()?.receive(name, fd, options)?;
+
+        if !options.no_mount {
+            let (fs, _) = snapname.as_str().split_once('@').unwrap_or_default();
+            let ds = self.dataset_by_name(fs, DatasetTypeMask::all())?;
+            if ds.get_type() == DatasetType::Filesystem
+                && ds.get_property("canmount")? == "on"
+                && matches!(ds.mountpoint()?, Mountpoint::Path(_))
+                && !ds.is_mounted()
+            {
+                ds.mount()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Work out what [`receive`](Self::receive) would do with the stream in `fd`, without
+    /// changing anything: which snapshot (and filesystem or volume, if it doesn't exist yet)
+    /// would be created, and whether an existing one would be rolled back first.
+    ///
+    /// Fails, like `receive` would, if the stream is a full one and the filesystem or volume
+    /// already exists, unless `options.force` is set.
+    ///
+    /// Only the stream's BEGIN record is read, but that means the rest of it can't be received
+    /// from `fd` afterwards, unless it can be seeked back to the start.
+    pub fn plan_receive<N: AsSafeStr>(
+        &self,
+        name: N,
+        fd: BorrowedFd<'_>,
+        options: &ReceiveOptions,
+    ) -> Result<Plan> {
+        let begin = BeginRecord::read(fd)?;
+        let snapname = begin.snapshot_name(name)?;
+        let (fs, _) = snapname.as_str().split_once('@').unwrap_or_default();
+        let mut plan = Plan::default();
+        match self.dataset_by_name(fs, DatasetTypeMask::all()) {
+            Ok(ds) => {
+                if !begin.is_incremental() && !options.force {
+                    return Err(Error::exists(format!(
+                        "{} exists; a full stream can only be received into it with force",
+                        fs)));
+                }
+                if options.force {
+                    plan.rolled_back.push(ds.get_name());
+                }
+            }
+            Err(e) if e.is_not_found() => plan.created.push(SafeString::try_from(fs)?),
+            Err(e) => return Err(e),
+        }
+        plan.created.push(snapname);
+        Ok(plan)
+    }
+}
+
+impl LibZfsCore {
+    /// Receive a send stream read from `fd`, like [`LibZfs::receive`], but without mounting
+    /// anything afterwards, whatever `options.no_mount` says. Returns the full name of the
+    /// snapshot received.
+    pub fn receive<N: AsSafeStr>(&self, name: N, fd: BorrowedFd<'_>, options: &ReceiveOptions)
+        -> Result<SafeString>
+    {
+        let begin = BeginRecord::read(fd)?;
+        let snapname = begin.snapshot_name(name)?;
+
+        let ret = lzc_call!(unsafe {
+            sys::lzc_receive_with_header(
+                snapname.as_ptr(),
+                ptr::null_mut(), // props
+                ptr::null(),     // origin
+                options.force as sys::boolean_t,
+                options.resumable as sys::boolean_t,
+                begin.is_raw() as sys::boolean_t,
+                fd.as_raw_fd(),
+                begin.buf.as_ptr() as *const sys::dmu_replay_record,
+            )
+        }, snapname);
+        if ret != 0 {
+            return Err(receive_error(ret, &snapname));
+        }
+        Ok(snapname)
+    }
+}
+
+/// Turn an errno from receiving into an error, explaining the ZFS-specific ones.
+fn receive_error(errno: i32, snapname: &SafeString) -> Error {
+    use sys::zfs_errno::*;
+    let error = |code, msg: &str| Error::Zfs(ZfsError {
+        code,
+        msg: msg.to_owned(),
+        action: format!("cannot receive {}", snapname),
+        errno: Some(errno),
+    });
+    let bad_stream = |msg| error(sys::zfs_error::EZFS_BADSTREAM, msg);
+    match errno as sys::zfs_errno::Type {
+        ZFS_ERR_FROM_IVSET_GUID_MISSING => bad_stream(
+            "the incremental source snapshot has no IV set guid, so a raw stream can't be checked \
+            against it; see OpenZFS errata 4"),
+        ZFS_ERR_FROM_IVSET_GUID_MISMATCH => bad_stream(
+            "the raw stream's incremental source is not the same encrypted snapshot as the \
+            destination's most recent one"),
+        ZFS_ERR_UNKNOWN_SEND_STREAM_FEATURE => bad_stream(
+            "the stream uses a feature this system doesn't support"),
+        ZFS_ERR_STREAM_TRUNCATED => bad_stream("the stream is truncated"),
+        ZFS_ERR_CRYPTO_NOTSUP => error(sys::zfs_error::EZFS_CRYPTOFAILED,
+            "the stream uses an unsupported encryption suite"),
+        _ => Error::Sys(io::Error::from_raw_os_error(errno)),
+    }
+}
+
+impl Dataset {
+    /// Get the state of an interrupted resumable receive into this dataset, if there is one.
+    pub fn receive_resume_token(&self) -> Result<Option<ResumeToken>> {
+        let token = self.get_native_property(sys::zfs_prop_t::ZFS_PROP_RECEIVE_RESUME_TOKEN)?;
+        if token.is_empty() || token == "-" {
+            return Ok(None);
+        }
+        self.libzfs().parse_resume_token(&token).map(Some)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::os::raw::{c_char, c_int, c_ulong};
+
+    #[link(name = "z")]
+    extern "C" {
+        fn compressBound(source_len: c_ulong) -> c_ulong;
+        fn compress(dest: *mut u8, dest_len: *mut c_ulong, source: *const u8, source_len: c_ulong)
+            -> c_int;
+    }
+
+    /// Parsing tokens needs a libzfs handle, which needs the ZFS module loaded.
+    fn libzfs() -> Option<LibZfs> {
+        match LibZfs::new() {
+            Ok(libzfs) => Some(libzfs),
+            Err(e) => {
+                eprintln!("skipping: can't open libzfs: {}", e);
+                None
+            }
+        }
+    }
+
+    fn pack(nvl: &NvList) -> Vec<u8> {
+        let mut buf: *mut c_char = ptr::null_mut();
+        let mut len = 0;
+        let ret = unsafe {
+            sys::nvlist_pack(nvl.as_ptr(), &mut buf, &mut len, sys::NV_ENCODE_XDR as c_int, 0)
+        };
+        assert_eq!(ret, 0);
+        let packed = unsafe { std::slice::from_raw_parts(buf as *const u8, len) }.to_vec();
+        unsafe { libc::free(buf.cast()) };
+        packed
+    }
+
+    fn zlib(data: &[u8]) -> Vec<u8> {
+        let mut len = unsafe { compressBound(data.len() as c_ulong) };
+        let mut buf = vec![0; len as usize];
+        let ret = unsafe {
+            compress(buf.as_mut_ptr(), &mut len, data.as_ptr(), data.len() as c_ulong)
+        };
+        assert_eq!(ret, 0);
+        buf.truncate(len as usize);
+        buf
+    }
+
+    /// The first word of the Fletcher-4 checksum ZFS puts in tokens, which ignores any bytes
+    /// past the last whole 32-bit word.
+    fn fletcher4(data: &[u8]) -> u64 {
+        data.chunks_exact(4)
+            .map(|word| u64::from(u32::from_ne_bytes(word.try_into().unwrap())))
+            .fold(0, u64::wrapping_add)
+    }
+
+    fn hex(data: &[u8]) -> String {
+        data.iter().map(|byte| format!("{:02x}", byte)).collect()
+    }
+
+    /// Make a token the way the kernel does: version 1, then the checksum of the compressed
+    /// nvlist, its length uncompressed, and the compressed nvlist itself in hex.
+    fn token(compressed: &[u8], packed_len: usize) -> String {
+        format!("1-{:x}-{:x}-{}", fletcher4(compressed), packed_len, hex(compressed))
+    }
+
+    fn packed_nvlist() -> Vec<u8> {
+        let mut nvl = NvList::new().unwrap();
+        nvl.add_uint64("object", 12).unwrap();
+        nvl.add_uint64("offset", 131072).unwrap();
+        nvl.add_uint64("bytes", 4096).unwrap();
+        nvl.add_uint64("toguid", 0x1234_5678_9abc_def0).unwrap();
+        nvl.add_string("toname", "tank/fs@snap").unwrap();
+        nvl.add_boolean("embedok").unwrap();
+        nvl.add_boolean("compressok").unwrap();
+        pack(&nvl)
+    }
+
+    fn assert_corrupt(libzfs: &LibZfs, token: &str, why: &str) {
+        match libzfs.parse_resume_token(token) {
+            Err(Error::Zfs(e)) => assert!(e.msg.contains(why), "{:?}: {}", token, e),
+            other => panic!("{:?}: expected {:?}, got {:?}", token, why, other),
+        }
+    }
+
+    #[test]
+    fn good_token() {
+        let Some(libzfs) = libzfs() else { return };
+        let packed = packed_nvlist();
+        let token = token(&zlib(&packed), packed.len());
+        assert_eq!(libzfs.parse_resume_token(&token).unwrap(), ResumeToken {
+            token: token.clone(),
+            toname: "tank/fs@snap".to_owned(),
+            toguid: 0x1234_5678_9abc_def0,
+            fromguid: None,
+            object: 12,
+            offset: 131072,
+            bytes: 4096,
+            embedok: true,
+            largeblockok: false,
+            compressok: true,
+            rawok: false,
+        });
+    }
+
+    #[test]
+    fn corrupt_header() {
+        let Some(libzfs) = libzfs() else { return };
+        let packed = packed_nvlist();
+        let good = token(&zlib(&packed), packed.len());
+        assert_corrupt(&libzfs, "", "invalid format");
+        assert_corrupt(&libzfs, "1-abc", "invalid format");
+        assert_corrupt(&libzfs, &format!("2{}", &good[1..]), "invalid version");
+    }
+
+    #[test]
+    fn corrupt_checksum() {
+        let Some(libzfs) = libzfs() else { return };
+        let packed = packed_nvlist();
+        let compressed = zlib(&packed);
+        let checksum = fletcher4(&compressed);
+
+        let wrong = format!("1-{:x}-{:x}-{}", checksum ^ 1, packed.len(), hex(&compressed));
+        assert_corrupt(&libzfs, &wrong, "incorrect checksum");
+
+        let mut flipped = compressed.clone();
+        flipped[0] ^= 1;
+        let wrong = format!("1-{:x}-{:x}-{}", checksum, packed.len(), hex(&flipped));
+        assert_corrupt(&libzfs, &wrong, "incorrect checksum");
+
+        let good = token(&compressed, packed.len());
+        assert_corrupt(&libzfs, &good[..good.len() - 8], "incorrect checksum");
+    }
+
+    #[test]
+    fn corrupt_hex() {
+        let Some(libzfs) = libzfs() else { return };
+        let packed = packed_nvlist();
+        let mut token = token(&zlib(&packed), packed.len());
+        let payload = token.rfind('-').unwrap() + 1;
+        token.replace_range(payload..payload + 2, "zz");
+        assert_corrupt(&libzfs, &token, "not hex-encoded");
+    }
+
+    #[test]
+    fn corrupt_length() {
+        let Some(libzfs) = libzfs() else { return };
+        let packed = packed_nvlist();
+        let compressed = zlib(&packed);
+        assert_corrupt(&libzfs, &token(&compressed, packed.len() - 1), "decompression failed");
+        assert_corrupt(&libzfs, &token(&compressed, packed.len() + 1), "decompression failed");
+    }
+
+    #[test]
+    fn corrupt_payload() {
+        let Some(libzfs) = libzfs() else { return };
+        let packed = packed_nvlist();
+        // Not compressed at all.
+        assert_corrupt(&libzfs, &token(&packed, packed.len()), "decompression failed");
+
+        // Compressed, but not a packed nvlist.
+        let junk = vec![0xa5; packed.len()];
+        assert_corrupt(&libzfs, &token(&zlib(&junk), junk.len()), "nvlist_unpack failed");
+        let truncated = &packed[..packed.len() / 2];
+        assert_corrupt(&libzfs, &token(&zlib(truncated), truncated.len()), "nvlist_unpack failed");
+    }
+
+    #[test]
+    fn missing_fields() {
+        let Some(libzfs) = libzfs() else { return };
+        let mut nvl = NvList::new().unwrap();
+        nvl.add_uint64("object", 12).unwrap();
+        nvl.add_uint64("offset", 0).unwrap();
+        nvl.add_uint64("toguid", 1).unwrap();
+        let packed = pack(&nvl);
+        let err = libzfs.parse_resume_token(&token(&zlib(&packed), packed.len())).unwrap_err();
+        assert!(err.to_string().contains("toname"), "{}", err);
+    }
+}
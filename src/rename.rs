@@ -0,0 +1,89 @@
+use crate::{sys, ztry, Dataset, DatasetType, Error, ListFilter, Plan, Result};
+use crate::{SafeString, ZfsError};
+
+use std::os::raw::c_int;
+
+/// Options for [`Dataset::rename`].
+#[derive(Debug, Clone, Default)]
+pub struct RenameOptions {
+    /// For a snapshot, also rename the snapshot of the same name of every descendant of its
+    /// filesystem, like `zfs rename -r`.
+    pub recursive: bool,
+    /// Unmount filesystems even if they're busy, like `zfs rename -f`.
+    pub force_unmount: bool,
+    /// Leave filesystems mounted where they are instead of unmounting and mounting them again
+    /// at their new mountpoints, like `zfs rename -u`. They move to the new mountpoints the next
+    /// time they're mounted. This avoids failing on busy filesystems, or encrypted ones whose
+    /// keys aren't loaded.
+    pub no_remount: bool,
+}
+
+impl Dataset {
+    /// Rename this dataset, like `zfs rename`, given its new full name. A filesystem or volume
+    /// can be moved anywhere in the same pool, taking its descendants and snapshots with it. A
+    /// snapshot can only be renamed within its filesystem, like `pool/fs@old` to `pool/fs@new`.
+    ///
+    /// Mounted filesystems which move are unmounted and then mounted again at their new
+    /// mountpoints, unless [`no_remount`](RenameOptions::no_remount) is set. Open the dataset
+    /// again by its new name to keep using it.
+    pub fn rename(&self, new_name: &str, options: &RenameOptions) -> Result<()> {
+        let target = SafeString::try_from(new_name)?;
+        let mut flags = sys::renameflags_t::default();
+        flags.set_recursive(options.recursive as c_int);
+        flags.set_forceunmount(options.force_unmount as c_int);
+        flags.set_nounmount(options.no_remount as c_int);
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_rename(self.handle, target.as_ptr(), flags)
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Work out what [`rename`](Self::rename) would do with the given options, without changing
+    /// anything: which datasets would be renamed to what, and which filesystems would be
+    /// unmounted to be mounted again. Snapshots which move along with their filesystem aren't
+    /// listed separately.
+    pub fn plan_rename(&self, new_name: &str, options: &RenameOptions) -> Result<Plan> {
+        let old_name = self.get_name();
+        let old_name = old_name.as_str();
+        let mut plan = Plan::default();
+        if self.get_type() == DatasetType::Snapshot {
+            let (fs_name, short) = old_name.split_once('@').unwrap_or_default();
+            let new_short = match new_name.split_once('@') {
+                Some((new_fs, new_short)) if new_fs == fs_name => new_short,
+                _ => return Err(Error::invalid_input(format!(
+                    "{:?} is not a snapshot of {}", new_name, fs_name))),
+            };
+            plan.renamed.push((self.get_name(), SafeString::try_from(new_name)?));
+            if options.recursive {
+                let libzfs = self.libzfs();
+                let fs = libzfs.dataset_by_name(fs_name, DatasetType::Filesystem.into())?;
+                for ds in fs.list(&ListFilter::default())? {
+                    if ds.name() == fs.name() {
+                        continue;
+                    }
+                    let snap = format!("{}@{}", ds.name(), short);
+                    if libzfs.dataset_by_name(&snap, DatasetType::Snapshot.into()).is_ok() {
+                        plan.renamed.push((
+                            SafeString::try_from(snap)?,
+                            SafeString::try_from(format!("{}@{}", ds.name(), new_short))?,
+                        ));
+                    }
+                }
+            }
+        } else {
+            for ds in self.list(&ListFilter::default())? {
+                let renamed = SafeString::try_from(
+                    format!("{}{}", new_name, &ds.name().as_str()[old_name.len() ..]))?;
+                if !options.no_remount
+                    && ds.get_type() == DatasetType::Filesystem
+                    && ds.is_mounted()
+                {
+                    plan.unmounted.push(ds.get_name());
+                }
+                plan.renamed.push((ds.get_name(), renamed));
+            }
+        }
+        Ok(plan)
+    }
+}
@@ -0,0 +1,187 @@
+use crate::{Dataset, DatasetType, Error, LibZfs, ReceiveOptions, Result, SafeString};
+use crate::{SendOptions, SendProgress};
+
+use std::io;
+use std::os::fd::AsFd;
+use std::thread;
+use std::time::Duration;
+
+/// One send in a [`ReplicationPlan`]: a snapshot, sent in full or incrementally.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplicationStep {
+    /// The snapshot to send (full name).
+    pub snapshot: SafeString,
+    /// The snapshot or bookmark (full name) to send it incrementally from, or `None` for a full
+    /// send.
+    pub from: Option<SafeString>,
+}
+
+impl ReplicationStep {
+    /// Options for sending this step's snapshot, which can be added to, like to make it raw.
+    pub fn send_options(&self) -> SendOptions {
+        match &self.from {
+            Some(from) => SendOptions::new().from(from.clone()),
+            None => SendOptions::new(),
+        }
+    }
+}
+
+/// The sends needed to bring a copy of a filesystem or volume up to date with its newest
+/// snapshot, from [`Dataset::plan_replication`]. Each step's stream is received into the copy in
+/// turn, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ReplicationPlan {
+    /// The filesystem or volume being copied.
+    pub source: SafeString,
+    /// The newest snapshot or bookmark the copy already has, which the first send is from, or
+    /// `None` if it has nothing in common with the source and needs a full send.
+    pub base: Option<SafeString>,
+    /// The sends to do. Empty if the copy is already up to date.
+    pub steps: Vec<ReplicationStep>,
+}
+
+impl ReplicationPlan {
+    /// Whether the copy already has the source's newest snapshot.
+    pub fn is_up_to_date(&self) -> bool {
+        self.steps.is_empty()
+    }
+}
+
+impl Dataset {
+    /// Work out the sends needed to bring a copy of this filesystem or volume up to date with
+    /// its newest snapshot, given the GUIDs of the snapshots the copy has, which are empty if it
+    /// doesn't exist yet. The sends start from the newest snapshot or bookmark here which the
+    /// copy also has (see [`common_base`](Self::common_base)).
+    ///
+    /// With `intermediates`, every snapshot after that is sent, one step each, so the copy ends
+    /// up with them all; otherwise there's only one step, straight to the newest snapshot. A
+    /// copy with no snapshots gets a full send of the first snapshot sent.
+    ///
+    /// This fails if the copy has snapshots but none in common with this dataset, since then
+    /// nothing but destroying them would let it receive a full send. If the copy has snapshots
+    /// or changes made after the base, the first receive needs
+    /// [`force`](crate::ReceiveOptions::force) to roll them back.
+    pub fn plan_replication(&self, target_guids: &[u64], intermediates: bool)
+        -> Result<ReplicationPlan>
+    {
+        if !matches!(self.get_type(), DatasetType::Filesystem | DatasetType::Volume) {
+            return Err(Error::invalid_input(
+                format!("{} is not a filesystem or volume", self.name())));
+        }
+        let snapshots = self.snapshot_infos()?;
+        let Some(newest) = snapshots.last() else {
+            return Err(Error::not_found(format!("{} has no snapshots to send", self.name())));
+        };
+        let base = self.common_base(target_guids)?;
+        if base.is_none() && !target_guids.is_empty() {
+            return Err(Error::invalid_input(format!(
+                "the copy of {} has snapshots, but none in common with it", self.name())));
+        }
+
+        let mut plan = ReplicationPlan {
+            source: self.get_name(),
+            base: base.as_ref().map(Dataset::get_name),
+            steps: vec![],
+        };
+        if base.as_ref().is_some_and(|base| base.guid() == newest.guid) {
+            return Ok(plan);
+        }
+
+        let after = base.as_ref().map(Dataset::createtxg);
+        let to_send = if intermediates {
+            snapshots.iter()
+                .filter(|snap| after.is_none_or(|txg| snap.createtxg > txg))
+                .collect::<Vec<_>>()
+        } else {
+            vec![newest]
+        };
+        let mut from = plan.base.clone();
+        for snap in to_send {
+            plan.steps.push(ReplicationStep { snapshot: snap.name.clone(), from: from.take() });
+            from = Some(snap.name.clone());
+        }
+        Ok(plan)
+    }
+}
+
+/// Options for [`LibZfs::replicate`].
+#[derive(Debug, Clone)]
+pub struct ReplicateOptions {
+    /// How to send the snapshot. Sending intermediate snapshots isn't supported; use
+    /// [`Dataset::plan_replication`] and replicate each step instead.
+    pub send: SendOptions,
+    /// How to receive it.
+    pub receive: ReceiveOptions,
+    /// How often to report progress. Defaults to a second.
+    pub progress_interval: Duration,
+}
+
+impl Default for ReplicateOptions {
+    fn default() -> Self {
+        ReplicateOptions {
+            send: SendOptions::default(),
+            receive: ReceiveOptions::default(),
+            progress_interval: Duration::from_secs(1),
+        }
+    }
+}
+
+impl LibZfs {
+    /// Copy a snapshot to another dataset on this host, like `zfs send snap | zfs receive
+    /// target`, such as to move data to another pool. `target` is named like for
+    /// [`receive`](Self::receive). While the stream is going, `progress` is called every
+    /// `options.progress_interval` with how far the send has got and the estimated total size
+    /// of the stream, if that could be worked out. Blocks until the copy is done.
+    pub fn replicate<F>(
+        &self,
+        source_snapshot: &str,
+        target: &str,
+        options: &ReplicateOptions,
+        mut progress: F,
+    ) -> Result<()>
+        where F: FnMut(SendProgress, Option<u64>),
+    {
+        if options.send.intermediates {
+            return Err(Error::invalid_input(
+                "intermediate snapshots can't be replicated in one stream"));
+        }
+        let snap = self.dataset_by_name(source_snapshot, DatasetType::Snapshot.into())?;
+        let estimated_total = snap.estimate_send_size_with_options(&options.send).ok();
+
+        let (reader, writer) = io::pipe().map_err(Error::Sys)?;
+        thread::scope(|scope| {
+            let libzfs = LibZfs { handle: self.handle.clone() };
+            let receiver = scope.spawn(move || {
+                libzfs.receive(target, reader.as_fd(), &options.receive)
+            });
+            let join = |receiver: thread::ScopedJoinHandle<'_, Result<()>>| {
+                receiver.join().unwrap_or_else(|_| {
+                    Err(Error::Sys(io::Error::other("receive thread panicked")))
+                })
+            };
+
+            // If the send can't start, the pipe is closed, so the receive fails too; its error
+            // about the stream ending early isn't the interesting one.
+            let send = match snap.send_with_options(&options.send, writer.into()) {
+                Ok(send) => send,
+                Err(e) => {
+                    let _ = join(receiver);
+                    return Err(e);
+                }
+            };
+            while !send.is_finished() {
+                if let Ok(p) = send.progress() {
+                    progress(p, estimated_total);
+                }
+                thread::sleep(options.progress_interval);
+            }
+            let sent = send.wait();
+            // If the receive fails, the send fails too, writing to a closed pipe, so the
+            // receive's error is the interesting one.
+            join(receiver)?;
+            sent
+        })
+    }
+}
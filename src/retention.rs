@@ -0,0 +1,323 @@
+use crate::{Dataset, DatasetType, Error, LibZfsCore, Result, SafeString};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Which of a filesystem's or volume's snapshots to keep, like the retention settings of a
+/// backup tool, for [`Dataset::prune_snapshots`]. Everything the policy doesn't keep is
+/// destroyed, so the default policy, which keeps nothing, destroys every snapshot it covers.
+///
+/// A snapshot is kept if any rule keeps it. The hourly, daily, and weekly rules keep the newest
+/// snapshot in each of that many of the most recent hours, days, or weeks which have any. Periods
+/// are in UTC, and weeks start on Monday.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetentionPolicy {
+    /// Keep this many of the newest snapshots, whenever they were taken.
+    pub latest: usize,
+    /// Keep the newest snapshot from each of this many hours.
+    pub hourly: usize,
+    /// Keep the newest snapshot from each of this many days.
+    pub daily: usize,
+    /// Keep the newest snapshot from each of this many weeks.
+    pub weekly: usize,
+    /// Keep every snapshot younger than this.
+    pub min_age: Duration,
+    /// Only look at snapshots whose names (after the `@`) start with one of these, like `auto-`,
+    /// leaving others alone. If empty, the policy covers every snapshot.
+    pub prefixes: Vec<String>,
+}
+
+/// What a [`RetentionPolicy`] does with a filesystem's or volume's snapshots, from
+/// [`Dataset::plan_prune`] or [`Dataset::prune_snapshots`]. Snapshots the policy doesn't cover
+/// are in none of the lists. Each list is newest first.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct RetentionPlan {
+    /// Snapshots the policy keeps.
+    pub keep: Vec<SafeString>,
+    /// Snapshots to be destroyed.
+    pub destroy: Vec<SafeString>,
+    /// Snapshots the policy would destroy, but which are kept because they have user holds.
+    pub held: Vec<SafeString>,
+    /// Snapshots the policy would destroy, but which are kept because they have clones, which
+    /// depend on them.
+    pub has_dependents: Vec<SafeString>,
+}
+
+impl RetentionPolicy {
+    /// Whether the policy covers a snapshot, given its full or short name.
+    pub fn covers(&self, name: &str) -> bool {
+        let short = name.rsplit_once('@').map_or(name, |(_, short)| short);
+        self.prefixes.is_empty() || self.prefixes.iter().any(|prefix| short.starts_with(prefix))
+    }
+
+    /// Sort snapshots, given as names and creation times, into those to keep and those to
+    /// destroy, as of `now`, without looking at holds or touching any pool. Snapshots the
+    /// policy doesn't cover are left out.
+    pub fn select(&self, snapshots: &[(SafeString, SystemTime)], now: SystemTime)
+        -> RetentionPlan
+    {
+        let mut snapshots = snapshots.iter()
+            .filter(|(name, _)| self.covers(name.as_str()))
+            .collect::<Vec<_>>();
+        snapshots.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| b.0.cmp(&a.0)));
+
+        let mut keep = vec![false; snapshots.len()];
+        for (i, (_, time)) in snapshots.iter().enumerate() {
+            let age = now.duration_since(*time).unwrap_or_default();
+            if i < self.latest || age < self.min_age {
+                keep[i] = true;
+            }
+        }
+        // Each period's length and offset in seconds. 1970-01-01 was a Thursday, so weeks are
+        // offset by three days to start on Monday.
+        let periods = [
+            (self.hourly, 3600, 0),
+            (self.daily, 86400, 0),
+            (self.weekly, 7 * 86400, 3 * 86400),
+        ];
+        for (count, length, offset) in periods {
+            let mut last = None;
+            let mut kept = 0;
+            for (i, (_, time)) in snapshots.iter().enumerate() {
+                if kept == count {
+                    break;
+                }
+                let secs = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+                let bucket = (secs + offset) / length;
+                if last != Some(bucket) {
+                    last = Some(bucket);
+                    keep[i] = true;
+                    kept += 1;
+                }
+            }
+        }
+
+        let mut plan = RetentionPlan::default();
+        for ((name, _), keep) in snapshots.into_iter().zip(keep) {
+            if keep {
+                plan.keep.push(name.clone());
+            } else {
+                plan.destroy.push(name.clone());
+            }
+        }
+        plan
+    }
+}
+
+impl Dataset {
+    /// Work out what [`prune_snapshots`](Self::prune_snapshots) would do with this filesystem's
+    /// or volume's snapshots under the given policy, without destroying anything.
+    pub fn plan_prune(&self, policy: &RetentionPolicy) -> Result<RetentionPlan> {
+        if !matches!(self.get_type(), DatasetType::Filesystem | DatasetType::Volume) {
+            return Err(Error::invalid_input(
+                format!("{} is not a filesystem or volume", self.name())));
+        }
+        let snapshots = self.get_snapshots_ordered()?;
+        let created = snapshots.iter()
+            .map(|snap| (snap.get_name(), snap.creation()))
+            .collect::<Vec<_>>();
+        let mut plan = policy.select(&created, SystemTime::now());
+
+        if !plan.destroy.is_empty() {
+            let core = LibZfsCore::new()?;
+            let mut destroy = vec![];
+            for name in plan.destroy {
+                let cloned = match snapshots.iter().find(|snap| snap.get_name() == name) {
+                    Some(snap) => !snap.clones()?.is_empty(),
+                    None => false,
+                };
+                if !core.holds(&name)?.is_empty() {
+                    plan.held.push(name);
+                } else if cloned {
+                    plan.has_dependents.push(name);
+                } else {
+                    destroy.push(name);
+                }
+            }
+            plan.destroy = destroy;
+        }
+        Ok(plan)
+    }
+
+    /// Destroy the snapshots of this filesystem or volume which the given policy doesn't keep,
+    /// leaving alone any with user holds or clones. Returns what was done.
+    ///
+    /// The snapshots are destroyed in one batch, so if any can't be, like because a hold was
+    /// placed on it or it was cloned since it was checked, none are.
+    pub fn prune_snapshots(&self, policy: &RetentionPolicy) -> Result<RetentionPlan> {
+        let plan = self.plan_prune(policy)?;
+        if plan.destroy.is_empty() {
+            return Ok(plan);
+        }
+        let libzfs = self.libzfs();
+        libzfs.destroy_snapshots(plan.destroy.iter())?;
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Wednesday, 2024-05-01T12:00:00Z
+    const NOW: u64 = 1_714_564_800;
+    const HOUR: u64 = 3600;
+    const DAY: u64 = 24 * HOUR;
+    /// Monday, 2024-04-29T00:00:00Z
+    const MONDAY: u64 = NOW - 2 * DAY - 12 * HOUR;
+
+    fn snaps(list: &[(&str, u64)]) -> Vec<(SafeString, SystemTime)> {
+        list.iter()
+            .map(|&(name, secs)| {
+                (SafeString::try_from(name).unwrap(), UNIX_EPOCH + Duration::from_secs(secs))
+            })
+            .collect()
+    }
+
+    fn names(list: &[SafeString]) -> Vec<&str> {
+        list.iter().map(|name| name.as_str()).collect()
+    }
+
+    fn select(policy: &RetentionPolicy, list: &[(&str, u64)]) -> RetentionPlan {
+        policy.select(&snaps(list), UNIX_EPOCH + Duration::from_secs(NOW))
+    }
+
+    #[test]
+    fn default_keeps_nothing() {
+        let plan = select(&RetentionPolicy::default(), &[("t@a", NOW - HOUR), ("t@b", NOW)]);
+        assert!(plan.keep.is_empty());
+        assert_eq!(names(&plan.destroy), ["t@b", "t@a"]);
+        assert!(plan.held.is_empty());
+        assert!(plan.has_dependents.is_empty());
+    }
+
+    #[test]
+    fn latest() {
+        let policy = RetentionPolicy { latest: 2, ..RetentionPolicy::default() };
+        let plan = select(&policy, &[("t@a", NOW - 3 * DAY), ("t@c", NOW), ("t@b", NOW - DAY)]);
+        assert_eq!(names(&plan.keep), ["t@c", "t@b"]);
+        assert_eq!(names(&plan.destroy), ["t@a"]);
+    }
+
+    #[test]
+    fn hourly() {
+        let list = [
+            ("t@1200", NOW),
+            ("t@1150", NOW - 10 * 60),
+            ("t@1110", NOW - 50 * 60),
+            ("t@1030", NOW - 90 * 60),
+            ("t@0900", NOW - 3 * HOUR),
+        ];
+        let policy = RetentionPolicy { hourly: 3, ..RetentionPolicy::default() };
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@1200", "t@1150", "t@1030"]);
+        assert_eq!(names(&plan.destroy), ["t@1110", "t@0900"]);
+    }
+
+    #[test]
+    fn daily() {
+        let list = [
+            ("t@today-late", NOW - HOUR),
+            ("t@today-early", NOW - 11 * HOUR),
+            ("t@yesterday-late", NOW - 13 * HOUR),
+            ("t@yesterday-early", NOW - DAY),
+            ("t@long-ago", NOW - 10 * DAY),
+        ];
+        let policy = RetentionPolicy { daily: 2, ..RetentionPolicy::default() };
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@today-late", "t@yesterday-late"]);
+        assert_eq!(names(&plan.destroy), ["t@today-early", "t@yesterday-early", "t@long-ago"]);
+
+        // Days with no snapshots don't count.
+        let policy = RetentionPolicy { daily: 3, ..RetentionPolicy::default() };
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@today-late", "t@yesterday-late", "t@long-ago"]);
+    }
+
+    #[test]
+    fn weekly_starts_on_monday() {
+        let list = [
+            ("t@wed", NOW),
+            ("t@mon", MONDAY),
+            ("t@sun-late", MONDAY - 1),
+            ("t@sun", MONDAY - 12 * HOUR),
+            ("t@mon-before", MONDAY - 7 * DAY),
+            ("t@sun-before", MONDAY - 7 * DAY - 1),
+        ];
+        let policy = RetentionPolicy { weekly: 3, ..RetentionPolicy::default() };
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@wed", "t@sun-late", "t@sun-before"]);
+        assert_eq!(names(&plan.destroy), ["t@mon", "t@sun", "t@mon-before"]);
+    }
+
+    #[test]
+    fn min_age() {
+        let policy = RetentionPolicy {
+            min_age: Duration::from_secs(HOUR),
+            ..RetentionPolicy::default()
+        };
+        let list = [
+            ("t@future", NOW + HOUR),
+            ("t@new", NOW - 10 * 60),
+            ("t@edge", NOW - HOUR),
+            ("t@old", NOW - 2 * HOUR),
+        ];
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@future", "t@new"]);
+        assert_eq!(names(&plan.destroy), ["t@edge", "t@old"]);
+    }
+
+    #[test]
+    fn rules_overlap() {
+        // The newest snapshot is both the latest one and the newest of its hour and day, so
+        // it only counts once, and the rules together keep fewer than they would apart.
+        let policy = RetentionPolicy {
+            latest: 1,
+            hourly: 1,
+            daily: 2,
+            ..RetentionPolicy::default()
+        };
+        let list = [
+            ("t@a", NOW),
+            ("t@b", NOW - HOUR),
+            ("t@c", NOW - DAY),
+            ("t@d", NOW - DAY - HOUR),
+        ];
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@a", "t@c"]);
+        assert_eq!(names(&plan.destroy), ["t@b", "t@d"]);
+    }
+
+    #[test]
+    fn ties_break_by_name() {
+        let list = [("t@a", NOW), ("t@c", NOW), ("t@b", NOW)];
+        let policy = RetentionPolicy { latest: 1, ..RetentionPolicy::default() };
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@c"]);
+        assert_eq!(names(&plan.destroy), ["t@b", "t@a"]);
+
+        let policy = RetentionPolicy { hourly: 1, ..RetentionPolicy::default() };
+        assert_eq!(select(&policy, &list), plan);
+    }
+
+    #[test]
+    fn prefixes() {
+        let policy = RetentionPolicy {
+            latest: 1,
+            prefixes: vec!["auto-".to_owned(), "hourly-".to_owned()],
+            ..RetentionPolicy::default()
+        };
+        let list = [
+            ("t@manual", NOW),
+            ("t@auto-2", NOW - HOUR),
+            ("t@hourly-1", NOW - 2 * HOUR),
+            ("t@auto", NOW - 3 * HOUR),
+        ];
+        let plan = select(&policy, &list);
+        assert_eq!(names(&plan.keep), ["t@auto-2"]);
+        assert_eq!(names(&plan.destroy), ["t@hourly-1"]);
+        assert!(policy.covers("hourly-1"));
+        assert!(!policy.covers("t@manual"));
+    }
+}
@@ -0,0 +1,55 @@
+use crate::Result;
+
+use std::thread;
+use std::time::Duration;
+
+/// How to retry operations which fail because something is briefly busy, like unmounting or
+/// destroying a filesystem right after a receive, while something still has it open.
+///
+/// Nothing retries on its own; wrap an operation in [`run`](Self::run) to opt in, like
+/// `RetryPolicy::default().run(|| ds.unmount())`.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    /// How many times to try in all, including the first. Zero is treated as one.
+    pub attempts: u32,
+    /// How long to wait after the first failure.
+    pub initial_backoff: Duration,
+    /// Each wait is this many times as long as the one before.
+    pub multiplier: u32,
+    /// The longest to wait between tries.
+    pub max_backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    /// Five tries, waiting 100ms, 200ms, 400ms, then 800ms in between.
+    fn default() -> Self {
+        RetryPolicy {
+            attempts: 5,
+            initial_backoff: Duration::from_millis(100),
+            multiplier: 2,
+            max_backoff: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Run the operation, trying it again after a backoff each time it fails with a busy error
+    /// (see [`Error::is_busy`](crate::Error::is_busy)), until it succeeds, fails some other way,
+    /// or runs out of attempts. Returns the last result.
+    pub fn run<T, F>(&self, mut op: F) -> Result<T>
+        where F: FnMut() -> Result<T>,
+    {
+        let mut backoff = self.initial_backoff;
+        let mut tries = 1;
+        loop {
+            match op() {
+                Err(e) if e.is_busy() && tries < self.attempts => {
+                    thread::sleep(backoff);
+                    backoff = backoff.saturating_mul(self.multiplier).min(self.max_backoff);
+                    tries += 1;
+                }
+                result => return result,
+            }
+        }
+    }
+}
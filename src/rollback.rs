@@ -0,0 +1,82 @@
+use crate::{sys, ztry, Dataset, DatasetType, DestroyOptions, Error, Plan, Result};
+use crate::ZfsError;
+
+/// Options for [`Dataset::rollback`].
+#[derive(Debug, Clone, Default)]
+pub struct RollbackOptions {
+    /// Destroy any snapshots and bookmarks newer than the one being rolled back to, like
+    /// `zfs rollback -r`. Otherwise, if there are any, the rollback fails.
+    pub destroy_newer: bool,
+    /// Also destroy clones of those newer snapshots, and everything under them, like
+    /// `zfs rollback -R`. Implies `destroy_newer`.
+    pub destroy_clones: bool,
+    /// Unmount the filesystem even if it's busy, like `zfs rollback -f`.
+    pub force_unmount: bool,
+}
+
+impl Dataset {
+    /// Roll this snapshot's filesystem or volume back to it, like `zfs rollback`, discarding
+    /// every change made since it was taken. A mounted filesystem is unmounted and mounted
+    /// again around the rollback.
+    pub fn rollback(&self, options: &RollbackOptions) -> Result<()> {
+        let (fs, _) = self.rollback_targets(options)?;
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_rollback(fs.handle, self.handle, options.force_unmount as sys::boolean_t)
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Work out what [`rollback`](Self::rollback) would do with the given options, without
+    /// changing anything: which filesystem would be rolled back, which newer snapshots,
+    /// bookmarks, and clones would be destroyed, and which filesystems would be unmounted.
+    /// Fails the same way it would if it can't be done.
+    pub fn plan_rollback(&self, options: &RollbackOptions) -> Result<Plan> {
+        let (fs, targets) = self.rollback_targets(options)?;
+        let mut plan = Plan::default();
+        plan.destroy(&targets);
+        // Whatever isn't a newer snapshot or bookmark of the filesystem itself is a clone of
+        // one, or under a clone.
+        plan.clones_destroyed = targets.iter()
+            .filter(|ds| {
+                let (name, fs_name) = (ds.name(), fs.name());
+                let rest = name.as_str().strip_prefix(fs_name.as_str());
+                !rest.is_some_and(|rest| rest.starts_with(['@', '#']))
+            })
+            .map(Dataset::get_name)
+            .collect();
+        if fs.get_type() == DatasetType::Filesystem && fs.is_mounted() {
+            plan.unmounted.push(fs.get_name());
+        }
+        plan.rolled_back.push(fs.get_name());
+        Ok(plan)
+    }
+
+    /// The filesystem or volume rolling back to this snapshot would roll back, and everything
+    /// it would destroy, in an order in which it can be.
+    fn rollback_targets(&self, options: &RollbackOptions) -> Result<(Dataset, Vec<Dataset>)> {
+        if self.get_type() != DatasetType::Snapshot {
+            return Err(Error::invalid_input(format!("{} is not a snapshot", self.name())));
+        }
+        let name = self.get_name();
+        let (fs_name, _) = name.as_str().split_once('@').unwrap_or_default();
+        let libzfs = self.libzfs();
+        let fs = libzfs.dataset_by_name(fs_name, DatasetType::Filesystem | DatasetType::Volume)?;
+
+        let txg = self.createtxg();
+        let newer = fs.get_snapshots_ordered()?.into_iter()
+            .chain(fs.bookmarks()?)
+            .filter(|ds| ds.createtxg() > txg);
+        let mut targets = vec![];
+        for ds in newer {
+            if !(options.destroy_newer || options.destroy_clones) {
+                return Err(Error::busy(format!(
+                    "{} is not the most recent snapshot of {}: {} is newer",
+                    self.name(), fs_name, ds.name())));
+            }
+            let clones = DestroyOptions { clones: options.destroy_clones, dry_run: false };
+            targets.extend(ds.destroy_targets(&clones)?);
+        }
+        Ok((fs, targets))
+    }
+}
@@ -0,0 +1,209 @@
+use crate::{sys, ztry, Error, NvListRef, Result, ZfsError, ZPool};
+use crate::vdev::vdev_tree;
+#[cfg(not(zfs_2_2))]
+use crate::compat;
+#[cfg(zfs_2_2)]
+use sys::pool_scan_func_t::POOL_SCAN_ERRORSCRUB;
+use sys::pool_scan_func_t::{POOL_SCAN_NONE, POOL_SCAN_SCRUB};
+use sys::pool_scrub_cmd_t::{POOL_SCRUB_NORMAL, POOL_SCRUB_PAUSE};
+
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The kind of scan a pool is doing or last did.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScanFunction {
+    Scrub,
+    Resilver,
+    /// A scrub of only the blocks in the pool's error log, from [`ZPool::error_scrub`].
+    ErrorScrub,
+}
+
+/// Where a pool's scan is at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum ScanState {
+    /// In progress, though possibly paused; see [`ScanStatus::paused`].
+    Scanning,
+    Finished,
+    Canceled,
+}
+
+/// The progress of a pool's current or most recent scrub or resilver, like the `scan:` section
+/// of `zpool status`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ScanStatus {
+    pub function: ScanFunction,
+    pub state: ScanState,
+    /// Whether the scan is paused, for a scrub paused with [`ZPool::pause_scrub`].
+    pub paused: bool,
+    pub start_time: SystemTime,
+    /// When the scan finished or was canceled, if it has.
+    pub end_time: Option<SystemTime>,
+    /// When the current pass started. A pass starts at the beginning of the scan, and again
+    /// each time it is resumed or the pool is imported.
+    pub pass_start: SystemTime,
+    /// How much data is to be scanned in total, in bytes.
+    pub to_examine: u64,
+    /// How much data has been read through, in bytes. This runs ahead of `issued`, since blocks
+    /// are gathered up and sorted before being verified.
+    pub examined: u64,
+    /// How much data has actually been verified, in bytes.
+    pub issued: u64,
+    /// How much data was repaired, in bytes. Always 0 for an error scrub.
+    pub repaired: u64,
+    /// How many errors were found which couldn't be repaired. Always 0 for an error scrub,
+    /// whose results go straight to the pool's error log.
+    pub errors: u64,
+}
+
+impl ScanStatus {
+    fn from_sys(pss: &sys::pool_scan_stat_t) -> Option<Self> {
+        use sys::dsl_scan_state_t::*;
+        use sys::pool_scan_func_t::*;
+        let time = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+        let function = match pss.pss_func as sys::pool_scan_func_t::Type {
+            POOL_SCAN_SCRUB => ScanFunction::Scrub,
+            POOL_SCAN_RESILVER => ScanFunction::Resilver,
+            _ => return None,
+        };
+        let state = match pss.pss_state as sys::dsl_scan_state_t::Type {
+            DSS_SCANNING => ScanState::Scanning,
+            DSS_FINISHED => ScanState::Finished,
+            DSS_CANCELED => ScanState::Canceled,
+            _ => return None,
+        };
+        Some(ScanStatus {
+            function,
+            state,
+            paused: state == ScanState::Scanning && pss.pss_pass_scrub_pause != 0,
+            start_time: time(pss.pss_start_time),
+            end_time: (state != ScanState::Scanning).then(|| time(pss.pss_end_time)),
+            pass_start: time(pss.pss_pass_start),
+            to_examine: pss.pss_to_examine,
+            examined: pss.pss_examined,
+            issued: pss.pss_issued,
+            repaired: pss.pss_processed,
+            errors: pss.pss_errors,
+        })
+    }
+
+    #[cfg(zfs_2_2)]
+    fn from_sys_error_scrub(pss: &sys::pool_scan_stat_t) -> Option<Self> {
+        use sys::dsl_scan_state_t::*;
+        let time = |secs: u64| UNIX_EPOCH + Duration::from_secs(secs);
+        if pss.pss_error_scrub_func != POOL_SCAN_ERRORSCRUB as u64 {
+            return None;
+        }
+        let state = match pss.pss_error_scrub_state as sys::dsl_scan_state_t::Type {
+            DSS_ERRORSCRUBBING => ScanState::Scanning,
+            DSS_FINISHED => ScanState::Finished,
+            DSS_CANCELED => ScanState::Canceled,
+            _ => return None,
+        };
+        Some(ScanStatus {
+            function: ScanFunction::ErrorScrub,
+            state,
+            paused: state == ScanState::Scanning && pss.pss_pass_error_scrub_pause != 0,
+            start_time: time(pss.pss_error_scrub_start),
+            end_time: (state != ScanState::Scanning).then(|| time(pss.pss_error_scrub_end)),
+            // Error scrubs are done in one pass.
+            pass_start: time(pss.pss_error_scrub_start),
+            to_examine: pss.pss_error_scrub_to_be_examined,
+            examined: pss.pss_error_scrub_examined,
+            issued: pss.pss_error_scrub_examined,
+            repaired: 0,
+            errors: 0,
+        })
+    }
+
+    /// Error scrubs are new in OpenZFS 2.2.
+    #[cfg(not(zfs_2_2))]
+    fn from_sys_error_scrub(_pss: &sys::pool_scan_stat_t) -> Option<Self> {
+        None
+    }
+}
+
+impl ZPool {
+    /// Start a scrub of the pool, or resume a paused one.
+    pub fn scrub(&self) -> Result<()> {
+        self.scan_cmd(POOL_SCAN_SCRUB, POOL_SCRUB_NORMAL)
+    }
+
+    /// Start an error scrub, like `zpool scrub -e`, or resume a paused one. Instead of the whole
+    /// pool, this only reads the blocks in the pool's error log (as listed by
+    /// `zpool status -v`), and removes those which turn out to be fine. It runs alongside a
+    /// regular scrub, rather than replacing it. Needs OpenZFS 2.2.
+    pub fn error_scrub(&self) -> Result<()> {
+        #[cfg(zfs_2_2)]
+        return self.scan_cmd(POOL_SCAN_ERRORSCRUB, POOL_SCRUB_NORMAL);
+        #[cfg(not(zfs_2_2))]
+        return Err(compat::unsupported("error scrubs", "2.2"));
+    }
+
+    /// Pause an in-progress error scrub, like `zpool scrub -e -p`.
+    pub fn pause_error_scrub(&self) -> Result<()> {
+        #[cfg(zfs_2_2)]
+        return self.scan_cmd(POOL_SCAN_ERRORSCRUB, POOL_SCRUB_PAUSE);
+        #[cfg(not(zfs_2_2))]
+        return Err(compat::unsupported("error scrubs", "2.2"));
+    }
+
+    /// Stop an in-progress scrub or error scrub.
+    pub fn cancel_scrub(&self) -> Result<()> {
+        self.scan_cmd(POOL_SCAN_NONE, POOL_SCRUB_NORMAL)
+    }
+
+    /// Pause an in-progress scrub, like `zpool scrub -p`. It keeps its place, and carries on
+    /// from there when resumed with [`resume_scrub`](Self::resume_scrub), even across exports.
+    pub fn pause_scrub(&self) -> Result<()> {
+        self.scan_cmd(POOL_SCAN_SCRUB, POOL_SCRUB_PAUSE)
+    }
+
+    /// Resume a paused scrub. Fails if there is no paused scrub, rather than starting a new one.
+    pub fn resume_scrub(&self) -> Result<()> {
+        let paused = self.scan_status()?.is_some_and(|status| status.paused);
+        if !paused {
+            return Err(Error::invalid_input("there is no paused scrub to resume"));
+        }
+        self.scrub()
+    }
+
+    /// Get the progress of the current or most recent scrub or resilver, if the pool has ever
+    /// had one. Error scrubs are tracked separately; see
+    /// [`error_scrub_status`](Self::error_scrub_status).
+    pub fn scan_status(&self) -> Result<Option<ScanStatus>> {
+        let config = self.get_config()?;
+        self.scan_status_in(&config)
+    }
+
+    /// Get the progress of the current or most recent scan from an already fetched config.
+    pub(crate) fn scan_status_in(&self, config: &NvListRef) -> Result<Option<ScanStatus>> {
+        Ok(scan_stats_in(config)?.as_ref().and_then(ScanStatus::from_sys))
+    }
+
+    /// Get the progress of the current or most recent error scrub, if the pool has ever had one.
+    pub fn error_scrub_status(&self) -> Result<Option<ScanStatus>> {
+        Ok(self.scan_stats()?.as_ref().and_then(ScanStatus::from_sys_error_scrub))
+    }
+
+    fn scan_cmd(&self, func: sys::pool_scan_func_t::Type, cmd: sys::pool_scrub_cmd_t::Type)
+        -> Result<()>
+    {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe { sys::zpool_scan(self.handle, func, cmd) }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Get the raw stats of the current or most recent scan (scrub or resilver), if the pool has
+    /// ever had one.
+    pub(crate) fn scan_stats(&self) -> Result<Option<sys::pool_scan_stat_t>> {
+        let config = self.get_config()?;
+        scan_stats_in(&config)
+    }
+}
+
+fn scan_stats_in(config: &NvListRef) -> Result<Option<sys::pool_scan_stat_t>> {
+    Ok(vdev_tree(config)?.lookup_struct("scan_stats"))
+}
@@ -0,0 +1,190 @@
+use crate::{Dataset, DatasetType, Error, Result, SafeString, SendProgress, ZfsSend};
+use crate::ZfsSendFlags;
+
+use std::collections::HashSet;
+use std::io::{self, Write};
+use std::os::fd::OwnedFd;
+use std::thread;
+use std::time::Duration;
+
+/// How to send a snapshot, for [`Dataset::send_with_options`]. By default, a full stream with
+/// blocks decompressed and without large blocks or embedded data, like a plain `zfs send`.
+#[derive(Debug, Clone, Default)]
+pub struct SendOptions {
+    from: Option<SafeString>,
+    pub(crate) intermediates: bool,
+    redaction_bookmark: Option<SafeString>,
+    embed_data: bool,
+    large_blocks: bool,
+    compressed: bool,
+    raw: bool,
+    saved: bool,
+}
+
+impl SendOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Send incrementally from this snapshot or bookmark (full name), like `zfs send -i`.
+    pub fn from(mut self, from: SafeString) -> Self {
+        self.from = Some(from);
+        self
+    }
+
+    /// Also send every snapshot between the `from` snapshot and this one, like `zfs send -I`.
+    /// See [`Dataset::send_intermediates`].
+    pub fn intermediates(mut self, intermediates: bool) -> Self {
+        self.intermediates = intermediates;
+        self
+    }
+
+    /// Leave out blocks redacted by this redaction bookmark (full name), like
+    /// `zfs send --redact`. See [`Dataset::send_redacted`].
+    pub fn redact(mut self, bookmark: SafeString) -> Self {
+        self.redaction_bookmark = Some(bookmark);
+        self
+    }
+
+    /// Send blocks which are embedded in block pointers as they are, like `zfs send -e`. The
+    /// receiving pool needs the `embedded_data` feature.
+    pub fn embed_data(mut self, embed_data: bool) -> Self {
+        self.embed_data = embed_data;
+        self
+    }
+
+    /// Send blocks larger than 128 KiB as they are, like `zfs send -L`. The receiving pool
+    /// needs the `large_blocks` feature.
+    pub fn large_blocks(mut self, large_blocks: bool) -> Self {
+        self.large_blocks = large_blocks;
+        self
+    }
+
+    /// Send compressed blocks as they are on disk, like `zfs send -c`.
+    pub fn compressed(mut self, compressed: bool) -> Self {
+        self.compressed = compressed;
+        self
+    }
+
+    /// Send encrypted blocks as they are on disk, like `zfs send -w`, so the key needn't be
+    /// loaded. Implies embedded data, large blocks, and compression.
+    pub fn raw(mut self, raw: bool) -> Self {
+        self.raw = raw;
+        self
+    }
+
+    /// Send the partially received state of an interrupted resumable receive, like
+    /// `zfs send -S`. Can't be incremental or redacted.
+    pub fn saved(mut self, saved: bool) -> Self {
+        self.saved = saved;
+        self
+    }
+
+    /// Get the libzfs_core flags for these options, checking that they make sense together.
+    pub fn flags(&self) -> Result<ZfsSendFlags> {
+        if self.intermediates && self.from.is_none() {
+            return Err(Error::invalid_input("intermediate snapshots need a from snapshot"));
+        }
+        if self.intermediates && self.redaction_bookmark.is_some() {
+            return Err(Error::invalid_input("can't send intermediate snapshots redacted"));
+        }
+        if self.saved && (self.from.is_some() || self.redaction_bookmark.is_some()) {
+            return Err(Error::invalid_input("a saved send can't be incremental or redacted"));
+        }
+
+        let mut flags = ZfsSendFlags(0);
+        if self.embed_data || self.raw {
+            flags |= ZfsSendFlags::LZC_SEND_FLAG_EMBED_DATA;
+        }
+        if self.large_blocks || self.raw {
+            flags |= ZfsSendFlags::LZC_SEND_FLAG_LARGE_BLOCK;
+        }
+        if self.compressed || self.raw {
+            flags |= ZfsSendFlags::LZC_SEND_FLAG_COMPRESS;
+        }
+        if self.raw {
+            flags |= ZfsSendFlags::LZC_SEND_FLAG_RAW;
+        }
+        if self.saved {
+            flags |= ZfsSendFlags::LZC_SEND_FLAG_SAVED;
+        }
+        Ok(flags)
+    }
+}
+
+impl Dataset {
+    /// Send this snapshot as a stream written to `fd`, as described by `options`.
+    pub fn send_with_options(&self, options: &SendOptions, fd: OwnedFd) -> Result<ZfsSend<'_>> {
+        let flags = options.flags()?;
+        match (&options.from, &options.redaction_bookmark) {
+            (Some(from), _) if options.intermediates => {
+                self.send_intermediates(from.clone(), fd, flags)
+            }
+            (from, Some(bookmark)) => self.send_redacted(from.clone(), bookmark.clone(), fd, flags),
+            (from, None) => self.send(from.clone(), fd, flags),
+        }
+    }
+
+    /// Send this snapshot as described by `options`, writing the stream to `writer`. Blocks
+    /// until the send is done.
+    pub fn send_to_writer<W: Write>(&self, options: &SendOptions, mut writer: W) -> Result<()> {
+        let (mut reader, pipe_writer) = io::pipe().map_err(Error::Sys)?;
+        let send = self.send_with_options(options, pipe_writer.into())?;
+        let copied = io::copy(&mut reader, &mut writer).and_then(|_| writer.flush());
+        // If copying failed, closing the pipe makes the send fail too, instead of blocking.
+        drop(reader);
+        send.wait()?;
+        copied.map_err(Error::Sys)
+    }
+
+    /// Send this snapshot as described by `options`, writing the stream to `fd`, and call
+    /// `progress` every `interval` with how far it has got and the estimated total size of the
+    /// stream, if that could be worked out. Blocks until the send is done.
+    pub fn send_with_progress<F>(
+        &self,
+        options: &SendOptions,
+        fd: OwnedFd,
+        interval: Duration,
+        mut progress: F,
+    ) -> Result<()>
+        where F: FnMut(SendProgress, Option<u64>),
+    {
+        let estimated_total = self.estimate_send_size_with_options(options).ok();
+        let send = self.send_with_options(options, fd)?;
+        while !send.is_finished() {
+            // Progress can't be read while the send is starting or finishing, or while sending
+            // the intermediate snapshots of an incremental send; just skip those.
+            if let Ok(p) = send.progress() {
+                progress(p, estimated_total);
+            }
+            thread::sleep(interval);
+        }
+        send.wait()
+    }
+
+    /// Estimate how many bytes sending this snapshot as described by `options` would write. See
+    /// [`estimate_send_size`](Self::estimate_send_size).
+    pub fn estimate_send_size_with_options(&self, options: &SendOptions) -> Result<u64> {
+        self.estimate_send_size(options.from.as_deref(), options.flags()?)
+    }
+
+    /// Find the newest snapshot or bookmark of this filesystem or volume which the other side
+    /// also has, given the GUIDs of the other side's snapshots (like from
+    /// [`snapshot_infos`](Self::snapshot_infos) or the `guid` property), to use as the base of
+    /// an incremental send with [`SendOptions::from`]. A snapshot and a bookmark made from it
+    /// have the same GUID; if both are here, the snapshot is returned. Returns `None` if there's
+    /// nothing in common, in which case only a full send will do.
+    pub fn common_base(&self, guids: &[u64]) -> Result<Option<Dataset>> {
+        if !matches!(self.get_type(), DatasetType::Filesystem | DatasetType::Volume) {
+            return Err(Error::invalid_input(
+                format!("{} is not a filesystem or volume", self.name())));
+        }
+        let guids = guids.iter().copied().collect::<HashSet<_>>();
+        let mut candidates = self.get_snapshots()?;
+        candidates.extend(self.bookmarks()?);
+        // The newest, preferring a snapshot to a bookmark made from it.
+        Ok(candidates.into_iter()
+            .filter(|ds| guids.contains(&ds.guid()))
+            .max_by_key(|ds| (ds.createtxg(), ds.get_type() == DatasetType::Snapshot)))
+    }
+}
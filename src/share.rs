@@ -0,0 +1,68 @@
+use crate::{compat, sys, ztry, Dataset, DatasetType, Error, Result, SafeString, ZfsError};
+
+/// A filesystem which couldn't be shared or unshared, and why.
+#[derive(Debug)]
+pub struct ShareError {
+    pub name: SafeString,
+    pub error: Error,
+}
+
+impl Dataset {
+    /// Share this filesystem over NFS and/or SMB, according to its `sharenfs` and `sharesmb`
+    /// properties, like `zfs share`. It must be mounted; protocols whose property is `off` are
+    /// skipped.
+    pub fn share(&self) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe { compat::zfs_share(self.handle) }, self.libzfs.raw(), self.name());
+        unsafe { compat::zfs_commit_shares() };
+        Ok(())
+    }
+
+    /// Stop sharing this filesystem over any protocol, like `zfs unshare`.
+    pub fn unshare(&self) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe { compat::zfs_unshare(self.handle) }, self.libzfs.raw(), self.name());
+        unsafe { compat::zfs_commit_shares() };
+        Ok(())
+    }
+
+    /// Share this filesystem and all mounted filesystems under it, according to their
+    /// properties, like `zfs share -a` limited to this subtree. Carries on past filesystems which
+    /// can't be shared, and returns them along with the reason; an empty list means all went
+    /// well.
+    pub fn share_all(&self) -> Result<Vec<ShareError>> {
+        self.foreach_share(|ds| {
+            ztry!(unsafe { compat::zfs_share(ds.handle) }, ds.libzfs.raw(), ds.name());
+            Ok(())
+        })
+    }
+
+    /// Stop sharing this filesystem and all filesystems under it. Carries on past filesystems
+    /// which can't be unshared, and returns them along with the reason; an empty list means all
+    /// went well.
+    pub fn unshare_all(&self) -> Result<Vec<ShareError>> {
+        self.foreach_share(|ds| {
+            ztry!(unsafe { compat::zfs_unshare(ds.handle) }, ds.libzfs.raw(), ds.name());
+            Ok(())
+        })
+    }
+
+    /// Call `f` on each mounted filesystem in this subtree, then commit the changes to the
+    /// shares all at once.
+    fn foreach_share<F>(&self, f: F) -> Result<Vec<ShareError>>
+        where F: Fn(&Dataset) -> Result<()>,
+    {
+        let mut failed = vec![];
+        let _lock = self.libzfs.lock();
+        for ds in self.self_and_descendants()? {
+            if ds.get_type() != DatasetType::Filesystem || !ds.is_mounted() {
+                continue;
+            }
+            if let Err(error) = f(&ds) {
+                failed.push(ShareError { name: ds.get_name(), error });
+            }
+        }
+        unsafe { compat::zfs_commit_shares() };
+        Ok(failed)
+    }
+}
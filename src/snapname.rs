@@ -0,0 +1,217 @@
+use crate::{Dataset, DatasetType, Error, Result, SafeString};
+
+use std::ffi::CStr;
+use std::mem;
+use std::os::raw::c_char;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+extern "C" {
+    // The libc crate doesn't declare this for every platform.
+    fn strptime(s: *const c_char, format: *const c_char, tm: *mut libc::tm) -> *mut c_char;
+}
+
+/// Names snapshots after the time they're taken, like `auto-2024-05-01T12:00`, and reads the
+/// time back out of such names, so snapshots can be made and pruned on a schedule.
+///
+/// A name is the prefix, then the time formatted with `strftime`. If that name is already taken
+/// (see [`unique_name`](Self::unique_name)), `-1`, `-2`, and so on are added to the end.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotNamer {
+    /// Goes before the time, like `auto-` or `hourly_`.
+    pub prefix: String,
+    /// A `strftime` format for the time. Defaults to `%Y-%m-%dT%H:%M`. It should only produce
+    /// characters allowed in snapshot names: letters, digits, and `-_.: `. Anything it leaves
+    /// out, like seconds by default, is taken as zero when parsing.
+    pub format: String,
+    /// Use UTC rather than local time. Defaults to true, which keeps names from repeating or
+    /// going backwards when the clocks change.
+    pub utc: bool,
+}
+
+impl Default for SnapshotNamer {
+    fn default() -> Self {
+        SnapshotNamer {
+            prefix: "auto-".to_owned(),
+            format: "%Y-%m-%dT%H:%M".to_owned(),
+            utc: true,
+        }
+    }
+}
+
+impl SnapshotNamer {
+    /// Name snapshots with the given prefix and the default format.
+    pub fn new(prefix: &str) -> Self {
+        SnapshotNamer { prefix: prefix.to_owned(), ..SnapshotNamer::default() }
+    }
+
+    /// Make the name, without any filesystem and `@`, for a snapshot taken at the given time.
+    pub fn name(&self, time: SystemTime) -> Result<String> {
+        let secs = time.duration_since(UNIX_EPOCH)
+            .map_err(|_| Error::invalid_input("time is before 1970"))?
+            .as_secs() as libc::time_t;
+        let format = SafeString::try_from(self.format.as_str())?;
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        let converted = unsafe {
+            if self.utc {
+                libc::gmtime_r(&secs, &mut tm)
+            } else {
+                libc::localtime_r(&secs, &mut tm)
+            }
+        };
+        if converted.is_null() {
+            return Err(Error::invalid_input("time is out of range"));
+        }
+        let mut buf = vec![0 as c_char; 256];
+        let len = unsafe { libc::strftime(buf.as_mut_ptr(), buf.len(), format.as_ptr(), &tm) };
+        if len == 0 {
+            return Err(Error::invalid_input(format!(
+                "snapshot time format {:?} gives nothing, or too much", self.format)));
+        }
+        let stamp = unsafe { CStr::from_ptr(buf.as_ptr()) }.to_string_lossy();
+        let name = format!("{}{}", self.prefix, stamp);
+        let valid = |c: char| c.is_ascii_alphanumeric() || "-_.: ".contains(c);
+        if let Some(c) = name.chars().find(|&c| !valid(c)) {
+            return Err(Error::invalid_input(format!(
+                "invalid character {:?} in snapshot name {:?}", c, name)));
+        }
+        Ok(name)
+    }
+
+    /// Get the time from the name of a snapshot made by this namer, with or without the
+    /// filesystem and `@`, and with or without a suffix added to avoid a collision. Returns
+    /// `None` if the name wasn't made this way.
+    pub fn parse(&self, name: &str) -> Option<SystemTime> {
+        let name = name.rsplit_once('@').map_or(name, |(_, short)| short);
+        let stamp = SafeString::try_from(name.strip_prefix(self.prefix.as_str())?).ok()?;
+        let format = SafeString::try_from(self.format.as_str()).ok()?;
+        let mut tm: libc::tm = unsafe { mem::zeroed() };
+        tm.tm_isdst = -1;
+        let end = unsafe { strptime(stamp.as_ptr(), format.as_ptr(), &mut tm) };
+        if end.is_null() {
+            return None;
+        }
+        let rest = unsafe { CStr::from_ptr(end) }.to_str().ok()?;
+        if !rest.is_empty() {
+            let suffix = rest.strip_prefix('-')?;
+            if suffix.is_empty() || !suffix.bytes().all(|b| b.is_ascii_digit()) {
+                return None;
+            }
+        }
+        let secs = unsafe {
+            if self.utc {
+                libc::timegm(&mut tm)
+            } else {
+                libc::mktime(&mut tm)
+            }
+        };
+        u64::try_from(secs).ok().map(|secs| UNIX_EPOCH + Duration::from_secs(secs))
+    }
+
+    /// Make a name for a snapshot of the given filesystem or volume taken at the given time,
+    /// adding `-1`, `-2`, and so on to the end if it already has a snapshot of that name, like
+    /// when two are taken in the same minute.
+    pub fn unique_name(&self, dataset: &Dataset, time: SystemTime) -> Result<String> {
+        let name = self.name(time)?;
+        let libzfs = dataset.libzfs();
+        let exists = |short: &str| {
+            let full = format!("{}@{}", dataset.name(), short);
+            libzfs.validate_dataset_name(&full, DatasetType::Snapshot)?;
+            match libzfs.dataset_by_name(&full, DatasetType::Snapshot.into()) {
+                Ok(_) => Ok(true),
+                Err(e) if e.is_not_found() => Ok(false),
+                Err(e) => Err(e),
+            }
+        };
+        if !exists(&name)? {
+            return Ok(name);
+        }
+        let mut n = 1;
+        loop {
+            let candidate = format!("{}-{}", name, n);
+            if !exists(&candidate)? {
+                return Ok(candidate);
+            }
+            n += 1;
+        }
+    }
+
+    /// Get the snapshots of the given filesystem or volume named by this namer, with their
+    /// times, oldest first.
+    pub fn snapshots(&self, dataset: &Dataset) -> Result<Vec<(Dataset, SystemTime)>> {
+        let mut snaps = dataset.get_snapshots_ordered()?.into_iter()
+            .filter_map(|snap| {
+                let time = self.parse(snap.name().as_str())?;
+                Some((snap, time))
+            })
+            .collect::<Vec<_>>();
+        snaps.sort_by_key(|&(_, time)| time);
+        Ok(snaps)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2024-05-01T12:00:00Z
+    const NOON: u64 = 1_714_564_800;
+
+    fn at(secs: u64) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn name_roundtrip() {
+        let namer = SnapshotNamer::new("auto-");
+        assert_eq!(namer.name(at(NOON)).unwrap(), "auto-2024-05-01T12:00");
+        assert_eq!(namer.parse("auto-2024-05-01T12:00"), Some(at(NOON)));
+        assert_eq!(namer.parse("tank/fs@auto-2024-05-01T12:00"), Some(at(NOON)));
+
+        // Seconds aren't in the default format, so they come back as zero.
+        let name = namer.name(at(NOON + 59)).unwrap();
+        assert_eq!(name, "auto-2024-05-01T12:00");
+        assert_eq!(namer.parse(&name), Some(at(NOON)));
+    }
+
+    #[test]
+    fn custom_format_roundtrip() {
+        let namer = SnapshotNamer {
+            prefix: "hourly_".to_owned(),
+            format: "%Y%m%d.%H%M%S".to_owned(),
+            utc: true,
+        };
+        let name = namer.name(at(NOON + 61)).unwrap();
+        assert_eq!(name, "hourly_20240501.120101");
+        assert_eq!(namer.parse(&name), Some(at(NOON + 61)));
+    }
+
+    #[test]
+    fn collision_suffix() {
+        let namer = SnapshotNamer::default();
+        assert_eq!(namer.parse("auto-2024-05-01T12:00-1"), Some(at(NOON)));
+        assert_eq!(namer.parse("tank@auto-2024-05-01T12:00-12"), Some(at(NOON)));
+        assert_eq!(namer.parse("auto-2024-05-01T12:00-"), None);
+        assert_eq!(namer.parse("auto-2024-05-01T12:00-x"), None);
+        assert_eq!(namer.parse("auto-2024-05-01T12:00-1-2"), None);
+    }
+
+    #[test]
+    fn foreign_names() {
+        let namer = SnapshotNamer::default();
+        assert_eq!(namer.parse("daily-2024-05-01T12:00"), None);
+        assert_eq!(namer.parse("tank@manual"), None);
+        assert_eq!(namer.parse("auto-yesterday"), None);
+        assert_eq!(namer.parse("auto-2024-05-01T12:00Z"), None);
+        assert_eq!(namer.parse("auto-"), None);
+    }
+
+    #[test]
+    fn invalid_characters() {
+        let slashes = SnapshotNamer { format: "%Y/%m/%d".to_owned(), ..SnapshotNamer::default() };
+        assert!(slashes.name(at(NOON)).is_err());
+        assert!(SnapshotNamer::new("a@b-").name(at(NOON)).is_err());
+        assert!(SnapshotNamer::new("auto+").name(at(NOON)).is_err());
+        let empty = SnapshotNamer { prefix: String::new(), format: String::new(), utc: true };
+        assert!(empty.name(at(NOON)).is_err());
+    }
+}
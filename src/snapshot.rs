@@ -0,0 +1,229 @@
+use crate::{sys, ztry, Dataset, DatasetType, Error, LibZfs, NvList, Result, SafeString};
+use crate::{LibZfsCore, ZfsError};
+use crate::create::props_nvlist;
+use crate::error::{batch_errors, lzc_call};
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+/// A snapshot which couldn't be destroyed, and why.
+#[derive(Debug)]
+pub struct SnapshotError {
+    pub name: SafeString,
+    pub error: Error,
+}
+
+/// Just the identity of a snapshot, from [`Dataset::snapshot_infos`], without a handle to it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SnapshotInfo {
+    pub name: SafeString,
+    pub guid: u64,
+    pub createtxg: u64,
+}
+
+impl Dataset {
+    /// List the names, GUIDs, and createtxgs of this dataset's snapshots, oldest first, like
+    /// [`sort_snapshots`](crate::sort_snapshots) orders them.
+    ///
+    /// This is much faster than [`get_snapshots`](Self::get_snapshots) when there are many
+    /// snapshots: the kernel only looks up these three things, instead of all of each
+    /// snapshot's properties, and no handles are kept open.
+    pub fn snapshot_infos(&self) -> Result<Vec<SnapshotInfo>> {
+        struct Context {
+            infos: Vec<SnapshotInfo>,
+            error: Option<Error>,
+        }
+
+        extern "C" fn collect(handle: *mut sys::zfs_handle_t, context: *mut c_void) -> c_int {
+            let ctx = unsafe { &mut *(context as *mut Context) };
+            let name = unsafe { CStr::from_ptr(sys::zfs_get_name(handle)) };
+            match SafeString::try_from(name) {
+                // Simple handles have these from the listing itself, without loading props.
+                Ok(name) => ctx.infos.push(SnapshotInfo {
+                    name,
+                    guid: unsafe { sys::zfs_prop_get_int(handle, sys::zfs_prop_t::ZFS_PROP_GUID) },
+                    createtxg: unsafe {
+                        sys::zfs_prop_get_int(handle, sys::zfs_prop_t::ZFS_PROP_CREATETXG)
+                    },
+                }),
+                Err(e) => {
+                    ctx.error.get_or_insert(e);
+                }
+            }
+            unsafe { sys::zfs_close(handle) };
+            0
+        }
+
+        let _lock = self.libzfs.lock();
+        let mut ctx = Context { infos: vec![], error: None };
+        ztry!(unsafe {
+            sys::zfs_iter_snapshots(
+                self.handle,
+                1, // simple
+                Some(collect),
+                &mut ctx as *mut _ as *mut c_void,
+                0, // min_txg: none
+                0, // max_txg: none
+            )
+        }, self.libzfs.raw(), self.name());
+        if let Some(e) = ctx.error {
+            return Err(e);
+        }
+        ctx.infos.sort_by(|a, b| a.createtxg.cmp(&b.createtxg).then_with(|| a.name.cmp(&b.name)));
+        Ok(ctx.infos)
+    }
+}
+
+impl LibZfs {
+    /// Destroy snapshots in bulk, given their full names (like `pool/fs@snap`). Snapshots which
+    /// don't exist are ignored.
+    ///
+    /// Fails with the error for the first snapshot which couldn't be destroyed; use
+    /// [`LibZfs::destroy_snapshots_each`] to find out about all of them.
+    pub fn destroy_snapshots<I, T>(&self, names: I) -> Result<()>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        match self.destroy_snapshots_each(names, false)?.into_iter().next() {
+            Some(failed) => Err(failed.error),
+            None => Ok(()),
+        }
+    }
+
+    /// Destroy snapshots in bulk, given their full names (like `pool/fs@snap`), like
+    /// [`LibZfsCore::destroy_snapshots`]. Returns the snapshots which weren't destroyed, along
+    /// with the reason.
+    pub fn destroy_snapshots_each<I, T>(&self, names: I, defer: bool)
+        -> Result<Vec<SnapshotError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        LibZfsCore::new()?.destroy_snapshots(names, defer)
+    }
+}
+
+impl LibZfsCore {
+    /// Destroy snapshots in bulk, given their full names (like `pool/fs@snap`). Snapshots which
+    /// don't exist are ignored.
+    ///
+    /// With `defer`, snapshots which are held or have clones are marked for destruction once
+    /// they're released or their clones are destroyed, like `zfs destroy -d`, instead of
+    /// failing.
+    ///
+    /// The snapshots are destroyed in one batch per pool, and each batch is all-or-nothing: if
+    /// any snapshot in a pool can't be destroyed, none in that pool are. Returns the snapshots
+    /// which weren't destroyed, along with the reason; an empty list means all went well.
+    pub fn destroy_snapshots<I, T>(&self, names: I, defer: bool) -> Result<Vec<SnapshotError>>
+        where I: Iterator<Item = T>,
+              T: AsRef<str>,
+    {
+        let mut failed = vec![];
+        for names in snapshots_by_pool(names)? {
+            let mut nvl = NvList::new()?;
+            for name in &names {
+                nvl.add_boolean(name.as_ref())?;
+            }
+
+            // The kernel unmounts the snapshots itself.
+            let mut errlist = ptr::null_mut();
+            let ret = lzc_call!(unsafe {
+                sys::lzc_destroy_snaps(nvl.as_ptr(), defer as sys::boolean_t, &mut errlist)
+            }, format_args!("{} snapshots", names.len()));
+            if ret == 0 {
+                continue;
+            }
+
+            failed.extend(batch_errors("destroy", names, ret, errlist).into_iter()
+                .map(|(name, error)| SnapshotError { name, error }));
+        }
+
+        Ok(failed)
+    }
+}
+
+/// Group snapshot names by pool, since libzfs_core requires all the snapshots in one call to be
+/// in the same pool.
+pub(crate) fn snapshots_by_pool<I, T>(names: I) -> Result<Vec<Vec<SafeString>>>
+    where I: Iterator<Item = T>,
+          T: AsRef<str>,
+{
+    let mut by_pool = BTreeMap::<String, Vec<SafeString>>::new();
+    for name in names {
+        let name = name.as_ref();
+        if !name.contains('@') {
+            return Err(Error::invalid_input(format!("{:?} is not a snapshot name", name)));
+        }
+        let pool = name.split(['/', '@']).next().unwrap_or_default();
+        by_pool.entry(pool.to_owned()).or_default().push(SafeString::try_from(name)?);
+    }
+    Ok(by_pool.into_values().collect())
+}
+
+impl Dataset {
+    /// Snapshot this filesystem or volume and all its descendants at once, like
+    /// `zfs snapshot -r`, giving every snapshot the given properties. Returns the new snapshots,
+    /// parents before children.
+    pub fn snapshot_recursive(&self, snapname: &str, props: &[(&str, &str)])
+        -> Result<Vec<Dataset>>
+    {
+        if snapname.is_empty() || snapname.contains(['@', '/', '#']) {
+            return Err(Error::invalid_input(format!("invalid snapshot name {:?}", snapname)));
+        }
+        if !matches!(self.get_type(), DatasetType::Filesystem | DatasetType::Volume) {
+            return Err(Error::invalid_input(
+                format!("{} is not a filesystem or volume", self.name())));
+        }
+
+        let names = self.self_and_descendants()?.iter()
+            .map(|ds| format!("{}@{}", ds.name(), snapname))
+            .collect::<Vec<_>>();
+        let mut snaps = NvList::new()?;
+        for name in &names {
+            snaps.add_boolean(name)?;
+        }
+        let props = props_nvlist(props)?;
+        {
+            let _lock = self.libzfs.lock();
+            ztry!(unsafe {
+                sys::zfs_snapshot_nvl(self.libzfs.raw(), snaps.as_ptr(), props.as_ptr())
+            }, self.libzfs.raw(), self.name());
+        }
+
+        let libzfs = self.libzfs();
+        names.iter()
+            .map(|name| libzfs.dataset_by_name(name.as_str(), DatasetType::Snapshot.into()))
+            .collect()
+    }
+
+    /// Destroy a contiguous range of this dataset's snapshots, from `first` through `last`
+    /// inclusive, in order of creation, like `zfs destroy fs@first%last`. Snapshots are given by
+    /// their short names (after the `@`). A range with no `first` starts at the oldest snapshot,
+    /// and one with no `last` ends at the newest. Returns the names of the destroyed snapshots.
+    pub fn destroy_snapshot_range(&self, first: Option<&str>, last: Option<&str>)
+        -> Result<Vec<SafeString>>
+    {
+        let snapshots = self.get_snapshots_ordered()?;
+        let names = snapshots.iter().map(Dataset::get_name).collect::<Vec<_>>();
+        let position = |short: &str| {
+            names.iter()
+                .position(|name| name.as_str().rsplit_once('@').map(|(_, s)| s) == Some(short))
+                .ok_or_else(|| Error::not_found(format!("no snapshot {}@{}", self.name(), short)))
+        };
+        let start = first.map(position).transpose()?.unwrap_or(0);
+        let end = match last {
+            Some(last) => position(last)? + 1,
+            None => names.len(),
+        };
+        if start >= end {
+            return Ok(vec![]);
+        }
+
+        let range = names[start .. end].to_vec();
+        let libzfs = self.libzfs();
+        libzfs.destroy_snapshots(range.iter())?;
+        Ok(range)
+    }
+}
@@ -0,0 +1,472 @@
+use crate::{sys, Dataset, DatasetType, Error, Result, SafeString, ZPool};
+
+use std::collections::{BTreeMap, HashMap};
+use std::io;
+use std::mem;
+use std::ptr;
+use std::time::{Duration, SystemTime};
+
+/// I/O counters for one dataset, like `zfs iostat` shows, since its pool was imported. Only
+/// operations through the filesystem or volume are counted, not sends, receives, or scrubs.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DatasetIoStats {
+    pub read_ops: u64,
+    pub read_bytes: u64,
+    pub write_ops: u64,
+    pub write_bytes: u64,
+    /// Files queued for deletion.
+    pub unlinks: u64,
+    /// Files whose deletion has finished.
+    pub unlinked: u64,
+}
+
+impl Dataset {
+    /// Get this filesystem's or volume's I/O counters. Returns `None` if it has none, which is
+    /// the case when it isn't mounted (or, for a volume, its device isn't open).
+    ///
+    /// Only supported on Linux, FreeBSD, and macOS, where the counters come from kstats.
+    pub fn io_stats(&self) -> Result<Option<DatasetIoStats>> {
+        match self.get_type() {
+            DatasetType::Filesystem | DatasetType::Volume => (),
+            _ => return Err(Error::invalid_input(
+                format!("{} is not a filesystem or volume", self.name()))),
+        }
+        let objset = format!("objset-0x{:x}",
+            self.get_int_property(sys::zfs_prop_t::ZFS_PROP_OBJSETID));
+        let pool = self.get_pool_name();
+        #[cfg(any(target_os = "freebsd", target_os = "macos"))]
+        let path = [pool.as_str(), "dataset", &objset];
+        #[cfg(not(any(target_os = "freebsd", target_os = "macos")))]
+        let path = [pool.as_str(), &objset];
+
+        let fields = ["reads", "nread", "writes", "nwritten", "nunlinks", "nunlinked"];
+        let Some(values) = read_kstat(&path, &fields)? else {
+            return Ok(None);
+        };
+        let get = |name: &str| values.get(name).copied().unwrap_or(0);
+        Ok(Some(DatasetIoStats {
+            read_ops: get("reads"),
+            read_bytes: get("nread"),
+            write_ops: get("writes"),
+            write_bytes: get("nwritten"),
+            unlinks: get("nunlinks"),
+            unlinked: get("nunlinked"),
+        }))
+    }
+}
+
+impl ZPool {
+    /// Get the I/O counters of every filesystem and volume in the pool which has any, keyed by
+    /// dataset name. See [`Dataset::io_stats`].
+    pub fn dataset_io_stats(&self) -> Result<BTreeMap<SafeString, DatasetIoStats>> {
+        let mut stats = BTreeMap::new();
+        for dataset in self.get_datasets()? {
+            if let Some(io) = dataset.io_stats()? {
+                stats.insert(dataset.get_name(), io);
+            }
+        }
+        Ok(stats)
+    }
+}
+
+/// Where a pool is at in terms of transaction groups, from [`ZPool::txg_info`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct PoolTxgInfo {
+    /// The transaction group the pool's config was last written in. Changes to the vdevs,
+    /// among other things, update it; ordinary writes don't.
+    pub config_txg: u64,
+    /// A random number picked each time the pool is imported, so it tells apart different
+    /// imports of the same pool.
+    pub load_guid: u64,
+    /// The most recent transaction group to have been synced to disk. Any snapshot taken since
+    /// has a later `createtxg`.
+    pub synced_txg: Option<u64>,
+    /// The transaction group currently taking new changes.
+    pub open_txg: Option<u64>,
+    /// About when the most recent sync finished.
+    pub last_sync: Option<SystemTime>,
+}
+
+impl ZPool {
+    /// Get the pool's current transaction groups and import GUID.
+    ///
+    /// The synced and open transaction groups and the last sync time come from the `txgs`
+    /// kstat, so they are only known on Linux, and only while the `zfs_txg_history` module
+    /// parameter is nonzero (which it is by default).
+    pub fn txg_info(&self) -> Result<PoolTxgInfo> {
+        let config = self.get_config()?;
+        let load_guid = {
+            let _lock = self.libzfs.lock();
+            unsafe {
+                sys::zpool_get_prop_int(
+                    self.handle,
+                    sys::zpool_prop_t::ZPOOL_PROP_LOAD_GUID,
+                    ptr::null_mut(),
+                )
+            }
+        };
+        let txgs = read_txgs(self.name().as_str())?;
+        Ok(PoolTxgInfo {
+            config_txg: config.lookup_uint64("txg")
+                .ok_or_else(|| Error::invalid_data("pool config has no txg"))?,
+            load_guid,
+            synced_txg: txgs.map(|t| t.synced_txg),
+            open_txg: txgs.and_then(|t| t.open_txg),
+            last_sync: txgs.and_then(|t| t.last_sync),
+        })
+    }
+}
+
+/// Sizes of a set of dedup table entries, and of the references to them: a `ddt_stat_t`. Sizes
+/// are in bytes: `lsize` is logical, `psize` is after compression, and `dsize` is what's
+/// allocated on disk, including parity.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DedupTableStat {
+    /// Blocks stored.
+    pub blocks: u64,
+    pub lsize: u64,
+    pub psize: u64,
+    pub dsize: u64,
+    /// References to those blocks, which is what they'd take up without dedup.
+    pub ref_blocks: u64,
+    pub ref_lsize: u64,
+    pub ref_psize: u64,
+    pub ref_dsize: u64,
+}
+
+impl DedupTableStat {
+    /// How many times over dedup stretches the space these blocks take on disk, like the
+    /// `dedupratio` pool property. `None` if there are no blocks.
+    pub fn ratio(&self) -> Option<f64> {
+        (self.dsize != 0).then(|| self.ref_dsize as f64 / self.dsize as f64)
+    }
+}
+
+/// A `ddt_object_t`, summed over all of a pool's dedup tables.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct DdtObject {
+    ddo_count: u64,
+    ddo_dspace: u64,
+    ddo_mspace: u64,
+}
+
+/// A pool's dedup table, like `zpool status -D` shows, from [`ZPool::dedup_stats`].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DedupStats {
+    /// Entries in the table: one for each unique block.
+    pub entries: u64,
+    /// The average size of an entry on disk, in bytes.
+    pub entry_size_on_disk: u64,
+    /// The average size of an entry in memory, in bytes. Performance suffers badly if the whole
+    /// table doesn't fit in the ARC.
+    pub entry_size_in_core: u64,
+    /// All the entries together.
+    pub total: DedupTableStat,
+    /// The entries grouped by how many references they have, as pairs of the smallest count in
+    /// the group and the group's stats. Each group runs up to twice its smallest count, so the
+    /// groups are for 1, 2-3, 4-7, and so on. Empty groups are left out.
+    pub histogram: Vec<(u64, DedupTableStat)>,
+}
+
+impl DedupStats {
+    /// The size of the whole table on disk, in bytes.
+    pub fn size_on_disk(&self) -> u64 {
+        self.entries * self.entry_size_on_disk
+    }
+
+    /// The size the whole table takes in memory, in bytes.
+    pub fn size_in_core(&self) -> u64 {
+        self.entries * self.entry_size_in_core
+    }
+}
+
+impl ZPool {
+    /// Get statistics about the pool's dedup table, which tell how much space dedup saves, and
+    /// how much space and memory that costs. Returns `None` if the table is empty, like when
+    /// dedup has never been turned on.
+    pub fn dedup_stats(&self) -> Result<Option<DedupStats>> {
+        let config = self.get_config()?;
+        let Some(object) = config.lookup_struct::<DdtObject>("ddt_object_stats") else {
+            return Ok(None);
+        };
+        if object.ddo_count == 0 {
+            return Ok(None);
+        }
+        let total = config.lookup_struct::<DedupTableStat>("ddt_stats").unwrap_or_default();
+        let width = mem::size_of::<DedupTableStat>() / mem::size_of::<u64>();
+        let histogram = config.lookup_uint64_array("ddt_histogram").unwrap_or_default()
+            .chunks_exact(width)
+            .enumerate()
+            .filter_map(|(bucket, values)| {
+                let stat = DedupTableStat {
+                    blocks: values[0],
+                    lsize: values[1],
+                    psize: values[2],
+                    dsize: values[3],
+                    ref_blocks: values[4],
+                    ref_lsize: values[5],
+                    ref_psize: values[6],
+                    ref_dsize: values[7],
+                };
+                (stat.blocks != 0).then(|| (1u64 << bucket, stat))
+            })
+            .collect();
+        Ok(Some(DedupStats {
+            entries: object.ddo_count,
+            entry_size_on_disk: object.ddo_dspace,
+            entry_size_in_core: object.ddo_mspace,
+            total,
+            histogram,
+        }))
+    }
+}
+
+#[derive(Copy, Clone)]
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+struct Txgs {
+    synced_txg: u64,
+    open_txg: Option<u64>,
+    last_sync: Option<SystemTime>,
+}
+
+/// Read the most recent entries of a pool's `txgs` kstat, which is a table of recent
+/// transaction groups with their state (`O`pen, `Q`uiesced, `W`aiting for sync, `S`yncing, or
+/// `C`ommitted) and timings.
+#[cfg(target_os = "linux")]
+fn read_txgs(pool: &str) -> Result<Option<Txgs>> {
+    let text = match std::fs::read_to_string(format!("/proc/spl/kstat/zfs/{}/txgs", pool)) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::Sys(e)),
+    };
+    let mut synced = None;
+    let mut open_txg = None;
+    // The first line names the columns: txg, birth, state, ndirty, nread, nwritten, reads,
+    // writes, otime, qtime, wtime, and stime.
+    for line in text.lines().skip(1) {
+        let cols = line.split_whitespace().collect::<Vec<_>>();
+        let num = |i: usize| cols.get(i).and_then(|col| col.parse::<u64>().ok());
+        let Some(txg) = num(0) else {
+            continue;
+        };
+        match cols.get(2) {
+            Some(&"C") => {
+                // Birth is when the txg opened, and the times are how long it spent in each
+                // state, all in nanoseconds of the kernel's raw monotonic clock.
+                let synced_at = [1, 8, 9, 10, 11].iter().map(|&i| num(i)).sum::<Option<u64>>();
+                synced = Some((txg, synced_at));
+            }
+            Some(&"O") => open_txg = Some(txg),
+            _ => (),
+        }
+    }
+    let Some((synced_txg, synced_at)) = synced else {
+        return Ok(None);
+    };
+    let last_sync = synced_at.and_then(|synced_at| {
+        let mut now = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+        if unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC_RAW, &mut now) } != 0 {
+            return None;
+        }
+        let now = now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64;
+        let ago = Duration::from_nanos(now.saturating_sub(synced_at));
+        SystemTime::now().checked_sub(ago)
+    });
+    Ok(Some(Txgs { synced_txg, open_txg, last_sync }))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn read_txgs(_pool: &str) -> Result<Option<Txgs>> {
+    Ok(None)
+}
+
+/// Read the numeric values of a ZFS kstat, given its path under `/proc/spl/kstat/zfs` on Linux
+/// or `kstat.zfs` on FreeBSD and macOS, like `["tank", "objset-0x36"]`. On Linux every value in
+/// it is returned; elsewhere, where each value is a separate sysctl, only the given fields are
+/// read. Signed values are returned as their two's complement. Returns `None` if the kstat
+/// doesn't exist.
+#[cfg(target_os = "linux")]
+pub(crate) fn read_kstat(path: &[&str], _fields: &[&str]) -> Result<Option<HashMap<String, u64>>> {
+    let file = format!("/proc/spl/kstat/zfs/{}", path.join("/"));
+    let text = match std::fs::read_to_string(file) {
+        Ok(text) => text,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(Error::Sys(e)),
+    };
+    // The first line is the kstat header, and the second names the columns.
+    let mut values = HashMap::new();
+    for line in text.lines().skip(2) {
+        let mut cols = line.split_whitespace();
+        let (Some(name), Some(_type), Some(value)) = (cols.next(), cols.next(), cols.next()) else {
+            continue;
+        };
+        let value = value.parse::<u64>().ok()
+            .or_else(|| value.parse::<i64>().ok().map(|v| v as u64));
+        if let Some(value) = value {
+            values.insert(name.to_owned(), value);
+        }
+    }
+    Ok(Some(values))
+}
+
+#[cfg(any(target_os = "freebsd", target_os = "macos"))]
+pub(crate) fn read_kstat(path: &[&str], fields: &[&str]) -> Result<Option<HashMap<String, u64>>> {
+    use std::ffi::CString;
+
+    let mut values = HashMap::new();
+    for &field in fields {
+        let name = CString::new(format!("kstat.zfs.{}.{}", path.join("."), field))
+            .map_err(Error::invalid_input)?;
+        let mut value = 0u64;
+        let mut len = std::mem::size_of::<u64>();
+        let ret = unsafe {
+            libc::sysctlbyname(
+                name.as_ptr(),
+                (&mut value as *mut u64).cast(),
+                &mut len,
+                std::ptr::null(),
+                0,
+            )
+        };
+        if ret != 0 {
+            let e = io::Error::last_os_error();
+            if e.kind() == io::ErrorKind::NotFound {
+                if values.is_empty() {
+                    return Ok(None);
+                }
+                continue;
+            }
+            return Err(Error::Sys(e));
+        }
+        values.insert(field.to_owned(), value);
+    }
+    Ok(Some(values))
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "freebsd", target_os = "macos")))]
+pub(crate) fn read_kstat(_path: &[&str], _fields: &[&str])
+    -> Result<Option<HashMap<String, u64>>>
+{
+    Err(Error::Sys(io::Error::new(io::ErrorKind::Unsupported,
+        "ZFS kstats are only available on Linux, FreeBSD, and macOS")))
+}
+
+/// A snapshot of the ARC (the in-memory cache) and L2ARC counters, from [`arc_stats`]. Sizes are
+/// in bytes, and the hit and miss counters are totals since the module was loaded.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ArcStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub demand_data_hits: u64,
+    pub demand_data_misses: u64,
+    pub demand_metadata_hits: u64,
+    pub demand_metadata_misses: u64,
+    pub prefetch_data_hits: u64,
+    pub prefetch_data_misses: u64,
+    pub prefetch_metadata_hits: u64,
+    pub prefetch_metadata_misses: u64,
+    /// The current size of the ARC.
+    pub size: u64,
+    /// The size the ARC is currently aiming for (`c`).
+    pub target_size: u64,
+    /// The lower limit of the target size (`c_min`, from `zfs_arc_min`).
+    pub min_size: u64,
+    /// The upper limit of the target size (`c_max`, from `zfs_arc_max`).
+    pub max_size: u64,
+    pub data_size: u64,
+    pub metadata_size: u64,
+    /// Space used by the most recently used list.
+    pub mru_size: u64,
+    /// Space used by the most frequently used list.
+    pub mfu_size: u64,
+    pub l2_hits: u64,
+    pub l2_misses: u64,
+    /// Space cached on L2ARC devices, before compression.
+    pub l2_size: u64,
+    /// Space cached on L2ARC devices, after compression.
+    pub l2_asize: u64,
+    /// How much more memory the ARC thinks the system can spare. Negative when it's short and
+    /// the ARC is shrinking.
+    pub memory_available: i64,
+}
+
+impl ArcStats {
+    /// The fraction of all ARC lookups which were hits, or `None` if there have been none.
+    pub fn hit_ratio(&self) -> Option<f64> {
+        ratio(self.hits, self.misses)
+    }
+
+    /// The fraction of demand (non-prefetch) ARC lookups which were hits, which is usually the
+    /// more meaningful figure, or `None` if there have been none.
+    pub fn demand_hit_ratio(&self) -> Option<f64> {
+        ratio(self.demand_data_hits + self.demand_metadata_hits,
+            self.demand_data_misses + self.demand_metadata_misses)
+    }
+
+    /// The fraction of L2ARC lookups which were hits, or `None` if there have been none.
+    pub fn l2_hit_ratio(&self) -> Option<f64> {
+        ratio(self.l2_hits, self.l2_misses)
+    }
+}
+
+fn ratio(hits: u64, misses: u64) -> Option<f64> {
+    let total = hits + misses;
+    if total == 0 {
+        None
+    } else {
+        Some(hits as f64 / total as f64)
+    }
+}
+
+/// Read the ARC statistics, like `arcstat` and `arc_summary` do. Only supported on Linux,
+/// FreeBSD, and macOS, and fails if the ZFS module isn't loaded.
+pub fn arc_stats() -> Result<ArcStats> {
+    #[cfg(any(target_os = "freebsd", target_os = "macos"))]
+    let path = ["misc", "arcstats"];
+    #[cfg(not(any(target_os = "freebsd", target_os = "macos")))]
+    let path = ["arcstats"];
+
+    let fields = [
+        "hits", "misses",
+        "demand_data_hits", "demand_data_misses",
+        "demand_metadata_hits", "demand_metadata_misses",
+        "prefetch_data_hits", "prefetch_data_misses",
+        "prefetch_metadata_hits", "prefetch_metadata_misses",
+        "size", "c", "c_min", "c_max", "data_size", "metadata_size", "mru_size", "mfu_size",
+        "l2_hits", "l2_misses", "l2_size", "l2_asize", "memory_available_bytes",
+    ];
+    let values = read_kstat(&path, &fields)?
+        .ok_or_else(|| Error::not_found("no arcstats kstat; is the ZFS module loaded?"))?;
+    let get = |name: &str| values.get(name).copied().unwrap_or(0);
+    Ok(ArcStats {
+        hits: get("hits"),
+        misses: get("misses"),
+        demand_data_hits: get("demand_data_hits"),
+        demand_data_misses: get("demand_data_misses"),
+        demand_metadata_hits: get("demand_metadata_hits"),
+        demand_metadata_misses: get("demand_metadata_misses"),
+        prefetch_data_hits: get("prefetch_data_hits"),
+        prefetch_data_misses: get("prefetch_data_misses"),
+        prefetch_metadata_hits: get("prefetch_metadata_hits"),
+        prefetch_metadata_misses: get("prefetch_metadata_misses"),
+        size: get("size"),
+        target_size: get("c"),
+        min_size: get("c_min"),
+        max_size: get("c_max"),
+        data_size: get("data_size"),
+        metadata_size: get("metadata_size"),
+        mru_size: get("mru_size"),
+        mfu_size: get("mfu_size"),
+        l2_hits: get("l2_hits"),
+        l2_misses: get("l2_misses"),
+        l2_size: get("l2_size"),
+        l2_asize: get("l2_asize"),
+        memory_available: get("memory_available_bytes") as i64,
+    })
+}
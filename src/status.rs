@@ -0,0 +1,318 @@
+use crate::{sys, ztry, Error, NvList, NvValue, Result, ScanStatus, Vdev, ZfsError, ZPool};
+use crate::string::string_from_buf;
+use crate::vdev::vdev_tree;
+use sys::zpool_status_t::*;
+
+use std::ffi::CStr;
+use std::os::raw::c_char;
+use std::ptr;
+
+/// How long a path `zpool_obj_to_path` can give, from `MAXPATHLEN * 2`.
+const OBJ_PATH_LEN: usize = 8192;
+
+/// Everything `zpool status -v` shows about a pool, gathered into one plain-data report by
+/// [`ZPool::status_report`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct PoolStatusReport {
+    pub name: String,
+    /// Like `"ONLINE"` or `"DEGRADED"`, like the `state:` line.
+    pub health: String,
+    /// What's wrong, if anything, like the `status:` line.
+    pub status: Option<String>,
+    /// What to do about it, like the `action:` line.
+    pub action: Option<String>,
+    /// The ZFS message ID for the problem, like `ZFS-8000-9P`, which is documented at
+    /// `https://openzfs.github.io/openzfs-docs/msg/<id>`.
+    pub message_id: Option<String>,
+    /// The current or most recent scrub or resilver, if there has been one.
+    pub scan: Option<ScanStatus>,
+    /// The vdev tree, including log vdevs, with each vdev's state and error counters.
+    pub vdevs: Vec<Vdev>,
+    /// Cache (L2ARC) devices.
+    pub cache: Vec<Vdev>,
+    /// Hot spares.
+    pub spares: Vec<Vdev>,
+    /// How many data errors are in the pool's error log.
+    pub error_count: u64,
+    /// The files, or datasets and objects, with permanent errors, like `zpool status -v`; see
+    /// [`ZPool::error_paths`]. `None` if reading the list was denied, since it takes root.
+    pub errors: Option<Vec<String>>,
+}
+
+/// A block with a permanent error, from a pool's error log; see [`ZPool::error_log`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ErrorLogEntry {
+    /// The object number of the dataset it's in.
+    pub dataset: u64,
+    /// The object number of the file or other object it's in, within the dataset.
+    pub object: u64,
+}
+
+impl ZPool {
+    /// Gather up everything `zpool status -v` shows about this pool: its health, what's wrong
+    /// and what to do about it, scrub or resilver progress, the vdev tree with error counters,
+    /// and the files with permanent errors.
+    pub fn status_report(&self) -> Result<PoolStatusReport> {
+        let config = self.get_config()?;
+        let tree = vdev_tree(&config)?;
+        let vdevs = |name| {
+            tree.lookup_nvlist_array(name).unwrap_or_default()
+                .into_iter()
+                .map(|vdev| self.vdev(vdev))
+                .collect()
+        };
+
+        let (status, message_id) = {
+            let _lock = self.libzfs.lock();
+            let mut msgid = ptr::null_mut::<c_char>() as _;
+            let mut errata = 0;
+            let status = unsafe { sys::zpool_get_status(self.handle, &mut msgid, &mut errata) };
+            let message_id = (!msgid.is_null())
+                .then(|| unsafe { CStr::from_ptr(msgid) }.to_string_lossy().into_owned());
+            (status, message_id)
+        };
+        let (status, action) = status_text(status).unzip();
+
+        Ok(PoolStatusReport {
+            name: self.name().to_string(),
+            health: self.get_prop_string(sys::zpool_prop_t::ZPOOL_PROP_HEALTH)?,
+            status: status.map(str::to_owned),
+            action: action.map(str::to_owned),
+            message_id,
+            scan: self.scan_status()?,
+            vdevs: vdevs("children"),
+            cache: vdevs("l2cache"),
+            spares: vdevs("spares"),
+            error_count: config.lookup_uint64("error_count").unwrap_or_default(),
+            errors: match self.error_paths() {
+                Ok(paths) => Some(paths),
+                Err(e) if is_access_denied(&e) => None,
+                Err(e) => return Err(e),
+            },
+        })
+    }
+
+    /// Read the pool's persistent error log: the blocks found to have permanent errors, by the
+    /// dataset and object they belong to, like the list `zpool status -v` shows. Reading it
+    /// takes root.
+    pub fn error_log(&self) -> Result<Vec<ErrorLogEntry>> {
+        let _lock = self.libzfs.lock();
+        let mut nvl = ptr::null_mut();
+        ztry!(unsafe {
+            sys::zpool_get_errlog(self.handle, &mut nvl)
+        }, self.libzfs.raw(), self.name());
+        if nvl.is_null() {
+            return Ok(vec![]);
+        }
+        let errlog = unsafe { NvList::from_raw(nvl) };
+        let mut entries = errlog.iter()
+            .map(|pair| {
+                let NvValue::NvList(err) = pair.value() else {
+                    return Err(Error::invalid_data("malformed error log entry"));
+                };
+                match (err.lookup_uint64("dataset"), err.lookup_uint64("object")) {
+                    (Some(dataset), Some(object)) => Ok(ErrorLogEntry { dataset, object }),
+                    _ => Err(Error::invalid_data("malformed error log entry")),
+                }
+            })
+            .collect::<Result<Vec<_>>>()?;
+        entries.sort();
+        entries.dedup();
+        Ok(entries)
+    }
+
+    /// Work out which file an error log entry is in, the way `zpool status -v` shows it: its
+    /// path if its filesystem is mounted, like `/tank/data/file`; otherwise the filesystem and
+    /// the path within it, like `tank/data:/file`. Where the file or filesystem can't be found,
+    /// such as when it's since been deleted, or for metadata, object numbers are given instead,
+    /// like `tank/data:<0x1f>` or `<0x36>:<0x1f>`.
+    pub fn error_path(&self, entry: &ErrorLogEntry) -> String {
+        let _lock = self.libzfs.lock();
+        let mut buf = vec![0 as c_char; OBJ_PATH_LEN];
+        unsafe {
+            sys::zpool_obj_to_path(
+                self.handle,
+                entry.dataset,
+                entry.object,
+                buf.as_mut_ptr(),
+                buf.len(),
+            )
+        };
+        string_from_buf(&buf)
+    }
+
+    /// Get the files with permanent errors, from the pool's error log, as given by
+    /// [`error_path`](Self::error_path), in order.
+    pub fn error_paths(&self) -> Result<Vec<String>> {
+        let mut paths = self.error_log()?.iter()
+            .map(|entry| self.error_path(entry))
+            .collect::<Vec<_>>();
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// Whether an error is EPERM or EACCES, which is how reading the error log fails without root.
+fn is_access_denied(e: &Error) -> bool {
+    let errno = match e {
+        Error::Sys(e) => e.raw_os_error(),
+        Error::Zfs(e) => e.errno,
+        Error::Context { source, .. } => return is_access_denied(source),
+    };
+    matches!(errno, Some(libc::EPERM | libc::EACCES))
+}
+
+/// The `status:` and `action:` text `zpool status` shows for a pool's status, if it needs
+/// attention.
+fn status_text(status: sys::zpool_status_t::Type) -> Option<(&'static str, &'static str)> {
+    Some(match status {
+        ZPOOL_STATUS_MISSING_DEV_R => (
+            "One or more devices could not be opened. Sufficient replicas exist for the pool to \
+            continue functioning in a degraded state.",
+            "Attach the missing device and online it using 'zpool online'.",
+        ),
+        ZPOOL_STATUS_MISSING_DEV_NR => (
+            "One or more devices could not be opened. There are insufficient replicas for the \
+            pool to continue functioning.",
+            "Attach the missing device and online it using 'zpool online'.",
+        ),
+        ZPOOL_STATUS_CORRUPT_LABEL_R => (
+            "One or more devices could not be used because the label is missing or invalid. \
+            Sufficient replicas exist for the pool to continue functioning in a degraded state.",
+            "Replace the device using 'zpool replace'.",
+        ),
+        ZPOOL_STATUS_CORRUPT_LABEL_NR => (
+            "One or more devices could not be used because the label is missing or invalid. \
+            There are insufficient replicas for the pool to continue functioning.",
+            "Destroy and re-create the pool from a backup source.",
+        ),
+        ZPOOL_STATUS_FAILING_DEV => (
+            "One or more devices has experienced an unrecoverable error. An attempt was made to \
+            correct the error. Applications are unaffected.",
+            "Determine if the device needs to be replaced, and clear the errors using \
+            'zpool clear' or replace the device with 'zpool replace'.",
+        ),
+        ZPOOL_STATUS_OFFLINE_DEV => (
+            "One or more devices has been taken offline by the administrator. Sufficient \
+            replicas exist for the pool to continue functioning in a degraded state.",
+            "Online the device using 'zpool online' or replace the device with 'zpool replace'.",
+        ),
+        ZPOOL_STATUS_REMOVED_DEV => (
+            "One or more devices has been removed by the administrator. Sufficient replicas \
+            exist for the pool to continue functioning in a degraded state.",
+            "Online the device using 'zpool online' or replace the device with 'zpool replace'.",
+        ),
+        ZPOOL_STATUS_RESILVERING | ZPOOL_STATUS_REBUILDING => (
+            "One or more devices is currently being resilvered. The pool will continue to \
+            function, possibly in a degraded state.",
+            "Wait for the resilver to complete.",
+        ),
+        ZPOOL_STATUS_REBUILD_SCRUB => (
+            "One or more devices have been sequentially resilvered, scrubbing the pool is \
+            recommended.",
+            "Use 'zpool scrub' to verify all data checksums.",
+        ),
+        ZPOOL_STATUS_CORRUPT_DATA => (
+            "One or more devices has experienced an error resulting in data corruption. \
+            Applications may be affected.",
+            "Restore the file in question if possible. Otherwise restore the entire pool from \
+            backup.",
+        ),
+        ZPOOL_STATUS_CORRUPT_POOL => (
+            "The pool metadata is corrupted and the pool cannot be opened.",
+            "Destroy and re-create the pool from a backup source.",
+        ),
+        ZPOOL_STATUS_FAULTED_DEV_R => (
+            "One or more devices are faulted in response to persistent errors. Sufficient \
+            replicas exist for the pool to continue functioning in a degraded state.",
+            "Replace the faulted device, or use 'zpool clear' to mark the device repaired.",
+        ),
+        ZPOOL_STATUS_FAULTED_DEV_NR => (
+            "One or more devices are faulted in response to persistent errors. There are \
+            insufficient replicas for the pool to continue functioning.",
+            "Destroy and re-create the pool from a backup source. Manually marking the device \
+            repaired using 'zpool clear' may allow some data to be recovered.",
+        ),
+        ZPOOL_STATUS_IO_FAILURE_WAIT | ZPOOL_STATUS_IO_FAILURE_CONTINUE => (
+            "One or more devices are faulted in response to IO failures.",
+            "Make sure the affected devices are connected, then run 'zpool clear'.",
+        ),
+        ZPOOL_STATUS_IO_FAILURE_MMP => (
+            "The pool is suspended because multihost writes failed or were delayed; another \
+            system could import the pool undetected.",
+            "Make sure the pool's devices are connected, then reboot your system and import the \
+            pool.",
+        ),
+        ZPOOL_STATUS_BAD_LOG => (
+            "An intent log record could not be read. Waiting for administrator intervention to \
+            fix the faulted pool.",
+            "Either restore the affected device(s) and run 'zpool online', or ignore the intent \
+            log records by running 'zpool clear'.",
+        ),
+        ZPOOL_STATUS_VERSION_OLDER => (
+            "The pool is formatted using a legacy on-disk format. The pool can still be used, \
+            but some features are unavailable.",
+            "Upgrade the pool using 'zpool upgrade'. Once this is done, the pool will no longer \
+            be accessible on software that does not support feature flags.",
+        ),
+        ZPOOL_STATUS_VERSION_NEWER => (
+            "The pool has been upgraded to a newer, incompatible on-disk version. The pool \
+            cannot be accessed on this system.",
+            "Access the pool from a system running more recent software, or restore the pool \
+            from backup.",
+        ),
+        ZPOOL_STATUS_FEAT_DISABLED => (
+            "Some supported and requested features are not enabled on the pool. The pool can \
+            still be used, but some features are unavailable.",
+            "Enable all features using 'zpool upgrade'. Once this is done, the pool may no \
+            longer be accessible by software that does not support the features.",
+        ),
+        ZPOOL_STATUS_UNSUP_FEAT_READ => (
+            "The pool cannot be accessed on this system because it uses features not supported \
+            on this system.",
+            "Access the pool from a system that supports the required features, or restore the \
+            pool from backup.",
+        ),
+        ZPOOL_STATUS_UNSUP_FEAT_WRITE => (
+            "The pool can only be accessed in read-only mode on this system. It cannot be \
+            accessed in read-write mode because it uses features not supported on this system.",
+            "Import the pool with \"-o readonly=on\", access the pool from a system that \
+            supports the required features, or restore the pool from backup.",
+        ),
+        ZPOOL_STATUS_HOSTID_MISMATCH => (
+            "Mismatch between pool hostid and system hostid on imported pool. This pool was \
+            previously imported into a system with a different hostid, and then was verbatim \
+            imported into this system.",
+            "Export this pool on all systems on which it is imported. Then import it to correct \
+            the mismatch.",
+        ),
+        ZPOOL_STATUS_NON_NATIVE_ASHIFT => (
+            "One or more devices are configured to use a non-native block size. Expect reduced \
+            performance.",
+            "Replace affected devices with devices that support the configured block size, or \
+            migrate data to a properly configured pool.",
+        ),
+        ZPOOL_STATUS_ERRATA => (
+            "An on-disk format errata affecting this pool was detected.",
+            "See the message ID for what to do.",
+        ),
+        #[cfg(zfs_2_1)]
+        ZPOOL_STATUS_COMPATIBILITY_ERR => (
+            "This pool has a compatibility list specified, but it could not be read or parsed \
+            at this time. The pool can still be used, but this should be investigated.",
+            "Check the value of the 'compatibility' property against the appropriate file in \
+            /usr/share/zfs/compatibility.d or /etc/zfs/compatibility.d.",
+        ),
+        #[cfg(zfs_2_1)]
+        ZPOOL_STATUS_INCOMPATIBLE_FEAT => (
+            "One or more features are enabled on the pool despite not being requested by the \
+            'compatibility' property.",
+            "Consider setting 'compatibility' to an appropriate value, or adding needed \
+            features to the relevant file in /etc/zfs/compatibility.d or \
+            /usr/share/zfs/compatibility.d.",
+        ),
+        _ => return None,
+    })
+}
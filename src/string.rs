@@ -1,5 +1,11 @@
+use crate::{Error, Result};
+use crate::handle::LockGuard;
+
+use std::borrow::{Borrow, Cow};
 use std::ffi::{CStr, CString};
 use std::fmt;
+use std::ops::Deref;
+use std::os::raw::c_char;
 
 /// A FFI-friendly string: null-terminated, no internal nulls, well-formed UTF-8. Lets us skip
 /// checks and reallocations when passing around between functions.
@@ -24,17 +30,36 @@ impl From<SafeString> for String {
     }
 }
 
-impl From<String> for SafeString {
-    fn from(s: String) -> SafeString {
-        SafeString {
-            inner: CString::new(s).unwrap()
+impl TryFrom<String> for SafeString {
+    type Error = Error;
+    fn try_from(s: String) -> Result<SafeString> {
+        match CString::new(s) {
+            Ok(inner) => Ok(SafeString { inner }),
+            Err(e) => Err(Error::invalid_input(e)),
+        }
+    }
+}
+
+impl<'a> TryFrom<&'a str> for SafeString {
+    type Error = Error;
+    fn try_from(s: &'a str) -> Result<SafeString> {
+        SafeString::try_from(s.to_owned())
+    }
+}
+
+impl<'a> TryFrom<&'a CStr> for SafeString {
+    type Error = Error;
+    fn try_from(s: &'a CStr) -> Result<SafeString> {
+        match s.to_str() {
+            Ok(_) => Ok(SafeString { inner: s.to_owned() }),
+            Err(e) => Err(Error::invalid_data(e)),
         }
     }
 }
 
-impl<'a> From<&'a str> for SafeString {
-    fn from(s: &'a str) -> SafeString {
-        SafeString::from(s.to_owned())
+impl<'a> From<&'a SafeString> for SafeString {
+    fn from(s: &'a SafeString) -> SafeString {
+        s.clone()
     }
 }
 
@@ -53,13 +78,253 @@ impl AsRef<CStr> for SafeString {
 }
 
 impl fmt::Debug for SafeString {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Debug::fmt(&self.inner, f)
     }
 }
 
 impl fmt::Display for SafeString {
-    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         fmt::Display::fmt(AsRef::<str>::as_ref(self), f)
     }
 }
+
+impl Deref for SafeString {
+    type Target = SafeStr;
+    fn deref(&self) -> &SafeStr {
+        // Safety: we already checked its UTF-8'ness
+        unsafe { SafeStr::from_cstr_unchecked(&self.inner) }
+    }
+}
+
+impl Borrow<SafeStr> for SafeString {
+    fn borrow(&self) -> &SafeStr {
+        self
+    }
+}
+
+impl<'a> From<&'a SafeStr> for SafeString {
+    fn from(s: &'a SafeStr) -> SafeString {
+        SafeString { inner: s.inner.to_owned() }
+    }
+}
+
+/// The borrowed counterpart of [`SafeString`], like `str` is to `String`. Lets names which are
+/// already null-terminated, like those from libzfs or C string literals, be passed back in
+/// without copying.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[repr(transparent)]
+pub struct SafeStr {
+    inner: CStr,
+}
+
+impl SafeStr {
+    /// Borrow a C string as a `SafeStr`, checking that it's valid UTF-8.
+    pub fn from_cstr(s: &CStr) -> Result<&SafeStr> {
+        match s.to_str() {
+            Ok(_) => Ok(unsafe { SafeStr::from_cstr_unchecked(s) }),
+            Err(e) => Err(Error::invalid_data(e)),
+        }
+    }
+
+    /// Borrow a C string as a `SafeStr` without checking it.
+    ///
+    /// # Safety
+    /// The string must be valid UTF-8.
+    pub unsafe fn from_cstr_unchecked(s: &CStr) -> &SafeStr {
+        &*(s as *const CStr as *const SafeStr)
+    }
+
+    pub fn as_ptr(&self) -> *const c_char {
+        self.inner.as_ptr()
+    }
+
+    pub fn as_str(&self) -> &str {
+        // Safety: we already checked its UTF-8'ness
+        unsafe { std::str::from_utf8_unchecked(self.inner.to_bytes()) }
+    }
+
+    pub fn as_c_str(&self) -> &CStr {
+        &self.inner
+    }
+}
+
+impl ToOwned for SafeStr {
+    type Owned = SafeString;
+    fn to_owned(&self) -> SafeString {
+        SafeString::from(self)
+    }
+}
+
+impl<'a> TryFrom<&'a CStr> for &'a SafeStr {
+    type Error = Error;
+    fn try_from(s: &'a CStr) -> Result<&'a SafeStr> {
+        SafeStr::from_cstr(s)
+    }
+}
+
+impl AsRef<str> for SafeStr {
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl AsRef<CStr> for SafeStr {
+    fn as_ref(&self) -> &CStr {
+        &self.inner
+    }
+}
+
+impl fmt::Debug for SafeStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.inner, f)
+    }
+}
+
+impl fmt::Display for SafeStr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+/// Things which can be passed where libzfs wants a name: string types, which are checked and
+/// copied, and `SafeStr`/`SafeString`, which are used as they are.
+pub trait AsSafeStr {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>>;
+}
+
+impl AsSafeStr for SafeStr {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl AsSafeStr for SafeString {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>> {
+        Ok(Cow::Borrowed(self))
+    }
+}
+
+impl AsSafeStr for str {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>> {
+        SafeString::try_from(self).map(Cow::Owned)
+    }
+}
+
+impl AsSafeStr for String {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>> {
+        self.as_str().as_safe_str()
+    }
+}
+
+impl<T: AsSafeStr + ?Sized> AsSafeStr for &T {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>> {
+        (**self).as_safe_str()
+    }
+}
+
+/// The name of a pool or dataset, borrowed from its libzfs handle. The library handle's lock is
+/// held for as long as it's borrowed, so the name can't be changed under it, like by a rename
+/// on another thread.
+pub struct BorrowedName<'a> {
+    name: &'a SafeStr,
+    _lock: LockGuard<'a>,
+}
+
+impl<'a> BorrowedName<'a> {
+    /// Borrow `name` from a handle whose lock is held by `lock`.
+    pub(crate) fn new(name: &'a SafeStr, lock: LockGuard<'a>) -> Self {
+        BorrowedName { name, _lock: lock }
+    }
+}
+
+impl Deref for BorrowedName<'_> {
+    type Target = SafeStr;
+    fn deref(&self) -> &SafeStr {
+        self.name
+    }
+}
+
+impl PartialEq for BorrowedName<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+    }
+}
+
+impl Eq for BorrowedName<'_> {}
+
+impl PartialOrd for BorrowedName<'_> {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for BorrowedName<'_> {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.name.cmp(other.name)
+    }
+}
+
+impl AsRef<str> for BorrowedName<'_> {
+    fn as_ref(&self) -> &str {
+        self.name.as_str()
+    }
+}
+
+impl AsRef<CStr> for BorrowedName<'_> {
+    fn as_ref(&self) -> &CStr {
+        self.name.as_c_str()
+    }
+}
+
+impl fmt::Debug for BorrowedName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(self.name, f)
+    }
+}
+
+impl fmt::Display for BorrowedName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self.name, f)
+    }
+}
+
+impl AsSafeStr for BorrowedName<'_> {
+    fn as_safe_str(&self) -> Result<Cow<'_, SafeStr>> {
+        Ok(Cow::Borrowed(self.name))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SafeStr {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+        where S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for SafeString {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+        where S: serde::Serializer,
+    {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for SafeString {
+    fn deserialize<D>(deserializer: D) -> std::result::Result<Self, D::Error>
+        where D: serde::Deserializer<'de>,
+    {
+        let s = String::deserialize(deserializer)?;
+        SafeString::try_from(s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Read a null-terminated string out of a buffer that libzfs filled in.
+pub(crate) fn string_from_buf(buf: &[c_char]) -> String {
+    let bytes: Vec<u8> = buf.iter().take_while(|&&c| c != 0).map(|&c| c as u8).collect();
+    String::from_utf8_lossy(&bytes).into_owned()
+}
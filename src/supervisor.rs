@@ -0,0 +1,221 @@
+use crate::{sys, Result, SafeString, ZPool, ZfsSend};
+
+/// Identifies an operation tracked by a [`Supervisor`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct OperationId(u64);
+
+/// The kind of a long-running operation.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Activity {
+    Scrub,
+    Trim,
+    Send,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OperationState {
+    Running,
+    Paused,
+    Finished,
+    Canceled,
+    Failed(String),
+}
+
+impl OperationState {
+    /// Whether the operation has stopped for good, one way or another.
+    pub fn is_done(&self) -> bool {
+        !matches!(self, OperationState::Running | OperationState::Paused)
+    }
+}
+
+/// How far along an operation is, in bytes.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Progress {
+    pub done: u64,
+    /// The (possibly estimated) total, if known.
+    pub total: Option<u64>,
+}
+
+/// A snapshot of the status of a tracked operation, as of the last [`Supervisor::poll`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OperationStatus {
+    pub id: OperationId,
+    pub activity: Activity,
+    /// The pool or dataset the operation is running on.
+    pub target: SafeString,
+    pub state: OperationState,
+    pub progress: Option<Progress>,
+    /// Why the status couldn't be refreshed by the last poll, if it couldn't. The rest of it is
+    /// then as of the last poll which could.
+    pub refresh_error: Option<String>,
+}
+
+enum Tracked<'a> {
+    Pool(&'a ZPool),
+    Send(Option<ZfsSend<'a>>),
+}
+
+type CompletionCallback<'a> = Box<dyn FnMut(&OperationStatus) + 'a>;
+
+struct Entry<'a> {
+    status: OperationStatus,
+    op: Tracked<'a>,
+    callbacks: Vec<CompletionCallback<'a>>,
+}
+
+/// Tracks long-running operations (scrubs, TRIMs, and sends) started through this crate, so
+/// their progress can be checked in one place and callbacks run when they complete.
+///
+/// Nothing happens in the background: statuses are refreshed, and callbacks run, only when
+/// [`poll`](Supervisor::poll) is called.
+#[derive(Default)]
+pub struct Supervisor<'a> {
+    next_id: u64,
+    entries: Vec<Entry<'a>>,
+}
+
+impl<'a> Supervisor<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a scrub of the given pool and track it.
+    pub fn start_scrub(&mut self, pool: &'a ZPool) -> Result<OperationId> {
+        pool.scrub()?;
+        Ok(self.track(Activity::Scrub, pool.get_name(), Tracked::Pool(pool)))
+    }
+
+    /// Start a TRIM of the given pool and track it.
+    pub fn start_trim(&mut self, pool: &'a ZPool) -> Result<OperationId> {
+        pool.trim()?;
+        Ok(self.track(Activity::Trim, pool.get_name(), Tracked::Pool(pool)))
+    }
+
+    /// Track a send which has already been started.
+    pub fn track_send(&mut self, send: ZfsSend<'a>) -> OperationId {
+        let target = send.dataset.get_name();
+        self.track(Activity::Send, target, Tracked::Send(Some(send)))
+    }
+
+    fn track(&mut self, activity: Activity, target: SafeString, op: Tracked<'a>) -> OperationId {
+        let id = OperationId(self.next_id);
+        self.next_id += 1;
+        self.entries.push(Entry {
+            status: OperationStatus {
+                id,
+                activity,
+                target,
+                state: OperationState::Running,
+                progress: None,
+                refresh_error: None,
+            },
+            op,
+            callbacks: vec![],
+        });
+        id
+    }
+
+    /// Register a callback to be run (from within [`poll`](Supervisor::poll)) when the given
+    /// operation completes. Returns false if the operation isn't being tracked.
+    pub fn on_complete<F>(&mut self, id: OperationId, callback: F) -> bool
+        where F: FnMut(&OperationStatus) + 'a,
+    {
+        match self.entries.iter_mut().find(|e| e.status.id == id) {
+            Some(entry) => {
+                entry.callbacks.push(Box::new(callback));
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Get the status of all operations still in progress, as of the last poll.
+    pub fn list_active(&self) -> Vec<&OperationStatus> {
+        self.entries.iter().map(|e| &e.status).collect()
+    }
+
+    /// Refresh the status of all tracked operations. Operations which have completed have their
+    /// callbacks run, stop being tracked, and have their final status returned. An operation
+    /// whose status can't be refreshed keeps being tracked, with the error in its
+    /// [`refresh_error`](OperationStatus::refresh_error), and the rest are still refreshed.
+    pub fn poll(&mut self) -> Vec<OperationStatus> {
+        for entry in &mut self.entries {
+            entry.status.refresh_error = refresh(entry).err().map(|e| e.to_string());
+        }
+
+        let mut done = vec![];
+        let mut i = 0;
+        while i < self.entries.len() {
+            if self.entries[i].status.state.is_done() {
+                let mut entry = self.entries.remove(i);
+                for cb in &mut entry.callbacks {
+                    cb(&entry.status);
+                }
+                done.push(entry.status);
+            } else {
+                i += 1;
+            }
+        }
+        done
+    }
+}
+
+fn refresh(entry: &mut Entry<'_>) -> Result<()> {
+    let status = &mut entry.status;
+    match (&mut entry.op, status.activity) {
+        (Tracked::Pool(pool), Activity::Scrub) => {
+            use sys::dsl_scan_state_t::*;
+            let Some(pss) = pool.scan_stats()? else {
+                status.state = OperationState::Finished;
+                return Ok(());
+            };
+            status.state = if pss.pss_func != sys::pool_scan_func_t::POOL_SCAN_SCRUB as u64 {
+                // Something else (like a resilver) replaced our scrub.
+                OperationState::Canceled
+            } else {
+                match pss.pss_state as sys::dsl_scan_state_t::Type {
+                    DSS_FINISHED => OperationState::Finished,
+                    DSS_CANCELED => OperationState::Canceled,
+                    _ if pss.pss_pass_scrub_pause != 0 => OperationState::Paused,
+                    _ => OperationState::Running,
+                }
+            };
+            status.progress = Some(Progress {
+                done: pss.pss_issued,
+                total: Some(pss.pss_to_examine),
+            });
+        }
+        (Tracked::Pool(pool), _) => {
+            use sys::vdev_trim_state_t::*;
+            let stats = pool.leaf_vdev_stats()?;
+            let any = |state| stats.iter().any(|vs| vs.vs_trim_state == state as u64);
+            status.state = if any(VDEV_TRIM_ACTIVE) {
+                OperationState::Running
+            } else if any(VDEV_TRIM_SUSPENDED) {
+                OperationState::Paused
+            } else if any(VDEV_TRIM_CANCELED) {
+                OperationState::Canceled
+            } else {
+                OperationState::Finished
+            };
+            status.progress = Some(Progress {
+                done: stats.iter().map(|vs| vs.vs_trim_bytes_done).sum(),
+                total: Some(stats.iter().map(|vs| vs.vs_trim_bytes_est).sum()),
+            });
+        }
+        (Tracked::Send(send), _) => {
+            if send.as_ref().map(|s| s.is_finished()).unwrap_or(true) {
+                status.state = match send.take().map(ZfsSend::wait) {
+                    Some(Err(e)) => OperationState::Failed(e.to_string()),
+                    _ => OperationState::Finished,
+                };
+            } else if let Some(Ok(p)) = send.as_ref().map(ZfsSend::progress) {
+                status.progress = Some(Progress {
+                    done: p.bytes_written,
+                    total: None,
+                });
+            }
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,57 @@
+use crate::ZfsError;
+
+use std::fmt::Display;
+use std::io;
+use std::os::raw::c_int;
+use std::time::Instant;
+
+/// Log a libzfs call made through [`ztry!`](crate::ztry): which function it was, what it was
+/// called on (if known), how long it took, and the error code and action, if it failed.
+/// Successful calls are logged at debug level and failures at warn, all with the `libzfs` target.
+pub(crate) fn ffi_call(expr: &str, target: &dyn Display, start: Instant, error: Option<&ZfsError>) {
+    let op = function_name(expr);
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    match error {
+        None => tracing::debug!(target: "libzfs", op, target = %target, elapsed_us, "ok"),
+        Some(e) => tracing::warn!(
+            target: "libzfs",
+            op,
+            target = %target,
+            elapsed_us,
+            code = ?e.code,
+            action = %e.action,
+            "{}", e.msg,
+        ),
+    }
+}
+
+/// Log a libzfs_core call made through `lzc_call!`, the same way as [`ffi_call`], with the
+/// errno it returned if it failed.
+pub(crate) fn lzc_call(expr: &str, target: &dyn Display, start: Instant, ret: c_int) {
+    let op = function_name(expr);
+    let elapsed_us = start.elapsed().as_micros() as u64;
+    if ret == 0 {
+        tracing::debug!(target: "libzfs", op, target = %target, elapsed_us, "ok");
+    } else {
+        tracing::warn!(
+            target: "libzfs",
+            op,
+            target = %target,
+            elapsed_us,
+            errno = ret,
+            "{}", io::Error::from_raw_os_error(ret),
+        );
+    }
+}
+
+/// Find the name of the libzfs function called in the text of an expression, like `zfs_mount`
+/// in `unsafe { sys::zfs_mount(self.handle, ...) }`, or `zfs_share` in a call to the shim
+/// `compat::zfs_share`.
+fn function_name(expr: &str) -> &str {
+    let Some((_, rest)) = expr.split_once("sys").or_else(|| expr.split_once("compat")) else {
+        return expr;
+    };
+    let rest = rest.trim_start().trim_start_matches("::").trim_start();
+    let end = rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(rest.len());
+    &rest[.. end]
+}
@@ -0,0 +1,133 @@
+use crate::{sys, ztry, Dataset, Error, Result, ZfsError};
+use sys::zfs_userquota_prop_t::*;
+
+use std::collections::BTreeMap;
+use std::ffi::CStr;
+use std::os::raw::{c_char, c_int, c_void};
+
+/// What space is accounted to, for [`Dataset::space_usage`].
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum SpaceOwner {
+    /// The user owning each file, like `zfs userspace`.
+    User,
+    /// The group owning each file, like `zfs groupspace`.
+    Group,
+    /// The project ID of each file, like `zfs projectspace`.
+    Project,
+}
+
+impl SpaceOwner {
+    /// The start of the names of the properties for this kind of owner, like `user`.
+    fn prefix(self) -> &'static str {
+        match self {
+            SpaceOwner::User => "user",
+            SpaceOwner::Group => "group",
+            SpaceOwner::Project => "project",
+        }
+    }
+
+    /// The properties for space used, space quota, objects used, and objects quota.
+    fn props(self) -> [sys::zfs_userquota_prop_t::Type; 4] {
+        match self {
+            SpaceOwner::User => [
+                ZFS_PROP_USERUSED, ZFS_PROP_USERQUOTA,
+                ZFS_PROP_USEROBJUSED, ZFS_PROP_USEROBJQUOTA,
+            ],
+            SpaceOwner::Group => [
+                ZFS_PROP_GROUPUSED, ZFS_PROP_GROUPQUOTA,
+                ZFS_PROP_GROUPOBJUSED, ZFS_PROP_GROUPOBJQUOTA,
+            ],
+            SpaceOwner::Project => [
+                ZFS_PROP_PROJECTUSED, ZFS_PROP_PROJECTQUOTA,
+                ZFS_PROP_PROJECTOBJUSED, ZFS_PROP_PROJECTOBJQUOTA,
+            ],
+        }
+    }
+}
+
+/// How much one user, group, or project uses of a filesystem, and its quotas there, like a line
+/// of `zfs userspace`, `groupspace`, or `projectspace`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SpaceUsage {
+    /// For Windows SIDs from SMB, the domain part; the `id` is then the SID's last part.
+    /// Empty for POSIX IDs.
+    pub domain: String,
+    /// The user, group, or project ID.
+    pub id: u32,
+    /// Space used, in bytes.
+    pub used: u64,
+    /// The space quota in bytes, if there is one.
+    pub quota: Option<u64>,
+    /// How many files and directories (objects) it has.
+    pub objects_used: u64,
+    /// The object quota, if there is one.
+    pub objects_quota: Option<u64>,
+}
+
+impl Dataset {
+    /// List the space used by each user, group, or project in this filesystem, along with any
+    /// quotas, like `zfs userspace -n`, `groupspace -n`, or `projectspace`. Everything which
+    /// uses any space or has a quota is listed, ordered by domain and ID.
+    pub fn space_usage(&self, owner: SpaceOwner) -> Result<Vec<SpaceUsage>> {
+        struct Context {
+            values: BTreeMap<(String, u32), [u64; 4]>,
+            which: usize,
+        }
+
+        extern "C" fn collect(
+            context: *mut c_void,
+            domain: *const c_char,
+            rid: libc::uid_t,
+            space: u64,
+        ) -> c_int {
+            let ctx = unsafe { &mut *(context as *mut Context) };
+            let domain = if domain.is_null() {
+                String::new()
+            } else {
+                unsafe { CStr::from_ptr(domain) }.to_string_lossy().into_owned()
+            };
+            ctx.values.entry((domain, rid)).or_default()[ctx.which] = space;
+            0
+        }
+
+        let _lock = self.libzfs.lock();
+        let mut ctx = Context { values: BTreeMap::new(), which: 0 };
+        for (which, prop) in owner.props().into_iter().enumerate() {
+            ctx.which = which;
+            ztry!(unsafe {
+                let ctx = &mut ctx as *mut _ as *mut c_void;
+                sys::zfs_userspace(self.handle, prop, Some(collect), ctx)
+            }, self.libzfs.raw(), self.name());
+        }
+
+        let quota = |q: u64| (q != 0).then_some(q);
+        Ok(ctx.values.into_iter()
+            .map(|((domain, id), [used, space_quota, objects_used, objects_quota])| SpaceUsage {
+                domain,
+                id,
+                used,
+                quota: quota(space_quota),
+                objects_used,
+                objects_quota: quota(objects_quota),
+            })
+            .collect())
+    }
+
+    /// Set or remove the limit on the space used in this filesystem by the given user, group, or
+    /// project ID, in bytes, like `zfs set userquota@<id>=`.
+    pub fn set_space_quota(&self, owner: SpaceOwner, id: u32, quota: Option<u64>) -> Result<()> {
+        let value = quota.map_or("none".to_owned(), |q| q.to_string());
+        self.set_property(&format!("{}quota@{}", owner.prefix(), id), &value)
+    }
+
+    /// Set or remove the limit on how many files and directories the given user, group, or
+    /// project ID may have in this filesystem, like `zfs set userobjquota@<id>=`.
+    pub fn set_objects_quota(&self, owner: SpaceOwner, id: u32, quota: Option<u64>)
+        -> Result<()>
+    {
+        let value = quota.map_or("none".to_owned(), |q| q.to_string());
+        self.set_property(&format!("{}objquota@{}", owner.prefix(), id), &value)
+    }
+}
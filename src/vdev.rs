@@ -0,0 +1,632 @@
+use crate::compat;
+use crate::{sys, ztry, Error, NvList, NvListRef, Result, SafeString, ScanState, ZfsError, ZPool};
+
+use std::borrow::Cow;
+use std::ffi::CStr;
+use std::fs;
+use std::os::raw::c_int;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Indexes into the per-type I/O counters in vdev stats.
+const ZIO_TYPE_READ: usize = 1;
+const ZIO_TYPE_WRITE: usize = 2;
+
+/// The health of a vdev, like the `STATE` column of `zpool status`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum VdevState {
+    Unknown,
+    Closed,
+    Offline,
+    Removed,
+    /// The device couldn't be opened, usually because it's missing.
+    CantOpen,
+    Faulted,
+    Degraded,
+    Healthy,
+}
+
+impl VdevState {
+    pub(crate) fn from_raw(raw: sys::vdev_state::Type) -> Self {
+        use sys::vdev_state::*;
+        match raw {
+            VDEV_STATE_CLOSED => VdevState::Closed,
+            VDEV_STATE_OFFLINE => VdevState::Offline,
+            VDEV_STATE_REMOVED => VdevState::Removed,
+            VDEV_STATE_CANT_OPEN => VdevState::CantOpen,
+            VDEV_STATE_FAULTED => VdevState::Faulted,
+            VDEV_STATE_DEGRADED => VdevState::Degraded,
+            VDEV_STATE_HEALTHY => VdevState::Healthy,
+            _ => VdevState::Unknown,
+        }
+    }
+}
+
+/// A vdev in a pool's configuration, and the vdevs under it, from [`ZPool::vdevs`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Vdev {
+    /// The name `zpool status` shows, like `sda` or `mirror-0`.
+    pub name: String,
+    /// The kind of vdev, like `disk`, `file`, `mirror`, or `raidz`.
+    pub vdev_type: String,
+    pub guid: u64,
+    /// The device's path, for disks and files.
+    pub path: Option<PathBuf>,
+    pub state: VdevState,
+    /// Whether this is a top-level vdev for the intent log.
+    pub is_log: bool,
+    /// The usable size, in bytes.
+    pub size: u64,
+    /// How many bytes are in use.
+    pub allocated: u64,
+    /// How many more bytes the vdev could hold if expanded with [`ZPool::vdev_expand`], because
+    /// its devices have grown.
+    pub expand_size: u64,
+    /// Read and write operations and bytes since the pool was imported. For a cache device,
+    /// reads are L2ARC hits.
+    pub read_ops: u64,
+    pub write_ops: u64,
+    pub read_bytes: u64,
+    pub write_bytes: u64,
+    /// I/O errors since the pool was imported or the errors were last cleared, as `zpool
+    /// status` counts them.
+    pub read_errors: u64,
+    pub write_errors: u64,
+    pub checksum_errors: u64,
+    pub children: Vec<Vdev>,
+}
+
+impl Vdev {
+    /// The vdev's GUID as a string, which the vdev methods of [`ZPool`] (like
+    /// [`vdev_offline`](ZPool::vdev_offline)) accept in place of a device name. Unlike a path, it
+    /// doesn't change when devices are renumbered.
+    pub fn id(&self) -> String {
+        self.guid.to_string()
+    }
+}
+
+/// Which part of the pool a vdev is in.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum VdevClass {
+    /// A regular data vdev, or part of one.
+    Data,
+    /// An intent log vdev, or part of one.
+    Log,
+    Cache,
+    Spare,
+}
+
+/// A vdev found by [`ZPool::find_vdev`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FoundVdev {
+    pub vdev: Vdev,
+    pub class: VdevClass,
+}
+
+/// The raw stats of a RAIDZ expansion, as in OpenZFS 2.3's `pool_raidz_expand_stat_t`, which
+/// older headers don't have.
+#[repr(C)]
+#[derive(Debug, Copy, Clone)]
+struct RaidzExpandStat {
+    pres_state: u64,
+    pres_expanding_vdev: u64,
+    pres_start_time: u64,
+    pres_end_time: u64,
+    pres_to_reflow: u64,
+    pres_reflowed: u64,
+    pres_waiting_for_resilver: u64,
+}
+
+/// The progress of the current or most recent RAIDZ expansion, from
+/// [`ZPool::raidz_expansion_status`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RaidzExpansionStatus {
+    /// The index among the pool's top-level vdevs of the RAIDZ vdev being expanded.
+    pub vdev: u64,
+    pub state: ScanState,
+    pub start_time: SystemTime,
+    /// When the expansion finished or was canceled, if it has.
+    pub end_time: Option<SystemTime>,
+    /// How much data needs to be moved onto the new layout, in bytes.
+    pub to_reflow: u64,
+    /// How much data has been moved so far, in bytes.
+    pub reflowed: u64,
+    /// Whether the expansion is held up until a resilver finishes.
+    pub waiting_for_resilver: bool,
+}
+
+/// Where a leaf vdev's TRIM is at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum TrimState {
+    /// It has never been trimmed.
+    None,
+    Active,
+    Suspended,
+    Canceled,
+    Complete,
+    /// The device doesn't support TRIM.
+    Unsupported,
+}
+
+/// The TRIM progress of one leaf vdev, like a line of `zpool status -t`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VdevTrimStatus {
+    /// The device's path, for disks and files.
+    pub path: Option<PathBuf>,
+    pub guid: u64,
+    pub state: TrimState,
+    pub bytes_done: u64,
+    /// Roughly how many bytes there are to trim in total.
+    pub bytes_est: u64,
+    /// When the TRIM was started, or if it's over, when it finished, was canceled, or was
+    /// suspended.
+    pub action_time: Option<SystemTime>,
+    pub errors: u64,
+}
+
+impl VdevTrimStatus {
+    /// The average rate of an active TRIM so far, in bytes per second.
+    pub fn rate(&self) -> Option<u64> {
+        if self.state != TrimState::Active {
+            return None;
+        }
+        let elapsed = self.action_time?.elapsed().ok()?.as_secs();
+        (elapsed > 0).then(|| self.bytes_done / elapsed)
+    }
+}
+
+/// Where a leaf vdev's initialization is at.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum InitializeState {
+    /// It has never been initialized.
+    None,
+    Active,
+    Suspended,
+    Canceled,
+    Complete,
+}
+
+/// The initialization progress of one leaf vdev, like a line of `zpool status -i`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VdevInitializeStatus {
+    /// The device's path, for disks and files.
+    pub path: Option<PathBuf>,
+    pub guid: u64,
+    pub state: InitializeState,
+    pub bytes_done: u64,
+    /// Roughly how many bytes there are to initialize in total.
+    pub bytes_est: u64,
+    /// When initialization was started, or if it's over, when it finished, was canceled, or
+    /// was suspended.
+    pub action_time: Option<SystemTime>,
+    pub errors: u64,
+}
+
+impl ZPool {
+    /// Start TRIMming every leaf vdev in the pool which supports it.
+    pub fn trim(&self) -> Result<()> {
+        self.trim_cmd(sys::pool_trim_func_t::POOL_TRIM_START)
+    }
+
+    /// Stop an in-progress TRIM of the pool.
+    pub fn cancel_trim(&self) -> Result<()> {
+        self.trim_cmd(sys::pool_trim_func_t::POOL_TRIM_CANCEL)
+    }
+
+    fn trim_cmd(&self, cmd: sys::pool_trim_func_t::Type) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        let config = self.get_config()?;
+        let nvroot = vdev_tree(&config)?;
+        let vdevs = NvList::new()?;
+        unsafe { sys::zpool_collect_leaves(self.handle, nvroot.as_ptr(), vdevs.as_ptr()) };
+        let mut flags = sys::trimflags_t {
+            fullpool: 1,
+            secure: 0,
+            wait: 0,
+            rate: 0,
+        };
+        ztry!(unsafe {
+            sys::zpool_trim(self.handle, cmd, vdevs.as_ptr(), &mut flags)
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Get the pool's top-level vdevs, and the vdevs under them, like the tree printed by
+    /// `zpool status`.
+    pub fn vdevs(&self) -> Result<Vec<Vdev>> {
+        let config = self.get_config()?;
+        self.vdevs_in(&config)
+    }
+
+    /// Get the pool's top-level vdevs from an already fetched config.
+    pub(crate) fn vdevs_in(&self, config: &NvListRef) -> Result<Vec<Vdev>> {
+        let top_level = vdev_tree(config)?.lookup_nvlist_array("children").unwrap_or_default();
+        Ok(top_level.into_iter().map(|vdev| self.vdev(vdev)).collect())
+    }
+
+    /// Get the pool's cache (L2ARC) devices, like the `cache` section of `zpool status`.
+    pub fn cache_vdevs(&self) -> Result<Vec<Vdev>> {
+        let config = self.get_config()?;
+        let cache = vdev_tree(&config)?.lookup_nvlist_array("l2cache").unwrap_or_default();
+        Ok(cache.into_iter().map(|vdev| self.vdev(vdev)).collect())
+    }
+
+    /// Add devices to the pool as cache (L2ARC) devices, like `zpool add <pool> cache`.
+    pub fn add_cache_devices<P: AsRef<Path>>(&self, devices: &[P]) -> Result<()> {
+        let leaves = devices.iter()
+            .map(|device| leaf_nvlist(device.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        let mut nvroot = root_nvlist(&[])?;
+        nvroot.add_nvlist_array("l2cache", &refs(&leaves))?;
+        self.add_vdevs(&nvroot)
+    }
+
+    /// Get the pool's intent log (SLOG) vdevs, like the `logs` section of `zpool status`. A
+    /// missing or failed log device doesn't lose data unless the system also crashes, but
+    /// synchronous writes slow down until it's replaced.
+    pub fn log_vdevs(&self) -> Result<Vec<Vdev>> {
+        Ok(self.vdevs()?.into_iter().filter(|vdev| vdev.is_log).collect())
+    }
+
+    /// Add devices to the pool as intent log (SLOG) devices, like `zpool add <pool> log`. If
+    /// `mirrored`, they're added as a single mirrored log vdev, like
+    /// `zpool add <pool> log mirror`; otherwise each becomes a log vdev of its own.
+    ///
+    /// Log vdevs can be taken out again with [`remove_vdev`](Self::remove_vdev), by the name of
+    /// the device or the mirror (like `mirror-2`).
+    pub fn add_log_devices<P: AsRef<Path>>(&self, devices: &[P], mirrored: bool) -> Result<()> {
+        let mut leaves = devices.iter()
+            .map(|device| leaf_nvlist(device.as_ref()))
+            .collect::<Result<Vec<_>>>()?;
+        let top_level = if mirrored {
+            let mut mirror = NvList::new()?;
+            mirror.add_string("type", "mirror")?;
+            mirror.add_nvlist_array("children", &refs(&leaves))?;
+            mirror.add_uint64("is_log", 1)?;
+            vec![mirror]
+        } else {
+            for leaf in &mut leaves {
+                leaf.add_uint64("is_log", 1)?;
+            }
+            leaves
+        };
+        let nvroot = root_nvlist(&refs(&top_level))?;
+        self.add_vdevs(&nvroot)
+    }
+
+    /// Remove a device from the pool, like `zpool remove`. Cache devices, spares, and log
+    /// devices can always be removed; top-level data vdevs only on pools without RAIDZ, by
+    /// copying their data elsewhere, which carries on in the background. The `device` is named
+    /// as for `zpool remove`: a path, a name like `sda` or `mirror-1`, or a GUID.
+    pub fn remove_vdev(&self, device: &str) -> Result<()> {
+        let device = SafeString::try_from(device)?;
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zpool_vdev_remove(self.handle, device.as_ptr())
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    fn add_vdevs(&self, nvroot: &NvListRef) -> Result<()> {
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            compat::zpool_add(self.handle, nvroot.as_ptr())
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    pub(crate) fn vdev(&self, nv: &NvListRef) -> Vdev {
+        let name = {
+            let _lock = self.libzfs.lock();
+            let raw = unsafe {
+                sys::zpool_vdev_name(self.libzfs.raw(), self.handle, nv.as_ptr(), 0)
+            };
+            if raw.is_null() {
+                String::new()
+            } else {
+                let name = unsafe { CStr::from_ptr(raw) }.to_string_lossy().into_owned();
+                unsafe { libc::free(raw.cast()) };
+                name
+            }
+        };
+        let vs = nv.lookup_struct::<sys::vdev_stat_t>("vdev_stats");
+        Vdev {
+            name,
+            vdev_type: nv.lookup_string("type").map(|t| t.into_owned()).unwrap_or_default(),
+            guid: nv.lookup_uint64("guid").unwrap_or_default(),
+            path: leaf_path(nv),
+            state: vs.map_or(VdevState::Unknown, |vs| {
+                VdevState::from_raw(vs.vs_state as sys::vdev_state::Type)
+            }),
+            is_log: nv.lookup_uint64("is_log") == Some(1),
+            size: vs.map_or(0, |vs| vs.vs_space),
+            allocated: vs.map_or(0, |vs| vs.vs_alloc),
+            expand_size: vs.map_or(0, |vs| vs.vs_esize),
+            read_ops: vs.map_or(0, |vs| vs.vs_ops[ZIO_TYPE_READ]),
+            write_ops: vs.map_or(0, |vs| vs.vs_ops[ZIO_TYPE_WRITE]),
+            read_bytes: vs.map_or(0, |vs| vs.vs_bytes[ZIO_TYPE_READ]),
+            write_bytes: vs.map_or(0, |vs| vs.vs_bytes[ZIO_TYPE_WRITE]),
+            read_errors: vs.map_or(0, |vs| vs.vs_read_errors),
+            write_errors: vs.map_or(0, |vs| vs.vs_write_errors),
+            checksum_errors: vs.map_or(0, |vs| vs.vs_checksum_errors),
+            children: nv.lookup_nvlist_array("children").unwrap_or_default()
+                .into_iter()
+                .map(|child| self.vdev(child))
+                .collect(),
+        }
+    }
+
+    /// Look up a vdev the way the `zpool` commands do: by path (with or without `/dev/`), by a
+    /// name like `sda` or `mirror-0`, or by GUID. Returns `None` if there's no such vdev.
+    pub fn find_vdev(&self, path_or_guid: &str) -> Result<Option<FoundVdev>> {
+        let path = SafeString::try_from(path_or_guid)?;
+        let _lock = self.libzfs.lock();
+        let mut spare = 0;
+        let mut l2cache = 0;
+        let mut log = 0;
+        let nv = unsafe {
+            sys::zpool_find_vdev(self.handle, path.as_ptr(), &mut spare, &mut l2cache, &mut log)
+        };
+        if nv.is_null() {
+            return Ok(None);
+        }
+        // The nvlist belongs to the pool handle's config, so copy what we need out of it now.
+        let vdev = self.vdev(unsafe { NvListRef::from_ptr(nv) });
+        let class = if spare != 0 {
+            VdevClass::Spare
+        } else if l2cache != 0 {
+            VdevClass::Cache
+        } else if log != 0 {
+            VdevClass::Log
+        } else {
+            VdevClass::Data
+        };
+        Ok(Some(FoundVdev { vdev, class }))
+    }
+
+    /// Bring a device back online, like `zpool online`, returning its new state.
+    pub fn vdev_online(&self, device: &str) -> Result<VdevState> {
+        self.vdev_online_flags(device, 0)
+    }
+
+    /// Take a device offline, like `zpool offline`. If `temporary`, it comes back online when
+    /// the pool is next imported.
+    pub fn vdev_offline(&self, device: &str, temporary: bool) -> Result<()> {
+        let device = SafeString::try_from(device)?;
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zpool_vdev_offline(self.handle, device.as_ptr(), temporary as sys::boolean_t)
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Replace a device with a new one, like `zpool replace`. The old device stays in the pool
+    /// until the new one has been resilvered.
+    pub fn vdev_replace<P: AsRef<Path>>(&self, old: &str, new_device: P) -> Result<()> {
+        self.attach_or_replace(old, new_device.as_ref(), true)
+    }
+
+    /// Grow a device to use all of its space, like `zpool online -e`, after the disk or LUN
+    /// under it has been enlarged. The `device` is given as for `zpool online`: a path, a name
+    /// like `sda`, or a GUID. With the pool's `autoexpand` property on, this happens by itself.
+    ///
+    /// The extra space only becomes usable once every device in the top-level vdev has grown;
+    /// see [`Vdev::expand_size`].
+    pub fn vdev_expand(&self, device: &str) -> Result<VdevState> {
+        self.vdev_online_flags(device, sys::ZFS_ONLINE_EXPAND as c_int)
+    }
+
+    fn vdev_online_flags(&self, device: &str, flags: c_int) -> Result<VdevState> {
+        let device = SafeString::try_from(device)?;
+        let _lock = self.libzfs.lock();
+        let mut newstate = 0;
+        ztry!(unsafe {
+            sys::zpool_vdev_online(
+                self.handle,
+                device.as_ptr(),
+                flags,
+                &mut newstate,
+            )
+        }, self.libzfs.raw(), self.name());
+        Ok(VdevState::from_raw(newstate))
+    }
+
+    /// Attach a new device to an existing vdev, like `zpool attach`. The `existing` vdev is
+    /// named as for `zpool attach`: a path, a name like `sda` or `raidz1-0`, or a GUID.
+    ///
+    /// Attaching to a disk (or a mirror) adds a mirror of it, which is then resilvered.
+    /// Attaching to a RAIDZ top-level vdev widens it by one device, on OpenZFS 2.3 and later;
+    /// its progress is reported by [`raidz_expansion_status`](Self::raidz_expansion_status).
+    ///
+    /// Unlike the command, this doesn't partition whole disks; the device is used as given.
+    pub fn vdev_attach<P: AsRef<Path>>(&self, existing: &str, new_device: P) -> Result<()> {
+        self.attach_or_replace(existing, new_device.as_ref(), false)
+    }
+
+    fn attach_or_replace(&self, existing: &str, new_device: &Path, replacing: bool) -> Result<()> {
+        let existing = SafeString::try_from(existing)?;
+        let new_name = SafeString::try_from(new_device.to_str()
+            .ok_or_else(|| Error::invalid_input("device path is not valid UTF-8"))?)?;
+        let leaf = leaf_nvlist(new_device)?;
+        let nvroot = root_nvlist(&[&leaf])?;
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zpool_vdev_attach(
+                self.handle,
+                existing.as_ptr(),
+                new_name.as_ptr(),
+                nvroot.as_ptr(),
+                replacing as c_int,
+                0, // rebuild: resilver rather than sequentially rebuild
+            )
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+
+    /// Get the progress of the current or most recent RAIDZ expansion, if the pool has ever had
+    /// one. Always `None` before OpenZFS 2.3.
+    pub fn raidz_expansion_status(&self) -> Result<Option<RaidzExpansionStatus>> {
+        use sys::dsl_scan_state_t::*;
+        let config = self.get_config()?;
+        let Some(pres) = vdev_tree(&config)?.lookup_struct::<RaidzExpandStat>("raidz_expand_stats")
+        else {
+            return Ok(None);
+        };
+        let state = match pres.pres_state as sys::dsl_scan_state_t::Type {
+            DSS_SCANNING => ScanState::Scanning,
+            DSS_FINISHED => ScanState::Finished,
+            DSS_CANCELED => ScanState::Canceled,
+            _ => return Ok(None),
+        };
+        Ok(Some(RaidzExpansionStatus {
+            vdev: pres.pres_expanding_vdev,
+            state,
+            start_time: UNIX_EPOCH + Duration::from_secs(pres.pres_start_time),
+            end_time: (state != ScanState::Scanning)
+                .then(|| UNIX_EPOCH + Duration::from_secs(pres.pres_end_time)),
+            to_reflow: pres.pres_to_reflow,
+            reflowed: pres.pres_reflowed,
+            waiting_for_resilver: pres.pres_waiting_for_resilver != 0,
+        }))
+    }
+
+    /// Get the TRIM progress of every leaf vdev in the pool.
+    pub fn trim_status(&self) -> Result<Vec<VdevTrimStatus>> {
+        use sys::vdev_trim_state_t::*;
+        let config = self.get_config()?;
+        let mut statuses = vec![];
+        visit_leaves(vdev_tree(&config)?, &mut |leaf| {
+            let Some(vs) = leaf.lookup_struct::<sys::vdev_stat_t>("vdev_stats") else {
+                return;
+            };
+            let state = match vs.vs_trim_state as sys::vdev_trim_state_t::Type {
+                _ if vs.vs_trim_notsup != 0 => TrimState::Unsupported,
+                VDEV_TRIM_ACTIVE => TrimState::Active,
+                VDEV_TRIM_SUSPENDED => TrimState::Suspended,
+                VDEV_TRIM_CANCELED => TrimState::Canceled,
+                VDEV_TRIM_COMPLETE => TrimState::Complete,
+                _ => TrimState::None,
+            };
+            statuses.push(VdevTrimStatus {
+                path: leaf_path(leaf),
+                guid: leaf.lookup_uint64("guid").unwrap_or_default(),
+                state,
+                bytes_done: vs.vs_trim_bytes_done,
+                bytes_est: vs.vs_trim_bytes_est,
+                action_time: action_time(vs.vs_trim_action_time),
+                errors: vs.vs_trim_errors,
+            });
+        });
+        Ok(statuses)
+    }
+
+    /// Get the initialization progress of every leaf vdev in the pool.
+    pub fn initialize_status(&self) -> Result<Vec<VdevInitializeStatus>> {
+        use sys::vdev_initializing_state_t::*;
+        let config = self.get_config()?;
+        let mut statuses = vec![];
+        visit_leaves(vdev_tree(&config)?, &mut |leaf| {
+            let Some(vs) = leaf.lookup_struct::<sys::vdev_stat_t>("vdev_stats") else {
+                return;
+            };
+            let state = match vs.vs_initialize_state as sys::vdev_initializing_state_t::Type {
+                VDEV_INITIALIZE_ACTIVE => InitializeState::Active,
+                VDEV_INITIALIZE_SUSPENDED => InitializeState::Suspended,
+                VDEV_INITIALIZE_CANCELED => InitializeState::Canceled,
+                VDEV_INITIALIZE_COMPLETE => InitializeState::Complete,
+                _ => InitializeState::None,
+            };
+            statuses.push(VdevInitializeStatus {
+                path: leaf_path(leaf),
+                guid: leaf.lookup_uint64("guid").unwrap_or_default(),
+                state,
+                bytes_done: vs.vs_initialize_bytes_done,
+                bytes_est: vs.vs_initialize_bytes_est,
+                action_time: action_time(vs.vs_initialize_action_time),
+                errors: vs.vs_initialize_errors,
+            });
+        });
+        Ok(statuses)
+    }
+
+    /// Get the raw stats of every leaf vdev in the pool.
+    pub(crate) fn leaf_vdev_stats(&self) -> Result<Vec<sys::vdev_stat_t>> {
+        let config = self.get_config()?;
+        let mut stats = vec![];
+        visit_leaves(vdev_tree(&config)?, &mut |leaf| {
+            if let Some(vs) = leaf.lookup_struct("vdev_stats") {
+                stats.push(vs);
+            }
+        });
+        Ok(stats)
+    }
+}
+
+pub(crate) fn vdev_tree(config: &NvListRef) -> Result<&NvListRef> {
+    config.lookup_nvlist("vdev_tree")
+        .ok_or_else(|| Error::invalid_data("pool config has no vdev tree"))
+}
+
+/// Call a function for every leaf (i.e. a vdev with no children) under the given vdev.
+pub(crate) fn visit_leaves<'a>(vdev: &'a NvListRef, f: &mut dyn FnMut(&'a NvListRef)) {
+    match vdev.lookup_nvlist_array("children") {
+        Some(children) => {
+            for child in children {
+                visit_leaves(child, f);
+            }
+        }
+        None => f(vdev),
+    }
+}
+
+fn leaf_path(leaf: &NvListRef) -> Option<PathBuf> {
+    leaf.lookup_string("path").map(|path| PathBuf::from(path.into_owned()))
+}
+
+/// Convert a vdev's last action time, which is 0 if there hasn't been one.
+fn action_time(secs: u64) -> Option<SystemTime> {
+    (secs != 0).then(|| UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+/// Build the config for a leaf vdev on a device or file, to be added to a pool.
+pub(crate) fn leaf_nvlist(device: &Path) -> Result<NvList> {
+    let device = &*block_device(device);
+    let path = device.to_str()
+        .ok_or_else(|| Error::invalid_input("device path is not valid UTF-8"))?;
+    let metadata = fs::metadata(device).map_err(Error::Sys)?;
+    let mut nvl = NvList::new()?;
+    nvl.add_string("type", if metadata.file_type().is_block_device() { "disk" } else { "file" })?;
+    nvl.add_string("path", path)?;
+    nvl.add_uint64("whole_disk", 0)?;
+    Ok(nvl)
+}
+
+/// On macOS, each disk has a raw character device, `/dev/rdiskN`, as well as the block device
+/// `/dev/diskN` which ZFS uses, so the one is swapped for the other.
+#[cfg(target_os = "macos")]
+fn block_device(device: &Path) -> Cow<'_, Path> {
+    match device.to_str().and_then(|path| path.strip_prefix("/dev/rdisk")) {
+        Some(rest) => Cow::Owned(PathBuf::from(format!("/dev/disk{}", rest))),
+        None => Cow::Borrowed(device),
+    }
+}
+
+#[cfg(not(target_os = "macos"))]
+fn block_device(device: &Path) -> Cow<'_, Path> {
+    Cow::Borrowed(device)
+}
+
+/// Build the root of a vdev tree with the given vdevs under it.
+pub(crate) fn root_nvlist(children: &[&NvListRef]) -> Result<NvList> {
+    let mut nvl = NvList::new()?;
+    nvl.add_string("type", "root")?;
+    nvl.add_nvlist_array("children", children)?;
+    Ok(nvl)
+}
+
+pub(crate) fn refs(lists: &[NvList]) -> Vec<&NvListRef> {
+    lists.iter().map(|nvl| &**nvl).collect()
+}
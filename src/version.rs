@@ -0,0 +1,82 @@
+use crate::compat;
+use crate::{Error, LibZfs, Result};
+
+use std::fmt;
+use std::str::FromStr;
+
+/// A ZFS version, like `zfs-2.2.2-1` for the userland tools and libraries, or
+/// `zfs-kmod-2.2.2-1` for the kernel module.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZfsVersion {
+    pub major: u32,
+    pub minor: u32,
+    pub patch: u32,
+    /// Whatever follows the version number, without its leading `-`, like `1` or
+    /// `rc3_5_g1a2b3c4`. Empty if there's nothing.
+    pub release: String,
+    /// The whole version string, as ZFS gave it.
+    pub full: String,
+}
+
+impl ZfsVersion {
+    /// The version number, as `(major, minor, patch)`, for comparisons.
+    pub fn number(&self) -> (u32, u32, u32) {
+        (self.major, self.minor, self.patch)
+    }
+}
+
+impl FromStr for ZfsVersion {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<ZfsVersion> {
+        let bad = || Error::invalid_data(format!("unrecognized ZFS version {:?}", s));
+        let rest = s.trim();
+        let rest = rest.strip_prefix("zfs-kmod-")
+            .or_else(|| rest.strip_prefix("zfs-"))
+            .unwrap_or(rest);
+        let (number, release) = rest.split_once('-').unwrap_or((rest, ""));
+        let mut parts = number.split('.').map(|n| n.parse::<u32>().map_err(|_| bad()));
+        let major = parts.next().ok_or_else(bad)??;
+        let minor = parts.next().unwrap_or(Ok(0))?;
+        let patch = parts.next().unwrap_or(Ok(0))?;
+        if parts.next().is_some() {
+            return Err(bad());
+        }
+        Ok(ZfsVersion { major, minor, patch, release: release.to_owned(), full: s.to_owned() })
+    }
+}
+
+impl fmt::Display for ZfsVersion {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.full)
+    }
+}
+
+/// The versions of ZFS's userland and kernel module, like `zfs version` shows.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ZfsVersions {
+    /// The version of the libzfs this was built against.
+    pub userland: ZfsVersion,
+    /// The version of the loaded kernel module.
+    pub kernel: ZfsVersion,
+}
+
+impl ZfsVersions {
+    /// Whether the userland and kernel module have the same version number. Mismatched ones
+    /// mostly work, but newer features may fail with unhelpful errors.
+    pub fn matched(&self) -> bool {
+        self.userland.number() == self.kernel.number()
+    }
+}
+
+impl LibZfs {
+    /// Get the versions of the ZFS userland and kernel module, like `zfs version`. Fails if the
+    /// kernel module's version can't be read, such as if it isn't loaded.
+    pub fn versions(&self) -> Result<ZfsVersions> {
+        let userland = compat::zfs_version_userland().parse()?;
+        let kernel = compat::zfs_version_kernel().map_err(Error::Sys)?.parse()?;
+
+        Ok(ZfsVersions { userland, kernel })
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{sys, Dataset, Error, Result, SafeString, ZPool};
+use crate::error::lzc_call;
+
+use std::io;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// Background activity on a filesystem which [`Dataset::wait`] can wait for.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum FsWaitActivity {
+    /// Freeing the space of files which were deleted while still open, like
+    /// `zfs wait -t deleteq`.
+    DeleteQueue,
+}
+
+impl From<FsWaitActivity> for sys::zfs_wait_activity_t::Type {
+    fn from(activity: FsWaitActivity) -> Self {
+        match activity {
+            FsWaitActivity::DeleteQueue => sys::zfs_wait_activity_t::ZFS_WAIT_DELETEQ,
+        }
+    }
+}
+
+/// Background activity on a pool which [`ZPool::wait`] can wait for, like the activities of
+/// `zpool wait -t`.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum PoolWaitActivity {
+    /// Discarding a checkpoint.
+    CheckpointDiscard,
+    /// Freeing the space of destroyed datasets.
+    Free,
+    /// Initializing devices.
+    Initialize,
+    /// Replacing devices.
+    Replace,
+    /// Removing a device, and copying its data elsewhere.
+    Remove,
+    Resilver,
+    Scrub,
+    Trim,
+}
+
+impl From<PoolWaitActivity> for sys::zpool_wait_activity_t::Type {
+    fn from(activity: PoolWaitActivity) -> Self {
+        use sys::zpool_wait_activity_t::*;
+        match activity {
+            PoolWaitActivity::CheckpointDiscard => ZPOOL_WAIT_CKPT_DISCARD,
+            PoolWaitActivity::Free => ZPOOL_WAIT_FREE,
+            PoolWaitActivity::Initialize => ZPOOL_WAIT_INITIALIZE,
+            PoolWaitActivity::Replace => ZPOOL_WAIT_REPLACE,
+            PoolWaitActivity::Remove => ZPOOL_WAIT_REMOVE,
+            PoolWaitActivity::Resilver => ZPOOL_WAIT_RESILVER,
+            PoolWaitActivity::Scrub => ZPOOL_WAIT_SCRUB,
+            PoolWaitActivity::Trim => ZPOOL_WAIT_TRIM,
+        }
+    }
+}
+
+impl Dataset {
+    /// Block until the given background activity on this filesystem is done, like `zfs wait`.
+    /// Returns whether there was anything to wait for.
+    pub fn wait(&self, activity: FsWaitActivity) -> Result<bool> {
+        let mut waited = 0;
+        let ret = lzc_call!(unsafe {
+            sys::lzc_wait_fs(self.name().as_ptr(), activity.into(), &mut waited)
+        }, self.name());
+        match ret {
+            0 => Ok(waited != 0),
+            e => Err(Error::Sys(io::Error::from_raw_os_error(e))),
+        }
+    }
+}
+
+impl ZPool {
+    /// Block until the given background activity on this pool is done, like `zpool wait`.
+    /// Returns whether there was anything to wait for.
+    pub fn wait(&self, activity: PoolWaitActivity) -> Result<bool> {
+        pool_wait(&self.get_name(), activity)
+    }
+
+    /// Like [`wait`](Self::wait), but give up after `timeout`, returning `None`.
+    ///
+    /// The kernel offers no way to stop waiting early, so on timeout a background thread is
+    /// left blocked until the activity finishes.
+    pub fn wait_timeout(&self, activity: PoolWaitActivity, timeout: Duration)
+        -> Result<Option<bool>>
+    {
+        let name = self.get_name();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(pool_wait(&name, activity));
+        });
+        match rx.recv_timeout(timeout) {
+            Ok(result) => result.map(Some),
+            Err(mpsc::RecvTimeoutError::Timeout) => Ok(None),
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                Err(Error::Sys(io::Error::other("pool wait thread panicked")))
+            }
+        }
+    }
+}
+
+fn pool_wait(pool: &SafeString, activity: PoolWaitActivity) -> Result<bool> {
+    let mut waited = 0;
+    match lzc_call!(unsafe { sys::lzc_wait(pool.as_ptr(), activity.into(), &mut waited) }, pool) {
+        0 => Ok(waited != 0),
+        e => Err(Error::Sys(io::Error::from_raw_os_error(e))),
+    }
+}
@@ -0,0 +1,335 @@
+use crate::{sys, Dataset, DatasetTypeMask, LibZfs, NvList, Result, SafeString, ZfsEvent};
+use crate::ZPoolState;
+use crate::event::ZEVENT_SEEK_END;
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::ptr;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often to check the event stream while waiting for the polling interval to pass.
+const EVENT_CHECK_INTERVAL: Duration = Duration::from_millis(250);
+
+/// What a [`Watcher`] should watch.
+#[derive(Debug, Clone, Default)]
+pub struct WatchSelector {
+    /// The pool or dataset to watch, along with everything under it. `None` watches all pools.
+    pub root: Option<SafeString>,
+    /// Properties of filesystems and volumes to report changes of.
+    pub properties: Vec<String>,
+    /// Whether to report snapshots being added and removed.
+    pub snapshots: bool,
+}
+
+/// A change noticed by a [`Watcher`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    DatasetAdded(SafeString),
+    DatasetRemoved(SafeString),
+    /// A watched property changed. A value of `None` means it couldn't be read.
+    PropertyChanged {
+        dataset: SafeString,
+        property: String,
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// A pool's state changed. A state of `None` means the pool isn't imported.
+    PoolStateChanged {
+        pool: SafeString,
+        old: Option<ZPoolState>,
+        new: Option<ZPoolState>,
+    },
+}
+
+#[derive(Default)]
+struct State {
+    pools: BTreeMap<SafeString, ZPoolState>,
+    datasets: BTreeMap<SafeString, DatasetState>,
+    /// The `snapshots_changed` property of each dataset whose snapshots were listed.
+    snapshot_stamps: BTreeMap<SafeString, Option<String>>,
+}
+
+/// Which pools the events read since the last scan were about.
+enum Touched {
+    Nothing,
+    Pools(BTreeSet<SafeString>),
+    /// Events were dropped, or didn't say which pool they were about.
+    Everything,
+}
+
+impl Touched {
+    fn add(&mut self, pool: Option<&SafeString>) {
+        match (&mut *self, pool) {
+            (Touched::Everything, _) => (),
+            (_, None) => *self = Touched::Everything,
+            (Touched::Nothing, Some(pool)) => {
+                *self = Touched::Pools(BTreeSet::from([pool.clone()]));
+            }
+            (Touched::Pools(pools), Some(pool)) => {
+                pools.insert(pool.clone());
+            }
+        }
+    }
+}
+
+#[derive(Clone)]
+struct DatasetState {
+    /// Tells apart a dataset which was destroyed and re-created with the same name.
+    createtxg: Option<String>,
+    /// Values of the watched properties, in the selector's order. Empty for snapshots.
+    props: Vec<Option<String>>,
+}
+
+/// Watches pools and datasets for changes, created by [`LibZfs::watch`].
+///
+/// Changes are found by rescanning. Where the ZFS event stream can be read (which needs root),
+/// events trigger a rescan of only the pools they're about, and everything is rescanned once
+/// per polling interval, for changes which post no events, like a property set by the kernel.
+/// Otherwise, everything is rescanned once per polling interval. Rescans only list the snapshots
+/// of datasets whose `snapshots_changed` property has changed.
+pub struct Watcher<'a> {
+    libzfs: &'a LibZfs,
+    selector: WatchSelector,
+    interval: Duration,
+    /// `/dev/zfs`, for reading the event stream, if it could be opened.
+    zevent_fd: Option<OwnedFd>,
+    state: State,
+    /// When everything was last rescanned.
+    last_full_scan: Instant,
+}
+
+impl LibZfs {
+    /// Start watching the pools and datasets picked by the selector, checking for changes at
+    /// least once per interval. Things as they are now are the baseline: only changes from here
+    /// on are reported.
+    pub fn watch(&self, selector: WatchSelector, interval: Duration) -> Result<Watcher<'_>> {
+        let mut watcher = Watcher {
+            libzfs: self,
+            selector,
+            interval,
+            zevent_fd: None,
+            state: State::default(),
+            last_full_scan: Instant::now(),
+        };
+        watcher.zevent_fd = watcher.open_events();
+        watcher.state = watcher.scan(None)?;
+        Ok(watcher)
+    }
+}
+
+impl<'a> Watcher<'a> {
+    /// Check everything for changes now, without waiting.
+    pub fn poll(&mut self) -> Result<Vec<WatchEvent>> {
+        self.drain_events();
+        self.rescan(None)
+    }
+
+    /// Wait until ZFS posts an event or the polling interval passes, whichever comes first, then
+    /// check for changes: in the pools the events were about, or everywhere once the interval
+    /// has passed. The result may be empty if nothing relevant changed.
+    pub fn wait(&mut self) -> Result<Vec<WatchEvent>> {
+        let deadline = self.last_full_scan + self.interval;
+        if self.zevent_fd.is_none() {
+            thread::sleep(deadline.saturating_duration_since(Instant::now()));
+            return self.poll();
+        }
+        loop {
+            let now = Instant::now();
+            if now >= deadline {
+                return self.poll();
+            }
+            match self.drain_events() {
+                Touched::Nothing => thread::sleep(EVENT_CHECK_INTERVAL.min(deadline - now)),
+                Touched::Pools(pools) => return self.rescan(Some(&pools)),
+                Touched::Everything => return self.rescan(None),
+            }
+        }
+    }
+
+    /// Rescan everything, or only the given pools, and report what changed.
+    fn rescan(&mut self, only: Option<&BTreeSet<SafeString>>) -> Result<Vec<WatchEvent>> {
+        let new = self.scan(only)?;
+        let events = diff(&self.state, &new, &self.selector.properties);
+        self.state = new;
+        Ok(events)
+    }
+
+    /// Open the event stream, skipping over any events already posted.
+    fn open_events(&self) -> Option<OwnedFd> {
+        let fd = unsafe { libc::open(c"/dev/zfs".as_ptr(), libc::O_RDWR | libc::O_CLOEXEC) };
+        if fd < 0 {
+            return None;
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let _lock = self.libzfs.handle.lock();
+        match unsafe { sys::zpool_events_seek(self.libzfs.handle.raw(), ZEVENT_SEEK_END, fd.as_raw_fd()) } {
+            0 => Some(fd),
+            _ => None,
+        }
+    }
+
+    /// Read all pending events, returning which pools they were about.
+    fn drain_events(&self) -> Touched {
+        let mut touched = Touched::Nothing;
+        let Some(fd) = &self.zevent_fd else {
+            return touched;
+        };
+        let _lock = self.libzfs.handle.lock();
+        loop {
+            let mut nvl = ptr::null_mut();
+            let mut dropped = 0;
+            let ret = unsafe {
+                sys::zpool_events_next(
+                    self.libzfs.handle.raw(),
+                    &mut nvl,
+                    &mut dropped,
+                    sys::ZEVENT_NONBLOCK,
+                    fd.as_raw_fd(),
+                )
+            };
+            if dropped > 0 {
+                touched.add(None);
+            }
+            if ret != 0 || nvl.is_null() {
+                return touched;
+            }
+            let nvl = unsafe { NvList::from_raw(nvl) };
+            match ZfsEvent::from_nvlist(&nvl) {
+                Ok(event) => touched.add(event.pool()),
+                Err(_) => touched.add(None),
+            }
+        }
+    }
+
+    /// Scan everything watched, or only the given pools, keeping what was last seen of the
+    /// others.
+    fn scan(&mut self, only: Option<&BTreeSet<SafeString>>) -> Result<State> {
+        let mut new = State::default();
+        match only {
+            Some(only) => {
+                let other = |name: &SafeString| {
+                    !only.iter().any(|pool| pool.as_str() == pool_of(name.as_str()))
+                };
+                new.pools = self.state.pools.iter()
+                    .filter(|(name, _)| other(name))
+                    .map(|(name, state)| (name.clone(), *state))
+                    .collect();
+                new.datasets = self.state.datasets.iter()
+                    .filter(|(name, _)| other(name))
+                    .map(|(name, state)| (name.clone(), state.clone()))
+                    .collect();
+                new.snapshot_stamps = self.state.snapshot_stamps.iter()
+                    .filter(|(name, _)| other(name))
+                    .map(|(name, stamp)| (name.clone(), stamp.clone()))
+                    .collect();
+            }
+            None => self.last_full_scan = Instant::now(),
+        }
+
+        let root: Option<&str> = self.selector.root.as_ref().map(AsRef::as_ref);
+        let root_pool = root.map(|r| r.split('/').next().unwrap_or_default());
+        for pool in self.libzfs.get_zpools()? {
+            let name = pool.get_name();
+            if root_pool.is_some_and(|p| p != AsRef::<str>::as_ref(&name))
+                || only.is_some_and(|only| !only.contains(&name))
+            {
+                continue;
+            }
+            new.pools.insert(name.clone(), pool.get_state());
+            let top = self.selector.root.clone().unwrap_or(name);
+            // The root dataset may not exist (yet).
+            if let Ok(ds) = self.libzfs.dataset_by_name(&top, DatasetTypeMask::all()) {
+                self.scan_tree(ds, &mut new)?;
+            }
+        }
+        Ok(new)
+    }
+
+    fn scan_tree(&self, ds: Dataset, new: &mut State) -> Result<()> {
+        let name = ds.get_name();
+        new.datasets.insert(name.clone(), DatasetState {
+            createtxg: ds.get_property("createtxg").ok(),
+            props: self.selector.properties.iter().map(|p| ds.get_property(p).ok()).collect(),
+        });
+
+        if self.selector.snapshots {
+            let stamp = ds.get_property("snapshots_changed").ok();
+            if stamp.is_some() && self.state.snapshot_stamps.get(&name) == Some(&stamp) {
+                let prefix = format!("{}@", AsRef::<str>::as_ref(&name));
+                let prev = self.state.datasets.range(SafeString::try_from(prefix.as_str())?..)
+                    .take_while(|(snap, _)| AsRef::<str>::as_ref(*snap).starts_with(&prefix));
+                for (snap, state) in prev {
+                    new.datasets.insert(snap.clone(), state.clone());
+                }
+            } else {
+                for snap in ds.get_snapshots()? {
+                    new.datasets.insert(snap.get_name(), DatasetState {
+                        createtxg: snap.get_property("createtxg").ok(),
+                        props: vec![],
+                    });
+                }
+            }
+            new.snapshot_stamps.insert(name, stamp);
+        }
+
+        for child in ds.get_child_filesystems()? {
+            self.scan_tree(child, new)?;
+        }
+        Ok(())
+    }
+}
+
+/// The pool a dataset, snapshot, or bookmark is in.
+fn pool_of(name: &str) -> &str {
+    name.split(['/', '@', '#']).next().unwrap_or_default()
+}
+
+fn diff(old: &State, new: &State, props: &[String]) -> Vec<WatchEvent> {
+    let mut events = vec![];
+
+    for (pool, state) in &new.pools {
+        if old.pools.get(pool) != Some(state) {
+            events.push(WatchEvent::PoolStateChanged {
+                pool: pool.clone(),
+                old: old.pools.get(pool).copied(),
+                new: Some(*state),
+            });
+        }
+    }
+    for (pool, state) in &old.pools {
+        if !new.pools.contains_key(pool) {
+            events.push(WatchEvent::PoolStateChanged {
+                pool: pool.clone(),
+                old: Some(*state),
+                new: None,
+            });
+        }
+    }
+
+    for (name, ds) in &old.datasets {
+        match new.datasets.get(name) {
+            Some(n) if n.createtxg == ds.createtxg => (),
+            _ => events.push(WatchEvent::DatasetRemoved(name.clone())),
+        }
+    }
+    for (name, ds) in &new.datasets {
+        match old.datasets.get(name) {
+            Some(o) if o.createtxg == ds.createtxg => {
+                for ((prop, old), new) in props.iter().zip(&o.props).zip(&ds.props) {
+                    if old != new {
+                        events.push(WatchEvent::PropertyChanged {
+                            dataset: name.clone(),
+                            property: prop.clone(),
+                            old: old.clone(),
+                            new: new.clone(),
+                        });
+                    }
+                }
+            }
+            _ => events.push(WatchEvent::DatasetAdded(name.clone())),
+        }
+    }
+
+    events
+}
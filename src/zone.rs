@@ -0,0 +1,62 @@
+use crate::{sys, Dataset, Result};
+#[cfg(all(target_os = "linux", zfs_2_2))]
+use crate::{ztry, Error, ZfsError};
+
+use std::ffi::CStr;
+#[cfg(all(target_os = "linux", zfs_2_2))]
+use std::ffi::CString;
+#[cfg(all(target_os = "linux", zfs_2_2))]
+use std::os::raw::c_int;
+#[cfg(all(target_os = "linux", zfs_2_2))]
+use std::os::unix::ffi::OsStrExt;
+#[cfg(all(target_os = "linux", zfs_2_2))]
+use std::path::Path;
+
+// Delegating a filesystem to a container (or a FreeBSD jail) takes two steps: marking it with
+// the `zoned` property (`jailed` on FreeBSD), which stops the host from mounting it, and then
+// attaching it to the container's user namespace (or the jail).
+
+impl Dataset {
+    /// Whether this filesystem is delegated to containers or jails, or under one which is: its
+    /// `zoned` property (`jailed` on FreeBSD) is on. Delegated filesystems are mounted from
+    /// inside, not by the host.
+    pub fn is_delegated(&self) -> bool {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_ZONED) != 0
+    }
+
+    /// Turn the `zoned` property (`jailed` on FreeBSD) on or off, the first step in delegating
+    /// this filesystem and its descendants, or the last in taking them back. It should be
+    /// unmounted first.
+    pub fn set_delegated(&self, delegated: bool) -> Result<()> {
+        // The property's name depends on the platform.
+        let prop = unsafe { sys::zfs_prop_to_name(sys::zfs_prop_t::ZFS_PROP_ZONED) };
+        let name = unsafe { CStr::from_ptr(prop) }.to_string_lossy();
+        self.set_property(&name, if delegated { "on" } else { "off" })
+    }
+
+    /// Attach this filesystem to a Linux user namespace, like `zfs zone`, given the path of its
+    /// namespace file, like `/proc/<pid>/ns/user`. Processes in the namespace can then mount and
+    /// manage it and its descendants. Its `zoned` property must be on; see
+    /// [`set_delegated`](Self::set_delegated).
+    #[cfg(all(target_os = "linux", zfs_2_2))]
+    pub fn zone<P: AsRef<Path>>(&self, namespace: P) -> Result<()> {
+        self.set_zoned(namespace.as_ref(), true)
+    }
+
+    /// Detach this filesystem from a Linux user namespace, like `zfs unzone`.
+    #[cfg(all(target_os = "linux", zfs_2_2))]
+    pub fn unzone<P: AsRef<Path>>(&self, namespace: P) -> Result<()> {
+        self.set_zoned(namespace.as_ref(), false)
+    }
+
+    #[cfg(all(target_os = "linux", zfs_2_2))]
+    fn set_zoned(&self, namespace: &Path, attach: bool) -> Result<()> {
+        let namespace = CString::new(namespace.as_os_str().as_bytes())
+            .map_err(Error::invalid_input)?;
+        let _lock = self.libzfs.lock();
+        ztry!(unsafe {
+            sys::zfs_userns(self.handle, namespace.as_ptr(), attach as c_int)
+        }, self.libzfs.raw(), self.name());
+        Ok(())
+    }
+}
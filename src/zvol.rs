@@ -0,0 +1,108 @@
+use crate::{sys, Dataset, DatasetType, Error, Result, Snapdev, Volmode};
+
+use std::io;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Where the platform's ZFS port puts device nodes (or links to them) for volumes.
+#[cfg(target_os = "macos")]
+const ZVOL_DEV_DIR: &str = "/var/run/zfs/zvol/dsk";
+#[cfg(not(target_os = "macos"))]
+const ZVOL_DEV_DIR: &str = "/dev/zvol";
+
+/// How often to look for the device node while waiting for it.
+const DEVICE_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+impl Dataset {
+    /// Get the path of the device node for this volume, or snapshot of a volume, like
+    /// `/dev/zvol/tank/vol`, or on macOS, `/var/run/zfs/zvol/dsk/tank/vol`, a link to the
+    /// volume's `/dev/diskN`. The node may not exist yet; see
+    /// [`wait_for_device`](Self::wait_for_device).
+    pub fn zvol_device_path(&self) -> Result<PathBuf> {
+        let _lock = self.libzfs.lock();
+        let typ = DatasetType::from_raw(unsafe { sys::zfs_get_underlying_type(self.handle) });
+        if typ != DatasetType::Volume {
+            return Err(Error::invalid_input(format!("{} is not a volume", self.name())));
+        }
+        Ok(PathBuf::from(ZVOL_DEV_DIR).join(self.name().as_str()))
+    }
+
+    /// Wait for the device node for this volume to appear, returning its path. Nodes are created
+    /// asynchronously (by udev, on Linux), so they usually don't exist yet right after a volume
+    /// is created, cloned, or renamed.
+    pub fn wait_for_device(&self, timeout: Duration) -> Result<PathBuf> {
+        let path = self.zvol_device_path()?;
+        let deadline = Instant::now() + timeout;
+        loop {
+            if path.exists() {
+                return Ok(path);
+            }
+            let now = Instant::now();
+            if now >= deadline {
+                return Err(Error::Sys(io::Error::new(io::ErrorKind::TimedOut,
+                    format!("timed out waiting for {}", path.display()))));
+            }
+            thread::sleep(DEVICE_POLL_INTERVAL.min(deadline - now));
+        }
+    }
+
+    /// Get the size of this volume, in bytes.
+    pub fn volsize(&self) -> u64 {
+        self.get_int_property(sys::zfs_prop_t::ZFS_PROP_VOLSIZE)
+    }
+
+    /// Resize this volume, like `zfs set volsize=`. The new size must be a multiple of the
+    /// volume's block size. Shrinking a volume throws away whatever was stored past the new end,
+    /// so it is refused unless `allow_shrink` is set.
+    ///
+    /// As with the zfs command, a non-sparse volume's `refreservation` grows or shrinks along
+    /// with it.
+    pub fn set_volsize(&self, new_size: u64, allow_shrink: bool) -> Result<()> {
+        self.require_volume()?;
+        let volblocksize = self.get_int_property(sys::zfs_prop_t::ZFS_PROP_VOLBLOCKSIZE);
+        if new_size == 0 || !new_size.is_multiple_of(volblocksize) {
+            return Err(Error::invalid_input(format!(
+                "volume size {} is not a multiple of the volume block size {}",
+                new_size, volblocksize)));
+        }
+        let old_size = self.volsize();
+        if new_size < old_size && !allow_shrink {
+            return Err(Error::invalid_input(format!(
+                "refusing to shrink {} from {} to {} bytes", self.name(), old_size, new_size)));
+        }
+        self.set_property("volsize", &new_size.to_string())
+    }
+
+    /// Get whether this volume's snapshots have device nodes.
+    pub fn snapdev(&self) -> Result<Snapdev> {
+        self.require_volume()?;
+        self.get_typed_property()
+    }
+
+    /// Set whether this volume's snapshots have device nodes.
+    pub fn set_snapdev(&self, value: Snapdev) -> Result<()> {
+        self.require_volume()?;
+        self.set_typed_property(&value)
+    }
+
+    /// Get how this volume is exposed to the operating system.
+    pub fn volmode(&self) -> Result<Volmode> {
+        self.require_volume()?;
+        self.get_typed_property()
+    }
+
+    /// Set how this volume is exposed to the operating system. Device nodes are added or
+    /// removed asynchronously.
+    pub fn set_volmode(&self, value: Volmode) -> Result<()> {
+        self.require_volume()?;
+        self.set_typed_property(&value)
+    }
+
+    fn require_volume(&self) -> Result<()> {
+        if self.get_type() != DatasetType::Volume {
+            return Err(Error::invalid_input(format!("{} is not a volume", self.name())));
+        }
+        Ok(())
+    }
+}